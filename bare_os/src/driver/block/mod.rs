@@ -1,5 +1,6 @@
 mod virtio_block_dev;
 
+use crate::config::{VIRTIO0, VIRTIO2};
 use alloc::sync::Arc;
 use easyfs::BlockDevice;
 use lazy_static::lazy_static;
@@ -9,6 +10,23 @@ type BlockDeviceImpl = virtio_block_dev::VirtIOBlock;
 // #[cfg(feature = "board_k210")]
 // type BlockDeviceImpl = sdcard::SDCardWrapper;
 
+/// 根文件系统所在的块设备
+pub const ROOT_BLOCK_DEVICE: &str = "vda";
+
 lazy_static! {
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new(VIRTIO0));
+    //启动时没有连接第二块磁盘则为None
+    static ref SECOND_BLOCK_DEVICE: Option<Arc<dyn BlockDevice>> =
+        virtio_block_dev::probe(VIRTIO2).then(|| {
+            Arc::new(BlockDeviceImpl::new(VIRTIO2)) as Arc<dyn BlockDevice>
+        });
+}
+
+/// 根据名称找到块设备
+pub fn block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    match name {
+        "vda" => Some(BLOCK_DEVICE.clone()),
+        "vdb" => SECOND_BLOCK_DEVICE.clone(),
+        _ => None,
+    }
 }
@@ -1,40 +1,243 @@
-use crate::config::VIRTIO0;
-use crate::mm::address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr};
-use crate::mm::frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
+use crate::mm::address::{PhysAddr, PhysPageNum, VirtAddr};
+use crate::mm::frame_allocator::{frame_alloc_contiguous, FrameTracker};
 use crate::mm::page_table::PageTable;
 use crate::mm::KERNEL_SPACE;
+use alloc::collections::BTreeMap;
+use alloc::vec;
 use alloc::vec::Vec;
-use easyfs::BlockDevice;
+use core::hint::spin_loop;
+use easyfs::{
+    check_range, BlockCompletion, BlockDevice, BlockError, BlockRequest, BlockResult, BLOCK_SIZE,
+};
 use lazy_static::lazy_static;
 use spin::Mutex;
-use virtio_drivers::{VirtIOBlk, VirtIOHeader};
+use virtio_drivers::{BlkResp, RespStatus, VirtIOBlk, VirtIOHeader};
 
 //虚拟块设备
-pub struct VirtIOBlock(Mutex<VirtIOBlk<'static>>);
+//请求按扇区拆分后以非阻塞的方式交给设备，设备完成后从已用环中取回
+//同步读写提交请求后等待其完成，异步请求在poll时取回已经完成的结果
+pub struct VirtIOBlock {
+    base: usize, //设备寄存器的地址
+    inner: Mutex<VirtIOBlockInner>,
+}
+
+//virtio-mmio设备的配置空间，块设备的前8个字节是以扇区为单位的容量
+const VIRTIO_CONFIG_OFFSET: usize = 0x100;
+const VIRTIO_MAGIC: u32 = 0x7472_6976; //"virt"
+const VIRTIO_DEVICE_ID_OFFSET: usize = 0x8;
+const VIRTIO_BLOCK_DEVICE_ID: u32 = 2;
+//驱动的队列有16个描述符，每个扇区请求占用请求头、数据与状态3个描述符
+const QUEUE_SLOTS: usize = 16 / 3;
+
+/// 检查地址上是否连接了virtio块设备，没有连接设备的插槽设备号为0
+pub fn probe(base: usize) -> bool {
+    unsafe {
+        core::ptr::read_volatile(base as *const u32) == VIRTIO_MAGIC
+            && core::ptr::read_volatile((base + VIRTIO_DEVICE_ID_OFFSET) as *const u32)
+                == VIRTIO_BLOCK_DEVICE_ID
+    }
+}
+
+/// 提交给设备的一个请求，刷新请求没有数据
+struct Request {
+    block_id: usize,
+    write: bool,
+    data: Vec<u8>,       //读请求的目标或者写请求的数据，完成之前缓冲区的地址不能改变
+    resps: Vec<BlkResp>, //每个扇区的完成状态
+    submitted: usize,    //已经交给设备的扇区数
+    finished: usize,     //设备已经完成的扇区数
+}
+
+impl Request {
+    fn sectors(&self) -> usize {
+        self.data.len() / BLOCK_SIZE
+    }
+    fn is_finished(&self) -> bool {
+        self.finished == self.sectors()
+    }
+}
+
+struct VirtIOBlockInner {
+    blk: VirtIOBlk<'static>,
+    next_id: usize,
+    requests: BTreeMap<usize, Request>, //没有被取走的请求，按提交顺序排列
+    tokens: BTreeMap<u16, usize>,       //交给设备的扇区对应的请求号
+}
+
+impl VirtIOBlockInner {
+    fn submit(&mut self, block_id: usize, write: bool, data: Vec<u8>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let sectors = data.len() / BLOCK_SIZE;
+        let request = Request {
+            block_id,
+            write,
+            data,
+            resps: (0..sectors).map(|_| BlkResp::default()).collect(),
+            submitted: 0,
+            finished: 0,
+        };
+        self.requests.insert(id, request);
+        self.kick();
+        id
+    }
+    /// 队列有空闲时按提交顺序把扇区交给设备
+    fn kick(&mut self) {
+        let Self {
+            blk,
+            requests,
+            tokens,
+            ..
+        } = self;
+        for (id, request) in requests.iter_mut() {
+            while request.submitted < request.sectors() && tokens.len() < QUEUE_SLOTS {
+                let index = request.submitted;
+                let block_id = request.block_id + index;
+                let sector = &mut request.data[index * BLOCK_SIZE..(index + 1) * BLOCK_SIZE];
+                let resp = &mut request.resps[index];
+                //缓冲区与状态在请求完成之前一直保存在requests中
+                let token = unsafe {
+                    if request.write {
+                        blk.write_block_nb(block_id, sector, resp)
+                    } else {
+                        blk.read_block_nb(block_id, sector, resp)
+                    }
+                };
+                request.submitted += 1;
+                match token {
+                    Ok(token) => {
+                        tokens.insert(token, *id);
+                    }
+                    //没有交给设备的扇区状态不是Ok，请求完成时报告错误
+                    Err(_) => request.finished += 1,
+                }
+            }
+        }
+    }
+    /// 取回设备完成的一个扇区，没有完成的扇区时返回false
+    fn reap(&mut self) -> bool {
+        let token = match self.blk.pop_used() {
+            Ok(token) => token,
+            Err(_) => return false,
+        };
+        if let Some(id) = self.tokens.remove(&token) {
+            if let Some(request) = self.requests.get_mut(&id) {
+                request.finished += 1;
+            }
+        }
+        self.kick();
+        true
+    }
+    /// 请求是否已经完成，刷新请求在之前提交的请求全部完成后完成
+    fn is_done(&self, id: usize) -> bool {
+        let request = &self.requests[&id];
+        if request.sectors() != 0 {
+            return request.is_finished();
+        }
+        self.requests
+            .range(..id)
+            .all(|(_, request)| request.is_finished())
+    }
+    fn take(&mut self, id: usize) -> BlockCompletion {
+        let request = self.requests.remove(&id).unwrap();
+        let result = if request
+            .resps
+            .iter()
+            .all(|resp| resp.status() == RespStatus::Ok)
+        {
+            Ok(())
+        } else {
+            Err(BlockError::Io)
+        };
+        let data = if request.write {
+            Vec::new()
+        } else {
+            request.data
+        };
+        BlockCompletion { id, result, data }
+    }
+    /// 等待请求完成并取走结果
+    fn wait(&mut self, id: usize) -> BlockCompletion {
+        while !self.is_done(id) {
+            if !self.reap() {
+                spin_loop();
+            }
+        }
+        self.take(id)
+    }
+}
 
 impl VirtIOBlock {
-    pub fn new() -> Self {
+    pub fn new(base: usize) -> Self {
         //VirtIOHeader 表示以MMIO内存映射方式访问IO设备
         //所需要的一组寄存器
-        Self(Mutex::new(
-            VirtIOBlk::new(unsafe { &mut *(VIRTIO0 as *mut VirtIOHeader) }).unwrap(),
-        ))
+        Self {
+            base,
+            inner: Mutex::new(VirtIOBlockInner {
+                blk: VirtIOBlk::new(unsafe { &mut *(base as *mut VirtIOHeader) }).unwrap(),
+                next_id: 0,
+                requests: BTreeMap::new(),
+                tokens: BTreeMap::new(),
+            }),
+        }
     }
 }
 
 impl BlockDevice for VirtIOBlock {
-    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        self.0
-            .lock()
-            .read_block(block_id, buf)
-            .expect("Read from block error!");
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        self.read_blocks(block_id, buf)
     }
     //为块设备实现定义的接口
-    fn write_block(&self, block_id: usize, buf: &[u8]) {
-        self.0
-            .lock()
-            .write_block(block_id, buf)
-            .expect("Write to block error!");
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        self.write_blocks(block_id, buf)
+    }
+    fn num_blocks(&self) -> usize {
+        //扇区大小与文件系统的块大小都是512字节
+        let capacity =
+            unsafe { core::ptr::read_volatile((self.base + VIRTIO_CONFIG_OFFSET) as *const u64) };
+        capacity as usize
+    }
+    //连续的多个扇区同时交给设备，由设备并行处理
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        check_range(self, block_id, buf.len())?;
+        let mut inner = self.inner.lock();
+        let id = inner.submit(block_id, false, vec![0u8; buf.len()]);
+        let completion = inner.wait(id);
+        buf.copy_from_slice(&completion.data);
+        completion.result
+    }
+    fn write_blocks(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        check_range(self, block_id, buf.len())?;
+        let mut inner = self.inner.lock();
+        let id = inner.submit(block_id, true, buf.to_vec());
+        inner.wait(id).result
+    }
+    //设备按顺序完成请求，等待之前提交的异步写入全部完成即可
+    fn flush(&self) -> BlockResult {
+        let mut inner = self.inner.lock();
+        let id = inner.submit(0, true, Vec::new());
+        inner.wait(id).result
+    }
+    fn submit(&self, request: BlockRequest) -> BlockResult<usize> {
+        let (block_id, write, data) = match request {
+            BlockRequest::Read { block_id, count } => {
+                (block_id, false, vec![0u8; count * BLOCK_SIZE])
+            }
+            BlockRequest::Write { block_id, data } => (block_id, true, data),
+            BlockRequest::Flush => (0, true, Vec::new()),
+        };
+        check_range(self, block_id, data.len())?;
+        Ok(self.inner.lock().submit(block_id, write, data))
+    }
+    fn poll(&self) -> Option<BlockCompletion> {
+        let mut inner = self.inner.lock();
+        while inner.reap() {}
+        let id = inner
+            .requests
+            .keys()
+            .copied()
+            .find(|id| inner.is_done(*id))?;
+        Some(inner.take(id))
     }
 }
 lazy_static! {
@@ -45,27 +248,19 @@ lazy_static! {
 //这些接口负责为设备在内存中开辟部分空间用于cpu与设备进行通信
 #[no_mangle]
 pub extern "C" fn virtio_dma_alloc(pages: usize) -> PhysAddr {
-    //为其分配连续的物理页帧，由于是在内核初始化阶段进行，因此页帧的分配是连续的
-    let mut start_ppn = PhysPageNum(0);
-    for i in 0..pages {
-        let frame = frame_alloc().unwrap();
-        if i == 0 {
-            start_ppn = frame.ppn;
-        }
-        //判断页帧是否连续
-        assert_eq!(frame.ppn.0, start_ppn.0 + i);
-        QUEUE_FRAMES.lock().push(frame);
-    }
+    //为其分配连续的物理页帧
+    let frames = frame_alloc_contiguous(pages, 1).expect("No contiguous frames for DMA");
+    let start_ppn = frames[0].ppn;
+    QUEUE_FRAMES.lock().extend(frames);
     start_ppn.into()
 }
 #[no_mangle]
 pub fn virtio_dma_dealloc(paddr: PhysAddr, pages: usize) -> i32 {
-    //回收物理页帧
-    let mut phypage: PhysPageNum = paddr.into();
-    for _ in 0..pages {
-        frame_dealloc(phypage);
-        phypage.step();
-    }
+    //回收物理页帧,FrameTracker被释放时会自动回收
+    let start_ppn: PhysPageNum = paddr.into();
+    QUEUE_FRAMES
+        .lock()
+        .retain(|frame| frame.ppn.0 < start_ppn.0 || frame.ppn.0 >= start_ppn.0 + pages);
     0
 }
 #[no_mangle]
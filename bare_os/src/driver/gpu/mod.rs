@@ -1,29 +1,35 @@
 use crate::config::VIRTIO1;
 use crate::INFO;
-use virtio_drivers::{VirtIOGpu, VirtIOHeader};
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
 
 mod virtio_gpu;
 
-// lazy_static! {
-//     pub static ref GPU: Arc<VirtIoGpu> = Arc::new(VirtIoGpu::new());
-// }
+pub use virtio_gpu::VirtIOGpuDevice;
+
+lazy_static! {
+    //启动时没有连接显卡则为None
+    static ref GPU_DEVICE: Option<Arc<VirtIOGpuDevice>> = if virtio_gpu::probe(VIRTIO1) {
+        VirtIOGpuDevice::new(VIRTIO1).map(Arc::new)
+    } else {
+        None
+    };
+}
+
+pub fn gpu_device() -> Option<Arc<VirtIOGpuDevice>> {
+    GPU_DEVICE.clone()
+}
+
+/// 将整个屏幕填充为红色
 pub fn gpu() {
     INFO!("set the gpu");
-    let mut gpu = VirtIOGpu::new(unsafe { &mut *(VIRTIO1 as *mut VirtIOHeader) })
-        .expect("failed to create gpu driver");
-
+    let gpu = gpu_device().expect("failed to create gpu driver");
     INFO!("GET GPU");
-    let fb = gpu.setup_framebuffer().expect("failed to get fb");
-
-    for y in 0..768 {
-        for x in 0..1024 {
-            let idx = (y * 1024 + x) * 4;
-            fb[idx] = (0) as u8; //Blue
-            fb[idx + 1] = (0) as u8; //Green
-            fb[idx + 2] = (255) as u8; //Red
-            fb[idx + 3] = (0) as u8; //Alpha
-        }
+    let (width, height) = gpu.resolution();
+    let red = [0u8, 0, 255, 0]; //Blue Green Red Alpha
+    for pixel in 0..(width * height) as usize {
+        gpu.write_at(pixel * 4, &red);
     }
-    gpu.flush().expect("failed to flush");
+    assert!(gpu.flush(), "failed to flush");
     INFO!("virtio-gpu test finished");
 }
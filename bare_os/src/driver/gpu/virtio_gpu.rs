@@ -1 +1,65 @@
+use spin::Mutex;
+use virtio_drivers::{VirtIOGpu, VirtIOHeader};
 
+const VIRTIO_MAGIC: u32 = 0x7472_6976; //"virt"
+const VIRTIO_DEVICE_ID_OFFSET: usize = 0x8;
+const VIRTIO_GPU_DEVICE_ID: u32 = 16;
+
+/// 检查地址上是否连接了virtio显卡
+pub fn probe(base: usize) -> bool {
+    unsafe {
+        core::ptr::read_volatile(base as *const u32) == VIRTIO_MAGIC
+            && core::ptr::read_volatile((base + VIRTIO_DEVICE_ID_OFFSET) as *const u32)
+                == VIRTIO_GPU_DEVICE_ID
+    }
+}
+
+//虚拟显卡，帧缓冲区在初始化时由驱动申请，之后一直存在
+pub struct VirtIOGpuDevice {
+    gpu: Mutex<VirtIOGpu<'static>>,
+    fb: Mutex<&'static mut [u8]>,
+    resolution: (u32, u32),
+}
+
+impl VirtIOGpuDevice {
+    pub fn new(base: usize) -> Option<Self> {
+        let mut gpu = VirtIOGpu::new(unsafe { &mut *(base as *mut VirtIOHeader) }).ok()?;
+        let resolution = gpu.resolution();
+        let fb = gpu.setup_framebuffer().ok()?;
+        Some(Self {
+            gpu: Mutex::new(gpu),
+            fb: Mutex::new(fb),
+            resolution,
+        })
+    }
+    /// 屏幕的宽和高，每个像素4个字节
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+    pub fn size(&self) -> usize {
+        self.fb.lock().len()
+    }
+    /// 从帧缓冲区的offset处读取，返回读取的字节数
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let fb = self.fb.lock();
+        if offset >= fb.len() {
+            return 0;
+        }
+        let len = buf.len().min(fb.len() - offset);
+        buf[..len].copy_from_slice(&fb[offset..offset + len]);
+        len
+    }
+    /// 写入帧缓冲区的offset处，需要调用flush才会显示
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fb = self.fb.lock();
+        if offset >= fb.len() {
+            return 0;
+        }
+        let len = buf.len().min(fb.len() - offset);
+        fb[offset..offset + len].copy_from_slice(&buf[..len]);
+        len
+    }
+    pub fn flush(&self) -> bool {
+        self.gpu.lock().flush().is_ok()
+    }
+}
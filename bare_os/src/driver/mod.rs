@@ -1,6 +1,6 @@
 mod block;
 mod gpu;
 
-pub use block::BLOCK_DEVICE;
+pub use block::{block_device, ROOT_BLOCK_DEVICE};
 
-pub use gpu::gpu;
+pub use gpu::{gpu, gpu_device, VirtIOGpuDevice};
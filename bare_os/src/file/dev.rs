@@ -0,0 +1,373 @@
+///! 字符设备与块设备文件，由/dev中的设备节点打开
+///! 每次打开都会得到新的实例，因此读写位置不会在不同的打开之间共享
+use super::{File, OpenFlags, Stat, StatMode, Stdin, Stdout, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::driver::VirtIOGpuDevice;
+use crate::fs::is_mounted;
+use crate::mm::page_table::UserBuffer;
+use crate::timer::get_time;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use easyfs::{BlockDevice, BLOCK_SIZE};
+use spin::Mutex;
+
+/// 控制台，读取时等待键盘输入，写入时输出到串口
+pub struct Console;
+/// 读取时没有数据，写入的数据被丢弃
+pub struct Null;
+/// 读取时得到0，写入的数据被丢弃
+pub struct Zero;
+/// 伪随机数，写入的数据用于打乱状态
+pub struct Random;
+
+impl File for Console {
+    fn read(&self, buf: UserBuffer) -> usize {
+        Stdin.read(buf)
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        Stdout.write(buf)
+    }
+    fn kind(&self) -> &'static str {
+        "console"
+    }
+    fn fstat(&self) -> Stat {
+        char_stat()
+    }
+}
+
+impl File for Null {
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        buf.len()
+    }
+    fn kind(&self) -> &'static str {
+        "null"
+    }
+    fn fstat(&self) -> Stat {
+        char_stat()
+    }
+}
+
+impl File for Zero {
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        for buffer in buf.buffer.iter_mut() {
+            buffer.fill(0);
+        }
+        buf.len()
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        buf.len()
+    }
+    fn kind(&self) -> &'static str {
+        "zero"
+    }
+    fn fstat(&self) -> Stat {
+        char_stat()
+    }
+}
+
+//xorshift64*的状态，为0时表示还没有用时钟初始化
+static RANDOM_STATE: AtomicU64 = AtomicU64::new(0);
+
+fn next_random() -> u64 {
+    let mut x = RANDOM_STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = get_time() as u64 | 1;
+    }
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    RANDOM_STATE.store(x, Ordering::Relaxed);
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+impl File for Random {
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        for buffer in buf.buffer.iter_mut() {
+            for chunk in buffer.chunks_mut(8) {
+                let bytes = next_random().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+        buf.len()
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        for byte in buf.buffer.iter().flat_map(|buffer| buffer.iter()) {
+            let x = next_random() ^ *byte as u64;
+            RANDOM_STATE.store(x | 1, Ordering::Relaxed);
+        }
+        buf.len()
+    }
+    fn kind(&self) -> &'static str {
+        "random"
+    }
+    fn fstat(&self) -> Stat {
+        char_stat()
+    }
+}
+
+fn char_stat() -> Stat {
+    Stat::new(0, 0, StatMode::from_bits_truncate(0o020666), 1)
+}
+
+/// 按照whence移动读写位置，允许的范围是[0, size]
+fn seek_in(offset: &mut usize, size: usize, delta: isize, whence: usize) -> isize {
+    let base = match whence {
+        SEEK_SET => 0,
+        SEEK_CUR => *offset as isize,
+        SEEK_END => size as isize,
+        _ => return -1,
+    };
+    match base.checked_add(delta) {
+        Some(new) if new >= 0 && new as usize <= size => {
+            *offset = new as usize;
+            new
+        }
+        _ => -1,
+    }
+}
+
+/// 设备节点打开后得到的文件，按照打开时的访问模式限制读写
+pub struct DevFile {
+    device: Arc<dyn File + Send + Sync>,
+    readable: bool,
+    writeable: bool,
+    status: Mutex<OpenFlags>,
+}
+
+impl DevFile {
+    pub fn new(device: Arc<dyn File + Send + Sync>, flags: OpenFlags) -> Self {
+        let (readable, writeable) = flags.read_write();
+        Self {
+            device,
+            readable,
+            writeable,
+            status: Mutex::new(flags.status()),
+        }
+    }
+}
+
+impl File for DevFile {
+    fn read(&self, buf: UserBuffer) -> usize {
+        if !self.readable {
+            return -1isize as usize;
+        }
+        self.device.read(buf)
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        if !self.writeable {
+            return -1isize as usize;
+        }
+        self.device.write(buf)
+    }
+    fn fstat(&self) -> Stat {
+        self.device.fstat()
+    }
+    fn kind(&self) -> &'static str {
+        self.device.kind()
+    }
+    fn fsync(&self) -> isize {
+        self.device.fsync()
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        self.device.seek(offset, whence)
+    }
+    fn pread(&self, buf: UserBuffer, offset: usize) -> isize {
+        if !self.readable {
+            return -1;
+        }
+        self.device.pread(buf, offset)
+    }
+    fn pwrite(&self, buf: UserBuffer, offset: usize) -> isize {
+        if !self.writeable {
+            return -1;
+        }
+        self.device.pwrite(buf, offset)
+    }
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::access_mode(self.readable, self.writeable) | *self.status.lock()
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        *self.status.lock() = flags;
+    }
+}
+
+/// 以字节为单位读写整个块设备
+/// 读写绕过块缓存，开始前先写回缓存中的脏块；设备被挂载时直接写入会与缓存不一致，因此拒绝写入
+pub struct BlockDevFile {
+    name: &'static str,
+    device: Arc<dyn BlockDevice>,
+    offset: Mutex<usize>,
+}
+
+impl BlockDevFile {
+    pub fn new(name: &'static str, device: Arc<dyn BlockDevice>) -> Self {
+        Self {
+            name,
+            device,
+            offset: Mutex::new(0),
+        }
+    }
+    fn size(&self) -> usize {
+        self.device.num_blocks() * BLOCK_SIZE
+    }
+    /// 读写失败并且没有读写任何字节时返回Err
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut done = 0;
+        let len = buf.len().min(self.size().saturating_sub(offset));
+        while done < len {
+            let pos = offset + done;
+            let start = pos % BLOCK_SIZE;
+            let n = (BLOCK_SIZE - start).min(len - done);
+            if self
+                .device
+                .read_block(pos / BLOCK_SIZE, &mut block)
+                .is_err()
+            {
+                return if done == 0 { Err(()) } else { Ok(done) };
+            }
+            buf[done..done + n].copy_from_slice(&block[start..start + n]);
+            done += n;
+        }
+        Ok(done)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, ()> {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut done = 0;
+        let len = buf.len().min(self.size().saturating_sub(offset));
+        while done < len {
+            let pos = offset + done;
+            let start = pos % BLOCK_SIZE;
+            let n = (BLOCK_SIZE - start).min(len - done);
+            //只写入块的一部分时需要先读出原来的内容
+            if n < BLOCK_SIZE
+                && self
+                    .device
+                    .read_block(pos / BLOCK_SIZE, &mut block)
+                    .is_err()
+            {
+                return if done == 0 { Err(()) } else { Ok(done) };
+            }
+            block[start..start + n].copy_from_slice(&buf[done..done + n]);
+            if self.device.write_block(pos / BLOCK_SIZE, &block).is_err() {
+                return if done == 0 { Err(()) } else { Ok(done) };
+            }
+            done += n;
+        }
+        Ok(done)
+    }
+}
+
+impl File for BlockDevFile {
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        if easyfs::block_cache_sync_device(&self.device).is_err() {
+            return -1isize as usize;
+        }
+        let mut offset = self.offset.lock();
+        let mut read_size = 0;
+        for buffer in buf.buffer.iter_mut() {
+            let size = match self.read_at(*offset, buffer) {
+                Ok(size) => size,
+                Err(()) if read_size == 0 => return -1isize as usize,
+                Err(()) => break,
+            };
+            read_size += size;
+            *offset += size;
+            if size < buffer.len() {
+                break;
+            }
+        }
+        read_size
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        if is_mounted(self.name) || easyfs::block_cache_sync_device(&self.device).is_err() {
+            return -1isize as usize;
+        }
+        let mut offset = self.offset.lock();
+        let mut write_size = 0;
+        for buffer in buf.buffer.iter() {
+            let size = match self.write_at(*offset, buffer) {
+                Ok(size) => size,
+                Err(()) if write_size == 0 => return -1isize as usize,
+                Err(()) => break,
+            };
+            write_size += size;
+            *offset += size;
+            if size < buffer.len() {
+                break;
+            }
+        }
+        write_size
+    }
+    fn kind(&self) -> &'static str {
+        "block"
+    }
+    fn fstat(&self) -> Stat {
+        let mut stat = Stat::new(0, 0, StatMode::from_bits_truncate(0o060660), 1);
+        stat.size = self.size() as i64;
+        stat.blksize = BLOCK_SIZE as i32;
+        stat.blocks = self.device.num_blocks() as i64;
+        stat
+    }
+    fn fsync(&self) -> isize {
+        match self.device.flush() {
+            Ok(()) => 0,
+            Err(_) => -1,
+        }
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        seek_in(&mut self.offset.lock(), self.size(), offset, whence)
+    }
+}
+
+/// 显卡的帧缓冲区，每个像素按照BGRA顺序占4个字节，每次写入后刷新屏幕
+pub struct FrameBuffer {
+    gpu: Arc<VirtIOGpuDevice>,
+    offset: Mutex<usize>,
+}
+
+impl FrameBuffer {
+    pub fn new(gpu: Arc<VirtIOGpuDevice>) -> Self {
+        Self {
+            gpu,
+            offset: Mutex::new(0),
+        }
+    }
+}
+
+impl File for FrameBuffer {
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut offset = self.offset.lock();
+        let mut read_size = 0;
+        for buffer in buf.buffer.iter_mut() {
+            let size = self.gpu.read_at(*offset, buffer);
+            read_size += size;
+            *offset += size;
+        }
+        read_size
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut offset = self.offset.lock();
+        let mut write_size = 0;
+        for buffer in buf.buffer.iter() {
+            let size = self.gpu.write_at(*offset, buffer);
+            write_size += size;
+            *offset += size;
+        }
+        self.gpu.flush();
+        write_size
+    }
+    fn kind(&self) -> &'static str {
+        "fb"
+    }
+    fn fstat(&self) -> Stat {
+        let mut stat = char_stat();
+        stat.size = self.gpu.size() as i64;
+        stat
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        seek_in(&mut self.offset.lock(), self.gpu.size(), offset, whence)
+    }
+}
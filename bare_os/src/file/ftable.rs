@@ -3,25 +3,70 @@ bitflags! {
     pub struct StatMode: u32 {
         // StatMode 定义：
         const NULL  = 0;
+        /// 文件类型掩码
+        const TYPE_MASK = 0o170000;
         /// directory
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// character device
+        const CHAR  = 0o020000;
+        /// block device
+        const BLOCK = 0o060000;
+        /// symbolic link
+        const LINK  = 0o120000;
+        /// 所有者的读写执行权限
+        const OWNER_R = 0o400;
+        const OWNER_W = 0o200;
+        const OWNER_X = 0o100;
+        /// 所属组的读写执行权限
+        const GROUP_R = 0o040;
+        const GROUP_W = 0o020;
+        const GROUP_X = 0o010;
+        /// 其他用户的读写执行权限
+        const OTHER_R = 0o004;
+        const OTHER_W = 0o002;
+        const OTHER_X = 0o001;
     }
 }
+
+impl StatMode {
+    /// 只保留文件类型
+    pub fn file_type(&self) -> StatMode {
+        *self & StatMode::TYPE_MASK
+    }
+}
+
+/// 与Linux riscv64的struct stat布局相同
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Stat {
-    /// 文件所在磁盘驱动器号，该实验中写死为 0 即可
+    /// 文件所在文件系统的设备号，由alloc_dev为每个文件系统实例分配，管道、控制台等不属于文件系统的文件为0
     pub dev: u64,
     /// inode 文件所在 inode 编号
     pub ino: u64,
-    /// 文件类型
+    /// 文件类型与权限位
     pub mode: StatMode,
     /// 硬链接数量，初始为1
     pub nlink: u32,
-    /// 无需考虑，为了兼容性设计
-    pad: [u64; 7],
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    pad: u64,
+    /// 文件大小
+    pub size: i64,
+    /// 块大小
+    pub blksize: i32,
+    pad2: i32,
+    /// 占用的512B扇区数
+    pub blocks: i64,
+    pub atime: i64,
+    pub atime_nsec: u64,
+    pub mtime: i64,
+    pub mtime_nsec: u64,
+    pub ctime: i64,
+    pub ctime_nsec: u64,
+    unused: [u32; 2],
 }
 
 impl Stat {
@@ -31,7 +76,70 @@ impl Stat {
             ino,
             mode,
             nlink,
-            pad: [0; 7],
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            pad: 0,
+            size: 0,
+            blksize: 512,
+            pad2: 0,
+            blocks: 0,
+            atime: 0,
+            atime_nsec: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+            ctime: 0,
+            ctime_nsec: 0,
+            unused: [0; 2],
+        }
+    }
+}
+
+/// 与Linux riscv64的struct statfs布局相同，块数以bsize为单位
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct StatFs {
+    /// 文件系统的标识，与Linux的f_type相同
+    pub fs_type: i64,
+    pub bsize: i64,
+    /// 块的总数、空闲块数与普通用户可用的块数
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    /// inode的总数与空闲数，没有限制的文件系统为0
+    pub files: u64,
+    pub ffree: u64,
+    pub fsid: [i32; 2],
+    /// 文件名的最大长度
+    pub namelen: i64,
+    pub frsize: i64,
+    pub flags: i64,
+    spare: [i64; 4],
+}
+
+/// quotactl读写的块限额，以文件系统的块为单位，limit为0表示没有限额
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct DiskQuota {
+    pub limit: u64,
+    pub used: u64,
+}
+
+impl StatFs {
+    pub fn new(fs_type: i64, dev: u64, bsize: usize, namelen: usize) -> Self {
+        Self {
+            fs_type,
+            bsize: bsize as i64,
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: 0,
+            ffree: 0,
+            fsid: [dev as i32, 0],
+            namelen: namelen as i64,
+            frsize: bsize as i64,
+            flags: 0,
+            spare: [0; 4],
         }
     }
 }
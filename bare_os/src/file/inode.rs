@@ -1,29 +1,114 @@
-use crate::driver::BLOCK_DEVICE;
-use crate::file::{File, Stat, StatMode};
+use super::lock::release_file_locks;
+use crate::file::{DevFile, File, Stat, StatFs, StatMode, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::fs::{
+    filesystem_of, inode_key, is_mountpoint, lookup_parent, lookup_path, lookup_path_nofollow,
+    root_inode, sync_mounts, InodeType, VfsFileSystem, VfsInode,
+};
 use crate::mm::page_table::UserBuffer;
 use crate::println;
+use crate::task::processor::current_uid;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::bitflags;
-use easyfs::{FileSystem, Inode};
 use lazy_static::lazy_static;
 use spin::mutex::Mutex;
 
 pub struct FNode {
     writeable: bool,
     readable: bool,
+    status: Mutex<OpenFlags>, //APPEND时每次写入前移动到文件末尾，可以被F_SETFL修改
+    kind: InodeType,
+    parent_ino: usize, //打开时父目录的inode号，作为..的目录项
     inner: Mutex<FNodeInner>,
 }
 pub struct FNodeInner {
-    inode: Arc<Inode>,
-    offset: usize, //每个文件的偏移量
+    inode: Arc<dyn VfsInode>,
+    offset: usize, //每个文件的偏移量，目录中是下一个目录项的位置
+}
+
+//getdents64返回的目录项类型
+const DT_UNKNOWN: u8 = 0;
+const DT_CHR: u8 = 2;
+const DT_DIR: u8 = 4;
+const DT_BLK: u8 = 6;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+fn dirent_type(mode: StatMode) -> u8 {
+    match mode.file_type() {
+        StatMode::DIR => DT_DIR,
+        StatMode::FILE => DT_REG,
+        StatMode::CHAR => DT_CHR,
+        StatMode::BLOCK => DT_BLK,
+        StatMode::LINK => DT_LNK,
+        _ => DT_UNKNOWN,
+    }
+}
+
+/// 按照linux_dirent64的布局追加一个目录项，长度按8字节对齐
+fn push_dirent(buf: &mut Vec<u8>, ino: usize, off: usize, kind: u8, name: &str) {
+    let reclen = (19 + name.len() + 1 + 7) & !7;
+    buf.extend_from_slice(&(ino as u64).to_le_bytes());
+    buf.extend_from_slice(&(off as i64).to_le_bytes());
+    buf.extend_from_slice(&(reclen as u16).to_le_bytes());
+    buf.push(kind);
+    buf.extend_from_slice(name.as_bytes());
+    buf.resize(buf.len() + reclen - 19 - name.len(), 0);
+}
+
+/// 缓冲区还能放下时追加一个目录项，放不下时返回false
+fn try_push_dirent(
+    buf: &mut Vec<u8>,
+    limit: usize,
+    ino: usize,
+    off: usize,
+    kind: u8,
+    name: &str,
+) -> bool {
+    let len = buf.len();
+    push_dirent(buf, ino, off, kind, name);
+    if buf.len() > limit {
+        buf.truncate(len);
+        return false;
+    }
+    true
+}
+
+lazy_static! {
+    //每个inode被打开的次数，以(设备号，inode号)区分不同文件系统中的文件
+    //文件被删除后，最后一个打开的文件关闭时才回收inode
+    static ref OPEN_INODES: Mutex<BTreeMap<(u64, usize), usize>> = Mutex::new(BTreeMap::new());
+}
+
+fn is_open(inode: &dyn VfsInode) -> bool {
+    OPEN_INODES.lock().contains_key(&inode_key(inode))
+}
+
+///文件系统中是否还有打开的文件，有时不能卸载
+pub fn is_dev_busy(dev: u64) -> bool {
+    OPEN_INODES.lock().keys().any(|(d, _)| *d == dev)
 }
 
 impl FNode {
-    pub fn new(writeable: bool, readable: bool, inode: Arc<Inode>) -> FNode {
+    pub fn new(
+        writeable: bool,
+        readable: bool,
+        status: OpenFlags,
+        inode: Arc<dyn VfsInode>,
+        parent_ino: usize,
+    ) -> FNode {
+        *OPEN_INODES
+            .lock()
+            .entry(inode_key(inode.as_ref()))
+            .or_insert(0) += 1;
         Self {
             writeable,
             readable,
+            status: Mutex::new(status),
+            kind: inode.inode_type(),
+            parent_ino,
             inner: Mutex::new(FNodeInner { inode, offset: 0 }),
         }
     }
@@ -32,8 +117,8 @@ impl FNode {
         let mut data: Vec<u8> = Vec::new();
         let mut inner = self.inner.lock();
         let mut buffer = [0u8; 512];
-        loop {
-            let size = inner.inode.read_at(inner.offset, &mut buffer);
+        //读取失败时返回已经读出的部分
+        while let Ok(size) = inner.inode.read_at(inner.offset, &mut buffer) {
             if size == 0 {
                 break;
             }
@@ -46,21 +131,28 @@ impl FNode {
     pub fn get_file_size(&self) -> usize {
         //
         let inner = self.inner.lock();
-        inner.inode.get_file_size()
+        inner.inode.size()
     }
     pub fn make_fstat(&self) -> Stat {
         let inner = self.inner.lock();
-        let ftype = match inner.inode.get_disk_type() {
-            0o040000 => StatMode::DIR,
-            0o100000 => StatMode::FILE,
-            _ => StatMode::NULL,
-        };
-        Stat::new(
-            0,
-            inner.inode.get_disk_inode() as u64,
-            ftype,
-            inner.inode.get_disk_nlink(),
-        )
+        inner.inode.stat()
+    }
+}
+
+impl Drop for FNode {
+    fn drop(&mut self) {
+        let inode = self.inner.get_mut().inode.clone();
+        let inode_id = inode_key(inode.as_ref());
+        release_file_locks(inode_id, self as *const Self as usize);
+        let mut open_inodes = OPEN_INODES.lock();
+        let count = open_inodes.get_mut(&inode_id).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            open_inodes.remove(&inode_id);
+            drop(open_inodes);
+            //文件已经被删除
+            inode.release();
+        }
     }
 }
 
@@ -70,7 +162,12 @@ impl File for FNode {
         let mut read_size = 0;
         for buffer in buf.buffer.iter_mut() {
             // DEBUG!("[kernel] offset:{}", inner.offset);
-            let size = inner.inode.read_at(inner.offset, *buffer);
+            let size = match inner.inode.read_at(inner.offset, *buffer) {
+                Ok(size) => size,
+                //读写错误时返回已经读出的字节数，什么都没有读出时返回-1
+                Err(()) if read_size == 0 => return -1isize as usize,
+                Err(()) => break,
+            };
             if size == 0 {
                 break;
             }
@@ -80,35 +177,174 @@ impl File for FNode {
         read_size
     }
     fn write(&self, buf: UserBuffer) -> usize {
+        let len = buf.len();
         let mut inner = self.inner.lock();
+        if self.status.lock().contains(OpenFlags::APPEND) {
+            inner.offset = inner.inode.size();
+        }
         let mut write_size = 0;
         for buffer in buf.buffer.iter() {
-            let size = inner.inode.write_at(inner.offset, *buffer);
-            assert_eq!(size, buffer.len());
+            let size = match inner.inode.write_at(inner.offset, *buffer) {
+                Ok(size) => size,
+                Err(()) if write_size == 0 => return -1isize as usize,
+                Err(()) => break,
+            };
             write_size += size;
             inner.offset += size;
+            //只写入了一部分时停止，例如/proc中的只读文件
+            if size < buffer.len() {
+                break;
+            }
+        }
+        //磁盘已满或者超过限额时什么都没有写入
+        if write_size == 0 && len > 0 && !inner.inode.read_only() {
+            return -1isize as usize;
         }
         write_size
     }
     fn fstat(&self) -> Stat {
-        let inner = self.inner.lock();
-        let ino = inner.inode.get_disk_inode();
-        let mode = match inner.inode.get_disk_type() {
-            0o040000 => StatMode::DIR,
-            0o100000 => StatMode::FILE,
-            _ => StatMode::NULL,
+        self.make_fstat()
+    }
+    fn kind(&self) -> &'static str {
+        //读取/proc/<pid>/fd时可能持有本文件的锁，因此不能再访问inode
+        match self.kind {
+            InodeType::File => "file",
+            InodeType::Dir => "dir",
+            InodeType::Symlink => "symlink",
+        }
+    }
+    fn fsync(&self) -> isize {
+        if self.inner.lock().inode.fsync() {
+            0
+        } else {
+            -1
+        }
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        let mut inner = self.inner.lock();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.inode.size() as isize,
+            _ => return -1,
         };
-        let links = inner.inode.get_disk_nlink();
-        let fstat = Stat::new(0, ino as u64, mode, links);
-        fstat
+        //允许移动到文件末尾之后，之后的写入会扩大文件
+        match base.checked_add(offset) {
+            Some(offset) if offset >= 0 => {
+                inner.offset = offset as usize;
+                offset
+            }
+            _ => -1,
+        }
+    }
+    fn pread(&self, mut buf: UserBuffer, offset: usize) -> isize {
+        if !self.readable {
+            return -1;
+        }
+        let inner = self.inner.lock();
+        let mut read_size = 0;
+        for buffer in buf.buffer.iter_mut() {
+            let size = match inner.inode.read_at(offset + read_size, *buffer) {
+                Ok(size) => size,
+                Err(()) if read_size == 0 => return -1,
+                Err(()) => break,
+            };
+            read_size += size;
+            if size < buffer.len() {
+                break;
+            }
+        }
+        read_size as isize
+    }
+    fn pwrite(&self, buf: UserBuffer, offset: usize) -> isize {
+        if !self.writeable {
+            return -1;
+        }
+        let inner = self.inner.lock();
+        let mut write_size = 0;
+        for buffer in buf.buffer.iter() {
+            let size = match inner.inode.write_at(offset + write_size, *buffer) {
+                Ok(size) => size,
+                Err(()) if write_size == 0 => return -1,
+                Err(()) => break,
+            };
+            write_size += size;
+            if size < buffer.len() {
+                break;
+            }
+        }
+        if write_size == 0 && buf.len() > 0 && !inner.inode.read_only() {
+            return -1;
+        }
+        write_size as isize
+    }
+    fn truncate(&self, len: usize) -> isize {
+        if !self.writeable {
+            return -1;
+        }
+        //超过最大文件大小时失败
+        if self.inner.lock().inode.truncate(len) {
+            0
+        } else {
+            -1
+        }
+    }
+    fn getdents(&self, buf: UserBuffer) -> isize {
+        if self.kind != InodeType::Dir {
+            return -1;
+        }
+        let mut inner = self.inner.lock();
+        let inode = inner.inode.clone();
+        let limit = buf.len();
+        let mut records = Vec::new();
+        let mut offset = inner.offset;
+        let mut full = false;
+        //文件系统的目录中没有.与..，由打开时的路径补上，占用位置0与1
+        for (index, ino, name) in [(0, inode.ino(), "."), (1, self.parent_ino, "..")] {
+            if offset == index && !full {
+                full = !try_push_dirent(&mut records, limit, ino, index + 1, DT_DIR, name);
+                if !full {
+                    offset += 1;
+                }
+            }
+        }
+        //之后的位置是文件系统给出的目录游标加2
+        if !full {
+            inode.read_dir(offset - 2, &mut |next, name, child| {
+                let kind = dirent_type(child.stat().mode);
+                full = !try_push_dirent(&mut records, limit, child.ino(), next + 2, kind, name);
+                if !full {
+                    offset = next + 2;
+                }
+                !full
+            });
+        }
+        //缓冲区放不下一个目录项
+        if records.is_empty() && full {
+            return -1;
+        }
+        inner.offset = offset;
+        let mut written = 0;
+        for buffer in buf.buffer {
+            let size = buffer.len().min(records.len() - written);
+            buffer[..size].copy_from_slice(&records[written..written + size]);
+            written += size;
+        }
+        written as isize
+    }
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::access_mode(self.readable, self.writeable) | *self.status.lock()
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        *self.status.lock() = flags;
+    }
+    fn lock_key(&self) -> Option<(u64, usize)> {
+        Some(inode_key(self.inner.lock().inode.as_ref()))
+    }
+    fn statfs(&self) -> Option<StatFs> {
+        let inode = self.inner.lock().inode.clone();
+        Some(filesystem_of(inode.as_ref())?.statfs())
     }
-}
-lazy_static! {
-    //根目录
-    pub static ref ROOT_INODE:Arc<Inode> ={
-        let fs = FileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(FileSystem::root_inode(&fs))
-    };
 }
 
 //文件标志位
@@ -119,16 +355,53 @@ bitflags! {
         const RW = 1<<1;
         const C = 1<<9;
         const T = 1<<10;
+        const APPEND = 1<<11;
+        const NONBLOCK = 1<<12; //读写不等待，Linux中的位置已经被APPEND使用
+        const DIRECTORY = 1<<16; //只能打开目录
+        const NOFOLLOW = 1<<17; //最后一个分量是符号链接时失败
+        const CLOEXEC = 1<<19; //exec时关闭文件描述符
     }
 }
+
 pub fn list_apps() {
     println!("******APP LIST******");
-    for name in ROOT_INODE.ls().iter() {
+    for name in root_inode().ls().iter() {
         println!("{}", name);
     }
     println!("********************");
 }
+///将所有挂载的文件系统的修改写回磁盘，写回失败时返回false
+pub fn sync_all() -> bool {
+    sync_mounts()
+}
+
+///panic时挂载表与块缓存可能已经被锁住，因此只尝试写回块缓存中的脏块
+pub fn try_sync_all() -> bool {
+    easyfs::block_cache_try_sync()
+}
+
+///打印块缓存的统计信息
+pub fn print_block_cache_stats() {
+    let stats = easyfs::block_cache_stats();
+    println!(
+        "[kernel] block cache: hits {} misses {} evictions {} write backs {} cached {}/{}",
+        stats.hits, stats.misses, stats.evictions, stats.write_backs, stats.cached, stats.capacity
+    );
+}
+
 impl OpenFlags {
+    /// F_SETFL能够修改的文件状态标志
+    pub fn status(&self) -> OpenFlags {
+        *self & (OpenFlags::APPEND | OpenFlags::NONBLOCK)
+    }
+    /// F_GETFL返回的访问模式
+    pub fn access_mode(readable: bool, writeable: bool) -> OpenFlags {
+        match (readable, writeable) {
+            (true, true) => OpenFlags::RW,
+            (false, true) => OpenFlags::W,
+            _ => OpenFlags::R,
+        }
+    }
     pub fn read_write(&self) -> (bool, bool) {
         //返回读写位
         if self.is_empty() {
@@ -140,39 +413,190 @@ impl OpenFlags {
         }
     }
 }
-pub fn open_file(name: &str, flag: OpenFlags) -> Option<Arc<FNode>> {
+pub fn open_file(path: &str, flag: OpenFlags) -> Option<Arc<FNode>> {
     let (readable, writeable) = flag.read_write();
-    // println!("open file {}",name);
-    if flag.contains(OpenFlags::C) {
-        if let Some(inode) = ROOT_INODE.find_inode(name) {
+    //新建的只能是普通文件
+    if flag.contains(OpenFlags::C | OpenFlags::DIRECTORY) {
+        return None;
+    }
+    let dentry = if flag.contains(OpenFlags::NOFOLLOW) {
+        lookup_path_nofollow(path)
+    } else {
+        lookup_path(path)
+    };
+    let (inode, parent_ino) = match dentry {
+        Some(dentry) => {
+            let inode = dentry.inode();
+            //O_NOFOLLOW时最后一个分量不能是符号链接
+            if inode.is_symlink() {
+                return None;
+            }
+            if flag.contains(OpenFlags::DIRECTORY) && !inode.is_dir() {
+                return None;
+            }
             //如果找到了存在就需要清空内容
-            inode.clear();
-            // DEBUG!("[kernel] create_inode:{}",inode.get_disk_inode());
-            Some(Arc::new(FNode::new(writeable, readable, inode)))
-        } else {
-            //没有找到就新建
-            ROOT_INODE.create(name).map(|inode| {
-                // DEBUG!("[kernel] create_inode:{}",inode.get_disk_inode());
-                Arc::new(FNode::new(writeable, readable, inode))
-            })
+            if flag.intersects(OpenFlags::C | OpenFlags::T) {
+                //目录不能被清空
+                if inode.is_dir() {
+                    return None;
+                }
+                inode.truncate(0);
+            }
+            (inode, dentry.parent().inode().ino())
         }
+        //没有找到就新建，不会通过悬空的符号链接新建目标
+        None if flag.contains(OpenFlags::C) => {
+            let (parent, name) = lookup_parent(path)?;
+            if parent.lookup(&name).is_some() {
+                return None;
+            }
+            let inode = parent
+                .inode()
+                .create(&name, InodeType::File, current_uid())?;
+            (inode, parent.inode().ino())
+        }
+        None => return None,
+    };
+    Some(Arc::new(FNode::new(
+        writeable,
+        readable,
+        flag.status(),
+        inode,
+        parent_ino,
+    )))
+}
+///打开文件或者设备节点，设备节点不会被新建或者清空
+pub fn open_path(path: &str, flag: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
+    if flag.contains(OpenFlags::DIRECTORY) {
+        return open_file(path, flag).map(|node| node as Arc<dyn File + Send + Sync>);
+    }
+    let dentry = if flag.contains(OpenFlags::NOFOLLOW) {
+        lookup_path_nofollow(path)
     } else {
-        ROOT_INODE.find_inode(name).map(|inode| {
-            if flag.contains(OpenFlags::T) {
-                //如果需要截断
-                inode.clear();
+        lookup_path(path)
+    };
+    if let Some(device) = dentry.and_then(|dentry| dentry.inode().open_device()) {
+        return Some(Arc::new(DevFile::new(device, flag)));
+    }
+    open_file(path, flag).map(|node| node as Arc<dyn File + Send + Sync>)
+}
+///新建目录
+pub fn make_dir(path: &str) -> isize {
+    match lookup_parent(path) {
+        Some((parent, name)) if parent.lookup(&name).is_none() => {
+            match parent.inode().create(&name, InodeType::Dir, current_uid()) {
+                Some(_) => 0,
+                None => -1,
+            }
+        }
+        _ => -1,
+    }
+}
+///新建指向target的符号链接，目标在使用时才解析，可以不存在
+pub fn make_symlink(target: &str, path: &str) -> isize {
+    match lookup_parent(path) {
+        Some((parent, name)) if parent.lookup(&name).is_none() => {
+            match parent.inode().symlink(&name, target, current_uid()) {
+                Some(_) => 0,
+                None => -1,
             }
-            // DEBUG!("[kernel] find_inode:{}",inode.get_disk_inode());
-            Arc::new(FNode::new(writeable, readable, inode))
-        })
+        }
+        _ => -1,
+    }
+}
+///修改文件的所有者，跟随符号链接
+pub fn chown_path(path: &str, uid: u32, gid: u32) -> isize {
+    match lookup_path(path) {
+        Some(dentry) if dentry.inode().set_owner(uid, gid) => 0,
+        _ => -1,
     }
 }
+///符号链接的目标，路径不是符号链接时返回None
+pub fn read_link(path: &str) -> Option<String> {
+    lookup_path_nofollow(path)?.inode().readlink()
+}
+///查看路径对应文件的信息，follow为false时不跟随最后一个符号链接
+pub fn stat_path(path: &str, follow: bool) -> Option<Stat> {
+    let dentry = if follow {
+        lookup_path(path)
+    } else {
+        lookup_path_nofollow(path)
+    };
+    Some(dentry?.inode().stat())
+}
+/// path所在的文件系统
+pub fn path_filesystem(path: &str) -> Option<Arc<dyn VfsFileSystem>> {
+    filesystem_of(lookup_path(path)?.inode().as_ref())
+}
 pub fn create_nlink_file(newfile: &str, oldfile: &str) -> isize {
-    if let Some(_) = ROOT_INODE.create_nlink(newfile, oldfile) {
-        return 0;
+    let inode = match lookup_path(oldfile) {
+        Some(dentry) => dentry.inode(),
+        None => return -1,
+    };
+    //不同文件系统之间不能建立硬链接
+    match lookup_parent(newfile) {
+        Some((parent, name)) if parent.inode().link(&name, &inode) => 0,
+        _ => -1,
     }
-    -1
 }
 pub fn delete_nlink_file(path: &str) -> isize {
-    ROOT_INODE.delete_nlink(path)
+    let (parent, name) = match lookup_parent(path) {
+        Some(pair) => pair,
+        None => return -1,
+    };
+    let parent = parent.inode();
+    //挂载点不能被删除，直接在inode中查找不会经过挂载点
+    match parent.lookup(&name) {
+        Some(inode) if !is_mountpoint(inode.as_ref()) => {}
+        _ => return -1,
+    }
+    match parent.unlink(&name) {
+        Some(inode) => {
+            //文件仍然被打开时推迟到关闭时回收
+            if !is_open(inode.as_ref()) {
+                inode.release();
+            }
+            0
+        }
+        None => -1,
+    }
+}
+/// 重命名或者移动文件，新旧路径需要位于同一个文件系统中
+pub fn rename_file(old_path: &str, new_path: &str) -> isize {
+    let (old_parent, old_name) = match lookup_parent(old_path) {
+        Some(pair) => pair,
+        None => return -1,
+    };
+    let (new_parent, new_name) = match lookup_parent(new_path) {
+        Some(pair) => pair,
+        None => return -1,
+    };
+    let (old_dir, new_dir) = (old_parent.inode(), new_parent.inode());
+    let inode = match old_dir.lookup(&old_name) {
+        Some(inode) => inode,
+        None => return -1,
+    };
+    //挂载点与被挂载覆盖的文件都不能被重命名
+    let covered = is_mountpoint(inode.as_ref())
+        || new_dir
+            .lookup(&new_name)
+            .map_or(false, |target| is_mountpoint(target.as_ref()));
+    if covered {
+        return -1;
+    }
+    //目录不能被移动到自身或者自己的子目录中
+    if inode.is_dir() && new_parent.is_within(inode.as_ref()) {
+        return -1;
+    }
+    match old_dir.rename(&old_name, &new_dir, &new_name) {
+        Ok(replaced) => {
+            if let Some(inode) = replaced {
+                if !is_open(inode.as_ref()) {
+                    inode.release();
+                }
+            }
+            0
+        }
+        Err(_) => -1,
+    }
 }
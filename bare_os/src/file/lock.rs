@@ -0,0 +1,272 @@
+///! 建议性文件锁，flock锁住整个文件，fcntl锁住文件中的一段字节
+///! 两种锁互不影响，都按照(设备号，inode号)记录，等待的任务挂在inode的等待队列上
+use super::{File, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::task::processor::copy_current_task;
+use crate::task::{add_task, block_current_run_next, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+//flock的操作
+const LOCK_SH: usize = 1;
+const LOCK_EX: usize = 2;
+const LOCK_NB: usize = 4;
+const LOCK_UN: usize = 8;
+
+//fcntl的记录锁命令
+pub const F_GETLK: usize = 5;
+pub const F_SETLK: usize = 6;
+pub const F_SETLKW: usize = 7;
+
+//记录锁的类型
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+
+/// 与Linux的struct flock布局相同
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    pub l_len: i64, //0表示一直到文件末尾之后
+    pub l_pid: i32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Owner {
+    File(usize),    //flock锁属于打开的文件，以FNode的地址区分
+    Process(usize), //fcntl锁属于进程
+}
+
+#[derive(Copy, Clone)]
+struct Lock {
+    owner: Owner,
+    exclusive: bool,
+    start: u64,
+    end: u64, //不包含end，u64::MAX表示到文件末尾之后
+}
+
+impl Lock {
+    fn conflicts(&self, other: &Lock) -> bool {
+        self.owner != other.owner
+            && core::mem::discriminant(&self.owner) == core::mem::discriminant(&other.owner)
+            && self.start < other.end
+            && other.start < self.end
+            && (self.exclusive || other.exclusive)
+    }
+}
+
+#[derive(Default)]
+struct InodeLocks {
+    locks: Vec<Lock>,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl InodeLocks {
+    /// 去掉owner在[start,end)上的锁，部分重叠的锁被切开
+    fn remove(&mut self, owner: Owner, start: u64, end: u64) {
+        let mut locks = Vec::new();
+        for lock in self.locks.drain(..) {
+            if lock.owner != owner || lock.end <= start || end <= lock.start {
+                locks.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                locks.push(Lock { end: start, ..lock });
+            }
+            if end < lock.end {
+                locks.push(Lock { start: end, ..lock });
+            }
+        }
+        self.locks = locks;
+    }
+    /// 锁发生变化后唤醒所有等待的任务，由它们重新检查
+    fn wake_all(&mut self) {
+        while let Some(task) = self.wait_queue.pop_front() {
+            add_task(task);
+        }
+    }
+}
+
+lazy_static! {
+    static ref LOCKS: Mutex<BTreeMap<(u64, usize), InodeLocks>> = Mutex::new(BTreeMap::new());
+}
+
+/// 加锁，冲突时wait为false返回false，否则阻塞到可以加锁为止
+fn acquire(key: (u64, usize), lock: Lock, wait: bool) -> bool {
+    loop {
+        let mut table = LOCKS.lock();
+        let state = table.entry(key).or_default();
+        if !state.locks.iter().any(|other| other.conflicts(&lock)) {
+            state.remove(lock.owner, lock.start, lock.end);
+            state.locks.push(lock);
+            state.wake_all();
+            return true;
+        }
+        if !wait {
+            return false;
+        }
+        state.wait_queue.push_back(copy_current_task().unwrap());
+        drop(table);
+        block_current_run_next();
+    }
+}
+
+fn release(key: (u64, usize), owner: Owner, start: u64, end: u64) {
+    let mut table = LOCKS.lock();
+    if let Some(state) = table.get_mut(&key) {
+        state.remove(owner, start, end);
+        state.wake_all();
+        if state.locks.is_empty() {
+            table.remove(&key);
+        }
+    }
+}
+
+/// 打开的文件对应的锁的所有者
+fn file_owner(file: &Arc<dyn File + Send + Sync>) -> Owner {
+    Owner::File(Arc::as_ptr(file) as *const u8 as usize)
+}
+
+/// flock系统调用，转换锁的类型时先释放原来的锁再等待
+pub fn flock(file: &Arc<dyn File + Send + Sync>, operation: usize) -> isize {
+    let key = match file.lock_key() {
+        Some(key) => key,
+        None => return -1,
+    };
+    let owner = file_owner(file);
+    let exclusive = match operation & !LOCK_NB {
+        LOCK_SH => false,
+        LOCK_EX => true,
+        LOCK_UN => {
+            release(key, owner, 0, u64::MAX);
+            return 0;
+        }
+        _ => return -1,
+    };
+    let lock = Lock {
+        owner,
+        exclusive,
+        start: 0,
+        end: u64::MAX,
+    };
+    if acquire(key, lock, false) {
+        return 0;
+    }
+    if operation & LOCK_NB != 0 {
+        return -1;
+    }
+    release(key, owner, 0, u64::MAX);
+    acquire(key, lock, true);
+    0
+}
+
+/// 打开的文件被关闭时释放它的flock锁，file是FNode的地址
+pub fn release_file_locks(key: (u64, usize), file: usize) {
+    release(key, Owner::File(file), 0, u64::MAX);
+}
+
+/// 进程不再打开文件时释放它在文件上的记录锁
+pub fn release_record_locks(key: (u64, usize), pid: usize) {
+    release(key, Owner::Process(pid), 0, u64::MAX);
+}
+
+/// 进程退出时释放它持有的所有记录锁
+pub fn release_process_locks(pid: usize) {
+    let keys: Vec<(u64, usize)> = LOCKS.lock().keys().copied().collect();
+    for key in keys {
+        release_record_locks(key, pid);
+    }
+}
+
+/// 将struct flock描述的范围转换为[start,end)
+fn lock_range(file: &Arc<dyn File + Send + Sync>, lock: &Flock) -> Option<(u64, u64)> {
+    let base = match lock.l_whence as usize {
+        SEEK_SET => 0,
+        SEEK_CUR => file.seek(0, SEEK_CUR) as i64,
+        SEEK_END => file.fstat().size,
+        _ => return None,
+    };
+    let start = base.checked_add(lock.l_start)?;
+    let (start, end) = match lock.l_len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start.checked_add(len)?)),
+        len => (start.checked_add(len)?, Some(start)),
+    };
+    if start < 0 {
+        return None;
+    }
+    Some((start as u64, end.map_or(u64::MAX, |end| end as u64)))
+}
+
+/// fcntl的F_GETLK、F_SETLK与F_SETLKW，记录锁属于调用的进程
+pub fn fcntl_lock(
+    file: &Arc<dyn File + Send + Sync>,
+    pid: usize,
+    cmd: usize,
+    flock: &mut Flock,
+) -> isize {
+    let key = match file.lock_key() {
+        Some(key) => key,
+        None => return -1,
+    };
+    let (start, end) = match lock_range(file, flock) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let owner = Owner::Process(pid);
+    let exclusive = match flock.l_type {
+        F_RDLCK => false,
+        F_WRLCK => true,
+        F_UNLCK if cmd != F_GETLK => {
+            release(key, owner, start, end);
+            return 0;
+        }
+        _ => return -1,
+    };
+    let lock = Lock {
+        owner,
+        exclusive,
+        start,
+        end,
+    };
+    match cmd {
+        F_GETLK => {
+            //返回第一个冲突的锁，没有冲突时只修改l_type
+            let table = LOCKS.lock();
+            let conflict = table
+                .get(&key)
+                .and_then(|state| state.locks.iter().find(|other| other.conflicts(&lock)));
+            match conflict {
+                Some(other) => {
+                    flock.l_type = if other.exclusive { F_WRLCK } else { F_RDLCK };
+                    flock.l_whence = SEEK_SET as i16;
+                    flock.l_start = other.start as i64;
+                    flock.l_len = if other.end == u64::MAX {
+                        0
+                    } else {
+                        (other.end - other.start) as i64
+                    };
+                    flock.l_pid = match other.owner {
+                        Owner::Process(pid) => pid as i32,
+                        Owner::File(_) => -1,
+                    };
+                }
+                None => flock.l_type = F_UNLCK,
+            }
+            0
+        }
+        F_SETLK | F_SETLKW => {
+            if acquire(key, lock, cmd == F_SETLKW) {
+                0
+            } else {
+                -1
+            }
+        }
+        _ => -1,
+    }
+}
@@ -139,6 +139,9 @@ impl File for Mail {
             }
         }
     }
+    fn kind(&self) -> &'static str {
+        "mail"
+    }
     fn fstat(&self) -> Stat {
         Stat::new(0, 0, StatMode::NULL, 1)
     }
@@ -1,20 +1,76 @@
 use crate::mm::page_table::UserBuffer;
 
+mod dev;
 mod ftable;
 mod inode;
+mod lock;
 mod mail;
 mod pipe;
 mod stdio;
 
+pub use dev::{BlockDevFile, Console, DevFile, FrameBuffer, Null, Random, Zero};
 pub use ftable::*;
 
-pub use inode::{create_nlink_file, delete_nlink_file, list_apps, open_file, FNode, OpenFlags};
+pub use inode::{
+    chown_path, create_nlink_file, delete_nlink_file, is_dev_busy, list_apps, make_dir,
+    make_symlink, open_file, open_path, path_filesystem, print_block_cache_stats, read_link,
+    rename_file, stat_path, sync_all, try_sync_all, FNode, OpenFlags,
+};
+pub use lock::{
+    fcntl_lock, flock, release_process_locks, release_record_locks, Flock, F_GETLK, F_SETLK,
+    F_SETLKW,
+};
 pub use mail::Mail;
 pub use pipe::Pipe;
 pub use stdio::{Stdin, Stdout};
 
+//lseek的whence参数
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+
 pub trait File: Send + Sync {
     fn read(&self, buf: UserBuffer) -> usize;
     fn write(&self, buf: UserBuffer) -> usize;
     fn fstat(&self) -> Stat;
+    /// 文件的种类，显示在/proc/<pid>/fd中
+    fn kind(&self) -> &'static str;
+    /// 将文件的修改写回磁盘，只有磁盘文件需要实现
+    fn fsync(&self) -> isize {
+        0
+    }
+    /// 移动读写位置，返回新的位置，管道等不支持定位的文件返回-1
+    fn seek(&self, _offset: isize, _whence: usize) -> isize {
+        -1
+    }
+    /// 从指定位置读取，不改变读写位置
+    fn pread(&self, _buf: UserBuffer, _offset: usize) -> isize {
+        -1
+    }
+    /// 写入指定位置，不改变读写位置
+    fn pwrite(&self, _buf: UserBuffer, _offset: usize) -> isize {
+        -1
+    }
+    /// 修改文件大小
+    fn truncate(&self, _len: usize) -> isize {
+        -1
+    }
+    /// 读取目录项，返回写入的字节数，读完时返回0，不是目录时返回-1
+    fn getdents(&self, _buf: UserBuffer) -> isize {
+        -1
+    }
+    /// 访问模式与文件状态标志，dup与fork得到的描述符共享同一份
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::RW
+    }
+    /// 修改APPEND与NONBLOCK，不支持的文件忽略
+    fn set_status_flags(&self, _flags: OpenFlags) {}
+    /// 可以加锁的文件返回所在inode的(设备号，inode号)
+    fn lock_key(&self) -> Option<(u64, usize)> {
+        None
+    }
+    /// 所在文件系统的使用情况，管道等不属于文件系统的文件返回None
+    fn statfs(&self) -> Option<StatFs> {
+        None
+    }
 }
@@ -1,5 +1,5 @@
 use crate::config::RING_BUFFER_SIZE;
-use crate::file::{File, Stat, StatMode};
+use crate::file::{File, OpenFlags, Stat, StatMode};
 use crate::mm::page_table::UserBuffer;
 use crate::println;
 use crate::task::suspend_current_run_next;
@@ -10,6 +10,7 @@ pub struct Pipe {
     readable: bool,
     writeable: bool,
     buffer: Arc<Mutex<RingBuffer>>,
+    status: Mutex<OpenFlags>, //NONBLOCK时缓冲区空或满不再等待
 }
 #[derive(Copy, Clone, PartialEq)]
 pub enum RingBufferStatus {
@@ -88,6 +89,7 @@ impl Pipe {
             readable: true,
             writeable: false,
             buffer,
+            status: Mutex::new(OpenFlags::empty()),
         }
     }
     fn write_from_buffer(buffer: Arc<Mutex<RingBuffer>>) -> Self {
@@ -95,6 +97,7 @@ impl Pipe {
             readable: false,
             writeable: true,
             buffer,
+            status: Mutex::new(OpenFlags::empty()),
         }
     }
     pub fn new() -> (Arc<Pipe>, Arc<Pipe>) {
@@ -104,6 +107,16 @@ impl Pipe {
         ringbuffer.lock().set_write_end(&write_end);
         (read_end, write_end)
     }
+    /// 非阻塞时没有读写任何内容返回-1
+    fn nonblock_result(&self, size: usize) -> Option<usize> {
+        if !self.status.lock().contains(OpenFlags::NONBLOCK) {
+            None
+        } else if size == 0 {
+            Some(-1isize as usize)
+        } else {
+            Some(size)
+        }
+    }
 }
 
 // todo!(检查读写是否正确)
@@ -120,6 +133,9 @@ impl File for Pipe {
                     return read_size; //如果写端已经全部关闭，那么就不需要再等待
                 }
                 drop(buffer);
+                if let Some(size) = self.nonblock_result(read_size) {
+                    return size;
+                }
                 suspend_current_run_next(); //等待之后的写端往这里面写内容
                 continue;
             }
@@ -144,6 +160,9 @@ impl File for Pipe {
             let available_size = buffer.available_write(); //查看可写数量
             if available_size == 0 {
                 drop(buffer);
+                if let Some(size) = self.nonblock_result(write_size) {
+                    return size;
+                }
                 suspend_current_run_next(); //等待之后的读端往这里面读内容
                 continue;
             }
@@ -160,6 +179,15 @@ impl File for Pipe {
             }
         }
     }
+    fn kind(&self) -> &'static str {
+        "pipe"
+    }
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::access_mode(self.readable, self.writeable) | *self.status.lock()
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        *self.status.lock() = flags;
+    }
     fn fstat(&self) -> Stat {
         Stat::new(0, 0, StatMode::NULL, 1)
     }
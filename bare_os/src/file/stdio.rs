@@ -1,4 +1,4 @@
-use crate::file::{File, Stat, StatMode};
+use crate::file::{File, OpenFlags, Stat, StatMode};
 use crate::mm::page_table::UserBuffer;
 use crate::print;
 use crate::sbi::console_getchar;
@@ -30,6 +30,12 @@ impl File for Stdin {
     fn write(&self, _buf: UserBuffer) -> usize {
         panic!("Stdin unsupported write");
     }
+    fn kind(&self) -> &'static str {
+        "stdin"
+    }
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::R
+    }
     fn fstat(&self) -> Stat {
         Stat::new(0, 0, StatMode::NULL, 1)
     }
@@ -45,6 +51,12 @@ impl File for Stdout {
         }
         buf.len()
     }
+    fn kind(&self) -> &'static str {
+        "stdout"
+    }
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::W
+    }
     fn fstat(&self) -> Stat {
         Stat::new(0, 0, StatMode::NULL, 1)
     }
@@ -0,0 +1,145 @@
+///! 目录项与路径解析
+///! easyfs等文件系统的目录中没有.与..，由Dentry记录的父目录处理
+///! 符号链接在解析路径时被替换为目标路径的目录项
+use super::mount::{covering, root_inode};
+use super::{inode_key, VfsInode};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// 路径解析得到的目录项，记录名称与父目录，被挂载的根目录的父目录是挂载点所在的目录
+pub struct Dentry {
+    name: String,
+    inode: Arc<dyn VfsInode>,
+    parent: Option<Arc<Dentry>>, //只有根目录没有父目录
+}
+
+impl Dentry {
+    pub fn root() -> Arc<Dentry> {
+        Arc::new(Self {
+            name: String::from("/"),
+            inode: root_inode(),
+            parent: None,
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn inode(&self) -> Arc<dyn VfsInode> {
+        self.inode.clone()
+    }
+    pub fn parent(self: &Arc<Self>) -> Arc<Dentry> {
+        self.parent.clone().unwrap_or_else(|| self.clone())
+    }
+    /// 从根目录开始的绝对路径
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut dentry = self;
+        while let Some(parent) = dentry.parent.as_ref() {
+            names.push(dentry.name.as_str());
+            dentry = parent;
+        }
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+    /// 目录项是否就是inode或者位于inode之下，目录不能被移动到这样的位置
+    pub fn is_within(&self, inode: &dyn VfsInode) -> bool {
+        let key = inode_key(inode);
+        let mut dentry = self;
+        loop {
+            if inode_key(dentry.inode.as_ref()) == key {
+                return true;
+            }
+            match dentry.parent.as_ref() {
+                Some(parent) => dentry = parent,
+                None => return false,
+            }
+        }
+    }
+    /// 查找子目录项，目录被挂载时返回被挂载文件系统的根目录
+    pub fn lookup(self: &Arc<Self>, name: &str) -> Option<Arc<Dentry>> {
+        match name {
+            "" | "." => Some(self.clone()),
+            ".." => Some(self.parent()),
+            _ => {
+                let mut inode = self.inode.lookup(name)?;
+                //同一个目录可能被多次挂载，只有最后一次挂载可见
+                while let Some(root) = covering(inode.as_ref()) {
+                    inode = root;
+                }
+                Some(Arc::new(Self {
+                    name: String::from(name),
+                    inode,
+                    parent: Some(self.clone()),
+                }))
+            }
+        }
+    }
+}
+
+/// 解析一个路径时最多跟随的符号链接数，超过时认为符号链接形成了环
+const MAX_SYMLINKS: usize = 40;
+
+/// 从start开始解析路径，绝对路径从根目录开始
+/// 中间的符号链接总是被跟随，最后一个分量只有follow为true时才被跟随
+/// links记录已经跟随的符号链接数，包括目标路径中嵌套的符号链接
+fn walk(start: Arc<Dentry>, path: &str, follow: bool, links: &mut usize) -> Option<Arc<Dentry>> {
+    let mut dentry = if path.starts_with('/') {
+        Dentry::root()
+    } else {
+        start
+    };
+    let mut names = path.split('/').peekable();
+    while let Some(name) = names.next() {
+        let next = dentry.lookup(name)?;
+        //以/结尾的路径的最后一个分量为空，之前的符号链接也需要跟随
+        if next.inode.is_symlink() && (follow || names.peek().is_some()) {
+            *links += 1;
+            if *links > MAX_SYMLINKS {
+                return None;
+            }
+            //相对路径的目标从符号链接所在的目录开始解析
+            let target = next.inode.readlink()?;
+            dentry = walk(dentry, &target, true, links)?;
+        } else {
+            dentry = next;
+        }
+    }
+    Some(dentry)
+}
+
+/// 没有当前目录的概念，相对路径也从根目录开始解析
+/// 路径中的符号链接都会被跟随，形成环时返回None
+pub fn lookup_path(path: &str) -> Option<Arc<Dentry>> {
+    walk(Dentry::root(), path, true, &mut 0)
+}
+
+/// 与lookup_path相同，但是最后一个分量是符号链接时返回符号链接本身
+pub fn lookup_path_nofollow(path: &str) -> Option<Arc<Dentry>> {
+    walk(Dentry::root(), path, false, &mut 0)
+}
+
+/// 解析路径的父目录，返回父目录与最后一个分量
+/// 最后一个分量为空、.或者..时没有可以操作的目录项，返回None
+pub fn lookup_parent(path: &str) -> Option<(Arc<Dentry>, String)> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    if matches!(name, "" | "." | "..") {
+        return None;
+    }
+    let parent = lookup_path(dir)?;
+    if !parent.inode.is_dir() {
+        return None;
+    }
+    Some((parent, String::from(name)))
+}
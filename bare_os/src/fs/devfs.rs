@@ -0,0 +1,140 @@
+///! 设备文件系统，挂载在/dev
+///! 目录中只有固定的设备节点，节点打开后直接得到设备对应的文件
+use super::{alloc_dev, InodeType, VfsFileSystem, VfsInode, NAME_MAX};
+use crate::config::PAGE_SIZE;
+use crate::driver::{block_device, gpu_device};
+use crate::file::{
+    BlockDevFile, Console, File, FrameBuffer, Null, Random, Stat, StatFs, StatMode, Zero,
+};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+
+const DEVFS_SUPER_MAGIC: i64 = 0x1373;
+
+pub struct DevFileSystem {
+    dev: u64,
+}
+
+impl DevFileSystem {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { dev: alloc_dev() })
+    }
+}
+
+impl VfsFileSystem for DevFileSystem {
+    fn fs_type(&self) -> &'static str {
+        "devfs"
+    }
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(DevInode {
+            dev: self.dev,
+            index: None,
+        })
+    }
+    fn statfs(&self) -> StatFs {
+        StatFs::new(DEVFS_SUPER_MAGIC, self.dev, PAGE_SIZE, NAME_MAX)
+    }
+}
+
+/// 所有可能出现的设备，块设备与显卡只有在启动时连接了才会出现
+const DEVICES: [&str; 7] = ["console", "null", "zero", "random", "vda", "vdb", "fb0"];
+
+fn is_present(name: &str) -> bool {
+    match name {
+        "vda" | "vdb" => block_device(name).is_some(),
+        "fb0" => gpu_device().is_some(),
+        _ => true,
+    }
+}
+
+/// 根目录的index为None，设备节点的index是在DEVICES中的位置
+pub struct DevInode {
+    dev: u64,
+    index: Option<usize>,
+}
+
+impl DevInode {
+    fn name(&self) -> Option<&'static str> {
+        self.index.map(|index| DEVICES[index])
+    }
+}
+
+impl VfsInode for DevInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dev(&self) -> u64 {
+        self.dev
+    }
+    fn ino(&self) -> usize {
+        self.index.map_or(1, |index| index + 2)
+    }
+    fn inode_type(&self) -> InodeType {
+        if self.index.is_none() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+    fn stat(&self) -> Stat {
+        let mode = match self.name() {
+            None => 0o040755,
+            Some("vda" | "vdb") => 0o060660,
+            Some(_) => 0o020666,
+        };
+        let nlink = if self.index.is_none() { 2 } else { 1 };
+        Stat::new(
+            self.dev,
+            self.ino() as u64,
+            StatMode::from_bits_truncate(mode),
+            nlink,
+        )
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    //设备节点本身没有内容，读写通过open_device得到的设备文件进行
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, ()> {
+        Ok(0)
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, ()> {
+        Ok(0)
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        if self.index.is_some() || !is_present(name) {
+            return None;
+        }
+        let index = DEVICES.iter().position(|device| *device == name)?;
+        Some(Arc::new(DevInode {
+            dev: self.dev,
+            index: Some(index),
+        }))
+    }
+    fn ls(&self) -> Vec<String> {
+        if self.index.is_some() {
+            return Vec::new();
+        }
+        DEVICES
+            .iter()
+            .filter(|name| is_present(name))
+            .map(|name| name.to_string())
+            .collect()
+    }
+    fn open_device(&self) -> Option<Arc<dyn File + Send + Sync>> {
+        let name = self.name()?;
+        let file: Arc<dyn File + Send + Sync> = match name {
+            "console" => Arc::new(Console),
+            "null" => Arc::new(Null),
+            "zero" => Arc::new(Zero),
+            "random" => Arc::new(Random),
+            "fb0" => Arc::new(FrameBuffer::new(gpu_device()?)),
+            _ => Arc::new(BlockDevFile::new(name, block_device(name)?)),
+        };
+        Some(file)
+    }
+}
@@ -0,0 +1,227 @@
+///! easyfs在虚拟文件系统中的实现
+use super::{alloc_dev, InodeType, VfsFileSystem, VfsInode};
+use crate::file::{Stat, StatFs, StatMode};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use easyfs::{BlockDevice, FileSystem, Inode, Metadata, BLOCK_SIZE, EFS_MAGIC, NAME_LENGTH_MAX};
+use spin::mutex::Mutex;
+
+pub struct EfsFileSystem {
+    dev: u64,
+    device: Arc<dyn BlockDevice>,
+    fs: Arc<Mutex<FileSystem>>,
+}
+
+impl EfsFileSystem {
+    /// 设备上不是可以打开的easyfs时返回None
+    pub fn open(device: Arc<dyn BlockDevice>) -> Option<Arc<Self>> {
+        let fs = FileSystem::try_open(device.clone())?;
+        Some(Arc::new(Self {
+            dev: alloc_dev(),
+            device,
+            fs,
+        }))
+    }
+}
+
+impl VfsFileSystem for EfsFileSystem {
+    fn fs_type(&self) -> &'static str {
+        "easyfs"
+    }
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(EfsInode {
+            dev: self.dev,
+            inode: Arc::new(FileSystem::root_inode(&self.fs)),
+        })
+    }
+    fn sync(&self) -> bool {
+        easyfs::block_cache_sync_device(&self.device).is_ok()
+    }
+    fn statfs(&self) -> StatFs {
+        let mut statfs = StatFs::new(EFS_MAGIC as i64, self.dev, BLOCK_SIZE, NAME_LENGTH_MAX);
+        //读取位图失败时只报告类型与块大小
+        let usage = match self.fs.lock().statfs() {
+            Ok(usage) => usage,
+            Err(_) => return statfs,
+        };
+        statfs.blocks = usage.blocks as u64;
+        statfs.bfree = usage.free_blocks as u64;
+        statfs.bavail = usage.free_blocks as u64;
+        statfs.files = usage.inodes as u64;
+        statfs.ffree = usage.free_inodes as u64;
+        statfs
+    }
+    fn quota(&self, uid: u32) -> Option<(usize, usize)> {
+        let quota = self.fs.lock().quota(uid)?;
+        Some((quota.limit, quota.used))
+    }
+    fn set_quota(&self, uid: u32, limit: usize) -> bool {
+        let limit = if limit == 0 { None } else { Some(limit) };
+        self.fs.lock().set_quota(uid, limit) == Ok(true)
+    }
+}
+
+pub struct EfsInode {
+    dev: u64,
+    inode: Arc<Inode>,
+}
+
+impl EfsInode {
+    fn wrap(&self, inode: Arc<Inode>) -> Arc<dyn VfsInode> {
+        Arc::new(EfsInode {
+            dev: self.dev,
+            inode,
+        })
+    }
+}
+
+impl VfsInode for EfsInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dev(&self) -> u64 {
+        self.dev
+    }
+    fn ino(&self) -> usize {
+        self.inode.get_disk_inode()
+    }
+    fn inode_type(&self) -> InodeType {
+        //读取失败时当作普通文件，之后的读写同样会失败
+        match self.inode.get_disk_type() {
+            Ok(0o040000) => InodeType::Dir,
+            Ok(0o120000) => InodeType::Symlink,
+            _ => InodeType::File,
+        }
+    }
+    fn stat(&self) -> Stat {
+        match self.inode.metadata() {
+            Ok(metadata) => metadata_stat(self.dev, metadata),
+            Err(_) => Stat::new(self.dev, self.ino() as u64, StatMode::empty(), 0),
+        }
+    }
+    fn size(&self) -> usize {
+        self.inode.get_file_size().unwrap_or(0)
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, ()> {
+        self.inode.read_at(offset, buf).map_err(|_| ())
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, ()> {
+        self.inode.write_at(offset, buf).map_err(|_| ())
+    }
+    fn truncate(&self, size: usize) -> bool {
+        self.inode.truncate(size)
+    }
+    fn fsync(&self) -> bool {
+        self.inode.fsync().is_ok()
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        //easyfs只能在目录中查找
+        if !self.is_dir() {
+            return None;
+        }
+        self.inode.find_inode(name).map(|inode| self.wrap(inode))
+    }
+    fn ls(&self) -> Vec<String> {
+        if !self.is_dir() {
+            return Vec::new();
+        }
+        self.inode.ls().unwrap_or_default()
+    }
+    fn read_dir(
+        &self,
+        mut cursor: usize,
+        f: &mut dyn FnMut(usize, &str, Arc<dyn VfsInode>) -> bool,
+    ) {
+        if !self.is_dir() {
+            return;
+        }
+        //读取失败时当作目录已经结束
+        while let Ok(Some((next, name, inode))) = self.inode.read_dirent(cursor) {
+            if !f(next, &name, self.wrap(inode)) {
+                return;
+            }
+            cursor = next;
+        }
+    }
+    fn create(&self, name: &str, kind: InodeType, uid: u32) -> Option<Arc<dyn VfsInode>> {
+        if !self.is_dir() {
+            return None;
+        }
+        let inode = match kind {
+            InodeType::File => self.inode.create_as(name, uid),
+            InodeType::Dir => self.inode.mkdir_as(name, uid),
+            //符号链接需要同时写入目标，由symlink新建
+            InodeType::Symlink => None,
+        };
+        inode.map(|inode| self.wrap(inode))
+    }
+    fn symlink(&self, name: &str, target: &str, uid: u32) -> Option<Arc<dyn VfsInode>> {
+        if !self.is_dir() {
+            return None;
+        }
+        self.inode
+            .symlink_as(name, target, uid)
+            .map(|inode| self.wrap(inode))
+    }
+    fn set_owner(&self, uid: u32, gid: u32) -> bool {
+        self.inode.set_owner(uid, gid) == Ok(true)
+    }
+    fn readlink(&self) -> Option<String> {
+        self.inode.readlink()
+    }
+    fn link(&self, name: &str, inode: &Arc<dyn VfsInode>) -> bool {
+        //只能链接同一个文件系统中的文件
+        match inode.as_any().downcast_ref::<EfsInode>() {
+            Some(target) if target.dev == self.dev && self.is_dir() => {
+                self.inode.link(name, &target.inode)
+            }
+            _ => false,
+        }
+    }
+    fn unlink(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        if !self.is_dir() {
+            return None;
+        }
+        self.inode.unlink(name).map(|inode| self.wrap(inode))
+    }
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn VfsInode>,
+        new_name: &str,
+    ) -> Result<Option<Arc<dyn VfsInode>>, ()> {
+        //只能在同一个文件系统中移动
+        let new_dir = match new_dir.as_any().downcast_ref::<EfsInode>() {
+            Some(dir) if dir.dev == self.dev && self.is_dir() => dir,
+            _ => return Err(()),
+        };
+        self.inode
+            .rename(old_name, &new_dir.inode, new_name)
+            .map(|replaced| replaced.map(|inode| self.wrap(inode)))
+            .map_err(|_| ())
+    }
+    fn release(&self) {
+        self.inode.release();
+    }
+}
+
+///将文件系统中的元数据转换为Stat
+fn metadata_stat(dev: u64, metadata: Metadata) -> Stat {
+    let mut stat = Stat::new(
+        dev,
+        metadata.ino as u64,
+        StatMode::from_bits_truncate(metadata.mode),
+        metadata.nlink,
+    );
+    stat.uid = metadata.uid;
+    stat.gid = metadata.gid;
+    stat.size = metadata.size as i64;
+    stat.blksize = BLOCK_SIZE as i32;
+    stat.blocks = metadata.blocks as i64;
+    stat.atime = metadata.atime as i64;
+    stat.mtime = metadata.mtime as i64;
+    stat.ctime = metadata.ctime as i64;
+    stat
+}
@@ -0,0 +1,159 @@
+///! 虚拟文件系统层
+///! 内核通过VfsInode访问文件，不再直接依赖easyfs，不同的文件系统可以挂载到任意目录下
+///! 路径解析由Dentry完成，经过挂载点时切换到被挂载文件系统的根目录
+mod dentry;
+mod devfs;
+mod efs;
+mod mount;
+mod procfs;
+mod tmpfs;
+mod vfat;
+
+use crate::file::{File, Stat, StatFs};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub use dentry::{lookup_parent, lookup_path, lookup_path_nofollow};
+pub use devfs::DevFileSystem;
+pub use efs::EfsFileSystem;
+pub use mount::{filesystem_of, is_mounted, is_mountpoint, mount, root_inode, sync_mounts, umount};
+pub use procfs::ProcFileSystem;
+pub use tmpfs::TmpFileSystem;
+pub use vfat::VfatFileSystem;
+
+/// 索引节点的类型
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InodeType {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// 各个文件系统的索引节点需要实现的接口
+/// 只读或者不支持目录的文件系统可以使用默认实现
+pub trait VfsInode: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn dev(&self) -> u64; //所在文件系统的设备号
+    fn ino(&self) -> usize;
+    fn inode_type(&self) -> InodeType;
+    fn stat(&self) -> Stat;
+    fn size(&self) -> usize;
+    /// 读写失败并且没有读写任何字节时返回Err
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, ()>;
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, ()>;
+    /// 只读的文件写入时什么都不做，其它文件没有写入任何字节表示空间不足
+    fn read_only(&self) -> bool {
+        false
+    }
+    /// 修改文件大小，超出文件系统的限制时返回false
+    fn truncate(&self, _size: usize) -> bool {
+        false
+    }
+    /// 将文件的修改写回存储设备，写回失败时返回false
+    fn fsync(&self) -> bool {
+        true
+    }
+    /// 在目录中按名称查找，不处理.与..
+    fn lookup(&self, _name: &str) -> Option<Arc<dyn VfsInode>> {
+        None
+    }
+    /// 目录下所有文件的名称
+    fn ls(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// 从位置cursor开始按顺序访问目录中的文件，f的参数为(下一个位置, 名称, 文件)，返回false时停止
+    /// 位置由文件系统决定，删除其它文件时不变，默认使用ls中的序号
+    fn read_dir(&self, cursor: usize, f: &mut dyn FnMut(usize, &str, Arc<dyn VfsInode>) -> bool) {
+        for (index, name) in self.ls().iter().enumerate().skip(cursor) {
+            //列出之后可能已经被删除
+            if let Some(child) = self.lookup(name) {
+                if !f(index + 1, name, child) {
+                    return;
+                }
+            }
+        }
+    }
+    /// 在目录中新建属于uid的文件或者子目录，已经存在时返回None
+    fn create(&self, _name: &str, _kind: InodeType, _uid: u32) -> Option<Arc<dyn VfsInode>> {
+        None
+    }
+    /// 在目录中新建属于uid的指向target的符号链接，不支持符号链接的文件系统返回None
+    fn symlink(&self, _name: &str, _target: &str, _uid: u32) -> Option<Arc<dyn VfsInode>> {
+        None
+    }
+    /// 修改文件的所有者，不记录所有者的文件系统或者超过新所有者的限额时返回false
+    fn set_owner(&self, _uid: u32, _gid: u32) -> bool {
+        false
+    }
+    /// 符号链接的目标，不是符号链接时返回None
+    fn readlink(&self) -> Option<String> {
+        None
+    }
+    /// 为同一个文件系统中的inode添加硬链接
+    fn link(&self, _name: &str, _inode: &Arc<dyn VfsInode>) -> bool {
+        false
+    }
+    /// 删除目录项，返回被删除的文件
+    fn unlink(&self, _name: &str) -> Option<Arc<dyn VfsInode>> {
+        None
+    }
+    /// 重命名为同一个文件系统中new_dir目录下的new_name，new_dir可以是自身，返回被替换的文件
+    fn rename(
+        &self,
+        _old_name: &str,
+        _new_dir: &Arc<dyn VfsInode>,
+        _new_name: &str,
+    ) -> Result<Option<Arc<dyn VfsInode>>, ()> {
+        Err(())
+    }
+    /// 文件没有硬链接并且不再被打开时回收
+    fn release(&self) {}
+    /// 设备节点打开时得到的设备文件，普通文件与目录返回None
+    fn open_device(&self) -> Option<Arc<dyn File + Send + Sync>> {
+        None
+    }
+    fn is_dir(&self) -> bool {
+        self.inode_type() == InodeType::Dir
+    }
+    fn is_symlink(&self) -> bool {
+        self.inode_type() == InodeType::Symlink
+    }
+}
+
+/// 可以被挂载的文件系统
+pub trait VfsFileSystem: Send + Sync {
+    fn fs_type(&self) -> &'static str;
+    fn root(&self) -> Arc<dyn VfsInode>;
+    /// 写回所有的修改，卸载前调用，写回失败时返回false
+    fn sync(&self) -> bool {
+        true
+    }
+    /// 块与inode的使用情况
+    fn statfs(&self) -> StatFs;
+    /// uid的块限额与已经占用的块数，不支持或者没有设置限额时返回None
+    fn quota(&self, _uid: u32) -> Option<(usize, usize)> {
+        None
+    }
+    /// 设置uid的块限额，0表示取消限额，不支持限额时返回false
+    fn set_quota(&self, _uid: u32, _limit: usize) -> bool {
+        false
+    }
+}
+
+//不限制文件名长度的文件系统报告的最大长度
+const NAME_MAX: usize = 255;
+
+static NEXT_DEV: AtomicU64 = AtomicU64::new(1);
+
+/// 为新的文件系统实例分配设备号，用于区分不同文件系统中编号相同的inode
+pub fn alloc_dev() -> u64 {
+    NEXT_DEV.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 同一个文件在所有文件系统中唯一的标识
+pub fn inode_key(inode: &dyn VfsInode) -> (u64, usize) {
+    (inode.dev(), inode.ino())
+}
@@ -0,0 +1,193 @@
+///! 挂载表
+///! 第一项是根文件系统，其它每一项记录被覆盖的目录与挂载在其上的文件系统
+use super::{
+    inode_key, lookup_path, DevFileSystem, EfsFileSystem, InodeType, ProcFileSystem, TmpFileSystem,
+    VfatFileSystem, VfsFileSystem, VfsInode,
+};
+use crate::config::BLOCK_CACHE_BLOCKS;
+use crate::driver::{block_device, ROOT_BLOCK_DEVICE};
+use crate::file::is_dev_busy;
+use crate::timer::get_costtime;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+struct Mount {
+    path: String,   //挂载时的路径
+    source: String, //块设备名称，proc等文件系统为其名称
+    fs: Arc<dyn VfsFileSystem>,
+    root_key: (u64, usize),        //文件系统根目录的标识
+    covered: Option<(u64, usize)>, //被覆盖的目录，根文件系统没有
+}
+
+lazy_static! {
+    static ref MOUNTS: Mutex<Vec<Mount>> = Mutex::new({
+        //文件系统使用内核时钟记录时间
+        easyfs::set_clock(|| (get_costtime() / 1_000_000) as u32);
+        easyfs::set_block_cache_capacity(BLOCK_CACHE_BLOCKS);
+        //根文件系统可以是FAT32，其它情况按照easyfs打开
+        let device = block_device(ROOT_BLOCK_DEVICE).unwrap();
+        let fs: Arc<dyn VfsFileSystem> = match VfatFileSystem::open(device.clone()) {
+            Some(fs) => fs,
+            None => match EfsFileSystem::open(device) {
+                Some(fs) => fs,
+                None => panic!("[kernel] no filesystem found on {}", ROOT_BLOCK_DEVICE),
+            },
+        };
+        let root = fs.root();
+        let mut mounts = vec![Mount {
+            path: String::from("/"),
+            source: String::from(ROOT_BLOCK_DEVICE),
+            fs,
+            root_key: inode_key(root.as_ref()),
+            covered: None,
+        }];
+        //内核自己提供的文件系统挂载在根目录下的固定位置
+        let builtin: [(&str, Arc<dyn VfsFileSystem>); 3] = [
+            ("proc", ProcFileSystem::new()),
+            ("dev", DevFileSystem::new()),
+            ("tmp", TmpFileSystem::new()),
+        ];
+        for (name, fs) in builtin {
+            if let Some(mount) = mount_builtin(&root, name, fs) {
+                mounts.push(mount);
+            }
+        }
+        mounts
+    });
+}
+
+/// 将不需要块设备的文件系统挂载到根目录下的name目录，目录不存在时新建
+fn mount_builtin(
+    root: &Arc<dyn VfsInode>,
+    name: &str,
+    fs: Arc<dyn VfsFileSystem>,
+) -> Option<Mount> {
+    let dir = root
+        .lookup(name)
+        .or_else(|| root.create(name, InodeType::Dir, 0))
+        .filter(|dir| dir.is_dir())?;
+    Some(Mount {
+        path: format!("/{}", name),
+        source: String::from(name),
+        root_key: inode_key(fs.root().as_ref()),
+        fs,
+        covered: Some(inode_key(dir.as_ref())),
+    })
+}
+
+/// 根文件系统的根目录
+pub fn root_inode() -> Arc<dyn VfsInode> {
+    MOUNTS.lock()[0].fs.root()
+}
+
+/// 目录被挂载时返回挂载在其上的文件系统的根目录
+pub fn covering(inode: &dyn VfsInode) -> Option<Arc<dyn VfsInode>> {
+    let key = inode_key(inode);
+    MOUNTS
+        .lock()
+        .iter()
+        .rev()
+        .find(|mount| mount.covered == Some(key))
+        .map(|mount| mount.fs.root())
+}
+
+/// inode所在的文件系统
+pub fn filesystem_of(inode: &dyn VfsInode) -> Option<Arc<dyn VfsFileSystem>> {
+    let dev = inode.dev();
+    MOUNTS
+        .lock()
+        .iter()
+        .find(|mount| mount.root_key.0 == dev)
+        .map(|mount| mount.fs.clone())
+}
+
+/// 挂载点不能被删除或者重命名
+pub fn is_mountpoint(inode: &dyn VfsInode) -> bool {
+    covering(inode).is_some()
+}
+
+/// 根据类型在块设备上打开文件系统，proc等不需要块设备的文件系统忽略source
+fn open_fs(source: &str, fs_type: &str) -> Option<Arc<dyn VfsFileSystem>> {
+    match fs_type {
+        "easyfs" => Some(EfsFileSystem::open(block_device(source)?)?),
+        "proc" => Some(ProcFileSystem::new()),
+        "devfs" => Some(DevFileSystem::new()),
+        "tmpfs" => Some(TmpFileSystem::new()),
+        "vfat" => Some(VfatFileSystem::open(block_device(source)?)?),
+        _ => None,
+    }
+}
+
+/// source上的文件系统是否已经被挂载
+pub fn is_mounted(source: &str) -> bool {
+    MOUNTS.lock().iter().any(|mount| mount.source == source)
+}
+
+/// 将source上的文件系统挂载到target目录
+pub fn mount(source: &str, target: &str, fs_type: &str) -> isize {
+    let dentry = match lookup_path(target) {
+        Some(dentry) if dentry.inode().is_dir() => dentry,
+        _ => return -1,
+    };
+    let source = source.trim_start_matches("/dev/");
+    //同一个块设备同时只能被一个文件系统使用
+    if is_mounted(source) {
+        return -1;
+    }
+    let fs = match open_fs(source, fs_type) {
+        Some(fs) => fs,
+        None => return -1,
+    };
+    let root_key = inode_key(fs.root().as_ref());
+    MOUNTS.lock().push(Mount {
+        path: dentry.path(),
+        source: String::from(source),
+        fs,
+        root_key,
+        covered: Some(inode_key(dentry.inode().as_ref())),
+    });
+    0
+}
+
+/// 卸载挂载在target上的文件系统
+/// 文件系统中还有打开的文件、其它挂载点或者写回失败时失败
+pub fn umount(target: &str) -> isize {
+    let key = match lookup_path(target) {
+        Some(dentry) => inode_key(dentry.inode().as_ref()),
+        None => return -1,
+    };
+    let mut mounts = MOUNTS.lock();
+    let index = match mounts.iter().rposition(|mount| mount.root_key == key) {
+        Some(index) if index != 0 => index,
+        _ => return -1,
+    };
+    let dev = key.0;
+    if mounts
+        .iter()
+        .any(|mount| mount.covered.map(|(d, _)| d) == Some(dev))
+        || is_dev_busy(dev)
+    {
+        return -1;
+    }
+    //先写回修改，写回失败时文件系统保持挂载，修改仍然留在缓存中
+    if !mounts[index].fs.sync() {
+        return -1;
+    }
+    mounts.remove(index);
+    0
+}
+
+/// 写回所有文件系统的修改，有文件系统写回失败时返回false
+pub fn sync_mounts() -> bool {
+    let filesystems: Vec<_> = MOUNTS.lock().iter().map(|mount| mount.fs.clone()).collect();
+    let mut synced = true;
+    for fs in filesystems.iter() {
+        synced &= fs.sync();
+    }
+    synced
+}
@@ -0,0 +1,404 @@
+///! 进程文件系统，挂载在/proc
+///! 文件内容在读取时根据内核的当前状态生成，不占用存储空间
+///! 根目录下是meminfo、uptime、sched三个全局文件以及每个进程的目录
+use super::{alloc_dev, InodeType, VfsFileSystem, VfsInode, NAME_MAX};
+use crate::config::PAGE_SIZE;
+use crate::file::{Stat, StatFs, StatMode};
+use crate::mm::frame_allocator::frame_stats;
+use crate::mm::{MapPermission, MapType};
+use crate::system_allocator::heap_stats;
+use crate::task::{all_processes, find_process, sched_stats, ProcessControlBlock, TaskStatus};
+use crate::timer::get_costtime;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::Write;
+
+const PROC_SUPER_MAGIC: i64 = 0x9fa0;
+
+pub struct ProcFileSystem {
+    dev: u64,
+}
+
+impl ProcFileSystem {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { dev: alloc_dev() })
+    }
+}
+
+impl VfsFileSystem for ProcFileSystem {
+    fn fs_type(&self) -> &'static str {
+        "proc"
+    }
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(ProcInode {
+            dev: self.dev,
+            entry: ProcEntry::Root,
+        })
+    }
+    fn statfs(&self) -> StatFs {
+        StatFs::new(PROC_SUPER_MAGIC, self.dev, PAGE_SIZE, NAME_MAX)
+    }
+}
+
+/// /proc中的文件，进程相关的文件只记录pid，进程退出后读取不到内容
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ProcEntry {
+    Root,
+    MemInfo,
+    Uptime,
+    Sched,
+    PidDir(usize),
+    Status(usize),
+    Cmdline(usize),
+    Maps(usize),
+    Fd(usize),
+    Task(usize),
+}
+
+const GLOBAL_FILES: [&str; 3] = ["meminfo", "uptime", "sched"];
+const PID_FILES: [&str; 5] = ["status", "cmdline", "maps", "fd", "task"];
+
+impl ProcEntry {
+    fn global(name: &str) -> Option<Self> {
+        match name {
+            "meminfo" => Some(Self::MemInfo),
+            "uptime" => Some(Self::Uptime),
+            "sched" => Some(Self::Sched),
+            _ => None,
+        }
+    }
+    fn pid_file(pid: usize, name: &str) -> Option<Self> {
+        match name {
+            "status" => Some(Self::Status(pid)),
+            "cmdline" => Some(Self::Cmdline(pid)),
+            "maps" => Some(Self::Maps(pid)),
+            "fd" => Some(Self::Fd(pid)),
+            "task" => Some(Self::Task(pid)),
+            _ => None,
+        }
+    }
+    /// 根目录与全局文件使用较小的编号，进程目录中的文件编号由pid决定
+    fn ino(&self) -> usize {
+        match *self {
+            Self::Root => 1,
+            Self::MemInfo => 2,
+            Self::Uptime => 3,
+            Self::Sched => 4,
+            Self::PidDir(pid) => (pid + 1) << 3,
+            Self::Status(pid) => (pid + 1) << 3 | 1,
+            Self::Cmdline(pid) => (pid + 1) << 3 | 2,
+            Self::Maps(pid) => (pid + 1) << 3 | 3,
+            Self::Fd(pid) => (pid + 1) << 3 | 4,
+            Self::Task(pid) => (pid + 1) << 3 | 5,
+        }
+    }
+    fn is_dir(&self) -> bool {
+        matches!(self, Self::Root | Self::PidDir(_))
+    }
+}
+
+pub struct ProcInode {
+    dev: u64,
+    entry: ProcEntry,
+}
+
+impl ProcInode {
+    fn wrap(&self, entry: ProcEntry) -> Arc<dyn VfsInode> {
+        Arc::new(ProcInode {
+            dev: self.dev,
+            entry,
+        })
+    }
+    /// 生成文件的内容，进程已经被回收时返回None
+    fn content(&self) -> Option<String> {
+        let content = match self.entry {
+            ProcEntry::Root | ProcEntry::PidDir(_) => return None,
+            ProcEntry::MemInfo => meminfo(),
+            ProcEntry::Uptime => uptime(),
+            ProcEntry::Sched => sched(),
+            ProcEntry::Status(pid) => status(find_process(pid)?.as_ref()),
+            ProcEntry::Cmdline(pid) => cmdline(find_process(pid)?.as_ref()),
+            ProcEntry::Maps(pid) => maps(find_process(pid)?.as_ref()),
+            ProcEntry::Fd(pid) => fds(find_process(pid)?.as_ref()),
+            ProcEntry::Task(pid) => tasks(find_process(pid)?.as_ref()),
+        };
+        Some(content)
+    }
+}
+
+impl VfsInode for ProcInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dev(&self) -> u64 {
+        self.dev
+    }
+    fn ino(&self) -> usize {
+        self.entry.ino()
+    }
+    fn inode_type(&self) -> InodeType {
+        if self.entry.is_dir() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+    fn stat(&self) -> Stat {
+        //所有文件都是只读的，大小在读取前无法确定，记为0
+        let (mode, nlink) = if self.entry.is_dir() {
+            (0o040555, 2)
+        } else {
+            (0o100444, 1)
+        };
+        Stat::new(
+            self.dev,
+            self.ino() as u64,
+            StatMode::from_bits_truncate(mode),
+            nlink,
+        )
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, ()> {
+        let content = match self.content() {
+            Some(content) => content,
+            None => return Ok(0),
+        };
+        let bytes = content.as_bytes();
+        if offset >= bytes.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(bytes.len() - offset);
+        buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, ()> {
+        Ok(0)
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        let entry = match self.entry {
+            ProcEntry::Root => match ProcEntry::global(name) {
+                Some(entry) => entry,
+                None => {
+                    let pid = name.parse().ok()?;
+                    find_process(pid)?;
+                    ProcEntry::PidDir(pid)
+                }
+            },
+            ProcEntry::PidDir(pid) => {
+                find_process(pid)?;
+                ProcEntry::pid_file(pid, name)?
+            }
+            _ => return None,
+        };
+        Some(self.wrap(entry))
+    }
+    fn ls(&self) -> Vec<String> {
+        match self.entry {
+            ProcEntry::Root => {
+                let mut names: Vec<String> =
+                    GLOBAL_FILES.iter().map(|name| name.to_string()).collect();
+                names.extend(
+                    all_processes()
+                        .iter()
+                        .map(|process| process.get_pid().to_string()),
+                );
+                names
+            }
+            ProcEntry::PidDir(_) => PID_FILES.iter().map(|name| name.to_string()).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+///物理内存与内核堆的使用情况，以kB为单位
+fn meminfo() -> String {
+    let frames = frame_stats();
+    let heap = heap_stats();
+    let slab_used: usize = heap
+        .slabs
+        .iter()
+        .map(|slab| slab.in_use * slab.object_size)
+        .sum();
+    let kb = |pages: usize| pages * PAGE_SIZE / 1024;
+    format!(
+        "MemTotal:\t{} kB\nMemFree:\t{} kB\nMemUsed:\t{} kB\nLargestFree:\t{} kB\nFreeRuns:\t{}\nFragmentation:\t{}%\nHeapSize:\t{} kB\nHeapGrow:\t{}\nSlabUsed:\t{} kB\n",
+        kb(frames.total),
+        kb(frames.free),
+        kb(frames.total - frames.free),
+        kb(frames.largest_run),
+        frames.free_runs,
+        frames.fragmentation(),
+        heap.heap_size / 1024,
+        heap.grow_count,
+        slab_used / 1024
+    )
+}
+
+///开机以来的时间与其中cpu空闲的时间，以秒为单位
+fn uptime() -> String {
+    let uptime = get_costtime();
+    let idle = uptime.saturating_sub(sched_stats().busy_time);
+    format!(
+        "{}.{:02} {}.{:02}\n",
+        uptime / 1_000_000,
+        uptime % 1_000_000 / 10_000,
+        idle / 1_000_000,
+        idle % 1_000_000 / 10_000
+    )
+}
+
+///调度器的统计信息，时间以us为单位
+fn sched() -> String {
+    let stats = sched_stats();
+    let uptime = get_costtime();
+    format!(
+        "switches:\t{}\nready:\t{}\nprocesses:\t{}\nbusy_us:\t{}\nidle_us:\t{}\n",
+        stats.switches,
+        stats.ready,
+        all_processes().len(),
+        stats.busy_time,
+        uptime.saturating_sub(stats.busy_time)
+    )
+}
+
+///线程的状态：R可以运行，S阻塞，Z已经退出
+fn task_state(status: TaskStatus, exited: bool) -> char {
+    if exited {
+        return 'Z';
+    }
+    match status {
+        TaskStatus::Ready | TaskStatus::Running => 'R',
+        TaskStatus::Blocking => 'S',
+    }
+}
+
+fn status(process: &ProcessControlBlock) -> String {
+    let inner = process.get_inner_access();
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.get_pid());
+    let mut threads = 0;
+    let mut time = 0;
+    let mut state = 'S';
+    for task in inner.task.iter().flatten() {
+        let task_inner = task.get_inner_access();
+        time += task_inner.run_time;
+        if task_state(task_inner.task_status, task_inner.exit_code.is_some()) == 'R' {
+            state = 'R';
+        }
+        if task_inner.exit_code.is_none() {
+            threads += 1;
+        }
+    }
+    if inner.is_zombie() {
+        state = 'Z';
+    }
+    let areas = inner.memory_set.areas_info();
+    let pages: usize = areas
+        .iter()
+        .map(|area| (usize::from(area.end) - usize::from(area.start)) / PAGE_SIZE)
+        .sum();
+    let frames: usize = areas.iter().map(|area| area.frames).sum();
+    format!(
+        "Name:\t{}\nState:\t{}\nPid:\t{}\nPPid:\t{}\nThreads:\t{}\nFDs:\t{}\nVmPages:\t{}\nVmFrames:\t{}\nTime:\t{}\n",
+        inner.name(),
+        state,
+        process.get_pid(),
+        ppid,
+        threads,
+        inner.fd_table.iter().flatten().count(),
+        pages,
+        frames,
+        time
+    )
+}
+
+///启动参数，每个参数以\0结尾
+fn cmdline(process: &ProcessControlBlock) -> String {
+    let mut content = String::new();
+    for arg in process.get_inner_access().cmdline.iter() {
+        content.push_str(arg);
+        content.push('\0');
+    }
+    content
+}
+
+///每行一个逻辑段：地址范围、权限、私有页帧数与映射方式
+fn maps(process: &ProcessControlBlock) -> String {
+    let mut content = String::new();
+    for area in process.get_inner_access().memory_set.areas_info() {
+        let perm = area.map_perm;
+        let _ = writeln!(
+            content,
+            "{:016x}-{:016x} {}{}{}{} {:>5} {}",
+            usize::from(area.start),
+            usize::from(area.end),
+            if perm.contains(MapPermission::R) {
+                'r'
+            } else {
+                '-'
+            },
+            if perm.contains(MapPermission::W) {
+                'w'
+            } else {
+                '-'
+            },
+            if perm.contains(MapPermission::X) {
+                'x'
+            } else {
+                '-'
+            },
+            if area.map_type == MapType::Shared {
+                's'
+            } else {
+                'p'
+            },
+            area.frames,
+            match area.map_type {
+                MapType::Identical => "identical",
+                MapType::Framed => "framed",
+                MapType::Shared => "shared",
+            }
+        );
+    }
+    content
+}
+
+///每行一个打开的文件描述符及其种类
+fn fds(process: &ProcessControlBlock) -> String {
+    let mut content = String::new();
+    for (fd, file) in process.get_inner_access().fd_table.iter().enumerate() {
+        if let Some(file) = file {
+            let _ = writeln!(content, "{} {}", fd, file.kind());
+        }
+    }
+    content
+}
+
+///每行一个线程：tid、状态与运行时间(us)
+fn tasks(process: &ProcessControlBlock) -> String {
+    let mut content = String::new();
+    for (tid, task) in process.get_inner_access().task.iter().enumerate() {
+        if let Some(task) = task {
+            let inner = task.get_inner_access();
+            let _ = writeln!(
+                content,
+                "{} {} {}",
+                tid,
+                task_state(inner.task_status, inner.exit_code.is_some()),
+                inner.run_time
+            );
+        }
+    }
+    content
+}
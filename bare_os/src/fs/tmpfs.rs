@@ -0,0 +1,492 @@
+///! 内存文件系统，挂载在/tmp
+///! 文件内容保存在物理页帧中，目录保存在内核堆上，不经过块设备
+///! 文件系统被卸载或者关机时所有内容都会丢失
+///! 每个挂载的实例最多使用一半的物理页帧，用完时写入失败
+use super::{alloc_dev, InodeType, VfsFileSystem, VfsInode, NAME_MAX};
+use crate::config::PAGE_SIZE;
+use crate::file::{Stat, StatFs, StatMode};
+use crate::mm::frame_allocator::{frame_alloc, frame_stats, FrameTracker};
+use crate::timer::get_costtime;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::mutex::Mutex;
+
+const TMPFS_MAGIC: i64 = 0x01021994;
+
+pub struct TmpFileSystem {
+    dev: u64,
+    next_ino: Arc<AtomicUsize>,
+    budget: Arc<PageBudget>,
+    root: Arc<TmpNode>,
+}
+
+impl TmpFileSystem {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            dev: alloc_dev(),
+            next_ino: Arc::new(AtomicUsize::new(2)),
+            budget: Arc::new(PageBudget::new(frame_stats().total / 2)),
+            root: TmpNode::new(1, InodeType::Dir, 0),
+        })
+    }
+}
+
+/// 一个文件系统实例中文件内容可以使用的页帧数
+struct PageBudget {
+    capacity: usize,
+    used: AtomicUsize,
+}
+
+impl PageBudget {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            used: AtomicUsize::new(0),
+        }
+    }
+    /// 还可以使用的页帧数
+    fn available(&self) -> usize {
+        self.capacity - self.used.load(Ordering::Relaxed)
+    }
+    /// 预留count个页帧，超过容量时不预留并返回false
+    fn reserve(&self, count: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                if used + count <= self.capacity {
+                    Some(used + count)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+    fn free(&self, count: usize) {
+        self.used.fetch_sub(count, Ordering::Relaxed);
+    }
+}
+
+impl VfsFileSystem for TmpFileSystem {
+    fn fs_type(&self) -> &'static str {
+        "tmpfs"
+    }
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(TmpInode {
+            dev: self.dev,
+            next_ino: self.next_ino.clone(),
+            budget: self.budget.clone(),
+            node: self.root.clone(),
+        })
+    }
+    fn statfs(&self) -> StatFs {
+        //文件内容与内核共用空闲的页帧，空闲的页帧比剩余的容量少时以空闲的页帧为准
+        //inode的数量没有限制
+        let free = self.budget.available().min(frame_stats().free);
+        let mut statfs = StatFs::new(TMPFS_MAGIC, self.dev, PAGE_SIZE, NAME_MAX);
+        statfs.blocks = self.budget.capacity as u64;
+        statfs.bfree = free as u64;
+        statfs.bavail = free as u64;
+        statfs
+    }
+}
+
+/// 内存中的文件或者目录，被所有打开它的TmpInode共享
+struct TmpNode {
+    ino: usize,
+    kind: InodeType,
+    inner: Mutex<TmpNodeInner>,
+}
+
+struct TmpNodeInner {
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    size: usize,
+    pages: Vec<FrameTracker>, //文件的内容，第i个页帧保存[i*PAGE_SIZE, (i+1)*PAGE_SIZE)
+    entries: DirEntries,      //目录中的文件
+    atime: u32,
+    mtime: u32,
+    ctime: u32,
+}
+
+/// 目录中的文件，每个文件加入时得到递增的位置，读取目录时从位置继续
+/// 删除文件不改变其它文件的位置
+#[derive(Default)]
+struct DirEntries {
+    names: BTreeMap<String, (usize, Arc<TmpNode>)>,
+    slots: BTreeMap<usize, String>,
+    next_slot: usize,
+}
+
+impl DirEntries {
+    fn get(&self, name: &str) -> Option<&Arc<TmpNode>> {
+        self.names.get(name).map(|(_, node)| node)
+    }
+    fn contains_key(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+    fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    fn keys(&self) -> impl Iterator<Item = &String> {
+        self.slots.values()
+    }
+    /// 加入文件，同名的文件被替换
+    fn insert(&mut self, name: String, node: Arc<TmpNode>) {
+        self.remove(&name);
+        self.slots.insert(self.next_slot, name.clone());
+        self.names.insert(name, (self.next_slot, node));
+        self.next_slot += 1;
+    }
+    fn remove(&mut self, name: &str) -> Option<Arc<TmpNode>> {
+        let (slot, node) = self.names.remove(name)?;
+        self.slots.remove(&slot);
+        Some(node)
+    }
+    /// 位置不小于cursor的文件，返回(下一个位置, 名称, 文件)
+    fn iter_from(&self, cursor: usize) -> impl Iterator<Item = (usize, &String, &Arc<TmpNode>)> {
+        self.slots
+            .range(cursor..)
+            .map(|(slot, name)| (slot + 1, name, &self.names[name].1))
+    }
+}
+
+fn now() -> u32 {
+    (get_costtime() / 1_000_000) as u32
+}
+
+impl TmpNode {
+    fn new(ino: usize, kind: InodeType, uid: u32) -> Arc<Self> {
+        let time = now();
+        Arc::new(Self {
+            ino,
+            kind,
+            inner: Mutex::new(TmpNodeInner {
+                nlink: 1,
+                uid,
+                gid: 0,
+                size: 0,
+                pages: Vec::new(),
+                entries: DirEntries::default(),
+                atime: time,
+                mtime: time,
+                ctime: time,
+            }),
+        })
+    }
+}
+
+impl TmpNodeInner {
+    /// 修改文件大小，扩大时申请清零的页帧
+    /// 页帧不足或者超过文件系统的容量时返回false并保持原来的大小
+    fn resize(&mut self, size: usize, budget: &PageBudget) -> bool {
+        let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+        let old_pages = self.pages.len();
+        if pages > old_pages && !budget.reserve(pages - old_pages) {
+            return false;
+        }
+        while self.pages.len() < pages {
+            match frame_alloc() {
+                Some(frame) => self.pages.push(frame),
+                None => {
+                    self.pages.truncate(old_pages);
+                    budget.free(pages - old_pages);
+                    return false;
+                }
+            }
+        }
+        if pages < old_pages {
+            self.pages.truncate(pages);
+            budget.free(old_pages - pages);
+        }
+        //缩小后最后一页中超出文件末尾的部分清零，之后再扩大时读到的是0
+        let tail = size % PAGE_SIZE;
+        if size < self.size && tail != 0 {
+            self.pages[pages - 1].ppn.get_bytes_array()[tail..].fill(0);
+        }
+        self.size = size;
+        true
+    }
+}
+
+pub struct TmpInode {
+    dev: u64,
+    next_ino: Arc<AtomicUsize>,
+    budget: Arc<PageBudget>,
+    node: Arc<TmpNode>,
+}
+
+impl TmpInode {
+    fn wrap(&self, node: Arc<TmpNode>) -> Arc<dyn VfsInode> {
+        Arc::new(TmpInode {
+            dev: self.dev,
+            next_ino: self.next_ino.clone(),
+            budget: self.budget.clone(),
+            node,
+        })
+    }
+}
+
+impl VfsInode for TmpInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dev(&self) -> u64 {
+        self.dev
+    }
+    fn ino(&self) -> usize {
+        self.node.ino
+    }
+    fn inode_type(&self) -> InodeType {
+        self.node.kind
+    }
+    fn stat(&self) -> Stat {
+        let inner = self.node.inner.lock();
+        let mode = match self.node.kind {
+            InodeType::File => 0o100644,
+            InodeType::Dir => 0o040755,
+            InodeType::Symlink => 0o120777,
+        };
+        let mut stat = Stat::new(
+            self.dev,
+            self.node.ino as u64,
+            StatMode::from_bits_truncate(mode),
+            inner.nlink,
+        );
+        stat.uid = inner.uid;
+        stat.gid = inner.gid;
+        stat.size = inner.size as i64;
+        stat.blksize = PAGE_SIZE as i32;
+        stat.blocks = (inner.pages.len() * PAGE_SIZE / 512) as i64;
+        stat.atime = inner.atime as i64;
+        stat.mtime = inner.mtime as i64;
+        stat.ctime = inner.ctime as i64;
+        stat
+    }
+    fn size(&self) -> usize {
+        self.node.inner.lock().size
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut inner = self.node.inner.lock();
+        let end = inner.size.min(offset + buf.len());
+        let mut pos = offset;
+        while pos < end {
+            let start = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - start).min(end - pos);
+            let page = inner.pages[pos / PAGE_SIZE].ppn.get_bytes_array();
+            buf[pos - offset..pos - offset + len].copy_from_slice(&page[start..start + len]);
+            pos += len;
+        }
+        inner.atime = now();
+        Ok(end.saturating_sub(offset))
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, ()> {
+        if self.node.kind == InodeType::Dir {
+            return Ok(0);
+        }
+        let mut inner = self.node.inner.lock();
+        //容量不够时只写入能够放下的部分
+        let room = (inner.pages.len() + self.budget.available()) * PAGE_SIZE;
+        let end = room.min(offset + buf.len());
+        if end <= offset {
+            return Ok(0);
+        }
+        if end > inner.size && !inner.resize(end, &self.budget) {
+            return Ok(0);
+        }
+        let mut pos = offset;
+        while pos < end {
+            let start = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - start).min(end - pos);
+            let page = inner.pages[pos / PAGE_SIZE].ppn.get_bytes_array();
+            page[start..start + len].copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        inner.mtime = now();
+        Ok(end - offset)
+    }
+    fn truncate(&self, size: usize) -> bool {
+        if self.node.kind == InodeType::Dir {
+            return false;
+        }
+        let mut inner = self.node.inner.lock();
+        let resized = inner.resize(size, &self.budget);
+        inner.mtime = now();
+        resized
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        let node = self.node.inner.lock().entries.get(name)?.clone();
+        Some(self.wrap(node))
+    }
+    fn ls(&self) -> Vec<String> {
+        self.node.inner.lock().entries.keys().cloned().collect()
+    }
+    fn read_dir(
+        &self,
+        mut cursor: usize,
+        f: &mut dyn FnMut(usize, &str, Arc<dyn VfsInode>) -> bool,
+    ) {
+        //每次只取出一个文件，回调时不持有目录的锁
+        loop {
+            let child = self
+                .node
+                .inner
+                .lock()
+                .entries
+                .iter_from(cursor)
+                .next()
+                .map(|(next, name, node)| (next, name.clone(), node.clone()));
+            let (next, name, node) = match child {
+                Some(child) => child,
+                None => return,
+            };
+            if !f(next, &name, self.wrap(node)) {
+                return;
+            }
+            cursor = next;
+        }
+    }
+    fn create(&self, name: &str, kind: InodeType, uid: u32) -> Option<Arc<dyn VfsInode>> {
+        if self.node.kind != InodeType::Dir || name.is_empty() {
+            return None;
+        }
+        let mut inner = self.node.inner.lock();
+        if inner.entries.contains_key(name) {
+            return None;
+        }
+        let node = TmpNode::new(self.next_ino.fetch_add(1, Ordering::Relaxed), kind, uid);
+        inner.entries.insert(String::from(name), node.clone());
+        inner.mtime = now();
+        Some(self.wrap(node))
+    }
+    fn symlink(&self, name: &str, target: &str, uid: u32) -> Option<Arc<dyn VfsInode>> {
+        if target.is_empty() {
+            return None;
+        }
+        //目标与普通文件一样保存在页帧中
+        let inode = self.create(name, InodeType::Symlink, uid)?;
+        if inode.write_at(0, target.as_bytes()) != Ok(target.len()) {
+            if let Some(inode) = self.unlink(name) {
+                inode.release();
+            }
+            return None;
+        }
+        Some(inode)
+    }
+    fn set_owner(&self, uid: u32, gid: u32) -> bool {
+        let mut inner = self.node.inner.lock();
+        inner.uid = uid;
+        inner.gid = gid;
+        inner.ctime = now();
+        true
+    }
+    fn readlink(&self) -> Option<String> {
+        if self.node.kind != InodeType::Symlink {
+            return None;
+        }
+        let mut target = alloc::vec![0u8; self.size()];
+        self.read_at(0, &mut target).ok()?;
+        String::from_utf8(target).ok()
+    }
+    fn link(&self, name: &str, inode: &Arc<dyn VfsInode>) -> bool {
+        //只能链接同一个文件系统中的文件，目录不允许硬链接
+        let target = match inode.as_any().downcast_ref::<TmpInode>() {
+            Some(target) if target.dev == self.dev && target.node.kind == InodeType::File => {
+                target.node.clone()
+            }
+            _ => return false,
+        };
+        if self.node.kind != InodeType::Dir || name.is_empty() {
+            return false;
+        }
+        let mut inner = self.node.inner.lock();
+        if inner.entries.contains_key(name) {
+            return false;
+        }
+        target.inner.lock().nlink += 1;
+        inner.entries.insert(String::from(name), target);
+        inner.mtime = now();
+        true
+    }
+    fn unlink(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        let mut inner = self.node.inner.lock();
+        let node = inner.entries.get(name)?.clone();
+        //只能删除空目录
+        if node.kind == InodeType::Dir && !node.inner.lock().entries.is_empty() {
+            return None;
+        }
+        inner.entries.remove(name);
+        inner.mtime = now();
+        node.inner.lock().nlink -= 1;
+        Some(self.wrap(node))
+    }
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn VfsInode>,
+        new_name: &str,
+    ) -> Result<Option<Arc<dyn VfsInode>>, ()> {
+        //只能在同一个文件系统中移动
+        let dir = match new_dir.as_any().downcast_ref::<TmpInode>() {
+            Some(dir) if dir.dev == self.dev && dir.node.kind == InodeType::Dir => dir.node.clone(),
+            _ => return Err(()),
+        };
+        if new_name.is_empty() || self.node.kind != InodeType::Dir {
+            return Err(());
+        }
+        //两个目录按地址顺序加锁，避免与反方向的移动互相等待
+        let (mut inner, mut new_inner) = if Arc::ptr_eq(&dir, &self.node) {
+            (self.node.inner.lock(), None)
+        } else if Arc::as_ptr(&self.node) < Arc::as_ptr(&dir) {
+            let inner = self.node.inner.lock();
+            (inner, Some(dir.inner.lock()))
+        } else {
+            let new_inner = dir.inner.lock();
+            (self.node.inner.lock(), Some(new_inner))
+        };
+        let node = inner.entries.get(old_name).ok_or(())?.clone();
+        let target = new_inner
+            .as_ref()
+            .map_or(&inner.entries, |new_inner| &new_inner.entries)
+            .get(new_name)
+            .cloned();
+        let replaced = match target {
+            //指向同一个文件时不做任何事
+            Some(target) if Arc::ptr_eq(&target, &node) => return Ok(None),
+            //目录只能替换空目录，其它文件只能替换不是目录的文件
+            Some(target) if (target.kind == InodeType::Dir) != (node.kind == InodeType::Dir) => {
+                return Err(())
+            }
+            Some(target) if !target.inner.lock().entries.is_empty() => return Err(()),
+            target => target,
+        };
+        let time = now();
+        inner.entries.remove(old_name);
+        inner.mtime = time;
+        match new_inner.as_mut() {
+            Some(new_inner) => {
+                new_inner.entries.insert(String::from(new_name), node);
+                new_inner.mtime = time;
+            }
+            None => {
+                inner.entries.insert(String::from(new_name), node);
+            }
+        }
+        drop(inner);
+        drop(new_inner);
+        Ok(replaced.map(|target| {
+            target.inner.lock().nlink -= 1;
+            self.wrap(target)
+        }))
+    }
+    fn release(&self) {
+        //没有硬链接后立即释放页帧，目录项中已经没有它了
+        let mut inner = self.node.inner.lock();
+        if inner.nlink == 0 {
+            inner.resize(0, &self.budget);
+        }
+    }
+}
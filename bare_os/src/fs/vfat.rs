@@ -0,0 +1,167 @@
+///! FAT32在虚拟文件系统中的实现
+///! FAT不支持硬链接，文件的编号在被打开期间保持不变
+use super::{alloc_dev, inode_key, InodeType, VfsFileSystem, VfsInode};
+use crate::file::{Stat, StatFs, StatMode};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use easyfs::BlockDevice;
+use fat32::{FatFileSystem, FatInode, NAME_LENGTH_MAX};
+
+const MSDOS_SUPER_MAGIC: i64 = 0x4d44;
+
+pub struct VfatFileSystem {
+    dev: u64,
+    fs: Arc<FatFileSystem>,
+}
+
+impl VfatFileSystem {
+    /// 块设备上不是FAT32时返回None
+    pub fn open(device: Arc<dyn BlockDevice>) -> Option<Arc<Self>> {
+        Some(Arc::new(Self {
+            dev: alloc_dev(),
+            fs: FatFileSystem::open(device)?,
+        }))
+    }
+}
+
+impl VfsFileSystem for VfatFileSystem {
+    fn fs_type(&self) -> &'static str {
+        "vfat"
+    }
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(VfatInode {
+            dev: self.dev,
+            inode: self.fs.root(),
+        })
+    }
+    fn sync(&self) -> bool {
+        self.fs.sync().is_ok()
+    }
+    fn statfs(&self) -> StatFs {
+        //以簇为单位，FAT没有inode的数量限制
+        let mut statfs = StatFs::new(
+            MSDOS_SUPER_MAGIC,
+            self.dev,
+            self.fs.cluster_size(),
+            NAME_LENGTH_MAX,
+        );
+        //统计空闲簇失败时当作没有空闲空间
+        let free = self.fs.free_clusters().unwrap_or(0) as u64;
+        statfs.blocks = self.fs.cluster_count() as u64;
+        statfs.bfree = free;
+        statfs.bavail = free;
+        statfs
+    }
+}
+
+pub struct VfatInode {
+    dev: u64,
+    inode: Arc<FatInode>,
+}
+
+impl VfatInode {
+    fn wrap(&self, inode: Arc<FatInode>) -> Arc<dyn VfsInode> {
+        Arc::new(VfatInode {
+            dev: self.dev,
+            inode,
+        })
+    }
+}
+
+impl VfsInode for VfatInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dev(&self) -> u64 {
+        self.dev
+    }
+    fn ino(&self) -> usize {
+        self.inode.ino()
+    }
+    fn inode_type(&self) -> InodeType {
+        if self.inode.is_dir() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+    fn stat(&self) -> Stat {
+        let metadata = match self.inode.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => return Stat::new(self.dev, self.ino() as u64, StatMode::empty(), 0),
+        };
+        let mut stat = Stat::new(
+            self.dev,
+            metadata.ino as u64,
+            StatMode::from_bits_truncate(metadata.mode),
+            metadata.nlink,
+        );
+        stat.size = metadata.size as i64;
+        stat.blksize = easyfs::BLOCK_SIZE as i32;
+        stat.blocks = metadata.blocks as i64;
+        stat.atime = metadata.atime as i64;
+        stat.mtime = metadata.mtime as i64;
+        stat.ctime = metadata.ctime as i64;
+        stat
+    }
+    fn size(&self) -> usize {
+        self.inode.size().unwrap_or(0)
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, ()> {
+        self.inode.read_at(offset, buf).map_err(|_| ())
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, ()> {
+        self.inode.write_at(offset, buf).map_err(|_| ())
+    }
+    fn truncate(&self, size: usize) -> bool {
+        self.inode.truncate(size)
+    }
+    fn fsync(&self) -> bool {
+        self.inode.fsync().is_ok()
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        self.inode.find_inode(name).map(|inode| self.wrap(inode))
+    }
+    fn ls(&self) -> Vec<String> {
+        self.inode.ls().unwrap_or_default()
+    }
+    fn read_dir(&self, cursor: usize, f: &mut dyn FnMut(usize, &str, Arc<dyn VfsInode>) -> bool) {
+        //读取失败时当作目录已经结束
+        let _ = self
+            .inode
+            .read_dir_from(cursor, |next, name, inode| f(next, name, self.wrap(inode)));
+    }
+    fn create(&self, name: &str, kind: InodeType, _uid: u32) -> Option<Arc<dyn VfsInode>> {
+        //FAT不记录所有者
+        let inode = match kind {
+            InodeType::File => self.inode.create(name),
+            InodeType::Dir => self.inode.mkdir(name),
+            //FAT不支持符号链接
+            InodeType::Symlink => None,
+        };
+        inode.map(|inode| self.wrap(inode))
+    }
+    fn unlink(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        self.inode.unlink(name).map(|inode| self.wrap(inode))
+    }
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn VfsInode>,
+        new_name: &str,
+    ) -> Result<Option<Arc<dyn VfsInode>>, ()> {
+        //FAT只支持在同一个目录中重命名
+        if inode_key(new_dir.as_ref()) != inode_key(self) {
+            return Err(());
+        }
+        self.inode
+            .rename(old_name, new_name)
+            .map(|replaced| replaced.map(|inode| self.wrap(inode)))
+            .map_err(|_| ())
+    }
+    fn release(&self) {
+        self.inode.release();
+    }
+}
@@ -9,6 +9,7 @@ pub mod panic;
 mod config;
 mod driver;
 mod file;
+mod fs;
 mod mm;
 mod my_struct;
 mod sbi;
@@ -49,17 +50,17 @@ fn clear_bss() {
 extern "C" fn rust_main() -> ! {
     clear_bss();
     INFO!("[kernel] Godone OS");
-
+    system_allocator::init_heap();
     // test
     {
         // color_output_test();
         crate::system_allocator::heap_test();
-        // crate::mm::frame_allocator::frame_test();
     }
     // trap初始化，设置stvec的入口地址
     mm::init();
     mm::remap_test(); //测试内核映射的正确性
-                      //运行程序
+    system_allocator::slab_test();
+    //运行程序
 
     trap::init();
 
@@ -1,17 +1,22 @@
 ///物理页帧分配器
 use crate::config::MEMORY_END;
 use crate::mm::address::{PhysAddr, PhysPageNum};
-use crate::INFO;
+use crate::system_allocator::common::align_up;
+use crate::{println, INFO};
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::Debug;
 use core::option::Option;
 use lazy_static::lazy_static;
 use spin::Mutex;
 
+//当前使用的页帧分配器
+type FrameAllocatorImpl = BitmapFrameAllocator;
+
 //全局分配器
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: Mutex<StackFrameAllocator> =
-        Mutex::new(StackFrameAllocator::new());
+    pub static ref FRAME_ALLOCATOR: Mutex<FrameAllocatorImpl> =
+        Mutex::new(FrameAllocatorImpl::new());
 }
 extern "C" {
     fn ekernel();
@@ -20,19 +25,47 @@ extern "C" {
 pub fn init_frame_allocator() {
     //初始化分配器
     INFO!("[kernel] frame: {}-{}", ekernel as usize, MEMORY_END);
-    FRAME_ALLOCATOR.lock().init(
+    //位图在加锁之前分配，持有页帧分配器的锁时不会再分配堆内存
+    let mut allocator = FrameAllocatorImpl::new();
+    allocator.init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
+    *FRAME_ALLOCATOR.lock() = allocator;
 }
 //分配器trait，定义了一个物理页帧分配器应该实现的功能
 trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    /// 分配n个物理上连续的页帧，起始页帧号按align(页帧数，2的幂)对齐
+    fn alloc_contiguous(&mut self, n: usize, align: usize) -> Option<PhysPageNum>;
+    /// 页帧使用情况统计
+    fn stats(&self) -> FrameStats;
+}
+
+/// 页帧统计信息，用于查看空闲页帧与碎片情况
+#[derive(Debug, Copy, Clone)]
+pub struct FrameStats {
+    pub total: usize,       //可管理的页帧总数
+    pub free: usize,        //空闲页帧数
+    pub largest_run: usize, //最大的连续空闲页帧数
+    pub free_runs: usize,   //连续空闲区间的个数
+}
+
+impl FrameStats {
+    /// 外部碎片率(百分比)：空闲页帧中不属于最大连续区间的比例
+    pub fn fragmentation(&self) -> usize {
+        if self.free == 0 {
+            0
+        } else {
+            100 - self.largest_run * 100 / self.free
+        }
+    }
 }
 
 pub struct StackFrameAllocator {
+    begin: usize,         //管理的第一个页帧
     current: usize,       //起始页帧
     end: usize,           //终止页帧
     recycled: Vec<usize>, //回收的页帧
@@ -41,6 +74,7 @@ pub struct StackFrameAllocator {
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            begin: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
@@ -68,15 +102,154 @@ impl FrameAllocator for StackFrameAllocator {
         }
         self.recycled.push(ppn.into());
     }
+    fn alloc_contiguous(&mut self, n: usize, align: usize) -> Option<PhysPageNum> {
+        //回收栈中的页帧无法保证连续，只能从未分配的区域中分配
+        assert!(n > 0 && align.is_power_of_two());
+        let start = align_up(self.current, align);
+        if start + n > self.end {
+            return None;
+        }
+        //对齐时跳过的页帧放入回收栈中
+        self.recycled.extend(self.current..start);
+        self.current = start + n;
+        Some(start.into())
+    }
+    fn stats(&self) -> FrameStats {
+        let free = self.end - self.current + self.recycled.len();
+        FrameStats {
+            total: self.end - self.begin,
+            free,
+            largest_run: self.end - self.current,
+            free_runs: 1 + self.recycled.len(),
+        }
+    }
 }
 
 impl StackFrameAllocator {
     //设置页帧起始与结尾
     fn init(&mut self, begin: PhysPageNum, end: PhysPageNum) {
+        self.begin = begin.into();
         self.current = begin.into();
         self.end = end.into();
     }
 }
+/// 位图页帧分配器
+/// 每一位对应一个物理页帧，1表示已经分配
+/// 可以在位图中查找连续的空闲位来分配物理上连续的页帧
+pub struct BitmapFrameAllocator {
+    base: usize,      //管理的第一个页帧
+    frames: usize,    //管理的页帧数量
+    bitmap: Vec<u64>, //页帧位图
+    free: usize,      //空闲页帧数量
+    hint: usize,      //下一次单页分配开始查找的位置
+}
+
+impl BitmapFrameAllocator {
+    fn init(&mut self, begin: PhysPageNum, end: PhysPageNum) {
+        self.base = begin.into();
+        self.frames = end.0 - begin.0;
+        self.bitmap = vec![0; (self.frames + 63) / 64];
+        //超出范围的位直接标记为已分配
+        for index in self.frames..self.bitmap.len() * 64 {
+            self.bitmap[index / 64] |= 1 << (index % 64);
+        }
+        self.free = self.frames;
+        self.hint = 0;
+    }
+    fn is_used(&self, index: usize) -> bool {
+        self.bitmap[index / 64] & (1 << (index % 64)) != 0
+    }
+    fn set_used(&mut self, index: usize) {
+        self.bitmap[index / 64] |= 1 << (index % 64);
+    }
+    fn set_free(&mut self, index: usize) {
+        self.bitmap[index / 64] &= !(1 << (index % 64));
+    }
+}
+
+impl FrameAllocator for BitmapFrameAllocator {
+    fn new() -> Self {
+        Self {
+            base: 0,
+            frames: 0,
+            bitmap: Vec::new(),
+            free: 0,
+            hint: 0,
+        }
+    }
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        if self.free == 0 {
+            return None;
+        }
+        //从上一次分配的位置开始查找，避免每次都从头扫描
+        let words = self.bitmap.len();
+        for i in 0..words {
+            let word = (self.hint / 64 + i) % words;
+            if self.bitmap[word] != u64::MAX {
+                let index = word * 64 + self.bitmap[word].trailing_ones() as usize;
+                self.set_used(index);
+                self.free -= 1;
+                self.hint = index;
+                return Some((self.base + index).into());
+            }
+        }
+        None
+    }
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        let ppn: usize = ppn.into();
+        if ppn < self.base || ppn >= self.base + self.frames || !self.is_used(ppn - self.base) {
+            panic!("Frame ppn:{:#x} has not been allocated", ppn);
+        }
+        self.set_free(ppn - self.base);
+        self.free += 1;
+    }
+    fn alloc_contiguous(&mut self, n: usize, align: usize) -> Option<PhysPageNum> {
+        assert!(n > 0 && align.is_power_of_two());
+        if n > self.free {
+            return None;
+        }
+        //对齐是针对物理页帧号而言的，因此需要先换算为绝对页帧号
+        let mut start = align_up(self.base, align) - self.base;
+        'search: while start + n <= self.frames {
+            for index in start..start + n {
+                if self.is_used(index) {
+                    //跳过已分配的页帧，从下一个对齐位置重新查找
+                    start = align_up(self.base + index + 1, align) - self.base;
+                    continue 'search;
+                }
+            }
+            for index in start..start + n {
+                self.set_used(index);
+            }
+            self.free -= n;
+            return Some((self.base + start).into());
+        }
+        None
+    }
+    fn stats(&self) -> FrameStats {
+        let mut largest_run = 0;
+        let mut free_runs = 0;
+        let mut run = 0;
+        for index in 0..self.frames {
+            if self.is_used(index) {
+                run = 0;
+            } else {
+                if run == 0 {
+                    free_runs += 1;
+                }
+                run += 1;
+                largest_run = largest_run.max(run);
+            }
+        }
+        FrameStats {
+            total: self.frames,
+            free: self.free,
+            largest_run,
+            free_runs,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct FrameTracker {
     pub ppn: PhysPageNum,
@@ -110,23 +283,86 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(|ppn| FrameTracker::new(ppn))
 }
 
+/// 分配n个物理上连续的页帧，起始页帧号按align个页帧对齐
+/// 主要提供给DMA缓冲区等需要连续物理内存的地方使用
+pub fn frame_alloc_contiguous(n: usize, align: usize) -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR.lock().alloc_contiguous(n, align)?;
+    Some(
+        (start.0..start.0 + n)
+            .map(|ppn| FrameTracker::new(ppn.into()))
+            .collect(),
+    )
+}
+
+/// 为内核堆扩容分配连续页帧
+/// 这些页帧交给堆分配器管理，不再归还，因此不返回FrameTracker
+/// 使用try_lock避免在持有页帧分配器锁时分配堆内存造成死锁
+/// 持有该锁时不会分配堆内存，try_lock失败时扩容失败，由内核堆记录失败的次数
+pub fn frame_alloc_heap(n: usize, align: usize) -> Option<PhysPageNum> {
+    FRAME_ALLOCATOR.try_lock()?.alloc_contiguous(n, align)
+}
+
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
 
-#[allow(unused)]
+pub fn frame_stats() -> FrameStats {
+    FRAME_ALLOCATOR.lock().stats()
+}
+
+/// 打印页帧使用情况与碎片报告
+pub fn frame_report() {
+    let stats = frame_stats();
+    println!(
+        "[kernel] frames: total {} free {} used {}",
+        stats.total,
+        stats.free,
+        stats.total - stats.free
+    );
+    println!(
+        "[kernel] frames: largest free run {} free runs {} fragmentation {}%",
+        stats.largest_run,
+        stats.free_runs,
+        stats.fragmentation()
+    );
+}
+
 pub fn frame_test() {
-    // init_frame_allocator();
+    let before = frame_stats();
+    //单个页帧的分配与回收
     let mut framepages: Vec<FrameTracker> = Vec::new();
-    for i in 0..5 {
-        let temp = frame_alloc().unwrap();
-        // framepages.push(temp);
+    for _ in 0..5 {
+        framepages.push(frame_alloc().unwrap());
+    }
+    assert_eq!(frame_stats().free, before.free - 5);
+    for i in 0..framepages.len() {
+        for j in i + 1..framepages.len() {
+            assert_ne!(framepages[i].ppn, framepages[j].ppn);
+        }
     }
     framepages.clear();
-    for i in 0..5 {
-        let temp = frame_alloc().unwrap();
-        framepages.push(temp);
+    assert_eq!(frame_stats().free, before.free);
+    //连续页帧的分配，检查连续性与对齐
+    for &(n, align) in [(1, 1), (3, 1), (8, 8), (16, 512)].iter() {
+        let frames = frame_alloc_contiguous(n, align).unwrap();
+        assert_eq!(frames.len(), n);
+        assert_eq!(frames[0].ppn.0 % align, 0);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.ppn.0, frames[0].ppn.0 + i);
+        }
+        assert_eq!(frame_stats().free, before.free - n);
+        drop(frames);
+        assert_eq!(frame_stats().free, before.free);
     }
-    drop(framepages);
+    //在空洞中分配：释放中间的页帧后，连续分配不能占用它
+    let mut frames = frame_alloc_contiguous(4, 1).unwrap();
+    let hole = frames.remove(1).ppn;
+    let run = frame_alloc_contiguous(2, 1).unwrap();
+    assert!(run.iter().all(|frame| frame.ppn != hole));
+    drop(run);
+    drop(frames);
+    assert!(frame_alloc_contiguous(before.free + 1, 1).is_none());
+    assert_eq!(frame_stats().free, before.free);
+    frame_report();
     INFO!("Frame_alloc_test passed!");
 }
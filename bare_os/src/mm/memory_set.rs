@@ -1,8 +1,10 @@
 use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, USER_STACK_SIZE};
 use crate::mm::address::{PhysAddr, PhysPageNum, StepByOne, VPNRange, VirtAddr, VirtPageNum};
 use crate::mm::frame_allocator::{frame_alloc, FrameTracker};
-use crate::mm::page_table::{PTEFlags, PageTable, PageTableEntry};
+use crate::mm::page_table::{PTEFlags, PageSize, PageTable, PageTableEntry};
+use crate::mm::shm::{ShmAttach, SHM_BASE};
 use crate::{println, INFO};
+use alloc::boxed::Box;
 use alloc::collections::btree_map::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -20,6 +22,7 @@ use xmas_elf::ElfFile;
 pub enum MapType {
     Identical, //恒等映射==>logical address = physaddress
     Framed,    //其它映射
+    Shared,    //映射共享内存段的页帧
 }
 bitflags! {
     pub struct MapPermission:u8{
@@ -52,12 +55,24 @@ pub struct MapArea {
     map_type: MapType,
     //逻辑段的读取权限
     map_perm: MapPermission,
+    //共享内存段的映射
+    shm: Option<ShmAttach>,
+}
+
+/// 逻辑段的概要信息，用于在/proc/<pid>/maps中显示
+#[derive(Debug, Copy, Clone)]
+pub struct AreaInfo {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    pub map_type: MapType,
+    pub map_perm: MapPermission,
+    pub frames: usize, //段私有的物理页帧数，共享内存段为0
 }
 
 pub struct MemorySet {
     //应用程序的地址空间
-    //三级页表
-    page_table: PageTable,
+    //三级页表，从slab缓存中分配
+    page_table: Box<PageTable>,
     //所有的逻辑段
     areas: Vec<MapArea>,
 }
@@ -66,7 +81,7 @@ impl MemorySet {
     fn new_bare() -> Self {
         //空的地址空间
         Self {
-            page_table: PageTable::new(),
+            page_table: Box::new(PageTable::new()),
             areas: Vec::new(),
         }
     }
@@ -105,6 +120,46 @@ impl MemorySet {
         );
     }
 
+    /// 将共享内存段映射到start_addr开始的位置
+    pub fn insert_shared_area(
+        &mut self,
+        start_addr: VirtAddr,
+        attach: ShmAttach,
+        permission: MapPermission,
+    ) {
+        let end_addr: VirtAddr =
+            (usize::from(start_addr) + attach.segment().pages() * PAGE_SIZE).into();
+        let mut map_area = MapArea::new(start_addr, end_addr, MapType::Shared, permission);
+        map_area.shm = Some(attach);
+        self.push(map_area, None);
+    }
+    /// 从SHM_BASE开始寻找一段可以容纳pages个页的空闲区域
+    pub fn find_free_area(&self, pages: usize) -> VirtAddr {
+        let mut start = VirtAddr::from(SHM_BASE).floor();
+        while let Some(area) = self.areas.iter().find(|area| {
+            area.vpn_range.get_start().0 < start.0 + pages && start.0 < area.vpn_range.get_end().0
+        }) {
+            start = area.vpn_range.get_end();
+        }
+        start.into()
+    }
+    /// 解除共享内存段的映射
+    pub fn remove_shared_area(&mut self, start_addr: VirtAddr) -> bool {
+        let vpn: VirtPageNum = start_addr.into();
+        match self
+            .areas
+            .iter()
+            .position(|area| area.map_type == MapType::Shared && area.vpn_range.get_start() == vpn)
+        {
+            Some(index) => {
+                self.areas[index].unmap(&mut self.page_table);
+                self.areas.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn remove_from_startaddr(&mut self, startaddr: VirtAddr) {
         //从一个起始地址找到对应的段，将这个段对应的页删除
         let virtpage: VirtPageNum = startaddr.into(); //转换为虚拟页号
@@ -267,6 +322,9 @@ impl MemorySet {
         for area in src_memset.areas.iter() {
             let new_area = MapArea::copy_from_other(area); //拷贝一个maparea
             memoryset.push(new_area, None); //
+            if area.map_type == MapType::Shared {
+                continue; //共享内存段映射的是相同的页帧，不需要拷贝
+            }
             for vpn in area.vpn_range {
                 let src_data = src_memset.translate(vpn).unwrap().ppn(); //获取父进程的虚拟页的对应的物理页
                 let dis_data = memoryset.translate(vpn).unwrap().ppn(); //获取子进程虚拟页对应的物理页
@@ -288,6 +346,22 @@ impl MemorySet {
     pub fn clear_area_data(&mut self) {
         self.areas.clear() //回收所有的段
     }
+    /// 按起始地址排序的所有逻辑段
+    pub fn areas_info(&self) -> Vec<AreaInfo> {
+        let mut infos: Vec<AreaInfo> = self
+            .areas
+            .iter()
+            .map(|area| AreaInfo {
+                start: area.vpn_range.get_start().into(),
+                end: area.vpn_range.get_end().into(),
+                map_type: area.map_type,
+                map_perm: area.map_perm,
+                frames: area.data_frames.len(),
+            })
+            .collect();
+        infos.sort_by_key(|info| usize::from(info.start));
+        infos
+    }
 }
 
 impl MapArea {
@@ -309,6 +383,7 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            shm: None,
         }
     }
 
@@ -323,21 +398,51 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_perm: old_maparea.map_perm,
             map_type: old_maparea.map_type,
+            shm: old_maparea.shm.clone(),
         }
     }
     fn map(&mut self, page_table: &mut PageTable) {
         //段需要管理自己的虚拟页号和物理页号
         //将这些数据写入所属应用程序的页表中
+        if self.map_type == MapType::Identical {
+            let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+            for (vpn, size) in self.identical_pages() {
+                page_table.map_huge(vpn, PhysPageNum(vpn.0), pte_flags, size);
+            }
+            return;
+        }
         for vpn in self.vpn_range {
             self.map_one(page_table, vpn);
         }
     }
     fn unmap(&mut self, page_table: &mut PageTable) {
         //删除这个段对应的映射关系
+        if self.map_type == MapType::Identical {
+            for (vpn, _) in self.identical_pages() {
+                page_table.unmap(vpn);
+            }
+            return;
+        }
         for vpn in self.vpn_range {
             self.unmap_one(page_table, vpn);
         }
     }
+    /// 恒等映射的段尽量使用大页
+    /// 虚拟页号按大页对齐且剩余空间足够时使用1GiB或2MiB的页
+    fn identical_pages(&self) -> Vec<(VirtPageNum, PageSize)> {
+        let mut pages = Vec::new();
+        let end = self.vpn_range.get_end();
+        let mut vpn = self.vpn_range.get_start();
+        while vpn.0 < end.0 {
+            let size = [PageSize::Size1G, PageSize::Size2M]
+                .into_iter()
+                .find(|size| vpn.0 % size.pages() == 0 && vpn.0 + size.pages() <= end.0)
+                .unwrap_or(PageSize::Size4K);
+            pages.push((vpn, size));
+            vpn.0 += size.pages();
+        }
+        pages
+    }
     fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
         //向这个段映射的物理页面上写入数据
         assert_eq!(self.map_type, MapType::Framed);
@@ -372,6 +477,10 @@ impl MapArea {
                 ppn = frame.ppn; //物理页帧号
                 self.data_frames.insert(vpn, frame);
             }
+            MapType::Shared => {
+                let index = vpn.0 - self.vpn_range.get_start().0;
+                ppn = self.shm.as_ref().unwrap().segment().ppn(index);
+            }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         //构造一个页表项并插入页表中
@@ -427,5 +536,48 @@ pub fn remap_test() {
             .executable(),
         false
     );
+    //恒等映射的物理内存使用大页，检查大页边界两侧的映射与权限
+    let ekernel_vpn = VirtAddr::from(ekernel as usize).ceil();
+    let memory_end_vpn = VirtAddr::from(MEMORY_END).floor();
+    let huge_pages = PageSize::Size2M.pages();
+    let first_huge = VirtPageNum((ekernel_vpn.0 + huge_pages - 1) / huge_pages * huge_pages);
+    if first_huge.0 + huge_pages <= memory_end_vpn.0 {
+        let (_, size) = kernel_space.page_table.find_leaf(first_huge).unwrap();
+        assert_eq!(size, PageSize::Size2M);
+        //大页中的每一页都要转换到正确的物理页
+        for vpn in [
+            first_huge.0,
+            first_huge.0 + 1,
+            first_huge.0 + huge_pages - 1,
+        ] {
+            let pte = kernel_space.page_table.translate(VirtPageNum(vpn)).unwrap();
+            assert_eq!(pte.ppn().0, vpn);
+            assert!(pte.readable() && pte.writable() && !pte.executable());
+        }
+        //大页之前没有对齐的部分使用4KiB的页
+        if first_huge.0 > ekernel_vpn.0 {
+            let before = VirtPageNum(first_huge.0 - 1);
+            let (pte, size) = kernel_space.page_table.find_leaf(before).unwrap();
+            assert_eq!(size, PageSize::Size4K);
+            assert_eq!(pte.ppn().0, before.0);
+            assert!(pte.writable() && !pte.executable());
+        }
+    }
+    //代码段最后一页可执行不可写
+    let last_text = VirtAddr::from(etext as usize - 1).floor();
+    let pte = kernel_space.page_table.translate(last_text).unwrap();
+    assert!(pte.executable() && !pte.writable());
+    //物理内存的最后一页可以访问，之后的页没有映射
+    let last_page = VirtPageNum(memory_end_vpn.0 - 1);
+    assert_eq!(
+        kernel_space
+            .page_table
+            .translate(last_page)
+            .unwrap()
+            .ppn()
+            .0,
+        last_page.0
+    );
+    assert!(kernel_space.page_table.translate(memory_end_vpn).is_none());
     INFO!("The remap_test passed!");
 }
@@ -2,6 +2,7 @@ pub mod address;
 pub mod frame_allocator;
 mod memory_set;
 pub mod page_table;
+pub mod shm;
 
 use crate::println;
 pub use memory_set::{remap_test, MapArea, MapPermission, MapType, MemorySet, KERNEL_SPACE};
@@ -61,6 +61,42 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    pub fn is_leaf(&self) -> bool {
+        //R W X任意一位不为0时是叶子节点，否则指向下一级页表
+        self.is_valid()
+            && (self.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X)) != PTEFlags::empty()
+    }
+}
+
+/// sv39支持的页大小
+/// 在第一级或第二级页表中放置叶子节点即可得到1GiB或2MiB的大页
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageSize {
+    Size4K,
+    Size2M,
+    Size1G,
+}
+
+impl PageSize {
+    /// 叶子节点所在的页表级别
+    fn level(&self) -> usize {
+        match self {
+            PageSize::Size4K => 2,
+            PageSize::Size2M => 1,
+            PageSize::Size1G => 0,
+        }
+    }
+    fn from_level(level: usize) -> Self {
+        match level {
+            0 => PageSize::Size1G,
+            1 => PageSize::Size2M,
+            _ => PageSize::Size4K,
+        }
+    }
+    /// 包含的4KiB页数
+    pub fn pages(&self) -> usize {
+        1 << (9 * (2 - self.level()))
+    }
 }
 
 pub struct PageTable {
@@ -83,28 +119,57 @@ impl PageTable {
         8usize << 60 | self.root_ppn.0
     }
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        //添加一个虚拟页号到物理页号的映射
-        let pte = self.find_pte_create(vpn).unwrap();
+        self.map_huge(vpn, ppn, flags, PageSize::Size4K);
+    }
+    /// 添加一个指定大小的映射，虚拟页号和物理页号都需要按页大小对齐
+    pub fn map_huge(
+        &mut self,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+        size: PageSize,
+    ) {
+        assert_eq!(
+            vpn.0 % size.pages(),
+            0,
+            "vpn: {:?} is not aligned to {:?}",
+            vpn,
+            size
+        );
+        assert_eq!(
+            ppn.0 % size.pages(),
+            0,
+            "ppn: {:?} is not aligned to {:?}",
+            ppn,
+            size
+        );
+        let pte = self.find_pte_create(vpn, size.level()).unwrap();
         //查找虚拟页号是否已经被映射过了
         assert!(!pte.is_valid(), "vpn: {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V); //建立一个映射
     }
     pub fn unmap(&mut self, vpn: VirtPageNum) {
         //删除一个虚拟页号对应的页表项
-        // todo!(应该使用find_pte)
-        let pte = self.find_pte_create(vpn).unwrap();
-        assert!(pte.is_valid(), "vpn: {:?} is invalid before unmapping", vpn);
+        //大页需要使用其起始的虚拟页号删除
+        let (pte, size) = self.find_leaf_mut(vpn).expect("unmap an invalid vpn");
+        assert_eq!(
+            vpn.0 % size.pages(),
+            0,
+            "vpn: {:?} is inside a {:?} page",
+            vpn,
+            size
+        );
         *pte = PageTableEntry::empty(); //空项
     }
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
-        //根据虚拟页号找到页表项
+    fn find_pte_create(&mut self, vpn: VirtPageNum, level: usize) -> Option<&mut PageTableEntry> {
+        //根据虚拟页号找到第level级的页表项
         let idxs = vpn.index(); //将虚拟页表号划分3部分
                                 // DEBUG!("[Debug] idxs: {:?} root_ppn: {:?}",idxs,self.root_ppn);
         let mut ppn = self.root_ppn;
         let mut result: Option<&mut PageTableEntry> = None;
         for i in 0..3 {
             let pte = &mut ppn.get_pte_array()[idxs[i]];
-            if i == 2 {
+            if i == level {
                 result = Some(pte);
                 return result;
             }
@@ -113,10 +178,27 @@ impl PageTable {
                 *pte = PageTableEntry::new(new_frame.ppn, PTEFlags::V);
                 self.frames.push(new_frame);
             }
+            //不能在大页的范围内建立更小的映射
+            assert!(!pte.is_leaf(), "vpn: {:?} is covered by a huge page", vpn);
             ppn = pte.ppn();
         }
         result
     }
+    fn find_leaf_mut(&mut self, vpn: VirtPageNum) -> Option<(&mut PageTableEntry, PageSize)> {
+        let idxs = vpn.index();
+        let mut ppn = self.root_ppn;
+        for i in 0..3 {
+            let pte = &mut ppn.get_pte_array()[idxs[i]];
+            if !pte.is_valid() {
+                return None;
+            }
+            if i == 2 || pte.is_leaf() {
+                return Some((pte, PageSize::from_level(i)));
+            }
+            ppn = pte.ppn();
+        }
+        None
+    }
 
     //下方的代码用来手动查
     // 找页表项
@@ -129,7 +211,7 @@ impl PageTable {
     }
     pub fn translated_va(&self, va: VirtAddr) -> Option<PhysAddr> {
         //将一个虚拟地址转换为一个物理地址
-        self.find_pte(va.floor()).map(|pte| {
+        self.translate(va.floor()).map(|pte| {
             //找到对应的页表项
             let phyaddr: PhysAddr = pte.ppn().into();
             let offset = va.page_offset();
@@ -140,26 +222,33 @@ impl PageTable {
 
     pub fn find_pte(&self, vpn: VirtPageNum) -> Option<&PageTableEntry> {
         //根据虚拟页号找到页表项
+        self.find_leaf(vpn).map(|(pte, _)| pte)
+    }
+    /// 找到虚拟页号所在的叶子页表项以及对应的页大小
+    pub fn find_leaf(&self, vpn: VirtPageNum) -> Option<(&PageTableEntry, PageSize)> {
         let idxs = vpn.index(); //将虚拟页表号划分
         let mut ppn = self.root_ppn;
-        let mut result: Option<&PageTableEntry> = None;
         for i in 0..3 {
             let pte = &ppn.get_pte_array()[idxs[i]];
 
             if !pte.is_valid() {
                 return None;
             }
-            if i == 2 {
-                result = Some(pte);
-                return result;
+            if i == 2 || pte.is_leaf() {
+                return Some((pte, PageSize::from_level(i)));
             }
 
             ppn = pte.ppn();
         }
-        result
+        None
     }
+    /// 返回虚拟页号对应的4KiB页的页表项
+    /// 位于大页中时，物理页号加上页内的偏移
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).map(|pte| pte.clone())
+        self.find_leaf(vpn).map(|(pte, size)| {
+            let offset = vpn.0 & (size.pages() - 1);
+            PageTableEntry::new(PhysPageNum(pte.ppn().0 + offset), pte.flags())
+        })
     }
 }
 
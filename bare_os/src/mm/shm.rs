@@ -0,0 +1,136 @@
+///! 共享内存
+///! 与System V的shmget/shmat/shmdt类似，每个共享内存段由一组物理页帧构成
+///! 任意进程都可以通过key找到共享内存段并将其映射到自己的地址空间中
+///! 每一次映射都会增加段的引用计数，解除映射、进程退出时减少
+///! 最后一个使用者解除映射后，共享内存段被删除，物理页帧随之回收
+///! 没有被映射过的段需要通过shmctl(IPC_RMID)删除，仍有映射时只是标记删除，不能再被查找与映射
+use crate::config::PAGE_SIZE;
+use crate::mm::address::PhysPageNum;
+use crate::mm::frame_allocator::{frame_alloc, FrameTracker};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+pub const IPC_PRIVATE: usize = 0; //每次都创建新的共享内存段
+pub const IPC_CREAT: usize = 0o1000; //key不存在时创建
+pub const IPC_EXCL: usize = 0o2000; //与IPC_CREAT一起使用，key存在时返回错误
+pub const IPC_RMID: usize = 0; //shmctl删除共享内存段
+pub const SHM_BASE: usize = 0x10_0000_0000; //未指定映射地址时从这里开始寻找空闲区域
+
+pub struct ShmSegment {
+    pub id: usize,
+    key: usize,
+    frames: Vec<FrameTracker>, //共享内存段的物理页帧
+    attach: Mutex<usize>,      //映射次数
+}
+
+impl ShmSegment {
+    pub fn pages(&self) -> usize {
+        self.frames.len()
+    }
+    pub fn ppn(&self, index: usize) -> PhysPageNum {
+        self.frames[index].ppn
+    }
+    pub fn attach_count(&self) -> usize {
+        *self.attach.lock()
+    }
+}
+
+/// 一次映射
+/// 析构时减少引用计数，最后一次映射解除时删除共享内存段
+pub struct ShmAttach {
+    segment: Arc<ShmSegment>,
+}
+
+impl ShmAttach {
+    fn new(segment: Arc<ShmSegment>) -> Self {
+        *segment.attach.lock() += 1;
+        Self { segment }
+    }
+    pub fn segment(&self) -> &Arc<ShmSegment> {
+        &self.segment
+    }
+}
+
+impl Clone for ShmAttach {
+    fn clone(&self) -> Self {
+        //fork时子进程继承父进程的映射
+        Self::new(self.segment.clone())
+    }
+}
+
+impl Drop for ShmAttach {
+    fn drop(&mut self) {
+        let mut attach = self.segment.attach.lock();
+        *attach -= 1;
+        if *attach == 0 {
+            drop(attach);
+            SHM_MANAGER.lock().segments.remove(&self.segment.id);
+        }
+    }
+}
+
+struct ShmManager {
+    segments: BTreeMap<usize, Arc<ShmSegment>>,
+    next_id: usize,
+}
+
+lazy_static! {
+    static ref SHM_MANAGER: Mutex<ShmManager> = Mutex::new(ShmManager {
+        segments: BTreeMap::new(),
+        next_id: 1,
+    });
+}
+
+/// 根据key查找或创建共享内存段，返回段的标识符
+pub fn shm_get(key: usize, size: usize, flags: usize) -> Option<usize> {
+    let mut manager = SHM_MANAGER.lock();
+    if key != IPC_PRIVATE {
+        if let Some(segment) = manager.segments.values().find(|segment| segment.key == key) {
+            if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+                return None;
+            }
+            if size > segment.pages() * PAGE_SIZE {
+                return None;
+            }
+            return Some(segment.id);
+        }
+        if flags & IPC_CREAT == 0 {
+            return None;
+        }
+    }
+    if size == 0 {
+        return None;
+    }
+    let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    let mut frames = Vec::with_capacity(pages);
+    for _ in 0..pages {
+        frames.push(frame_alloc()?);
+    }
+    let id = manager.next_id;
+    manager.next_id += 1;
+    manager.segments.insert(
+        id,
+        Arc::new(ShmSegment {
+            id,
+            key,
+            frames,
+            attach: Mutex::new(0),
+        }),
+    );
+    Some(id)
+}
+
+/// 映射共享内存段前获取一次引用
+pub fn shm_attach(id: usize) -> Option<ShmAttach> {
+    let segment = SHM_MANAGER.lock().segments.get(&id)?.clone();
+    Some(ShmAttach::new(segment))
+}
+
+/// 删除共享内存段，段从管理器中移除后不能再被查找与映射
+/// 没有映射时物理页帧立即回收，否则在最后一次解除映射后回收
+pub fn shm_remove(id: usize) -> bool {
+    SHM_MANAGER.lock().segments.remove(&id).is_some()
+}
@@ -3,7 +3,7 @@ use core::panic::PanicInfo;
 // use crate::print::sys_exit;
 // use crate::println;
 use crate::println;
-use crate::sbi::shutdown;
+use crate::sbi::panic_shutdown;
 
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
@@ -17,5 +17,5 @@ fn panic(_info: &PanicInfo) -> ! {
     } else {
         println!("Panicked :{}", _info.message().unwrap())
     }
-    shutdown();
+    panic_shutdown();
 }
@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use crate::println;
 use core::arch::asm;
 ///使用RustSBI接口进行相关操作
 ///
@@ -38,6 +39,29 @@ pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 pub fn shutdown() -> ! {
+    //关机前打印堆内存的统计与尚未回收的分配
+    #[cfg(feature = "alloc_trace")]
+    {
+        crate::system_allocator::track::track_report();
+        crate::system_allocator::track::dump_live();
+    }
+    //写回所有挂载的文件系统，避免数据丢失
+    if !crate::file::sync_all() {
+        println!("[kernel] sync failed, some blocks may not be written back");
+    }
+    crate::file::print_block_cache_stats();
+    power_off()
+}
+/// panic时关机，持有锁的位置可能正是panic的位置，因此只尝试写回
+pub fn panic_shutdown() -> ! {
+    #[cfg(feature = "alloc_trace")]
+    crate::system_allocator::track::dump_live();
+    if !crate::file::try_sync_all() {
+        println!("[kernel] block cache busy, some blocks may not be written back");
+    }
+    power_off()
+}
+fn power_off() -> ! {
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     // panic!("It should shutdown\n");
     panic!()
@@ -1,12 +1,17 @@
-use crate::file::{create_nlink_file, delete_nlink_file, open_file, OpenFlags, Pipe, Stat};
-use crate::list_apps;
+use crate::file::{
+    chown_path, create_nlink_file, delete_nlink_file, fcntl_lock, flock, make_dir, make_symlink,
+    open_path, path_filesystem, read_link, rename_file, stat_path, sync_all, DiskQuota, Flock,
+    OpenFlags, Pipe, Stat, StatFs, F_GETLK, F_SETLK, F_SETLKW,
+};
+use crate::fs::{mount, umount};
 use crate::mm::page_table::{
-    translated_byte_buffer, translated_refmut, translated_str, UserBuffer,
+    translated_byte_buffer, translated_ref, translated_refmut, translated_str, UserBuffer,
 };
 use alloc::sync::Arc;
 
+use crate::config::MAX_FD;
 use crate::task::current_user_token;
-use crate::task::processor::current_process;
+use crate::task::processor::{current_process, current_uid};
 
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
@@ -48,7 +53,8 @@ pub fn sys_open(path: *const u8, flags: u32) -> isize {
     //打开文件返回一个描述符
     let token = current_user_token();
     let name = translated_str(token, path);
-    if let Some(node) = open_file(name.as_str(), OpenFlags::from_bits(flags).unwrap()) {
+    let flags = OpenFlags::from_bits(flags).unwrap();
+    if let Some(node) = open_path(name.as_str(), flags) {
         let process = current_process();
         let mut inner = process.get_inner_access();
         // let data = node.read_all();
@@ -56,6 +62,9 @@ pub fn sys_open(path: *const u8, flags: u32) -> isize {
         let fd = inner.get_one_fd(); //分配文件描述符
                                      //
         inner.fd_table[fd] = Some(node.clone());
+        if flags.contains(OpenFlags::CLOEXEC) {
+            inner.cloexec.insert(fd);
+        }
         fd as isize
     } else {
         -1
@@ -80,14 +89,12 @@ pub fn sys_close(fd: usize) -> isize {
     // "关闭进程打开的文件描述符
     let process = current_process();
     let mut process_inner = process.get_inner_access();
-    if fd >= process_inner.fd_table.len() {
-        return -1;
-    }
-    if process_inner.fd_table[fd].is_none() {
-        return -1; //检查是否已经关闭过
+    //检查是否已经关闭过
+    if process_inner.close_fd(process.get_pid(), fd) {
+        0
+    } else {
+        -1
     }
-    process_inner.fd_table[fd].take();
-    0
 }
 pub fn sys_mail_read(buf: *mut u8, len: usize) -> isize {
     sys_read(3, buf, len)
@@ -109,9 +116,122 @@ pub fn sys_dup(fd: usize) -> isize {
     inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap())); //复制fd
     new_fd as isize
 }
-pub fn sys_ls() -> isize {
-    list_apps();
-    0
+///将old_fd复制到new_fd，new_fd已经打开时先关闭，flags只能包含O_CLOEXEC
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (flags - OpenFlags::CLOEXEC).is_empty() => flags,
+        _ => return -1,
+    };
+    if old_fd == new_fd || new_fd >= MAX_FD {
+        return -1;
+    }
+    let process = current_process();
+    let mut inner = process.get_inner_access();
+    let file = match inner.fd_table.get(old_fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    inner.close_fd(process.get_pid(), new_fd);
+    if new_fd >= inner.fd_table.len() {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    if flags.contains(OpenFlags::CLOEXEC) {
+        inner.cloexec.insert(new_fd);
+    }
+    new_fd as isize
+}
+///flock系统调用，对整个文件加共享锁或独占锁
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            flock(&file, operation)
+        }
+        _ => -1,
+    }
+}
+//fcntl的命令，记录锁的命令在file::lock中
+const F_DUPFD: usize = 0;
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_DUPFD_CLOEXEC: usize = 1030;
+const FD_CLOEXEC: usize = 1;
+
+///fcntl系统调用，操作描述符的标志、文件状态标志与记录锁
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let mut inner = process.get_inner_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            if arg >= MAX_FD {
+                return -1;
+            }
+            let new_fd = inner.get_fd_from(arg);
+            inner.fd_table[new_fd] = Some(file);
+            if cmd == F_DUPFD_CLOEXEC {
+                inner.cloexec.insert(new_fd);
+            }
+            new_fd as isize
+        }
+        F_GETFD => {
+            if inner.cloexec.contains(&fd) {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            if arg & FD_CLOEXEC != 0 {
+                inner.cloexec.insert(fd);
+            } else {
+                inner.cloexec.remove(&fd);
+            }
+            0
+        }
+        //文件状态标志属于打开的文件，复制的描述符共享同一份
+        F_GETFL => file.status_flags().bits() as isize,
+        F_SETFL => {
+            drop(inner);
+            file.set_status_flags(OpenFlags::from_bits_truncate(arg as u32).status());
+            0
+        }
+        F_GETLK | F_SETLK | F_SETLKW => {
+            drop(inner);
+            //等待期间不持有用户内存的引用
+            let mut lock = *translated_ref(token, arg as *const Flock);
+            let result = fcntl_lock(&file, process.get_pid(), cmd, &mut lock);
+            if result == 0 && cmd == F_GETLK {
+                *translated_refmut(token, arg as *mut Flock) = lock;
+            }
+            result
+        }
+        _ => -1,
+    }
+}
+///读取目录fd中的目录项，以linux_dirent64的格式写入用户缓冲区
+pub fn sys_getdents64(fd: usize, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            file.getdents(UserBuffer::new(translated_byte_buffer(token, buf, len)))
+        }
+        _ => -1,
+    }
 }
 
 ///根据fd找到文件的相关信息
@@ -134,6 +254,76 @@ pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
         return -1; //不存在打开的文件
     }
 }
+///查看path所在文件系统的使用情况
+pub fn sys_statfs(path: *const u8, buf: *mut StatFs) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    match path_filesystem(path.as_str()) {
+        Some(fs) => {
+            *translated_refmut(token, buf) = fs.statfs();
+            0
+        }
+        None => -1,
+    }
+}
+///查看打开的文件所在文件系统的使用情况
+pub fn sys_fstatfs(fd: usize, buf: *mut StatFs) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.get_inner_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match file.statfs() {
+        Some(statfs) => {
+            *translated_refmut(token, buf) = statfs;
+            0
+        }
+        None => -1,
+    }
+}
+//quotactl的命令，与Linux的QCMD(Q_GETQUOTA, USRQUOTA)、QCMD(Q_SETQUOTA, USRQUOTA)相同
+const Q_GETQUOTA: usize = 0x80000700;
+const Q_SETQUOTA: usize = 0x80000800;
+///查看或者设置path所在文件系统中用户uid的块限额
+///没有设置限额或者文件系统不支持限额时Q_GETQUOTA返回-1
+///只有超级用户可以设置限额或者查看其它用户的限额
+pub fn sys_quotactl(cmd: usize, path: *const u8, uid: usize, quota: *mut DiskQuota) -> isize {
+    let caller = current_uid();
+    if caller != 0 && (cmd != Q_GETQUOTA || caller as usize != uid) {
+        return -1;
+    }
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let fs = match path_filesystem(path.as_str()) {
+        Some(fs) => fs,
+        None => return -1,
+    };
+    let quota = translated_refmut(token, quota);
+    match cmd {
+        Q_GETQUOTA => match fs.quota(uid as u32) {
+            Some((limit, used)) => {
+                quota.limit = limit as u64;
+                quota.used = used as u64;
+                0
+            }
+            None => -1,
+        },
+        Q_SETQUOTA if fs.set_quota(uid as u32, quota.limit as usize) => 0,
+        _ => -1,
+    }
+}
+///修改文件的所有者，只有超级用户可以修改
+pub fn sys_fchownat(path: *const u8, uid: usize, gid: usize) -> isize {
+    if current_uid() != 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    chown_path(path.as_str(), uid as u32, gid as u32)
+}
 ///建立硬链接
 pub fn sys_linkat(old_path: *const u8, new_path: *const u8) -> isize {
     let token = current_user_token();
@@ -141,9 +331,164 @@ pub fn sys_linkat(old_path: *const u8, new_path: *const u8) -> isize {
     let new_path = translated_str(token, new_path);
     create_nlink_file(new_path.as_str(), old_path.as_str())
 }
+///新建符号链接
+pub fn sys_symlinkat(target: *const u8, link_path: *const u8) -> isize {
+    let token = current_user_token();
+    let target = translated_str(token, target);
+    let link_path = translated_str(token, link_path);
+    make_symlink(target.as_str(), link_path.as_str())
+}
+///将符号链接的目标写入用户缓冲区，不添加'\0'，返回写入的字节数
+pub fn sys_readlinkat(path: *const u8, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let target = match read_link(path.as_str()) {
+        Some(target) => target,
+        None => return -1,
+    };
+    let mut written = 0;
+    for buffer in translated_byte_buffer(token, buf, len.min(target.len())) {
+        buffer.copy_from_slice(&target.as_bytes()[written..written + buffer.len()]);
+        written += buffer.len();
+    }
+    written as isize
+}
+//fstatat的flags，不跟随最后一个符号链接
+const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+///根据路径查看文件信息，flags包含AT_SYMLINK_NOFOLLOW时查看符号链接本身
+pub fn sys_fstatat(path: *const u8, stat: *mut Stat, flags: usize) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    match stat_path(path.as_str(), flags & AT_SYMLINK_NOFOLLOW == 0) {
+        Some(fstat) => {
+            *translated_refmut(token, stat) = fstat;
+            0
+        }
+        None => -1,
+    }
+}
 ///解除硬链接
 pub fn sys_unlinkat(path: *const u8) -> isize {
     let token = current_user_token();
     let path = translated_str(token, path);
     delete_nlink_file(path.as_str())
 }
+///重命名文件，目标文件存在时被替换
+pub fn sys_renameat(old_path: *const u8, new_path: *const u8) -> isize {
+    let token = current_user_token();
+    let old_path = translated_str(token, old_path);
+    let new_path = translated_str(token, new_path);
+    rename_file(old_path.as_str(), new_path.as_str())
+}
+
+///新建目录
+pub fn sys_mkdirat(path: *const u8) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    make_dir(path.as_str())
+}
+
+///将块设备source上类型为fs_type的文件系统挂载到target目录，只有超级用户可以挂载
+pub fn sys_mount(source: *const u8, target: *const u8, fs_type: *const u8, _flags: usize) -> isize {
+    if current_uid() != 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let source = translated_str(token, source);
+    let target = translated_str(token, target);
+    let fs_type = translated_str(token, fs_type);
+    mount(source.as_str(), target.as_str(), fs_type.as_str())
+}
+
+///卸载target上的文件系统，还有打开的文件时失败，只有超级用户可以卸载
+pub fn sys_umount(target: *const u8, _flags: usize) -> isize {
+    if current_uid() != 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let target = translated_str(token, target);
+    umount(target.as_str())
+}
+
+///将所有文件的修改写回磁盘
+pub fn sys_sync() -> isize {
+    if sync_all() {
+        0
+    } else {
+        -1
+    }
+}
+
+///将某个文件的修改写回磁盘
+pub fn sys_fsync(fd: usize) -> isize {
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            file.fsync()
+        }
+        _ => -1,
+    }
+}
+
+///移动文件的读写位置，返回新的位置
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            file.seek(offset, whence)
+        }
+        _ => -1,
+    }
+}
+
+///从文件的offset处读取，不改变文件的读写位置
+pub fn sys_pread(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            let buffer = translated_byte_buffer(token, buf, len);
+            file.pread(UserBuffer::new(buffer), offset)
+        }
+        _ => -1,
+    }
+}
+
+///写入文件的offset处，不改变文件的读写位置
+pub fn sys_pwrite(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            let buffer = translated_byte_buffer(token, buf, len);
+            file.pwrite(UserBuffer::new(buffer), offset)
+        }
+        _ => -1,
+    }
+}
+
+///将文件截断或扩大到len字节
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    let process = current_process();
+    let inner = process.get_inner_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => {
+            let file = file.clone();
+            drop(inner);
+            file.truncate(len)
+        }
+        _ => -1,
+    }
+}
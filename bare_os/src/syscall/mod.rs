@@ -3,7 +3,7 @@ mod multhread;
 mod process;
 mod sync;
 
-use crate::file::Stat;
+use crate::file::{DiskQuota, Stat, StatFs};
 use crate::syscall::file::*;
 use crate::timer::Time;
 use multhread::*;
@@ -21,6 +21,8 @@ const SYSCALL_EXEC: usize = 221;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_PID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_SETUID: usize = 146;
 const SYSCALL_MMAP: usize = 222;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_PIPE: usize = 59;
@@ -28,11 +30,35 @@ const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_MAILREAD: usize = 401;
 const SYSCALL_MAILWRITE: usize = 402;
 const SYSCALL_OPEN: usize = 56;
-const SYSCALL_DUP: usize = 24;
-const SYSCALL_LS: usize = 44; //自定义ls
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_FLOCK: usize = 32;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_LINKAT: usize = 37;
 const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_RENAMEAT: usize = 38;
+const SYSCALL_SYMLINKAT: usize = 36;
+const SYSCALL_READLINKAT: usize = 78;
+const SYSCALL_FSTATAT: usize = 79;
 const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_STATFS: usize = 43;
+const SYSCALL_FSTATFS: usize = 44;
+const SYSCALL_QUOTACTL: usize = 60;
+const SYSCALL_FCHOWNAT: usize = 54;
+const SYSCALL_SYNC: usize = 81;
+const SYSCALL_FSYNC: usize = 82;
+const SYSCALL_LSEEK: usize = 62;
+const SYSCALL_PREAD: usize = 67;
+const SYSCALL_PWRITE: usize = 68;
+const SYSCALL_FTRUNCATE: usize = 46;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_UMOUNT: usize = 39;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
 
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
@@ -48,7 +74,7 @@ const SYSCALL_MONITOR_CREATE: usize = 1030;
 const SYSCALL_MONITOR_SIGNAL: usize = 1031;
 const SYSCALL_MONITOR_WAIT: usize = 1032;
 
-pub fn syscall(call: usize, args: [usize; 3]) -> isize {
+pub fn syscall(call: usize, args: [usize; 4]) -> isize {
     match call {
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
@@ -62,17 +88,53 @@ pub fn syscall(call: usize, args: [usize; 3]) -> isize {
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
         SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_SHMGET => sys_shmget(args[0], args[1], args[2]),
+        SYSCALL_SHMCTL => sys_shmctl(args[0], args[1], args[2]),
+        SYSCALL_SHMAT => sys_shmat(args[0], args[1]),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
         SYSCALL_PID => sys_getpid(),
+        SYSCALL_GETUID => sys_getuid(),
+        SYSCALL_SETUID => sys_setuid(args[0]),
         SYSCALL_PIPE => sys_pipe(args[0] as usize as *mut usize),
         SYSCALL_CLOSE => sys_close(args[0]),
         SYSCALL_MAILREAD => sys_mail_read(args[0] as *mut u8, args[1]),
         SYSCALL_MAILWRITE => sys_mail_write(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_DUP => sys_dup(args[0]),
-        SYSCALL_LS => sys_ls(),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_FLOCK => sys_flock(args[0], args[1]),
+        SYSCALL_GETDENTS64 => sys_getdents64(args[0], args[1] as *const u8, args[2]),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_STATFS => sys_statfs(args[0] as *const u8, args[1] as *mut StatFs),
+        SYSCALL_FSTATFS => sys_fstatfs(args[0], args[1] as *mut StatFs),
+        SYSCALL_QUOTACTL => sys_quotactl(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as *mut DiskQuota,
+        ),
+        SYSCALL_FCHOWNAT => sys_fchownat(args[0] as *const u8, args[1], args[2]),
+        SYSCALL_SYNC => sys_sync(),
+        SYSCALL_FSYNC => sys_fsync(args[0]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_PREAD => sys_pread(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_PWRITE => sys_pwrite(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1]),
         SYSCALL_LINKAT => sys_linkat(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_SYMLINKAT => sys_symlinkat(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_READLINKAT => sys_readlinkat(args[0] as *const u8, args[1] as *const u8, args[2]),
+        SYSCALL_FSTATAT => sys_fstatat(args[0] as *const u8, args[1] as *mut Stat, args[2]),
         SYSCALL_UNLINKAT => sys_unlinkat(args[0] as *const u8),
+        SYSCALL_RENAMEAT => sys_renameat(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_MKDIRAT => sys_mkdirat(args[0] as *const u8),
+        SYSCALL_MOUNT => sys_mount(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2] as *const u8,
+            args[3],
+        ),
+        SYSCALL_UMOUNT => sys_umount(args[0] as *const u8, args[1]),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
         SYSCALL_WAITTID => sys_waittid(args[0]) as isize,
         SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
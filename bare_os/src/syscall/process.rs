@@ -5,11 +5,13 @@ use crate::mm::address::VirtAddr;
 use crate::mm::page_table::{translated_ref, translated_refmut, translated_str, PageTable};
 use crate::task::{current_user_token, exit_current_run_next, suspend_current_run_next};
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 
 const FD_STDOUT: usize = 1;
 const FD_STDIN: usize = 2;
 
+use crate::mm::shm::{shm_attach, shm_get, shm_remove, IPC_RMID};
 use crate::mm::MapPermission;
 use crate::task::processor::{current_add_area, current_delete_page, current_process};
 use crate::timer::Time;
@@ -74,6 +76,10 @@ pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
         let process = current_process();
         let len = args_v.len();
         process.exec(data.as_slice(), args_v);
+        //没有传入参数时以程序路径作为进程名称
+        if len == 0 {
+            process.get_inner_access().cmdline = vec![name];
+        }
         len as isize
     } else {
         -1
@@ -125,6 +131,20 @@ pub fn sys_getpid() -> isize {
     let current_process = current_process();
     current_process.get_pid() as isize
 }
+/// 当前进程的用户id
+pub fn sys_getuid() -> isize {
+    current_process().get_inner_access().uid as isize
+}
+/// 修改当前进程的用户id，只有超级用户可以切换到其它用户
+pub fn sys_setuid(uid: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.get_inner_access();
+    if inner.uid != 0 && inner.uid as usize != uid {
+        return -1;
+    }
+    inner.uid = uid as u32;
+    0
+}
 /// 申请长度为 len 字节的物理内存，
 /// 将其映射到 start 开始的虚存，内存页属性为 port
 pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
@@ -173,3 +193,67 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
     current_delete_page(start_vir);
     0
 }
+
+/// 获取共享内存段，返回段的标识符
+pub fn sys_shmget(key: usize, size: usize, flags: usize) -> isize {
+    match shm_get(key, size, flags) {
+        Some(id) => id as isize,
+        None => -1,
+    }
+}
+/// 控制共享内存段，目前只支持IPC_RMID
+pub fn sys_shmctl(shmid: usize, cmd: usize, _buf: usize) -> isize {
+    match cmd {
+        IPC_RMID if shm_remove(shmid) => 0,
+        _ => -1,
+    }
+}
+/// 将共享内存段映射到地址空间中，addr为0时由内核选择映射地址
+/// 返回映射的起始地址
+pub fn sys_shmat(shmid: usize, addr: usize) -> isize {
+    let start_vir: VirtAddr = addr.into();
+    if !start_vir.aligned() {
+        return -1;
+    }
+    let attach = match shm_attach(shmid) {
+        Some(attach) => attach,
+        None => return -1,
+    };
+    let pages = attach.segment().pages();
+    let process = current_process();
+    let mut process_inner = process.get_inner_access();
+    let start_vir = if addr == 0 {
+        process_inner.memory_set.find_free_area(pages)
+    } else {
+        //指定的区域不能已经被映射
+        let start_vpn = start_vir.floor();
+        if (start_vpn.0..start_vpn.0 + pages)
+            .any(|vpn| process_inner.memory_set.translate(vpn.into()).is_some())
+        {
+            return -1;
+        }
+        start_vir
+    };
+    process_inner.memory_set.insert_shared_area(
+        start_vir,
+        attach,
+        MapPermission::R | MapPermission::W | MapPermission::U,
+    );
+    usize::from(start_vir) as isize
+}
+/// 解除共享内存段的映射
+pub fn sys_shmdt(addr: usize) -> isize {
+    let start_vir: VirtAddr = addr.into();
+    if !start_vir.aligned() {
+        return -1;
+    }
+    if current_process()
+        .get_inner_access()
+        .memory_set
+        .remove_shared_area(start_vir)
+    {
+        0
+    } else {
+        -1
+    }
+}
@@ -30,6 +30,7 @@ pub struct Buddy {
     free_lists: [*mut Node; MAXLISTS], //每个队列都是按照2的幂进行对齐
     linked_list: Locked<LinkedListAllocator>,
     max_free_index:usize,
+    heap_size: usize, //buddy管理的内存总量
 }
 impl Debug for Buddy {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
@@ -47,6 +48,7 @@ impl Buddy {
             free_lists: [null_mut(); MAXLISTS],
             linked_list: Locked::new(LinkedListAllocator::new()),
             max_free_index: 0,
+            heap_size: 0,
         }
     }
     pub(crate) fn init(&mut self, heap_start: usize, heap_size: usize) {
@@ -54,11 +56,21 @@ impl Buddy {
         assert!(heap_size >= size_of::<Node>());
         // init the bump allocator
         self.linked_list.lock().init(heap_start, heap_size);
+        self.heap_size = heap_size;
+    }
+    /// 向堆中加入一段新的内存区域
+    pub(crate) fn add_to_heap(&mut self, start: usize, size: usize) {
+        assert!(size >= size_of::<Node>());
+        self.linked_list.lock().add_region(start, size);
+        self.heap_size += size;
+    }
+    pub fn heap_size(&self) -> usize {
+        self.heap_size
     }
     /// recycle the memory
     /// 尽量与前面的合并而不是后面
     /// 尽量保持序列有序
-    fn delete(&mut self, address: usize, size: usize) {
+    pub(crate) fn delete(&mut self, address: usize, size: usize) {
         //make sure the address align with BuddyNode
         assert_eq!(align_up(address, core::mem::align_of::<Node>()), address);
         assert!(size >= core::mem::size_of::<Node>());
@@ -140,7 +152,7 @@ impl Buddy {
         }
         null_mut()
     }
-    fn get(&mut self, layout: Layout) -> *mut u8 {
+    pub(crate) fn get(&mut self, layout: Layout) -> *mut u8 {
         //make sure the size is power of 2
         //找到Node和请求内存大小的较大者,并对齐到2的幂次
         // DEBUG!("{:?}", layout);
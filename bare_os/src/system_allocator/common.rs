@@ -20,6 +20,9 @@ impl<T> Locked<T> {
     pub fn lock(&self) -> MutexGuard<T> {
         self.inner.lock()
     }
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        self.inner.try_lock()
+    }
 }
 
 /// 内存对齐
@@ -38,6 +38,12 @@ impl LinkedListAllocator {
             self.push(heap_start, heap_size);
         }
     }
+    /// 加入新的空闲区域
+    pub fn add_region(&mut self, address: usize, size: usize) {
+        unsafe {
+            self.push(address, size);
+        }
+    }
     unsafe fn push(&mut self, address: usize, size: usize) {
         //判断是否满足对齐要求
         //是否满足大小要求
@@ -1,19 +1,29 @@
 #![allow(unused)]
-use crate::config::KERNEL_HEAP_SIZE;
+use crate::config::{KERNEL_HEAP_GROW_PAGES, KERNEL_HEAP_SIZE, PAGE_SIZE};
+use crate::mm::frame_allocator::frame_alloc_heap;
 use crate::system_allocator::bump_allocator::BumpAllocator;
 use crate::system_allocator::common::Locked;
 // use crate::system_allocator::linked_list::LinkedListAllocator;
 use crate::system_allocator::buddy::Buddy;
 use crate::system_allocator::linked_list::LinkedListAllocator;
-use crate::INFO;
+use crate::system_allocator::slab::{arc_layout, SlabCaches, SlabStats};
+use crate::{println, INFO};
+use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+use core::ptr::null_mut;
 
 extern crate buddy_system_allocator;
 pub mod buddy;
 /// 实现自己的堆分配器
 pub mod bump_allocator;
-mod common;
+pub mod common;
 mod linked_list;
+pub mod slab;
+/// 堆分配跟踪
+#[cfg(feature = "alloc_trace")]
+pub mod track;
 
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
@@ -21,18 +31,164 @@ static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 // pub static ALLOCATOR: Locked<BumpAllocator> = Locked::new(BumpAllocator::new());
 // static ALLOCATOR: Locked<LinkedListAllocator> = Locked::new(LinkedListAllocator::new());
 // static ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
-static ALLOCATOR: Locked<Buddy> = Locked::new(Buddy::new());
+// static ALLOCATOR: Locked<Buddy> = Locked::new(Buddy::new());
+static ALLOCATOR: KernelHeap = KernelHeap::new();
+
+/// 内核堆
+/// 固定大小的对象优先从slab缓存中分配，其余请求交给buddy分配器
+/// buddy分配器空间不足时向页帧分配器申请连续页帧扩充堆
+pub struct KernelHeap {
+    buddy: Locked<Buddy>,
+    slabs: Locked<SlabCaches>,
+    grow_count: Locked<usize>,
+    grow_failed: Locked<usize>,
+}
+
+/// 内核堆的使用情况
+#[derive(Debug)]
+pub struct HeapStats {
+    pub heap_size: usize,   //堆的总大小
+    pub grow_count: usize,  //扩容次数
+    pub grow_failed: usize, //扩容失败的次数
+    pub slabs: Vec<SlabStats>,
+}
+
+impl KernelHeap {
+    pub const fn new() -> Self {
+        Self {
+            buddy: Locked::new(Buddy::new()),
+            slabs: Locked::new(SlabCaches::new()),
+            grow_count: Locked::new(0),
+            grow_failed: Locked::new(0),
+        }
+    }
+    /// buddy分配器使用的布局，大小为2的幂并按大小对齐
+    fn buddy_layout(layout: Layout) -> Layout {
+        let size = layout
+            .size()
+            .max(layout.align())
+            .max(size_of::<buddy::Node>())
+            .next_power_of_two();
+        Layout::from_size_align(size, size).unwrap()
+    }
+    /// 从buddy分配器中分配，空间不足时扩充堆后重试
+    fn buddy_alloc(&self, layout: Layout) -> *mut u8 {
+        let layout = Self::buddy_layout(layout);
+        let mut buddy = self.buddy.lock();
+        let answer = buddy.get(layout);
+        if !answer.is_null() {
+            return answer;
+        }
+        //扩充的区域按照自身大小对齐，保证可以直接切分出请求的大小
+        let size = layout
+            .size()
+            .max(KERNEL_HEAP_GROW_PAGES * PAGE_SIZE)
+            .next_power_of_two();
+        let pages = size / PAGE_SIZE;
+        match frame_alloc_heap(pages, pages) {
+            Some(ppn) => {
+                buddy.add_to_heap(ppn.0 * PAGE_SIZE, size);
+                *self.grow_count.lock() += 1;
+                buddy.get(layout)
+            }
+            None => {
+                *self.grow_failed.lock() += 1;
+                null_mut()
+            }
+        }
+    }
+    fn buddy_dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let layout = Self::buddy_layout(layout);
+        self.buddy.lock().delete(ptr as usize, layout.size());
+    }
+    fn register_slab_cache(&self, name: &'static str, layout: Layout) -> bool {
+        self.slabs.lock().register(name, layout)
+    }
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            heap_size: self.buddy.lock().heap_size(),
+            grow_count: *self.grow_count.lock(),
+            grow_failed: *self.grow_failed.lock(),
+            slabs: self.slabs.lock().stats().collect(),
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        //加锁顺序: slab -> buddy -> 页帧分配器
+        let mut slabs = self.slabs.lock();
+        let answer = if let Some(cache) = slabs.find(layout) {
+            cache.alloc(|slab_layout| self.buddy_alloc(slab_layout))
+        } else {
+            drop(slabs);
+            self.buddy_alloc(layout)
+        };
+        #[cfg(feature = "alloc_trace")]
+        track::track_alloc(answer, layout);
+        answer
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        #[cfg(feature = "alloc_trace")]
+        track::track_dealloc(ptr, layout);
+        let mut slabs = self.slabs.lock();
+        if let Some(cache) = slabs.find(layout) {
+            cache.dealloc(ptr);
+            return;
+        }
+        drop(slabs);
+        self.buddy_dealloc(ptr, layout)
+    }
+}
+
+/// 初始化内核堆，slab缓存在第一次分配之前注册
+/// 回收时只根据布局判断对象是否属于slab，注册之前从buddy分配的同样布局的对象会被错误地放入slab
 pub fn init_heap() {
     unsafe {
         ALLOCATOR
+            .buddy
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
+    init_slab_caches();
+}
+
+/// 为频繁创建销毁的内核对象建立slab缓存
+/// 页表节点直接占用整个页帧，由页帧分配器负责，这里只缓存PageTable结构本身
+fn init_slab_caches() {
+    use crate::mm::page_table::PageTable;
+    use crate::task::{ProcessControlBlock, TaskControlBlock};
+    ALLOCATOR.register_slab_cache("task", arc_layout::<TaskControlBlock>());
+    ALLOCATOR.register_slab_cache("process", arc_layout::<ProcessControlBlock>());
+    ALLOCATOR.register_slab_cache("page_table", Layout::new::<PageTable>());
+    ALLOCATOR.register_slab_cache(
+        "block_cache",
+        arc_layout::<spin::Mutex<easyfs::BlockCache>>(),
+    );
+    INFO!("[kernel] init_slab_caches ok!");
+}
+
+pub fn heap_stats() -> HeapStats {
+    ALLOCATOR.stats()
+}
+
+/// 打印内核堆与各个slab缓存的使用情况
+pub fn heap_report() {
+    let stats = heap_stats();
+    println!(
+        "[kernel] heap: size {:#x} grow {} times, {} failed",
+        stats.heap_size, stats.grow_count, stats.grow_failed
+    );
+    for slab in stats.slabs.iter() {
+        println!(
+            "[kernel] slab {}: object {} slabs {} objects {} in use {}",
+            slab.name, slab.object_size, slab.slabs, slab.objects, slab.in_use
+        );
+    }
 }
 
 #[allow(unused)]
 pub fn heap_test() {
-    init_heap();
     use alloc::boxed::Box; //使用Box包装器
     use alloc::vec::Vec; //使用vec数组
     extern "C" {
@@ -60,3 +216,35 @@ pub fn heap_test() {
     drop(v);
     INFO!("[kernel] heap_test passed!");
 }
+
+#[allow(unused)]
+pub fn slab_test() {
+    use alloc::alloc::{alloc, dealloc};
+    use crate::task::TaskControlBlock;
+    let layout = arc_layout::<TaskControlBlock>();
+    let in_use = |stats: &HeapStats| {
+        stats
+            .slabs
+            .iter()
+            .find(|slab| slab.name == "task")
+            .unwrap()
+            .in_use
+    };
+    let before = in_use(&heap_stats());
+    let a = unsafe { alloc(layout) };
+    let b = unsafe { alloc(layout) };
+    assert!(!a.is_null() && !b.is_null());
+    assert_ne!(a, b);
+    assert_eq!(in_use(&heap_stats()), before + 2);
+    unsafe { dealloc(a, layout) };
+    //回收的对象会被下一次分配直接复用
+    let c = unsafe { alloc(layout) };
+    assert_eq!(a, c);
+    unsafe {
+        dealloc(b, layout);
+        dealloc(c, layout);
+    }
+    assert_eq!(in_use(&heap_stats()), before);
+    heap_report();
+    INFO!("[kernel] slab_test passed!");
+}
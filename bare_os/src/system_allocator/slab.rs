@@ -0,0 +1,144 @@
+///! slab缓存
+///! 为频繁创建与销毁的固定大小对象(线程控制块、进程控制块、块缓存项)建立专门的缓存
+///! 每个缓存从buddy分配器中申请一整块slab，再切分为大小相同的对象
+///! 回收的对象放入空闲链表中，下一次分配可以直接复用
+use crate::config::PAGE_SIZE;
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+
+pub const MAX_SLAB_CACHES: usize = 8;
+const SLAB_MIN_OBJECTS: usize = 8; //每个slab至少容纳的对象数
+
+/// 对象在Arc中的内存布局
+/// Arc::new会把强弱引用计数和对象放在一起分配
+pub fn arc_layout<T>() -> Layout {
+    Layout::new::<[usize; 2]>()
+        .extend(Layout::new::<T>())
+        .unwrap()
+        .0
+        .pad_to_align()
+}
+
+/// 某个缓存的使用情况
+#[derive(Debug, Copy, Clone)]
+pub struct SlabStats {
+    pub name: &'static str,
+    pub object_size: usize, //对象大小
+    pub slabs: usize,       //已经申请的slab数量
+    pub objects: usize,     //对象总数
+    pub in_use: usize,      //正在使用的对象数
+}
+
+pub struct SlabCache {
+    name: &'static str,
+    layout: Layout,     //缓存对象的布局，分配请求与其完全相同时才使用本缓存
+    object_size: usize, //每个对象实际占用的大小
+    free_list: usize,   //空闲对象链表，每个空闲对象的开头存放下一个空闲对象的地址
+    slabs: usize,
+    objects: usize,
+    in_use: usize,
+}
+
+impl SlabCache {
+    pub fn new(name: &'static str, layout: Layout) -> Self {
+        //空闲对象需要能存放一个指针
+        let align = layout.align().max(align_of::<usize>());
+        let object_size = layout.pad_to_align().size().max(size_of::<usize>());
+        let object_size = (object_size + align - 1) / align * align;
+        Self {
+            name,
+            layout,
+            object_size,
+            free_list: 0,
+            slabs: 0,
+            objects: 0,
+            in_use: 0,
+        }
+    }
+    pub fn matches(&self, layout: Layout) -> bool {
+        self.layout == layout
+    }
+    /// 每个slab的布局，大小为2的幂并按大小对齐
+    pub fn slab_layout(&self) -> Layout {
+        let size = (self.object_size * SLAB_MIN_OBJECTS)
+            .next_power_of_two()
+            .max(PAGE_SIZE);
+        Layout::from_size_align(size, size).unwrap()
+    }
+    fn push(&mut self, address: usize) {
+        unsafe {
+            (address as *mut usize).write(self.free_list);
+        }
+        self.free_list = address;
+    }
+    /// 分配一个对象，空闲链表为空时通过refill申请新的slab
+    pub fn alloc(&mut self, refill: impl FnOnce(Layout) -> *mut u8) -> *mut u8 {
+        if self.free_list == 0 {
+            let slab_layout = self.slab_layout();
+            let slab = refill(slab_layout);
+            if slab.is_null() {
+                return null_mut();
+            }
+            let count = slab_layout.size() / self.object_size;
+            //倒序插入，使得对象按地址从低到高分配
+            for i in (0..count).rev() {
+                self.push(slab as usize + i * self.object_size);
+            }
+            self.slabs += 1;
+            self.objects += count;
+        }
+        let object = self.free_list;
+        self.free_list = unsafe { (object as *const usize).read() };
+        self.in_use += 1;
+        object as *mut u8
+    }
+    pub fn dealloc(&mut self, ptr: *mut u8) {
+        self.push(ptr as usize);
+        self.in_use -= 1;
+    }
+    pub fn stats(&self) -> SlabStats {
+        SlabStats {
+            name: self.name,
+            object_size: self.object_size,
+            slabs: self.slabs,
+            objects: self.objects,
+            in_use: self.in_use,
+        }
+    }
+}
+
+/// 所有的slab缓存
+pub struct SlabCaches {
+    caches: [Option<SlabCache>; MAX_SLAB_CACHES],
+}
+
+impl SlabCaches {
+    pub const fn new() -> Self {
+        const EMPTY: Option<SlabCache> = None;
+        Self {
+            caches: [EMPTY; MAX_SLAB_CACHES],
+        }
+    }
+    /// 注册一个新的缓存，相同布局的缓存只会存在一个
+    pub fn register(&mut self, name: &'static str, layout: Layout) -> bool {
+        if self.find(layout).is_some() {
+            return false;
+        }
+        if let Some(slot) = self.caches.iter_mut().find(|cache| cache.is_none()) {
+            *slot = Some(SlabCache::new(name, layout));
+            true
+        } else {
+            false
+        }
+    }
+    pub fn find(&mut self, layout: Layout) -> Option<&mut SlabCache> {
+        self.caches
+            .iter_mut()
+            .flatten()
+            .find(|cache| cache.matches(layout))
+    }
+    pub fn stats(&self) -> impl Iterator<Item = SlabStats> + '_ {
+        self.caches.iter().flatten().map(|cache| cache.stats())
+    }
+}
@@ -0,0 +1,204 @@
+///! 堆分配跟踪
+///! 开启alloc_trace特性后，每一次堆分配与回收都会在这里登记
+///! 统计各个大小类别的分配次数、峰值使用量以及尚未回收的内存块
+///! 关机时打印所有未回收的分配以及分配时的调用地址，用于检查进程退出等路径上的内存泄漏
+use crate::system_allocator::buddy::find_last_min_pow2;
+use crate::system_allocator::common::Locked;
+use crate::println;
+use core::alloc::Layout;
+use core::arch::asm;
+
+const SIZE_CLASSES: usize = 32; //按照2的幂划分大小类别
+const MAX_LIVE: usize = 4096; //最多记录的存活分配数量
+const TRACE_DEPTH: usize = 4; //记录的调用层数
+const SKIP_FRAMES: usize = 2; //跳过分配器自身的栈帧
+
+#[derive(Copy, Clone)]
+struct LiveAlloc {
+    seq: usize, //分配序号，区分启动阶段与运行阶段的分配
+    ptr: usize,
+    size: usize,
+    callers: [usize; TRACE_DEPTH],
+}
+
+impl LiveAlloc {
+    const fn empty() -> Self {
+        Self {
+            seq: 0,
+            ptr: 0,
+            size: 0,
+            callers: [0; TRACE_DEPTH],
+        }
+    }
+}
+
+pub struct AllocTracker {
+    allocs: [usize; SIZE_CLASSES], //每个大小类别的分配次数
+    frees: [usize; SIZE_CLASSES],  //每个大小类别的回收次数
+    seq: usize,
+    current: usize,     //当前使用的字节数
+    peak: usize,        //使用字节数的峰值
+    outstanding: usize, //尚未回收的内存块
+    live: [LiveAlloc; MAX_LIVE],
+    live_len: usize,
+    untracked: usize, //记录表已满而没有记录的分配
+}
+
+/// 跟踪统计信息
+#[derive(Debug, Copy, Clone)]
+pub struct TrackStats {
+    pub allocs: [usize; SIZE_CLASSES],
+    pub frees: [usize; SIZE_CLASSES],
+    pub current: usize,
+    pub peak: usize,
+    pub outstanding: usize,
+}
+
+static TRACKER: Locked<AllocTracker> = Locked::new(AllocTracker::new());
+
+impl AllocTracker {
+    const fn new() -> Self {
+        Self {
+            allocs: [0; SIZE_CLASSES],
+            frees: [0; SIZE_CLASSES],
+            seq: 0,
+            current: 0,
+            peak: 0,
+            outstanding: 0,
+            live: [LiveAlloc::empty(); MAX_LIVE],
+            live_len: 0,
+            untracked: 0,
+        }
+    }
+    fn record_alloc(&mut self, ptr: usize, size: usize, callers: [usize; TRACE_DEPTH]) {
+        self.allocs[size_class(size)] += 1;
+        self.seq += 1;
+        self.current += size;
+        self.peak = self.peak.max(self.current);
+        self.outstanding += 1;
+        if self.live_len < MAX_LIVE {
+            self.live[self.live_len] = LiveAlloc {
+                seq: self.seq,
+                ptr,
+                size,
+                callers,
+            };
+            self.live_len += 1;
+        } else {
+            self.untracked += 1;
+        }
+    }
+    fn record_dealloc(&mut self, ptr: usize, size: usize) {
+        self.frees[size_class(size)] += 1;
+        //跟踪开始之前或者跟踪器忙时分配的块同样会被回收，计数不能下溢
+        self.current = self.current.saturating_sub(size);
+        self.outstanding = self.outstanding.saturating_sub(1);
+        if let Some(index) = self.live[..self.live_len]
+            .iter()
+            .position(|live| live.ptr == ptr)
+        {
+            //与最后一项交换后删除
+            self.live_len -= 1;
+            self.live[index] = self.live[self.live_len];
+        } else {
+            self.untracked = self.untracked.saturating_sub(1);
+        }
+    }
+    fn stats(&self) -> TrackStats {
+        TrackStats {
+            allocs: self.allocs,
+            frees: self.frees,
+            current: self.current,
+            peak: self.peak,
+            outstanding: self.outstanding,
+        }
+    }
+}
+
+fn size_class(size: usize) -> usize {
+    find_last_min_pow2(size.max(1).next_power_of_two()).min(SIZE_CLASSES - 1)
+}
+
+/// 沿着帧指针回溯调用栈
+/// 内核编译时开启了force-frame-pointers，ra保存在fp-8处，上一个fp保存在fp-16处
+fn backtrace() -> [usize; TRACE_DEPTH] {
+    let mut callers = [0; TRACE_DEPTH];
+    let mut fp: usize;
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+    }
+    let mut depth = 0;
+    while fp != 0 && fp % 8 == 0 && depth < SKIP_FRAMES + TRACE_DEPTH {
+        let (ra, prev_fp) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if depth >= SKIP_FRAMES {
+            callers[depth - SKIP_FRAMES] = ra;
+        }
+        //栈向下增长，调用者的帧指针一定更大
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+        depth += 1;
+    }
+    callers
+}
+
+pub fn track_alloc(ptr: *mut u8, layout: Layout) {
+    if ptr.is_null() {
+        return;
+    }
+    let callers = backtrace();
+    TRACKER
+        .lock()
+        .record_alloc(ptr as usize, layout.size(), callers);
+}
+
+pub fn track_dealloc(ptr: *mut u8, layout: Layout) {
+    TRACKER.lock().record_dealloc(ptr as usize, layout.size());
+}
+
+pub fn track_stats() -> TrackStats {
+    TRACKER.lock().stats()
+}
+
+/// 打印各个大小类别的统计信息
+pub fn track_report() {
+    //总量由dump_live打印
+    let stats = track_stats();
+    for class in 0..SIZE_CLASSES {
+        if stats.allocs[class] != 0 {
+            println!(
+                "[kernel] size {:>8}: alloc {} free {}",
+                1usize << class,
+                stats.allocs[class],
+                stats.frees[class]
+            );
+        }
+    }
+}
+
+/// 打印所有尚未回收的分配
+/// 可能在panic时调用，此时跟踪器可能已经被锁住，因此使用try_lock
+pub fn dump_live() {
+    let tracker = match TRACKER.try_lock() {
+        Some(tracker) => tracker,
+        None => {
+            println!("[kernel] alloc track: tracker busy, skip dump");
+            return;
+        }
+    };
+    println!(
+        "[kernel] alloc track: current {:#x} peak {:#x} outstanding {}",
+        tracker.current, tracker.peak, tracker.outstanding
+    );
+    println!(
+        "[kernel] alloc track: {} live allocations, {} untracked",
+        tracker.live_len, tracker.untracked
+    );
+    for live in tracker.live[..tracker.live_len].iter() {
+        println!(
+            "[kernel] #{} {:#x} size {} callers {:x?}",
+            live.seq, live.ptr, live.size, live.callers
+        );
+    }
+}
@@ -25,6 +25,9 @@ impl TaskManager {
     pub fn pop(&mut self) -> Option<Arc<TaskControlBlock>> {
         self.task_ready_queue.pop_front() //FIFO，先进先出调度
     }
+    pub fn len(&self) -> usize {
+        self.task_ready_queue.len()
+    }
 }
 lazy_static! {
     static ref TASKMANAGER: Mutex<TaskManager> = Mutex::new(TaskManager::new());
@@ -37,3 +40,7 @@ pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     let next = TASKMANAGER.lock().pop();
     next
 }
+/// 就绪队列中等待运行的线程数
+pub fn ready_count() -> usize {
+    TASKMANAGER.lock().len()
+}
@@ -7,21 +7,26 @@ mod switch;
 mod task;
 
 use crate::file::open_file;
+use crate::file::release_process_locks;
 use crate::file::OpenFlags;
 use crate::task::context::TaskContext;
-use crate::task::process::ProcessControlBlock;
-use crate::task::task::TaskStatus;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use lazy_static::lazy_static;
 pub use manager::add_task;
-pub use processor::{current_trap_cx_ptr, current_user_token, run, schedule, take_current_task};
-pub use task::TaskControlBlock;
+pub use process::ProcessControlBlock;
+pub use processor::{
+    current_trap_cx_ptr, current_user_token, run, sched_stats, schedule, take_current_task,
+};
+pub use task::{TaskControlBlock, TaskStatus};
 
 lazy_static! {
     pub static ref INITPROC:Arc<ProcessControlBlock> = {
         let node = open_file("initproc",OpenFlags::R).unwrap();
         let data = node.read_all();
-        ProcessControlBlock::new(data.as_slice())
+        ProcessControlBlock::new(data.as_slice(), vec![String::from("initproc")])
     };
     //初始化初始进程
 }
@@ -31,6 +36,25 @@ pub fn add_initproc() {
     let _initproc = INITPROC.clone();
 }
 
+/// 从初始进程开始按pid顺序列出所有还没有被回收的进程
+pub fn all_processes() -> Vec<Arc<ProcessControlBlock>> {
+    let mut processes = vec![INITPROC.clone()];
+    let mut index = 0;
+    while index < processes.len() {
+        let children = processes[index].get_inner_access().children.clone();
+        processes.extend(children);
+        index += 1;
+    }
+    processes.sort_by_key(|process| process.get_pid());
+    processes
+}
+
+pub fn find_process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    all_processes()
+        .into_iter()
+        .find(|process| process.get_pid() == pid)
+}
+
 pub fn suspend_current_run_next() {
     // DEBUG!("[kernel] suspend_run_next");
     //将当前任务变成暂停状态
@@ -92,6 +116,11 @@ pub fn exit_current_run_next(exit_code: i32) {
         }
         process_inner.children.clear(); //清空所有的子进程
         process_inner.memory_set.clear_area_data();
+        //关闭所有的文件，释放进程持有的文件锁
+        let fd_table = core::mem::take(&mut process_inner.fd_table);
+        drop(process_inner);
+        drop(fd_table);
+        release_process_locks(process.get_pid());
     }
     //自动解除引用
     drop(process);
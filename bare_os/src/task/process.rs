@@ -1,4 +1,4 @@
-use crate::file::{open_file, File, Mail, OpenFlags, Stdin, Stdout};
+use crate::file::{open_file, release_record_locks, File, Mail, OpenFlags, Stdin, Stdout};
 use crate::mm::page_table::translated_refmut;
 use crate::mm::{MemorySet, KERNEL_SPACE};
 use crate::my_struct::my_ref_cell::MyRefCell;
@@ -8,6 +8,7 @@ use crate::task::id::{pid_alloc, PidHandle, RecycleAllocator};
 use crate::task::task::TaskControlBlock;
 use crate::trap::context::TrapFrame;
 use crate::trap::trap_handler;
+use alloc::collections::BTreeSet;
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
@@ -28,11 +29,14 @@ pub struct ProcessControlBlockInner {
     pub children: Vec<Arc<ProcessControlBlock>>, //子进程需要引用计数
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>, //文件描述符表
     /// 文件描述符 (File Descriptor) 代表了一个特定读写属性的I/O资源。
+    pub cloexec: BTreeSet<usize>, //设置了FD_CLOEXEC的文件描述符，exec时关闭
     pub task: Vec<Option<Arc<TaskControlBlock>>>, //线程管理器
     pub task_res_allocator: RecycleAllocator,    //升级版分配器
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>, //记录进程拥有的互斥资源
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>, //记录信号量资源
     pub monitor_list: Vec<Option<Arc<Monitor>>>, //记录管程资源
+    pub cmdline: Vec<String>,                    //启动时的参数，第一个是程序名
+    pub uid: u32,                                //用户id，0为超级用户，fork时继承
 }
 
 impl ProcessControlBlockInner {
@@ -47,12 +51,39 @@ impl ProcessControlBlockInner {
     }
     pub fn get_one_fd(&mut self) -> usize {
         //查看文件描述符表获取一个最小的描述符
-        if let Some(fd) = (0..self.fd_table.len()).find(|x| self.fd_table[*x].is_none()) {
-            fd
-        } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+        self.get_fd_from(0)
+    }
+    /// 不小于start的最小的空闲描述符，新的描述符没有FD_CLOEXEC
+    pub fn get_fd_from(&mut self, start: usize) -> usize {
+        let fd = match (start..self.fd_table.len()).find(|x| self.fd_table[*x].is_none()) {
+            Some(fd) => fd,
+            None => {
+                let fd = self.fd_table.len().max(start);
+                self.fd_table.resize(fd + 1, None);
+                fd
+            }
+        };
+        self.cloexec.remove(&fd);
+        fd
+    }
+    /// 关闭文件描述符，进程不再打开文件时释放它在文件上的记录锁
+    pub fn close_fd(&mut self, pid: usize, fd: usize) -> bool {
+        let file = match self.fd_table.get_mut(fd).and_then(|file| file.take()) {
+            Some(file) => file,
+            None => return false,
+        };
+        self.cloexec.remove(&fd);
+        if let Some(key) = file.lock_key() {
+            let opened = self
+                .fd_table
+                .iter()
+                .flatten()
+                .any(|other| other.lock_key() == Some(key));
+            if !opened {
+                release_record_locks(key, pid);
+            }
         }
+        true
     }
     pub fn alloc_tid(&mut self) -> usize {
         // 申请一个tid：线程描述符
@@ -70,10 +101,17 @@ impl ProcessControlBlockInner {
         //根据tid获取线程
         self.task[tid].as_ref().unwrap().clone()
     }
+    /// 进程名称，即程序名去掉目录的部分
+    pub fn name(&self) -> &str {
+        match self.cmdline.first() {
+            Some(path) => path.rsplit('/').next().unwrap_or(path),
+            None => "",
+        }
+    }
 }
 
 impl ProcessControlBlock {
-    pub fn new(data: &[u8]) -> Arc<Self> {
+    pub fn new(data: &[u8], cmdline: Vec<String>) -> Arc<Self> {
         //构造用户地址空间
         let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(data);
         //为进程分配pid
@@ -92,11 +130,14 @@ impl ProcessControlBlock {
                     Some(Arc::new(Stdout)),
                     Some(Mail::new()), //邮箱文件描述符
                 ],
+                cloexec: BTreeSet::new(),
                 task: Vec::new(),
                 task_res_allocator: RecycleAllocator::new(),
                 mutex_list: Vec::new(),
                 semaphore_list: Vec::new(),
                 monitor_list: Vec::new(),
+                cmdline,
+                uid: 0,
             }),
         }); //构造任务控制块
             //创建主线程
@@ -135,10 +176,12 @@ impl ProcessControlBlock {
         let data = node.read_all();
         if data.len() != 0 {
             //这里直接new一个新的进程，会创建主线程
-            let process_control_block = ProcessControlBlock::new(data.as_slice());
+            let process_control_block =
+                ProcessControlBlock::new(data.as_slice(), vec![String::from(path)]);
             //修改其父进程的引用
             let mut inner = process_control_block.get_inner_access();
             inner.parent = Some(Arc::downgrade(self));
+            inner.uid = self.get_inner_access().uid;
             self.get_inner_access()
                 .children
                 .push(process_control_block.clone());
@@ -159,7 +202,15 @@ impl ProcessControlBlock {
         //开辟几个存放地址的空间，这几个地址会指向更低地址存放的参数
         let token = memoryset.token();
         //更换地址空间
-        self.get_inner_access().memory_set = memoryset;
+        let mut inner = self.get_inner_access();
+        inner.memory_set = memoryset;
+        inner.cmdline = args.clone();
+        //关闭设置了FD_CLOEXEC的文件描述符
+        let cloexec: Vec<usize> = inner.cloexec.iter().copied().collect();
+        for fd in cloexec {
+            inner.close_fd(self.get_pid(), fd);
+        }
+        drop(inner);
         //为主线程申请资源
         let main_task = self.get_inner_access().get_task(0); //主线程
         let mut main_task_inner = main_task.get_inner_access();
@@ -234,11 +285,14 @@ impl ProcessControlBlock {
                 parent: Some(Arc::downgrade(self)), //弱引用
                 children: Vec::new(),
                 fd_table: new_fdtable,
+                cloexec: parent_inner.cloexec.clone(),
                 task: Vec::new(),
                 task_res_allocator: RecycleAllocator::new(),
                 mutex_list: Vec::new(),
                 semaphore_list: Vec::new(),
                 monitor_list: Vec::new(),
+                cmdline: parent_inner.cmdline.clone(),
+                uid: parent_inner.uid,
             }),
         }); //构造任务控制块
             //加入子进程中
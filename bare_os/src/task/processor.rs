@@ -1,12 +1,14 @@
 use crate::mm::address::VirtAddr;
 use crate::mm::MapPermission;
 use crate::task::context::TaskContext;
-use crate::task::manager::fetch_task;
+use crate::task::manager::{fetch_task, ready_count};
 use crate::task::process::ProcessControlBlock;
 use crate::task::switch::__switch;
 use crate::task::task::{TaskControlBlock, TaskStatus};
+use crate::timer::get_costtime;
 use crate::trap::context::TrapFrame;
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 
@@ -14,6 +16,25 @@ lazy_static! {
     static ref PROCESSOR: Mutex<Processor> = Mutex::new(Processor::new());
 }
 
+static SWITCHES: AtomicUsize = AtomicUsize::new(0); //切换到用户线程的次数
+static BUSY_TIME: AtomicUsize = AtomicUsize::new(0); //运行用户线程的总时间(us)
+
+/// 调度器的统计信息
+#[derive(Debug, Copy, Clone)]
+pub struct SchedStats {
+    pub switches: usize,
+    pub busy_time: usize,
+    pub ready: usize, //就绪队列的长度
+}
+
+pub fn sched_stats() -> SchedStats {
+    SchedStats {
+        switches: SWITCHES.load(Ordering::Relaxed),
+        busy_time: BUSY_TIME.load(Ordering::Relaxed),
+        ready: ready_count(),
+    }
+}
+
 pub struct Processor {
     //当前cpu执行的线程
     current: Option<Arc<TaskControlBlock>>,
@@ -63,6 +84,10 @@ pub fn current_trap_cx_ptr() -> &'static mut TrapFrame {
 pub fn current_process() -> Arc<ProcessControlBlock> {
     copy_current_task().unwrap().process.upgrade().unwrap()
 }
+/// 当前进程的用户id，新建的文件属于这个用户
+pub fn current_uid() -> u32 {
+    current_process().get_inner_access().uid
+}
 /// 返回当前线程在用户态的trap上下文虚拟地址
 pub fn current_trap_cx_user_va() -> usize {
     copy_current_task()
@@ -120,12 +145,18 @@ pub fn run() {
 
             // INFO!("[kernel] find the nex task PID:{}",task.get_pid());
             drop(task_inner); //释放掉获取的引用，因为要切换进程了
-            processor.current = Some(task);
+            processor.current = Some(task.clone());
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             drop(processor); //释放引用
+            let start = get_costtime();
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            //线程让出cpu后回到这里，记录其运行时间
+            let time = get_costtime() - start;
+            task.get_inner_access().run_time += time;
+            SWITCHES.fetch_add(1, Ordering::Relaxed);
+            BUSY_TIME.fetch_add(time, Ordering::Relaxed);
         }
     }
 }
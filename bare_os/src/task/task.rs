@@ -28,6 +28,7 @@ pub struct TaskControlBlockInner {
     pub task_status: TaskStatus,
     pub trap_cx_ppn: PhysPageNum, //线程trap上下文所在位置
     pub exit_code: Option<i32>,   //保存退出码
+    pub run_time: usize,          //在cpu上运行的总时间(us)
 }
 
 impl TaskControlBlock {
@@ -49,6 +50,7 @@ impl TaskControlBlock {
                 task_status: TaskStatus::Ready,
                 trap_cx_ppn,
                 exit_code: None,
+                run_time: 0,
             }),
         }
     }
@@ -85,7 +85,8 @@ pub fn trap_handler() -> ! {
             //因此需要在执行系统调用后重新对其赋值
             let mut tf = current_trap_cx_ptr();
             tf.sepc += 4;
-            let answer = syscall(tf.reg[17], [tf.reg[10], tf.reg[11], tf.reg[12]]) as usize;
+            let answer =
+                syscall(tf.reg[17], [tf.reg[10], tf.reg[11], tf.reg[12], tf.reg[13]]) as usize;
             tf = current_trap_cx_ptr();
             tf.reg[10] = answer;
         }
@@ -1,7 +1,7 @@
 //索引节点区与数据区都需要位图
 //这里将位图抽象出来
 use crate::block_cache::get_block_cache;
-use crate::block_dev::BlockDevice;
+use crate::block_dev::{BlockDevice, BlockResult};
 use crate::BLOCK_SIZE;
 use alloc::sync::Arc;
 
@@ -9,6 +9,8 @@ use alloc::sync::Arc;
 pub struct Bitmap {
     start_block: usize, //起始块号
     blocks: usize,      //占用数量
+    limit: usize,       //可以分配的位数，超出的位不对应实际的块
+    next: usize,        //之前的块都已经分配满，从这里开始查找
 }
 type BitmapBlock = [u64; 64]; //将一个块的4096 = 512*8表示为u64数组
 const BLOCK_BITS: usize = BLOCK_SIZE * 8;
@@ -18,13 +20,23 @@ impl Bitmap {
         Self {
             start_block,
             blocks,
+            limit: blocks * BLOCK_BITS,
+            next: 0,
         }
     }
-    //分配一个位
-    pub fn alloc(&mut self, block_device: Arc<dyn BlockDevice>) -> Option<usize> {
-        for block_id in 0..self.blocks {
+    /// 限制可以分配的位数
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.min(self.blocks * BLOCK_BITS);
+        self
+    }
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+    //分配一个位，没有空闲位时返回None
+    pub fn alloc(&mut self, block_device: Arc<dyn BlockDevice>) -> BlockResult<Option<usize>> {
+        for block_id in self.next..self.blocks {
             //查找已有的块中是否还有剩余位置
-            let position = get_block_cache(block_id + self.start_block, block_device.clone())
+            let position = get_block_cache(block_id + self.start_block, block_device.clone())?
                 .lock()
                 .modify(0, |bitmap_block: &mut BitmapBlock| {
                     if let Some((bits_pos, inner_pos)) = bitmap_block
@@ -32,6 +44,9 @@ impl Bitmap {
                         .enumerate()
                         .find(|(_, bits64)| **bits64 != u64::MAX) //确认并没有达到最大值
                         .map(|(bits_pos, bits64)| (bits_pos, bits64.trailing_ones() as usize))
+                        .filter(|(bits_pos, inner_pos)| {
+                            block_id * BLOCK_BITS + bits_pos * 64 + inner_pos < self.limit
+                        })
                     {
                         bitmap_block[bits_pos] |= 1u64 << inner_pos;
                         Some(block_id * BLOCK_BITS + bits_pos * 64 + inner_pos)
@@ -40,10 +55,109 @@ impl Bitmap {
                     }
                 });
             if position.is_some() {
-                return position;
+                self.next = block_id;
+                return Ok(position);
             }
         }
-        None
+        self.next = self.blocks;
+        Ok(None)
+    }
+    /// 分配连续的最多count个位，返回起始位置与个数
+    /// goal空闲时紧接着goal分配，否则使用第一段足够长的空闲位，没有时使用遇到的最长的一段
+    pub fn alloc_contiguous(
+        &mut self,
+        goal: Option<usize>,
+        count: usize,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> BlockResult<Option<(usize, usize)>> {
+        let goal_len = match goal {
+            Some(goal) => self.free_run(goal, count, &block_device)?,
+            None => 0,
+        };
+        let (start, len) = match goal {
+            Some(goal) if goal_len > 0 => (goal, goal_len),
+            _ => match self.find_run(count, &block_device)? {
+                Some(run) => run,
+                None => return Ok(None),
+            },
+        };
+        for position in start..start + len {
+            self.set(position, block_device.clone())?;
+        }
+        Ok(Some((start, len)))
+    }
+    fn read_bitmap(
+        &self,
+        block_id: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<BitmapBlock> {
+        let cache = get_block_cache(block_id + self.start_block, block_device.clone())?;
+        let bitmap = cache
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| *bitmap_block);
+        Ok(bitmap)
+    }
+    /// 从position开始连续的空闲位个数，最多count个
+    fn free_run(
+        &self,
+        position: usize,
+        count: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<usize> {
+        let mut len = 0;
+        let mut bitmap: Option<(usize, BitmapBlock)> = None;
+        while len < count && position + len < self.limit {
+            let (block_id, bits_pos, inner_pos) = self.depositions(position + len);
+            if bitmap.as_ref().map(|(id, _)| *id) != Some(block_id) {
+                bitmap = Some((block_id, self.read_bitmap(block_id, block_device)?));
+            }
+            if bitmap.as_ref().unwrap().1[bits_pos] & (1u64 << inner_pos) != 0 {
+                break;
+            }
+            len += 1;
+        }
+        Ok(len)
+    }
+    /// 查找第一段长度为count的空闲位，没有时返回最长的一段
+    fn find_run(
+        &mut self,
+        count: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<Option<(usize, usize)>> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut run = (0, 0);
+        let mut first_free = None;
+        for block_id in self.next..self.blocks {
+            let bitmap = self.read_bitmap(block_id, block_device)?;
+            for (bits_pos, bits64) in bitmap.iter().enumerate() {
+                if *bits64 == u64::MAX {
+                    run.1 = 0;
+                    continue;
+                }
+                for inner_pos in 0..64 {
+                    let position = block_id * BLOCK_BITS + bits_pos * 64 + inner_pos;
+                    if position >= self.limit || bits64 & (1u64 << inner_pos) != 0 {
+                        run.1 = 0;
+                        continue;
+                    }
+                    first_free.get_or_insert(block_id);
+                    if run.1 == 0 {
+                        run.0 = position;
+                    }
+                    run.1 += 1;
+                    if run.1 > best.map_or(0, |best| best.1) {
+                        best = Some(run);
+                    }
+                    if run.1 == count {
+                        self.next = first_free.unwrap();
+                        return Ok(best);
+                    }
+                }
+            }
+        }
+        //之前的块都已经分配满
+        self.next = first_free.unwrap_or(self.blocks);
+        Ok(best)
     }
     fn depositions(&self, position: usize) -> (usize, usize, usize) {
         let block_id = position / BLOCK_BITS;
@@ -53,16 +167,49 @@ impl Bitmap {
         (block_id, bits_pos, inner_pos)
     }
     //回收分配出去的一个位
-    pub fn dealloc(&mut self, position: usize, device: Arc<dyn BlockDevice>) {
+    pub fn dealloc(&mut self, position: usize, device: Arc<dyn BlockDevice>) -> BlockResult {
         let (block_id, bits_pos, inner_pos) = self.depositions(position);
         // println!("{},{:?}",position,self.depositions(position));
-        get_block_cache(block_id + self.start_block, device.clone())
+        get_block_cache(block_id + self.start_block, device.clone())?
             .lock()
             .modify(0, |bitsmap_block: &mut BitmapBlock| {
                 //判断当前这个位置是否为1
                 assert!(bitsmap_block[bits_pos] & (1u64 << inner_pos) > 0); //==1
                 bitsmap_block[bits_pos] -= 1u64 << inner_pos;
             });
+        self.next = self.next.min(block_id);
+        Ok(())
+    }
+    //将指定的位标记为已分配
+    pub fn set(&mut self, position: usize, device: Arc<dyn BlockDevice>) -> BlockResult {
+        let (block_id, bits_pos, inner_pos) = self.depositions(position);
+        get_block_cache(block_id + self.start_block, device)?
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                bitmap_block[bits_pos] |= 1u64 << inner_pos;
+            });
+        Ok(())
+    }
+    //查看某一位是否已经分配
+    pub fn is_allocated(&self, position: usize, device: Arc<dyn BlockDevice>) -> BlockResult<bool> {
+        let (block_id, bits_pos, inner_pos) = self.depositions(position);
+        Ok(get_block_cache(block_id + self.start_block, device)?
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block[bits_pos] & (1u64 << inner_pos) != 0
+            }))
+    }
+    //已经分配的位数，超出limit的位不会被分配
+    pub fn count_allocated(&self, device: Arc<dyn BlockDevice>) -> BlockResult<usize> {
+        (0..self.blocks)
+            .map(|block_id| {
+                Ok(self
+                    .read_bitmap(block_id, &device)?
+                    .iter()
+                    .map(|bits64| bits64.count_ones() as usize)
+                    .sum::<usize>())
+            })
+            .sum()
     }
     pub fn bit_num(&self) -> usize {
         self.blocks * BLOCK_SIZE
@@ -1,8 +1,10 @@
-use crate::block_dev::BlockDevice;
+use crate::block_dev::{BlockDevice, BlockResult};
 ///实现磁盘块的缓存
 use crate::config::*;
-use alloc::collections::VecDeque;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::lazy_static;
 use spin::mutex::Mutex;
 
@@ -11,19 +13,23 @@ pub struct BlockCache {
     block_id: usize,                    //位于磁盘的块号
     block_device: Arc<dyn BlockDevice>, //所属的块设备
     modified: bool,                     //是否被修改过
+    data: bool,                         //是否是文件数据，文件数据不需要写入日志
 }
 
+static WRITE_BACKS: AtomicUsize = AtomicUsize::new(0); //写回磁盘的块数
+
 impl BlockCache {
-    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
-        //从块设备读取id对于的快内容
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> BlockResult<Self> {
+        //从块设备读取id对于的快内容，读取失败时不建立缓存
         let mut cache = [0 as u8; BLOCK_SIZE];
-        block_device.read_block(block_id, cache.as_mut());
-        Self {
+        block_device.read_block(block_id, cache.as_mut())?;
+        Ok(Self {
             cache,
             block_id,
             block_device,
             modified: false,
-        }
+            data: false,
+        })
     }
 
     fn addr_offset(&self, offset: usize) -> usize {
@@ -60,77 +66,316 @@ impl BlockCache {
         f(self.get_mut(offset))
     }
 
-    pub fn sync(&mut self) {
+    pub fn block_id(&self) -> usize {
+        self.block_id
+    }
+    pub fn is_dirty(&self) -> bool {
+        self.modified
+    }
+    /// 标记为文件数据或者新分配的块
+    /// 这些块不被已经提交的元数据引用，可以在事务提交前直接写回
+    pub fn mark_data(&mut self) {
+        self.data = true;
+    }
+    /// 修改过并且需要通过日志写回的元数据块
+    pub fn is_dirty_metadata(&self) -> bool {
+        self.modified && !self.data
+    }
+
+    /// 写回修改过的块，写入失败时块仍然是脏的，之后可以再次写回
+    pub fn sync(&mut self) -> BlockResult {
         //同步数据
         if self.modified {
+            self.block_device.write_block(self.block_id, &self.cache)?;
             self.modified = false;
-            self.block_device.write_block(self.block_id, &self.cache);
+            WRITE_BACKS.fetch_add(1, Ordering::Relaxed);
         }
+        self.data = false;
+        Ok(())
     }
 }
 
 impl Drop for BlockCache {
     fn drop(&mut self) {
-        self.sync();
+        //析构时无法报告错误，被替换出去的块都已经写回
+        let _ = self.sync();
     }
 }
 
+/// 块缓存的命中统计
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BlockCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,   //被替换出去的块
+    pub write_backs: usize, //写回磁盘的块
+    pub cached: usize,      //当前缓存的块
+    pub dirty: usize,       //尚未写回的块
+    pub capacity: usize,
+}
+
+/// 按照LRU顺序管理块缓存
+/// 队首为最久未使用的块，每次访问都会把块移动到队尾
+/// 被修改的块不会立即写回，而是在替换或者同步时按块号顺序批量写回
+type CacheEntry = ((usize, usize), Arc<Mutex<BlockCache>>); //(块号，块设备)作为索引
+
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    queue: VecDeque<CacheEntry>,
+    capacity: usize,
+    transactions: BTreeMap<usize, usize>, //块设备上正在进行的事务层数
+    aborted: BTreeSet<usize>,             //正在进行的事务已经被放弃的块设备
+    hits: usize,
+    misses: usize,
+    evictions: usize,
 }
 
 impl BlockCacheManager {
-    fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
             queue: VecDeque::new(),
+            capacity: capacity.max(1),
+            transactions: BTreeMap::new(),
+            aborted: BTreeSet::new(),
+            hits: 0,
+            misses: 0,
+            evictions: 0,
         }
     }
     pub fn get_block_cache(
         &mut self,
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
-    ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue.iter().find(|val| val.0 == block_id) {
-            Arc::clone(&pair.1)
-        } else {
-            //需要查看是否还有空间
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                //内存缓冲区已经满了
-                if let Some((idx, _)) = self
-                    .queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
-                {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("BlockCache is full");
-                }
+    ) -> BlockResult<Arc<Mutex<BlockCache>>> {
+        //不同的块设备可能同时存在，需要同时比较块号与块设备
+        let key = (block_id, device_id(&block_device));
+        if let Some(idx) = self.queue.iter().position(|val| val.0 == key) {
+            self.hits += 1;
+            //移动到队尾，成为最近使用的块
+            let pair = self.queue.remove(idx).unwrap();
+            let block_cache = Arc::clone(&pair.1);
+            self.queue.push_back(pair);
+            return Ok(block_cache);
+        }
+        self.misses += 1;
+        while self.queue.len() >= self.capacity {
+            //所有块都在使用时暂时超出容量，之后的替换会恢复到容量以内
+            if !self.evict() {
+                break;
+            }
+        }
+        let block_cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)?));
+        self.queue.push_back((key, Arc::clone(&block_cache)));
+        Ok(block_cache)
+    }
+    /// 替换最久未使用并且没有被引用的块
+    /// 写回失败的块留在缓存中，此时暂时超出容量
+    fn evict(&mut self) -> bool {
+        let idx = match self.queue.iter().position(|entry| self.evictable(entry)) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        if self.queue[idx].1.lock().is_dirty() {
+            //需要写回时顺便写回其它没有被引用的脏块
+            self.write_back_unused();
+            if self.queue[idx].1.lock().is_dirty() {
+                return false;
+            }
+        }
+        self.queue.remove(idx);
+        self.evictions += 1;
+        true
+    }
+    /// 没有被引用的块可以被替换
+    /// 正在进行事务的块设备上被修改的元数据块只能在提交时通过日志写回
+    fn evictable(&self, (key, cache): &CacheEntry) -> bool {
+        Arc::strong_count(cache) == 1
+            && !(self.transactions.contains_key(&key.1) && cache.lock().is_dirty_metadata())
+    }
+    /// 按块号顺序写回所有没有被引用的脏块，写回失败的块保持为脏块
+    /// 没有被引用的块不会被其它线程持有锁，因此在持有管理器锁时加锁是安全的
+    fn write_back_unused(&self) {
+        let mut dirty: Vec<_> = self
+            .queue
+            .iter()
+            .filter(|entry| self.evictable(entry))
+            .filter(|(_, cache)| cache.lock().is_dirty())
+            .collect();
+        dirty.sort_by_key(|(key, _)| *key);
+        dirty.iter().for_each(|(_, cache)| {
+            let _ = cache.lock().sync();
+        });
+    }
+    /// 按块号顺序取出缓存的块，device为None时取出所有块设备的块
+    fn caches(&self, device: Option<usize>) -> Vec<CacheEntry> {
+        let mut caches: Vec<_> = self
+            .queue
+            .iter()
+            .filter(|(key, _)| device.is_none() || device == Some(key.1))
+            .cloned()
+            .collect();
+        caches.sort_by_key(|(key, _)| (key.1, key.0));
+        caches
+    }
+    /// 开始一个事务，事务可以嵌套
+    pub fn begin(&mut self, block_device: &Arc<dyn BlockDevice>) {
+        *self
+            .transactions
+            .entry(device_id(block_device))
+            .or_insert(0) += 1;
+    }
+    /// 结束一个事务，最外层的事务结束时返回块设备上所有的脏块
+    pub fn end(
+        &mut self,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Option<Vec<Arc<Mutex<BlockCache>>>> {
+        let device = device_id(block_device);
+        let depth = self.transactions.get_mut(&device).unwrap();
+        *depth -= 1;
+        if *depth != 0 {
+            return None;
+        }
+        self.transactions.remove(&device);
+        self.aborted.remove(&device);
+        Some(
+            self.caches(Some(device))
+                .into_iter()
+                .map(|(_, cache)| cache)
+                .collect(),
+        )
+    }
+    /// 放弃块设备上正在进行的事务，最外层的事务结束时丢弃所有修改
+    pub fn abort(&mut self, block_device: &Arc<dyn BlockDevice>) {
+        self.aborted.insert(device_id(block_device));
+    }
+    pub fn is_aborted(&self, block_device: &Arc<dyn BlockDevice>) -> bool {
+        self.aborted.contains(&device_id(block_device))
+    }
+    /// 丢弃块设备上所有的缓存，用于日志恢复直接修改磁盘之后以及丢弃无法提交的事务
+    pub fn invalidate(&mut self, block_device: &Arc<dyn BlockDevice>) {
+        let device = device_id(block_device);
+        //崩溃时没有结束的事务也一起丢弃
+        self.transactions.remove(&device);
+        self.aborted.remove(&device);
+        self.queue.retain(|(key, cache)| {
+            if key.1 == device {
+                cache.lock().modified = false;
             }
-            let block_cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
-            block_cache
+            key.1 != device
+        });
+    }
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.queue.len() > self.capacity && self.evict() {}
+    }
+    pub fn stats(&self) -> BlockCacheStats {
+        BlockCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            write_backs: WRITE_BACKS.load(Ordering::Relaxed),
+            cached: self.queue.len(),
+            dirty: self
+                .queue
+                .iter()
+                .filter(|(_, cache)| match cache.try_lock() {
+                    Some(cache) => cache.is_dirty(),
+                    None => true, //正在使用的块视为脏块
+                })
+                .count(),
+            capacity: self.capacity,
         }
     }
 }
 
+/// 使用块设备对象的地址区分不同的块设备
+fn device_id(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const u8 as usize
+}
+
 lazy_static! {
     pub static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> =
-        Mutex::new(BlockCacheManager::new());
+        Mutex::new(BlockCacheManager::new(BLOCK_CACHE_SIZE));
 }
 
+/// 取得块的缓存，不在缓存中时从块设备读出，读取失败时返回错误
 pub fn get_block_cache(
     block_id: usize,
     block_device: Arc<dyn BlockDevice>,
-) -> Arc<Mutex<BlockCache>> {
+) -> BlockResult<Arc<Mutex<BlockCache>>> {
     BLOCK_CACHE_MANAGER
         .lock()
         .get_block_cache(block_id, block_device)
 }
-pub fn block_cache_sync() {
-    BLOCK_CACHE_MANAGER
+/// 一次从块设备读出连续的多个块，读出的块不放入缓存
+/// 已经在缓存中的块可能还没有写回，以缓存中的内容为准
+pub fn read_blocks(
+    block_id: usize,
+    buf: &mut [u8],
+    block_device: &Arc<dyn BlockDevice>,
+) -> BlockResult {
+    let blocks = block_id..block_id + buf.len() / BLOCK_SIZE;
+    let cached: Vec<_> = BLOCK_CACHE_MANAGER
         .lock()
-        .queue
-        .iter()
-        .for_each(|(idx, cache)| cache.lock().sync())
+        .caches(Some(device_id(block_device)))
+        .into_iter()
+        .filter(|(key, _)| blocks.contains(&key.0))
+        .collect();
+    block_device.read_blocks(block_id, buf)?;
+    for (key, cache) in cached {
+        let offset = (key.0 - block_id) * BLOCK_SIZE;
+        cache.lock().read(0, |data: &[u8; BLOCK_SIZE]| {
+            buf[offset..offset + BLOCK_SIZE].copy_from_slice(data)
+        });
+    }
+    Ok(())
+}
+/// 逐个写回脏块，某个块写入失败时继续写回其它块，返回第一个错误
+fn sync_caches(caches: &[CacheEntry]) -> BlockResult {
+    let mut result = Ok(());
+    for (_, cache) in caches {
+        let synced = cache.lock().sync();
+        if result.is_ok() {
+            result = synced;
+        }
+    }
+    result
+}
+/// 写回所有的脏块
+/// 先释放管理器的锁再逐个加锁，避免与持有块锁并请求新块的线程死锁
+pub fn block_cache_sync() -> BlockResult {
+    let caches = BLOCK_CACHE_MANAGER.lock().caches(None);
+    sync_caches(&caches)
+}
+/// 丢弃某个块设备的缓存
+pub fn block_cache_invalidate(block_device: &Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER.lock().invalidate(block_device);
+}
+/// 只写回某个块设备的脏块，并等待设备的写缓存落盘
+pub fn block_cache_sync_device(block_device: &Arc<dyn BlockDevice>) -> BlockResult {
+    let caches = BLOCK_CACHE_MANAGER
+        .lock()
+        .caches(Some(device_id(block_device)));
+    sync_caches(&caches)?;
+    block_device.flush()
+}
+/// 关机或者panic时使用，缓存正在被使用或者写回失败时返回false
+pub fn block_cache_try_sync() -> bool {
+    let caches = match BLOCK_CACHE_MANAGER.try_lock() {
+        Some(manager) => manager.caches(None),
+        None => return false,
+    };
+    let mut synced = true;
+    for (_, cache) in caches.iter() {
+        match cache.try_lock() {
+            Some(mut cache) => synced &= cache.sync().is_ok(),
+            None => synced = false,
+        }
+    }
+    synced
+}
+pub fn set_block_cache_capacity(capacity: usize) {
+    BLOCK_CACHE_MANAGER.lock().set_capacity(capacity);
+}
+pub fn block_cache_stats() -> BlockCacheStats {
+    BLOCK_CACHE_MANAGER.lock().stats()
 }
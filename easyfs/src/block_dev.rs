@@ -1,9 +1,84 @@
+use crate::BLOCK_SIZE;
+use alloc::vec::Vec;
 use core::any::Any;
+
+/// 块设备读写失败的原因
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    Io,          //设备返回错误
+    OutOfRange,  //块号超出设备的容量
+    InvalidBuf,  //缓冲区不是整数个块
+    Unsupported, //设备不支持该操作
+}
+
+pub type BlockResult<T = ()> = Result<T, BlockError>;
+
 /// 磁盘块设备的接口定义
 /// 在此文件系统的使用者来说，需要实现这些接口
 /// 这些接口对应于不同的驱动设备
-
 pub trait BlockDevice: Send + Sync + Any {
-    fn read_block(&self, block_id: usize, buf: &mut [u8]); //读取块内容
-    fn write_block(&self, block_id: usize, buf: &[u8]); //写块
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult; //读取块内容
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult; //写块
+    fn num_blocks(&self) -> usize; //设备的总块数
+    /// 读取从block_id开始的连续多个块，默认逐块读取
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        check_range(self, block_id, buf.len())?;
+        for (i, block) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            self.read_block(block_id + i, block)?;
+        }
+        Ok(())
+    }
+    /// 写入从block_id开始的连续多个块，默认逐块写入
+    fn write_blocks(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        check_range(self, block_id, buf.len())?;
+        for (i, block) in buf.chunks(BLOCK_SIZE).enumerate() {
+            self.write_block(block_id + i, block)?;
+        }
+        Ok(())
+    }
+    /// 等待之前的写入全部落盘，没有写缓存的设备不需要实现
+    fn flush(&self) -> BlockResult {
+        Ok(())
+    }
+    /// 提交一个异步请求，请求交给设备后立即返回请求号，设备完成后通过poll取得结果
+    /// 不支持请求队列的设备返回Unsupported，调用者应当改用同步接口
+    fn submit(&self, request: BlockRequest) -> BlockResult<usize> {
+        Err(BlockError::Unsupported)
+    }
+    /// 取出一个已经完成的请求，没有完成的请求时返回None
+    fn poll(&self) -> Option<BlockCompletion> {
+        None
+    }
+}
+
+/// 检查缓冲区是否是整数个块并且没有超出设备的范围
+pub fn check_range<D: BlockDevice + ?Sized>(
+    device: &D,
+    block_id: usize,
+    len: usize,
+) -> BlockResult {
+    let partial = len % BLOCK_SIZE;
+    if partial != 0 {
+        return Err(BlockError::InvalidBuf);
+    }
+    match block_id.checked_add(len / BLOCK_SIZE) {
+        Some(end) if end <= device.num_blocks() => Ok(()),
+        _ => Err(BlockError::OutOfRange),
+    }
+}
+
+/// 异步请求，写请求的数据随请求一起提交
+#[derive(Debug)]
+pub enum BlockRequest {
+    Read { block_id: usize, count: usize },
+    Write { block_id: usize, data: Vec<u8> },
+    Flush, //之前提交的请求全部完成后才完成
+}
+
+/// 完成的请求，读请求的数据放在data中
+#[derive(Debug)]
+pub struct BlockCompletion {
+    pub id: usize,
+    pub result: BlockResult,
+    pub data: Vec<u8>,
 }
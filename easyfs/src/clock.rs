@@ -0,0 +1,18 @@
+///! 文件系统使用的时钟
+///! 文件系统本身无法获取时间，由内核或者宿主机在打开文件系统前设置
+use spin::Mutex;
+
+static CLOCK: Mutex<fn() -> u32> = Mutex::new(zero_clock);
+
+fn zero_clock() -> u32 {
+    0
+}
+
+/// 设置获取当前时间(秒)的函数
+pub fn set_clock(clock: fn() -> u32) {
+    *CLOCK.lock() = clock;
+}
+
+pub fn now() -> u32 {
+    (*CLOCK.lock())()
+}
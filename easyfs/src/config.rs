@@ -1,8 +1,20 @@
 pub const BLOCK_SIZE: usize = 512; //块大小
-pub const BLOCK_CACHE_SIZE: usize = 16; //在内存驻留的快缓存数量
+pub const BLOCK_CACHE_SIZE: usize = 16; //默认在内存驻留的快缓存数量，可以通过set_block_cache_capacity修改
 pub const EFS_MAGIC: u32 = 0x3b800001; //文件系统的标识符
+pub const EFS_VERSION: u32 = 4; //磁盘格式版本，旧的镜像在打开时迁移
 pub const BLOCK_U32: usize = BLOCK_SIZE / 4;
-pub const DIRECT_MAX: usize = 27; //一级直接索引
-pub const INDIRECT1_MAX: usize = BLOCK_U32 + DIRECT_MAX; //二级索引的最大块号
-pub const INDIRECT2_MAX: usize = DIRECT_MAX + (BLOCK_U32) ^ 2; //三级索引最大块号
+pub const DIRECT_MAX: usize = 19; //直接索引，为inode元数据让出空间
+pub const INDIRECT_LEVELS: usize = 4; //一至四级间接索引，三级索引在512B的块下只能支持约1GiB的文件
+pub const INDIRECT1_MAX: usize = DIRECT_MAX + BLOCK_U32; //一级索引之后的第一个块号
+pub const INDIRECT2_MAX: usize = INDIRECT1_MAX + BLOCK_U32 * BLOCK_U32; //二级索引之后的第一个块号
+pub const INDIRECT3_MAX: usize = INDIRECT2_MAX + BLOCK_U32 * BLOCK_U32 * BLOCK_U32; //三级索引之后的第一个块号
+pub const INDIRECT4_MAX: usize = INDIRECT3_MAX + BLOCK_U32 * BLOCK_U32 * BLOCK_U32 * BLOCK_U32; //文件最多的数据块数
+pub const MAX_FILE_SIZE: u64 = INDIRECT4_MAX as u64 * BLOCK_SIZE as u64; //约128GiB
+pub const EXTENT_MAX: usize = 11; //extent记录与块索引共用inode中的空间
+pub const FEATURE_EXTENTS: u32 = 1; //新建的普通文件使用extent记录
+pub const QUOTA_SLOTS: usize = 32; //超级块中最多记录的块限额个数
 pub const NAME_LENGTH_MAX: usize = 27;
+pub const SYMLINK_INLINE_MAX: usize = (DIRECT_MAX + INDIRECT_LEVELS) * 4; //不超过该长度的符号链接目标直接存放在inode中
+pub const SYMLINK_MAX: usize = BLOCK_SIZE; //更长的目标存放在一个数据块中
+pub const JOURNAL_BLOCKS: usize = 64; //日志区的块数
+pub const WRITE_CHUNK_BLOCKS: usize = 32; //每个写入事务最多写入的数据块数
@@ -42,4 +42,8 @@ impl DirEntry {
     pub fn node_number(&self) -> u32 {
         self.node_number
     }
+    /// 被删除的目录项名称为空，可以被新的目录项复用
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
 }
@@ -1,33 +1,121 @@
-use crate::block_cache::get_block_cache;
-use crate::block_dev::BlockDevice;
-use crate::{BLOCK_SIZE, BLOCK_U32, DIRECT_MAX, INDIRECT1_MAX, INDIRECT2_MAX};
+use crate::block_cache::{get_block_cache, read_blocks};
+use crate::block_dev::{BlockDevice, BlockResult};
+use crate::clock::now;
+use crate::{BLOCK_SIZE, BLOCK_U32, DIRECT_MAX, EXTENT_MAX, INDIRECT_LEVELS, SYMLINK_INLINE_MAX};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ptr::{addr_of, addr_of_mut};
 #[derive(PartialEq)]
+#[repr(u8)]
 pub enum DiskNodeType {
     FILE,      //普通文件
     DIRECTORY, //目录
+    Symlink,   //符号链接，内容是目标路径
 }
 #[repr(C)]
 pub struct DiskNode {
-    pub nlink: u32,                //硬链接数量
-    pub size: u32,                 //记录文件/目录大小
-    pub direct: [u32; DIRECT_MAX], //存放数据的块号
-    pub indirect1: u32,            //一级索引
-    pub indirect2: u32,            //二级索引
-    node_type: DiskNodeType,       //文件/目录
+    pub size: u64,                        //记录文件/目录大小
+    pub nlink: u32,                       //硬链接数量
+    pub mode: u16,                        //rwx权限位
+    node_type: DiskNodeType,              //文件/目录/符号链接
+    flags: u8,      //EXTENT_FLAG表示使用extent记录，INLINE_FLAG表示内容存放在inode中
+    pub uid: u32,   //所有者
+    pub gid: u32,   //所属组
+    pub atime: u32, //最后访问时间
+    pub mtime: u32, //最后修改时间
+    pub ctime: u32, //inode最后修改时间
+    pub direct: [u32; DIRECT_MAX], //存放数据的块号，使用extent时与indirect一起存放extent记录
+    pub indirect: [u32; INDIRECT_LEVELS], //第k项为k+1级间接索引的根索引块
 } //每个索引节点占据128B
 
+const _: () = assert!(core::mem::size_of::<DiskNode>() == 128);
+
 type Indirect = [u32; BLOCK_SIZE / 4]; //128个u32数据,用来间接索引
 
+const EXTENT_FLAG: u8 = 1;
+const INLINE_FLAG: u8 = 2;
+
+/// 一段物理上连续的数据块，文件的数据块按顺序由各个extent首尾相接组成
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Extent {
+    pub start: u32, //起始块号
+    pub len: u32,   //块数，为0表示没有使用
+}
+
+const _: () = assert!(
+    core::mem::size_of::<[Extent; EXTENT_MAX]>()
+        <= core::mem::size_of::<[u32; DIRECT_MAX + INDIRECT_LEVELS]>()
+);
+
+///数据块位置所在的索引级别与在该级索引中的偏移，级别0为直接索引
+fn locate(pos: usize) -> (usize, usize) {
+    if pos < DIRECT_MAX {
+        return (0, pos);
+    }
+    let mut offset = pos - DIRECT_MAX;
+    let mut capacity = BLOCK_U32;
+    for level in 1..=INDIRECT_LEVELS {
+        if offset < capacity {
+            return (level, offset);
+        }
+        offset -= capacity;
+        capacity *= BLOCK_U32;
+    }
+    panic!("block position {} exceeds the maximum file size", pos);
+}
+
+///level级索引中深度为depth的索引块的每一项对应的数据块数
+fn stride(level: usize, depth: usize) -> usize {
+    BLOCK_U32.pow((level - 1 - depth) as u32)
+}
+
+fn index_entry(block_id: u32, index: usize, device: &Arc<dyn BlockDevice>) -> BlockResult<u32> {
+    let cache = get_block_cache(block_id as usize, device.clone())?;
+    let entry = cache.lock().read(0, |array: &Indirect| array[index]);
+    Ok(entry)
+}
+
+fn set_index_entry(
+    block_id: u32,
+    index: usize,
+    value: u32,
+    device: &Arc<dyn BlockDevice>,
+) -> BlockResult {
+    get_block_cache(block_id as usize, device.clone())?
+        .lock()
+        .modify(0, |array: &mut Indirect| array[index] = value);
+    Ok(())
+}
+
 impl DiskNode {
     pub fn initialize(&mut self, node_type: DiskNodeType) {
         self.nlink = 1;
         self.size = 0;
         self.node_type = node_type;
+        self.flags = 0;
         self.direct = [0; DIRECT_MAX];
-        self.indirect2 = 0;
-        self.indirect1 = 0;
+        self.indirect = [0; INDIRECT_LEVELS];
+        self.mode = match self.node_type {
+            DiskNodeType::FILE => 0o644,
+            DiskNodeType::DIRECTORY => 0o755,
+            DiskNodeType::Symlink => 0o777,
+        };
+        self.uid = 0;
+        self.gid = 0;
+        let time = now();
+        self.atime = time;
+        self.mtime = time;
+        self.ctime = time;
+    }
+    /// 文件类型与权限位，与Linux的st_mode相同
+    pub fn st_mode(&self) -> u32 {
+        let file_type = match self.node_type {
+            DiskNodeType::FILE => 0o100000,
+            DiskNodeType::DIRECTORY => 0o040000,
+            DiskNodeType::Symlink => 0o120000,
+        };
+        file_type | self.mode as u32
     }
     pub fn is_dir(&self) -> bool {
         self.node_type == DiskNodeType::DIRECTORY
@@ -35,218 +123,427 @@ impl DiskNode {
     pub fn is_file(&self) -> bool {
         self.node_type == DiskNodeType::FILE
     }
+    pub fn is_symlink(&self) -> bool {
+        self.node_type == DiskNodeType::Symlink
+    }
+    pub fn is_extent(&self) -> bool {
+        self.flags & EXTENT_FLAG != 0
+    }
+    pub fn is_inline(&self) -> bool {
+        self.flags & INLINE_FLAG != 0
+    }
+    /// 空文件的内容直接存放在direct与indirect的位置，不使用数据块
+    pub fn set_inline(&mut self, data: &[u8]) {
+        assert!(self.size == 0 && data.len() <= SYMLINK_INLINE_MAX);
+        self.flags |= INLINE_FLAG;
+        let bytes = unsafe { &mut *(addr_of_mut!(self.direct) as *mut [u8; SYMLINK_INLINE_MAX]) };
+        bytes[..data.len()].copy_from_slice(data);
+        self.size = data.len() as u64;
+    }
+    pub fn inline_data(&self) -> &[u8] {
+        let bytes = unsafe { &*(addr_of!(self.direct) as *const [u8; SYMLINK_INLINE_MAX]) };
+        &bytes[..self.size as usize]
+    }
+    /// 空文件改为使用extent记录
+    pub fn use_extents(&mut self) {
+        assert_eq!(self.size, 0);
+        self.flags |= EXTENT_FLAG;
+        *self.extents_mut() = [Extent::default(); EXTENT_MAX];
+    }
+    /// extent记录存放在direct与indirect的位置
+    pub fn extents(&self) -> &[Extent; EXTENT_MAX] {
+        unsafe { &*(addr_of!(self.direct) as *const [Extent; EXTENT_MAX]) }
+    }
+    fn extents_mut(&mut self) -> &mut [Extent; EXTENT_MAX] {
+        unsafe { &mut *(addr_of_mut!(self.direct) as *mut [Extent; EXTENT_MAX]) }
+    }
+    /// 在extent记录之后按顺序追加数据块，记录放不下时不做修改并返回false
+    pub fn append_extents(&mut self, new_size: u64, blocks: &[u32]) -> bool {
+        let mut extents = *self.extents();
+        let mut count = extents.iter().take_while(|extent| extent.len != 0).count();
+        for block_id in blocks {
+            if count > 0 && extents[count - 1].start + extents[count - 1].len == *block_id {
+                extents[count - 1].len += 1; //与上一段相邻
+            } else if count == EXTENT_MAX {
+                return false;
+            } else {
+                extents[count] = Extent {
+                    start: *block_id,
+                    len: 1,
+                };
+                count += 1;
+            }
+        }
+        *self.extents_mut() = extents;
+        self.size = new_size;
+        true
+    }
+    /// 按照块索引重新组织文件的数据块，index为需要的索引块
+    /// 用于extent记录放不下的文件以及旧版本镜像的迁移
+    pub fn map_blocks(
+        &mut self,
+        size: u64,
+        data: Vec<u32>,
+        index: Vec<u32>,
+        device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult {
+        assert_eq!(index.len(), Self::index_blocks(size));
+        //按照increase_size使用块的顺序排列数据块与索引块
+        let mut index = index.into_iter();
+        let mut blocks = Vec::with_capacity(data.len() + index.len());
+        for (i, block) in data.into_iter().enumerate() {
+            for _ in 0..Self::index_blocks_at(i) {
+                blocks.push(index.next().unwrap());
+            }
+            blocks.push(block);
+        }
+        self.flags &= !EXTENT_FLAG;
+        self.size = 0;
+        self.direct = [0; DIRECT_MAX];
+        self.indirect = [0; INDIRECT_LEVELS];
+        self.increase_size(size, device, blocks)
+    }
 
     ///找到数据块位置
-    pub fn get_block_id(&self, inner_pos: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
-        //根据inner_pos找到对应的块，inner_pos表示存放数据的某个块号l
-        let inner_pos = inner_pos as usize;
-        if inner_pos < DIRECT_MAX {
-            self.direct[inner_pos] //直接索引
-        } else if inner_pos < INDIRECT1_MAX {
-            //二级索引
-            //二级索引块上存放的全部是u32数据类型，指向一个块号
-            get_block_cache(self.indirect1 as usize, block_device.clone())
-                .lock()
-                .read(0, |array: &Indirect| array[inner_pos - DIRECT_MAX])
-        } else {
-            //三级索引
-            //需要计算其所在区间
-            let inner_inner_pos = inner_pos - INDIRECT1_MAX;
-
-            let array_index = get_block_cache(self.indirect2 as usize, block_device.clone())
-                .lock()
-                .read(0, |array: &Indirect| {
-                    array[inner_inner_pos / BLOCK_U32] //
-                });
-            get_block_cache(array_index as usize, block_device.clone())
-                .lock()
-                .read(0, |array: &Indirect| array[inner_inner_pos % BLOCK_U32])
+    pub fn get_block_id(
+        &self,
+        inner_pos: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<u32> {
+        //根据inner_pos找到对应的块，inner_pos表示文件中第几个数据块
+        if self.is_extent() {
+            let mut pos = inner_pos;
+            for extent in self.extents() {
+                if pos < extent.len {
+                    return Ok(extent.start + pos);
+                }
+                pos -= extent.len;
+            }
+            panic!("block position {} exceeds the extents", inner_pos);
+        }
+        let (level, offset) = locate(inner_pos as usize);
+        if level == 0 {
+            return Ok(self.direct[offset]); //直接索引
+        }
+        //从根索引块开始逐级查找
+        let mut block_id = self.indirect[level - 1];
+        for depth in 0..level {
+            block_id = index_entry(
+                block_id,
+                offset / stride(level, depth) % BLOCK_U32,
+                block_device,
+            )?;
         }
+        Ok(block_id)
+    }
+    ///修改某个数据块的块号，extent记录放不下时返回false
+    pub fn set_block_id(
+        &mut self,
+        inner_pos: u32,
+        block_id: u32,
+        device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<bool> {
+        if self.is_extent() {
+            let mut blocks = self.block_ids(device)?;
+            blocks[inner_pos as usize] = block_id;
+            //重新合并所有的数据块，失败时恢复原来的记录
+            let (size, extents) = (self.size, *self.extents());
+            *self.extents_mut() = [Extent::default(); EXTENT_MAX];
+            if !self.append_extents(size, &blocks) {
+                *self.extents_mut() = extents;
+                return Ok(false);
+            }
+            return Ok(true);
+        }
+        let (level, offset) = locate(inner_pos as usize);
+        if level == 0 {
+            self.direct[offset] = block_id;
+            return Ok(true);
+        }
+        let mut index_block = self.indirect[level - 1];
+        for depth in 0..level - 1 {
+            index_block = index_entry(
+                index_block,
+                offset / stride(level, depth) % BLOCK_U32,
+                device,
+            )?;
+        }
+        set_index_entry(index_block, offset % BLOCK_U32, block_id, device)?;
+        Ok(true)
+    }
+    ///从根索引块到数据块经过的所有块，bool表示该块是否从这个数据块开始使用
+    fn path(
+        &self,
+        inner_pos: usize,
+        device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<Vec<(u32, bool)>> {
+        let (level, offset) = locate(inner_pos);
+        if level == 0 {
+            return Ok(alloc::vec![(self.direct[offset], true)]);
+        }
+        let mut block_id = self.indirect[level - 1];
+        let mut path = alloc::vec![(block_id, offset == 0)];
+        for depth in 0..level {
+            let stride = stride(level, depth);
+            block_id = index_entry(block_id, offset / stride % BLOCK_U32, device)?;
+            path.push((block_id, offset % stride == 0));
+        }
+        Ok(path)
+    }
+    ///文件占用的所有块，前data_blocks个为按顺序排列的数据块，之后是索引块
+    pub fn block_ids(&self, device: &Arc<dyn BlockDevice>) -> BlockResult<Vec<u32>> {
+        if self.is_extent() {
+            return Ok(self
+                .extents()
+                .iter()
+                .flat_map(|extent| extent.start..extent.start + extent.len)
+                .collect());
+        }
+        let data_blocks = self.data_blocks() as usize;
+        let mut blocks: Vec<u32> = (0..data_blocks)
+            .map(|i| self.get_block_id(i as u32, device))
+            .collect::<BlockResult<_>>()?;
+        //索引块只可能从BLOCK_U32的整数倍位置开始使用
+        for pos in (DIRECT_MAX..data_blocks).step_by(BLOCK_U32) {
+            let path = self.path(pos, device)?;
+            blocks.extend(
+                path[..path.len() - 1]
+                    .iter()
+                    .filter(|(_, first)| *first)
+                    .map(|(block_id, _)| *block_id),
+            );
+        }
+        Ok(blocks)
+    }
+    ///从第inner_pos个数据块开始使用的索引块数量
+    pub fn index_blocks_at(inner_pos: usize) -> usize {
+        let (level, offset) = locate(inner_pos);
+        (0..level)
+            .filter(|depth| offset % (stride(level, *depth) * BLOCK_U32) == 0)
+            .count()
     }
-}
-
-impl DiskNode {
     ///这部分函数用来给文件扩增数据的时候使用
-    fn _data_blocks(size: u32) -> u32 {
+    fn _data_blocks(size: u64) -> u32 {
         //向上取整返回这些数据所占用块数量
-        (size + BLOCK_SIZE as u32 - 1) / (BLOCK_SIZE as u32)
+        ((size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64) as u32
     }
     pub fn data_blocks(&self) -> u32 {
+        if self.is_inline() {
+            return 0;
+        }
         Self::_data_blocks(self.size)
     }
-    pub fn total_blocks(size: u32) -> u32 {
-        let data_blocks = Self::_data_blocks(size);
+    pub fn total_blocks(size: u64) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
         let mut total_blocks = data_blocks;
-        if data_blocks as usize > DIRECT_MAX {
-            total_blocks += 1
-        };
-        if data_blocks as usize > INDIRECT1_MAX {
-            total_blocks += 1; //二级索引表块
-            total_blocks +=
-                ((data_blocks - INDIRECT1_MAX as u32) + BLOCK_U32 as u32 - 1) / (BLOCK_U32 as u32)
+        let mut rest = data_blocks.saturating_sub(DIRECT_MAX);
+        let mut capacity = BLOCK_U32;
+        for level in 1..=INDIRECT_LEVELS {
+            //level级索引中的数据块每BLOCK_U32^k个需要一个第k层的索引块
+            let used = rest.min(capacity);
+            let mut covered = 1;
+            for _ in 0..level {
+                covered *= BLOCK_U32;
+                total_blocks += (used + covered - 1) / covered;
+            }
+            rest -= used;
+            capacity *= BLOCK_U32;
+        }
+        total_blocks as u32
+    }
+    ///文件大小为size时块索引需要的索引块数量
+    pub fn index_blocks(size: u64) -> usize {
+        (Self::total_blocks(size) - Self::_data_blocks(size)) as usize
+    }
+    ///文件当前占用的数据块与索引块数量，使用extent时没有索引块
+    pub fn blocks(&self) -> u32 {
+        if self.is_inline() {
+            0
+        } else if self.is_extent() {
+            self.data_blocks()
+        } else {
+            Self::total_blocks(self.size)
         }
-        total_blocks
     }
-    pub fn addition_blocks(&self, new_size: u32) -> u32 {
+    pub fn addition_blocks(&self, new_size: u64) -> u32 {
         //求出扩容后需要增加的数据块与索引块
-        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+        if self.is_extent() {
+            Self::_data_blocks(new_size) - self.data_blocks()
+        } else {
+            Self::total_blocks(new_size) - Self::total_blocks(self.size)
+        }
+    }
+    ///文件的最后一个数据块，新的块尽量紧接着它分配
+    pub fn last_block(&self, device: &Arc<dyn BlockDevice>) -> BlockResult<Option<u32>> {
+        match self.data_blocks() {
+            0 => Ok(None),
+            blocks => self.get_block_id(blocks - 1, device).map(Some),
+        }
     }
     pub fn increase_size(
         &mut self,
-        new_size: u32, //扩充之后文件大小
+        new_size: u64, //扩充之后文件大小
         device: &Arc<dyn BlockDevice>,
         new_blocks: Vec<u32>, //上层传来的申请的存储块，可以做数据块和索引块
-    ) {
-        let mut current_data_blocks = self.data_blocks() as usize; //当前时刻的数据块数目
+    ) -> BlockResult {
+        assert!(!self.is_extent());
+        let current_data_blocks = self.data_blocks() as usize; //当前时刻的数据块数目
         self.size = new_size; //更改文件/目录大小
-        let mut after_data_blocks = self.data_blocks() as usize; //加入新的数据后的数目
-
-        // println!("[filesystem]inode::increase_size::after-current = {}",after_data_blocks-current_data_blocks);
+        let after_data_blocks = self.data_blocks() as usize; //加入新的数据后的数目
         let mut new_blocks_iter = new_blocks.into_iter();
-
-        while current_data_blocks < after_data_blocks.min(DIRECT_MAX) {
-            self.direct[current_data_blocks as usize] = new_blocks_iter.next().unwrap();
-            current_data_blocks += 1; //先填满可直接索引区域
-        }
-        if after_data_blocks > DIRECT_MAX {
-            //填充一级索引区域
-            if current_data_blocks == DIRECT_MAX {
-                //新建一个索引块
-                self.indirect1 = new_blocks_iter.next().unwrap();
+        //每个数据块之前先使用从它开始的索引块，由上至下
+        for pos in current_data_blocks..after_data_blocks {
+            let (level, offset) = locate(pos);
+            if level == 0 {
+                self.direct[offset] = new_blocks_iter.next().unwrap(); //直接索引
+                continue;
             }
-            current_data_blocks -= DIRECT_MAX;
-            after_data_blocks -= DIRECT_MAX;
-        } else {
-            return; //如果加入文件后还是小于直接索引那么就说明前面已经添加完成
-        }
-        //获取一级索引的缓存
-        //30 - 28 = 2
-        //33 - 28 = 5
-        get_block_cache(self.indirect1 as usize, device.clone())
-            .lock()
-            .modify(0, |array: &mut Indirect| {
-                while current_data_blocks < after_data_blocks.min(BLOCK_U32) {
-                    array[current_data_blocks] = new_blocks_iter.next().unwrap();
-                    current_data_blocks += 1;
-                }
-            });
-        if after_data_blocks > (INDIRECT1_MAX - DIRECT_MAX) {
-            //如果大于一级索引所能容纳的数量
-            if current_data_blocks == (INDIRECT1_MAX - DIRECT_MAX) {
-                self.indirect2 = new_blocks_iter.next().unwrap();
+            if offset == 0 {
+                self.indirect[level - 1] = new_blocks_iter.next().unwrap(); //新建根索引块
+            }
+            let mut block_id = self.indirect[level - 1];
+            for depth in 0..level {
+                let stride = stride(level, depth);
+                let index = offset / stride % BLOCK_U32;
+                block_id = if depth + 1 == level || offset % stride == 0 {
+                    let new_block = new_blocks_iter.next().unwrap();
+                    set_index_entry(block_id, index, new_block, device)?;
+                    new_block
+                } else {
+                    index_entry(block_id, index, device)?
+                };
             }
-            current_data_blocks -= INDIRECT1_MAX - DIRECT_MAX;
-            after_data_blocks -= INDIRECT1_MAX - DIRECT_MAX;
-        } else {
-            return;
         }
-        //
-        let mut c_first = current_data_blocks / (INDIRECT1_MAX - DIRECT_MAX);
-        let mut c_second = current_data_blocks % (INDIRECT1_MAX - DIRECT_MAX);
-        let a_first = after_data_blocks / (INDIRECT1_MAX - DIRECT_MAX);
-        let a_second = after_data_blocks % (INDIRECT1_MAX - DIRECT_MAX);
-        get_block_cache(self.indirect2 as usize, device.clone())
-            .lock()
-            .modify(0, |array: &mut Indirect| {
-                while (c_first < a_first) || (c_first == a_first && c_second < a_second) {
-                    //如果第一级的index不等于或者第二级index不等于
-                    if c_second == 0 {
-                        array[c_first] = new_blocks_iter.next().unwrap();
-                    }
-                    get_block_cache(array[c_first] as usize, device.clone())
-                        .lock()
-                        .modify(0, |array1: &mut Indirect| {
-                            array1[c_second] = new_blocks_iter.next().unwrap();
-                        });
-                    c_second += 1;
-                    if c_second == BLOCK_U32 {
-                        c_first += 1;
-                        c_second = 0;
-                    }
-                }
-            });
+        Ok(())
     }
-    pub fn clear_size(&mut self, device: &Arc<dyn BlockDevice>) -> Vec<u32> {
-        //清空文件数据后应该回收所有的数据和索引块
-        let mut useless_block: Vec<u32> = Vec::new();
-        let mut data_blocks = self.data_blocks() as usize;
-        self.size = 0; //文件大小为0
-        let mut current_blcoks = 0;
-        while current_blcoks < data_blocks.min(DIRECT_MAX) {
-            useless_block.push(self.direct[current_blcoks]);
-            self.direct[current_blcoks] = 0;
-            current_blcoks += 1;
-        }
-        if data_blocks > DIRECT_MAX {
-            useless_block.push(self.indirect1);
-            current_blcoks = 0;
-            data_blocks -= DIRECT_MAX;
-        } else {
-            return useless_block;
+    ///缩小文件，返回不再使用的数据块与索引块
+    ///最后一个数据块中超出新大小的部分清0，之后扩大文件时读到的是0
+    pub fn decrease_size(
+        &mut self,
+        new_size: u64,
+        device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<Vec<u32>> {
+        assert!(new_size <= self.size);
+        if self.is_inline() {
+            //内容不占用数据块，清空后恢复为普通的块索引
+            assert_eq!(new_size, 0);
+            self.flags &= !INLINE_FLAG;
+            self.size = 0;
+            self.direct = [0; DIRECT_MAX];
+            self.indirect = [0; INDIRECT_LEVELS];
+            return Ok(Vec::new());
         }
-        get_block_cache(self.indirect1 as usize, device.clone())
-            .lock()
-            .modify(0, |array: &mut Indirect| {
-                while current_blcoks < data_blocks.min(BLOCK_U32) {
-                    useless_block.push(array[current_blcoks]);
-                    current_blcoks += 1;
+        let old_blocks = self.data_blocks() as usize;
+        let new_blocks = Self::_data_blocks(new_size) as usize;
+        let mut useless_block: Vec<u32> = Vec::new();
+        if self.is_extent() {
+            //保留前new_blocks个块，之后的extent截短或者清空
+            let mut keep = new_blocks as u32;
+            for extent in self.extents_mut().iter_mut() {
+                if keep >= extent.len {
+                    keep -= extent.len;
+                    continue;
                 }
-            });
-        self.indirect1 = 0;
-        if data_blocks > BLOCK_U32 {
-            useless_block.push(self.indirect2);
-            data_blocks -= BLOCK_U32;
+                useless_block.extend(extent.start + keep..extent.start + extent.len);
+                extent.len = keep;
+                if keep == 0 {
+                    extent.start = 0;
+                }
+                keep = 0;
+            }
         } else {
-            return useless_block;
-        }
-
-        let a1 = data_blocks / BLOCK_U32;
-        let b1 = data_blocks % BLOCK_U32;
-
-        get_block_cache(self.indirect2 as usize, device.clone())
-            .lock()
-            .modify(0, |array1: &mut Indirect| {
-                for i in 0..a1 {
-                    useless_block.push(array1[i]);
-                    get_block_cache(array1[i] as usize, device.clone())
-                        .lock()
-                        .modify(0, |array2: &mut Indirect| {
-                            for j in 0..BLOCK_U32 {
-                                useless_block.push(array2[j])
-                            }
-                        });
+            //从后向前回收，根索引块在这一级的最后一个数据块回收之后才清除
+            for pos in (new_blocks..old_blocks).rev() {
+                let (level, offset) = locate(pos);
+                if level == 0 {
+                    useless_block.push(self.direct[offset]);
+                    self.direct[offset] = 0;
+                    continue;
                 }
-                if b1 > 0 {
-                    useless_block.push(array1[a1]);
-                    get_block_cache(array1[a1] as usize, device.clone())
-                        .lock()
-                        .modify(0, |array2: &mut Indirect| {
-                            for j in 0..b1 {
-                                useless_block.push(array2[j])
-                            }
-                        });
+                //从这个数据块开始使用的索引块整体不再使用
+                for (block_id, first) in self.path(pos, device)? {
+                    if first {
+                        useless_block.push(block_id);
+                    }
                 }
-            });
-
-        self.indirect2 = 0;
-        useless_block
+                if offset == 0 {
+                    self.indirect[level - 1] = 0;
+                }
+            }
+        }
+        let tail = new_size as usize % BLOCK_SIZE;
+        if tail != 0 {
+            let cache = get_block_cache(
+                self.get_block_id(new_blocks as u32 - 1, device)? as usize,
+                device.clone(),
+            )?;
+            let mut cache = cache.lock();
+            cache.modify(0, |array: &mut DataBlock| array[tail..].fill(0));
+            if self.is_file() {
+                cache.mark_data();
+            }
+        }
+        self.size = new_size;
+        Ok(useless_block)
+    }
+    pub fn clear_size(&mut self, device: &Arc<dyn BlockDevice>) -> BlockResult<Vec<u32>> {
+        //清空文件数据后应该回收所有的数据和索引块
+        self.decrease_size(0, device)
     }
 }
 
 type DataBlock = [u8; BLOCK_SIZE];
 
 impl DiskNode {
-    pub fn read_at(&self, offset: usize, buf: &mut [u8], device: &Arc<dyn BlockDevice>) -> usize {
+    /// 读取失败时返回块设备的错误，buf中可能已经写入了部分内容
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<usize> {
         // println!("{}",self.size);
+        if self.is_inline() {
+            let data = self.inline_data();
+            let end = (offset + buf.len()).min(data.len());
+            if offset >= end {
+                return Ok(0);
+            }
+            buf[..end - offset].copy_from_slice(&data[offset..end]);
+            return Ok(end - offset);
+        }
         let mut start = offset;
         //判断本文件大小
         let end = (offset + buf.len()).min(self.size as usize);
-        if start > end {
-            return 0;
+        if start >= end {
+            return Ok(0);
         }
         let mut start_block = start / BLOCK_SIZE; //起始块
         let mut read_size = 0usize;
         loop {
+            //整块读取时合并物理上连续的块，一次从块设备读出
+            let block_offset = start % BLOCK_SIZE;
+            if block_offset == 0 {
+                let block_id = self.get_block_id(start_block as u32, device)?;
+                let mut count = 1;
+                while (count + 1) * BLOCK_SIZE <= end - start
+                    && self.get_block_id((start_block + count) as u32, device)?
+                        == block_id + count as u32
+                {
+                    count += 1;
+                }
+                if count > 1 {
+                    let dst = &mut buf[read_size..read_size + count * BLOCK_SIZE];
+                    read_blocks(block_id as usize, dst, device)?;
+                    read_size += count * BLOCK_SIZE;
+                    if start + count * BLOCK_SIZE == end {
+                        break;
+                    }
+                    start_block += count;
+                    start += count * BLOCK_SIZE;
+                    continue;
+                }
+            }
             //计算本数据块的的末位位置
             let mut current_end_blcok = (start / BLOCK_SIZE + 1) * BLOCK_SIZE;
             current_end_blcok = current_end_blcok.min(end);
@@ -254,9 +551,9 @@ impl DiskNode {
             //目标缓冲区
             let dst = &mut buf[read_size..read_size + current_read_size];
             get_block_cache(
-                self.get_block_id(start_block as u32, device) as usize,
+                self.get_block_id(start_block as u32, device)? as usize,
                 device.clone(),
-            )
+            )?
             .lock()
             .read(0, |array: &DataBlock| {
                 let src = &array[start % BLOCK_SIZE..start % BLOCK_SIZE + current_read_size];
@@ -269,9 +566,15 @@ impl DiskNode {
             start_block += 1;
             start = current_end_blcok;
         }
-        read_size
+        Ok(read_size)
     }
-    pub fn write_at(&self, offset: usize, buf: &[u8], device: &Arc<dyn BlockDevice>) -> usize {
+    /// 写入失败时返回块设备的错误，之前的块可能已经写入
+    pub fn write_at(
+        &self,
+        offset: usize,
+        buf: &[u8],
+        device: &Arc<dyn BlockDevice>,
+    ) -> BlockResult<usize> {
         let mut start = offset;
         //判断本文件大小
         let end = (offset + buf.len()).min(self.size as usize);
@@ -285,17 +588,22 @@ impl DiskNode {
             //计算要写入的大小
             let current_write_size = current_end_blcok - start;
 
-            get_block_cache(
-                self.get_block_id(start_block as u32, device) as usize,
+            let cache = get_block_cache(
+                self.get_block_id(start_block as u32, device)? as usize,
                 device.clone(),
-            )
-            .lock()
-            .modify(0, |array: &mut DataBlock| {
+            )?;
+            let mut cache = cache.lock();
+            cache.modify(0, |array: &mut DataBlock| {
                 //目标缓冲区
                 let src = &buf[write_size..write_size + current_write_size];
                 let dst = &mut array[start % BLOCK_SIZE..start % BLOCK_SIZE + current_write_size];
                 dst.copy_from_slice(src);
             });
+            //目录项属于元数据，需要写入日志
+            if self.is_file() {
+                cache.mark_data();
+            }
+            drop(cache);
             write_size += current_write_size;
             if current_end_blcok == end {
                 break;
@@ -303,6 +611,6 @@ impl DiskNode {
             start_block += 1;
             start = current_end_blcok;
         }
-        write_size
+        Ok(write_size)
     }
 }
@@ -1,21 +1,46 @@
 use crate::bitmap::Bitmap;
-use crate::block_cache::get_block_cache;
-use crate::block_dev::BlockDevice;
+use crate::block_cache::{block_cache_sync, block_cache_sync_device, get_block_cache};
+use crate::block_dev::{BlockDevice, BlockResult};
 use crate::disknode::{DiskNode, DiskNodeType};
-use crate::layout::SuperBlock;
+use crate::journal::Journal;
+use crate::layout::{QuotaLimit, SuperBlock};
+use crate::migrate::migrate;
 use crate::vfs::Inode;
-use crate::BLOCK_SIZE;
+use crate::{BLOCK_SIZE, EFS_VERSION, FEATURE_EXTENTS, JOURNAL_BLOCKS};
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
 use spin::Mutex;
 
 ///! 负责组织下层抽象的各个文件结构，将其合理安排在磁盘上
 
+/// 文件系统的使用情况，块数只包括数据区
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StatFs {
+    pub block_size: usize,
+    pub blocks: usize,
+    pub free_blocks: usize,
+    pub inodes: usize,
+    pub free_inodes: usize,
+}
+
+/// 一个用户的块限额，used包括数据块与索引块
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: usize,
+    pub used: usize,
+}
+
 pub struct FileSystem {
     pub block_device: Arc<dyn BlockDevice>,
     pub inode_bitmap: Bitmap,
     pub data_bitmap: Bitmap,
     inode_area_blocks: u32,
     data_area_blocks: u32,
+    journal: Journal,
+    extents: bool,                //新建的普通文件使用extent记录
+    quotas: BTreeMap<u32, Quota>, //按uid设置的块限额，限额保存在超级块中，占用在打开时统计
 }
 type DataBlock = [u8; BLOCK_SIZE];
 
@@ -24,7 +49,12 @@ impl FileSystem {
         device: Arc<dyn BlockDevice>,
         total_blocks: usize,
         inode_bitmap_blocks: usize, //索引节点块个数
-    ) -> Arc<Mutex<Self>> {
+    ) -> BlockResult<Arc<Mutex<Self>>> {
+        assert!(
+            total_blocks <= device.num_blocks(),
+            "Device has only {} blocks",
+            device.num_blocks()
+        );
         //从第1块开始为索引节点位图所占的块
         let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks);
         let inode_num = inode_bitmap.bit_num(); //索引节点位图所能容纳的节点数目
@@ -32,23 +62,38 @@ impl FileSystem {
         let inode_area_blocks =
             (inode_num * core::mem::size_of::<DiskNode>() + BLOCK_SIZE - 1) / BLOCK_SIZE;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        //日志区位于磁盘末尾，磁盘太小时不使用日志
+        let journal_blocks = if total_blocks >= JOURNAL_BLOCKS * 16 {
+            JOURNAL_BLOCKS
+        } else {
+            0
+        };
+        let journal_start = total_blocks - journal_blocks;
         //剩余的属于数据节点位图和数据块
-        let data_total_blocks = total_blocks - inode_total_blocks;
+        let data_total_blocks = total_blocks - inode_total_blocks - journal_blocks;
         let BLOCK_SIZE_BIT = BLOCK_SIZE * 8;
         let data_bitmap_blocks = (data_total_blocks + BLOCK_SIZE_BIT - 1) / BLOCK_SIZE_BIT;
-        let data_area_blocks = total_blocks - 1 - inode_total_blocks - data_bitmap_blocks;
+        let data_area_blocks =
+            total_blocks - 1 - inode_total_blocks - data_bitmap_blocks - journal_blocks;
 
-        let data_bitmap = Bitmap::new(1 + inode_total_blocks, data_bitmap_blocks);
+        let data_bitmap =
+            Bitmap::new(1 + inode_total_blocks, data_bitmap_blocks).with_limit(data_area_blocks);
+        let journal = Journal::new(journal_start as u32, journal_blocks as u32);
         let mut fs = Self {
             block_device: device.clone(),
-            inode_bitmap,
+            inode_bitmap: inode_bitmap.with_limit(inode_num),
             data_bitmap,
             inode_area_blocks: (1 + inode_bitmap_blocks) as u32,
-            data_area_blocks: (1 + inode_total_blocks) as u32,
+            data_area_blocks: (1 + inode_total_blocks + data_bitmap_blocks) as u32,
+            journal,
+            extents: false,
+            quotas: BTreeMap::new(),
         };
+        //日志区不经过块缓存
+        journal.format(&device)?;
         //清空所有的块
-        for index in 0..total_blocks {
-            get_block_cache(index, Arc::clone(&device)).lock().modify(
+        for index in 0..journal_start {
+            get_block_cache(index, Arc::clone(&device))?.lock().modify(
                 0,
                 |data_block: &mut DataBlock| {
                     for byte in data_block.iter_mut() {
@@ -58,7 +103,7 @@ impl FileSystem {
             );
         }
         //初始化超级快
-        get_block_cache(0, device.clone())
+        get_block_cache(0, device.clone())?
             .lock()
             .modify(0, |super_block: &mut SuperBlock| {
                 super_block.initialize(
@@ -67,48 +112,98 @@ impl FileSystem {
                     inode_area_blocks as u32,
                     data_bitmap_blocks as u32,
                     data_area_blocks as u32,
-                )
+                );
+                super_block.journal_start = journal_start as u32;
+                super_block.journal_blocks = journal_blocks as u32;
             });
-        assert!(fs.alloc_inode() == 0);
+        assert!(fs.alloc_inode()? == Some(0));
         //建立根目录
         let (root_inode_block_id, root_inode_offset) = fs.get_disk_inode_pos(0);
-        get_block_cache(root_inode_block_id as usize, device.clone())
+        get_block_cache(root_inode_block_id as usize, device.clone())?
             .lock()
             .modify(root_inode_offset, |disk_inode: &mut DiskNode| {
                 disk_inode.initialize(DiskNodeType::DIRECTORY)
             });
-        Arc::new(Mutex::new(fs))
+        block_cache_sync_device(&device)?;
+        Ok(Arc::new(Mutex::new(fs)))
     }
 
     pub fn open(device: Arc<dyn BlockDevice>) -> Arc<Mutex<FileSystem>> {
-        //从一个已经写入文件系统的设备上恢复文件系统
+        Self::try_open(device).expect("Load error efs")
+    }
+    /// 从一个已经写入文件系统的设备上恢复文件系统
+    /// 超级块无效、版本不支持或者无法读取时返回None，不修改设备
+    pub fn try_open(device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<FileSystem>>> {
         //只要读取第一个存储块将超级快信息读出即可
-        get_block_cache(0, device.clone())
-            .lock()
-            .read(0, |superblock: &SuperBlock| {
-                assert!(superblock.is_valid(), "Load error efs");
-                let inode_total_blocks =
-                    superblock.inode_bitmap_blocks + superblock.inode_area_blocks;
-                let efs = Self {
-                    block_device: device,
-                    inode_bitmap: Bitmap::new(1, superblock.inode_bitmap_blocks as usize),
-                    data_bitmap: Bitmap::new(
-                        (1 + inode_total_blocks) as usize,
-                        superblock.data_bitmap_blocks as usize,
-                    ),
-                    inode_area_blocks: (1 + superblock.inode_bitmap_blocks) as u32,
-                    data_area_blocks: 1 + inode_total_blocks + superblock.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
+        //先写回缓存，日志恢复会直接修改磁盘
+        block_cache_sync_device(&device).ok()?;
+        //先绕过缓存检查超级块，不是easyfs的设备不会留下任何缓存
+        let mut block: DataBlock = [0; BLOCK_SIZE];
+        device.read_block(0, &mut block).ok()?;
+        let superblock = unsafe { core::ptr::read_unaligned(block.as_ptr() as *const SuperBlock) };
+        if !superblock.is_valid()
+            || superblock.version() > EFS_VERSION
+            || !superblock.fits(device.num_blocks())
+        {
+            return None;
+        }
+        let (mut efs, version) =
+            get_block_cache(0, device.clone())
+                .ok()?
+                .lock()
+                .read(0, |superblock: &SuperBlock| {
+                    let inode_total_blocks =
+                        superblock.inode_bitmap_blocks + superblock.inode_area_blocks;
+                    let inode_num = superblock.inode_area_blocks as usize * BLOCK_SIZE
+                        / core::mem::size_of::<DiskNode>();
+                    let efs = Self {
+                        block_device: device.clone(),
+                        inode_bitmap: Bitmap::new(1, superblock.inode_bitmap_blocks as usize)
+                            .with_limit(inode_num),
+                        data_bitmap: Bitmap::new(
+                            (1 + inode_total_blocks) as usize,
+                            superblock.data_bitmap_blocks as usize,
+                        )
+                        .with_limit(superblock.data_area_blocks as usize),
+                        inode_area_blocks: (1 + superblock.inode_bitmap_blocks) as u32,
+                        data_area_blocks: 1 + inode_total_blocks + superblock.data_bitmap_blocks,
+                        journal: Journal::new(superblock.journal_start, superblock.journal_blocks),
+                        extents: superblock.features & FEATURE_EXTENTS != 0,
+                        quotas: BTreeMap::new(),
+                    };
+                    (efs, superblock.version())
+                });
+        //重新写回上一次没有完成的事务
+        efs.journal.replay(&device).ok()?;
+        if version < EFS_VERSION {
+            //旧版本的镜像需要先迁移
+            migrate(&mut efs, version).ok()?;
+            get_block_cache(0, device)
+                .ok()?
+                .lock()
+                .modify(0, |superblock: &mut SuperBlock| {
+                    superblock.version = EFS_VERSION
+                });
+            block_cache_sync().ok()?;
+        }
+        efs.load_quotas().ok()?;
+        Some(Arc::new(Mutex::new(efs)))
     }
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
         //获取逻辑块号对于物理块号
         self.data_area_blocks + data_block_id
     }
-    pub fn alloc_inode(&mut self) -> u32 {
-        //从索引位图中分配一个inode;
-        self.inode_bitmap.alloc(self.block_device.clone()).unwrap() as u32
+    pub fn alloc_inode(&mut self) -> BlockResult<Option<u32>> {
+        //从索引位图中分配一个inode，用完时返回None
+        Ok(self
+            .inode_bitmap
+            .alloc(self.block_device.clone())?
+            .map(|inode| inode as u32))
+    }
+    pub fn dealloc_inode(&mut self, inode: u32) -> BlockResult {
+        //回收一个inode
+        self.inode_bitmap
+            .dealloc(inode as usize, self.block_device.clone())
     }
     pub fn get_disk_inode_pos(&self, inode: u32) -> (u32, usize) {
         //根据索引节点号找到位于索引节点区的位置和索引块编号
@@ -124,21 +219,218 @@ impl FileSystem {
         let inode = inode_size / core::mem::size_of::<DiskNode>();
         inode as u32
     }
-    pub fn dealloc_data(&mut self, block_id: u32) {
-        //回收一个数据块
-        get_block_cache(block_id as usize, self.block_device.clone())
-            .lock()
-            .modify(0, |data_block: &mut DataBlock| {
-                data_block.iter_mut().for_each(|p| *p = 0);
-            }); //将数据块初始化为0
-                // println!("block_id-data_area_blocks = {}",block_id-self.data_area_blocks);
+    pub fn dealloc_data(&mut self, block_id: u32) -> BlockResult {
+        //回收一个数据块，数据块在下一次分配时清0
         self.data_bitmap.dealloc(
             (block_id - self.data_area_blocks) as usize,
             self.block_device.clone(),
-        );
+        )
+    }
+    pub fn alloc_data(&mut self) -> BlockResult<Option<u32>> {
+        let block_id = match self.data_bitmap.alloc(self.block_device.clone())? {
+            Some(position) => position as u32 + self.data_area_blocks,
+            None => return Ok(None),
+        };
+        self.clear_data(block_id)?;
+        Ok(Some(block_id))
+    }
+    /// 分配count个数据块，尽量连续并且紧接着goal
+    /// 空闲块不够时回收已经分配的块并返回None
+    pub fn alloc_data_run(
+        &mut self,
+        goal: Option<u32>,
+        count: usize,
+    ) -> BlockResult<Option<Vec<u32>>> {
+        let mut blocks = Vec::with_capacity(count);
+        let mut goal = goal.map(|block_id| (block_id - self.data_area_blocks) as usize);
+        while blocks.len() < count {
+            let (start, len) = match self.data_bitmap.alloc_contiguous(
+                goal,
+                count - blocks.len(),
+                self.block_device.clone(),
+            )? {
+                Some(run) => run,
+                None => {
+                    for block_id in blocks {
+                        self.dealloc_data(block_id)?;
+                    }
+                    return Ok(None);
+                }
+            };
+            for position in start..start + len {
+                let block_id = position as u32 + self.data_area_blocks;
+                self.clear_data(block_id)?;
+                blocks.push(block_id);
+            }
+            goal = Some(start + len);
+        }
+        Ok(Some(blocks))
+    }
+    fn clear_data(&self, block_id: u32) -> BlockResult {
+        //新分配的块没有被已经提交的元数据引用，清0后不需要写入日志
+        let cache = get_block_cache(block_id as usize, self.block_device.clone())?;
+        let mut cache = cache.lock();
+        cache.modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        cache.mark_data();
+        Ok(())
+    }
+    /// 新建的普通文件是否使用extent记录
+    pub fn extents(&self) -> bool {
+        self.extents
+    }
+    /// 设置之后新建的普通文件是否使用extent记录，保存在超级块中
+    pub fn set_extents(&mut self, enable: bool) -> BlockResult {
+        get_block_cache(0, self.block_device.clone())?
+            .lock()
+            .modify(0, |superblock: &mut SuperBlock| {
+                if enable {
+                    superblock.features |= FEATURE_EXTENTS;
+                } else {
+                    superblock.features &= !FEATURE_EXTENTS;
+                }
+            });
+        self.extents = enable;
+        Ok(())
+    }
+    ///数据区的块号范围
+    pub fn data_area(&self) -> Range<u32> {
+        self.data_area_blocks..self.data_area_blocks + self.data_bitmap.limit() as u32
+    }
+    pub fn inode_count(&self) -> u32 {
+        self.inode_bitmap.limit() as u32
+    }
+    pub fn is_inode_allocated(&self, inode: u32) -> BlockResult<bool> {
+        self.inode_bitmap
+            .is_allocated(inode as usize, self.block_device.clone())
+    }
+    pub fn is_data_allocated(&self, block_id: u32) -> BlockResult<bool> {
+        self.data_bitmap.is_allocated(
+            (block_id - self.data_area_blocks) as usize,
+            self.block_device.clone(),
+        )
+    }
+    /// 数据块与inode的总数和空闲数
+    pub fn statfs(&self) -> BlockResult<StatFs> {
+        let blocks = self.data_bitmap.limit();
+        let inodes = self.inode_bitmap.limit();
+        Ok(StatFs {
+            block_size: BLOCK_SIZE,
+            blocks,
+            free_blocks: blocks
+                - self
+                    .data_bitmap
+                    .count_allocated(self.block_device.clone())?,
+            inodes,
+            free_inodes: inodes
+                - self
+                    .inode_bitmap
+                    .count_allocated(self.block_device.clone())?,
+        })
+    }
+    /// 设置uid最多占用的块数，None表示取消限额，限额写入超级块
+    /// 设置时扫描所有inode统计已经占用的块，已经超过限额的用户之后不能再分配
+    /// 限额超过u32的范围或者超级块中没有空闲的槽位时返回false
+    pub fn set_quota(&mut self, uid: u32, limit: Option<usize>) -> BlockResult<bool> {
+        let saved = match limit {
+            Some(0) | None => 0,
+            Some(limit) if limit > u32::MAX as usize => return Ok(false),
+            Some(limit) => limit as u32,
+        };
+        let stored = get_block_cache(0, self.block_device.clone())?
+            .lock()
+            .modify(0, |superblock: &mut SuperBlock| {
+                let slots = &mut superblock.quotas;
+                //先找到uid原有的槽位，新的限额使用第一个空闲的槽位
+                let slot = match slots.iter().position(|q| q.limit != 0 && q.uid == uid) {
+                    Some(slot) => Some(slot),
+                    None if saved == 0 => return true,
+                    None => slots.iter().position(|q| q.limit == 0),
+                };
+                match slot {
+                    Some(slot) => {
+                        slots[slot] = QuotaLimit { uid, limit: saved };
+                        true
+                    }
+                    None => false,
+                }
+            });
+        if !stored {
+            return Ok(false);
+        }
+        match limit {
+            Some(limit) if saved != 0 => {
+                let used = self.used_blocks(uid)?;
+                self.quotas.insert(uid, Quota { limit, used });
+            }
+            _ => {
+                self.quotas.remove(&uid);
+            }
+        }
+        Ok(true)
+    }
+    /// 读取超级块中保存的限额并统计已经占用的块
+    fn load_quotas(&mut self) -> BlockResult {
+        let limits = get_block_cache(0, self.block_device.clone())?
+            .lock()
+            .read(0, |superblock: &SuperBlock| superblock.quotas);
+        for slot in limits.iter().filter(|slot| slot.limit != 0) {
+            let used = self.used_blocks(slot.uid)?;
+            let limit = slot.limit as usize;
+            self.quotas.insert(slot.uid, Quota { limit, used });
+        }
+        Ok(())
+    }
+    /// 扫描所有inode统计uid占用的块
+    fn used_blocks(&self, uid: u32) -> BlockResult<usize> {
+        let mut used = 0;
+        for ino in 0..self.inode_count() {
+            if !self.is_inode_allocated(ino)? {
+                continue;
+            }
+            let (block_id, offset) = self.get_disk_inode_pos(ino);
+            used += get_block_cache(block_id as usize, self.block_device.clone())?
+                .lock()
+                .read(offset, |node: &DiskNode| {
+                    if node.uid == uid {
+                        node.blocks() as usize
+                    } else {
+                        0
+                    }
+                });
+        }
+        Ok(used)
+    }
+    /// 事务被丢弃之后，内存中记入的块数可能与磁盘不一致，重新统计
+    pub fn recount_quotas(&mut self) -> BlockResult {
+        let uids: Vec<u32> = self.quotas.keys().copied().collect();
+        for uid in uids {
+            let used = self.used_blocks(uid)?;
+            self.quotas.get_mut(&uid).unwrap().used = used;
+        }
+        Ok(())
+    }
+    pub fn quota(&self, uid: u32) -> Option<Quota> {
+        self.quotas.get(&uid).copied()
+    }
+    /// 为uid记入count个块，超过限额时不记入并返回false
+    pub fn charge(&mut self, uid: u32, count: usize) -> bool {
+        match self.quotas.get_mut(&uid) {
+            Some(quota) if quota.used + count > quota.limit => false,
+            Some(quota) => {
+                quota.used += count;
+                true
+            }
+            None => true,
+        }
+    }
+    /// 从uid的占用中减去count个块
+    pub fn credit(&mut self, uid: u32, count: usize) {
+        if let Some(quota) = self.quotas.get_mut(&uid) {
+            quota.used = quota.used.saturating_sub(count);
+        }
     }
-    pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(self.block_device.clone()).unwrap() as u32 + self.data_area_blocks
+    pub fn journal(&self) -> Journal {
+        self.journal
     }
     pub fn root_inode(fs: &Arc<Mutex<FileSystem>>) -> Inode {
         //提供根目录的索引节点号
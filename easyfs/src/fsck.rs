@@ -0,0 +1,418 @@
+///! 文件系统检查
+///! 从根目录出发遍历所有的目录项与索引节点，与两个位图互相对照
+///! 可以发现泄漏的块、被多个文件共享的块、指向未分配inode的目录项以及错误的硬链接计数
+///! 修复时以目录树为准修改位图与inode，无法安全修复的问题只报告
+use crate::block_cache::{block_cache_sync_device, get_block_cache};
+use crate::block_dev::BlockResult;
+use crate::dir_entry::{DirEntry, DIRENTRY_SIZE};
+use crate::disknode::DiskNode;
+use crate::efs::FileSystem;
+use crate::layout::SuperBlock;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Write};
+use spin::Mutex;
+
+/// 检查发现的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// 目录项指向未分配的inode
+    DanglingEntry { dir: u32, name: String, ino: u32 },
+    /// 硬链接计数与指向它的目录项数量不同
+    WrongNlink { ino: u32, nlink: u32, links: u32 },
+    /// 已分配但是没有任何目录项指向的inode
+    OrphanInode { ino: u32 },
+    /// 已分配但是没有被任何文件使用的块
+    LeakedBlock { block_id: u32 },
+    /// 文件使用了没有分配的块
+    UnallocatedBlock { block_id: u32, ino: u32 },
+    /// 同一个块被多个文件使用
+    SharedBlock { block_id: u32, ino: u32, owner: u32 },
+    /// 块号不在数据区中
+    BadBlock { block_id: u32, ino: u32 },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DanglingEntry { dir, name, ino } => write!(
+                f,
+                "entry \"{}\" in directory {} points to free inode {}",
+                name, dir, ino
+            ),
+            Problem::WrongNlink { ino, nlink, links } => {
+                write!(f, "inode {} has nlink {} but {} entries", ino, nlink, links)
+            }
+            Problem::OrphanInode { ino } => write!(f, "inode {} is not referenced", ino),
+            Problem::LeakedBlock { block_id } => {
+                write!(f, "block {} is allocated but not used", block_id)
+            }
+            Problem::UnallocatedBlock { block_id, ino } => write!(
+                f,
+                "block {} used by inode {} is not allocated",
+                block_id, ino
+            ),
+            Problem::SharedBlock {
+                block_id,
+                ino,
+                owner,
+            } => write!(
+                f,
+                "block {} used by inode {} is also used by inode {}",
+                block_id, ino, owner
+            ),
+            Problem::BadBlock { block_id, ino } => write!(
+                f,
+                "block {} used by inode {} is outside the data area",
+                block_id, ino
+            ),
+        }
+    }
+}
+
+/// 检查结果
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub problems: Vec<Problem>,
+    pub repaired: usize, //修复的问题数量
+    pub inodes: usize,   //从根目录可以访问的inode数量
+    pub blocks: usize,   //这些inode使用的块数量
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn read_node<V>(fs: &FileSystem, ino: u32, f: impl FnOnce(&DiskNode) -> V) -> BlockResult<V> {
+    let (block_id, offset) = fs.get_disk_inode_pos(ino);
+    Ok(get_block_cache(block_id as usize, fs.block_device.clone())?
+        .lock()
+        .read(offset, f))
+}
+
+fn modify_node<V>(fs: &FileSystem, ino: u32, f: impl FnOnce(&mut DiskNode) -> V) -> BlockResult<V> {
+    let (block_id, offset) = fs.get_disk_inode_pos(ino);
+    Ok(get_block_cache(block_id as usize, fs.block_device.clone())?
+        .lock()
+        .modify(offset, f))
+}
+
+/// 读取目录中所有的目录项，返回位置与目录项
+fn read_entries(fs: &FileSystem, dir: u32) -> BlockResult<Vec<(usize, DirEntry)>> {
+    read_node(fs, dir, |node| {
+        let mut entries = Vec::new();
+        for index in 0..node.size as usize / DIRENTRY_SIZE {
+            let mut entry = DirEntry::empty();
+            node.read_at(
+                index * DIRENTRY_SIZE,
+                entry.as_mut_bytes(),
+                &fs.block_device,
+            )?;
+            if !entry.is_empty() {
+                entries.push((index, entry));
+            }
+        }
+        Ok(entries)
+    })?
+}
+
+/// 检查文件系统，repair为true时同时修复发现的问题
+/// 读写失败时停止检查，已经完成的修复没有写回
+pub fn fsck(fs: &Arc<Mutex<FileSystem>>, repair: bool) -> BlockResult<FsckReport> {
+    let mut fs = fs.lock();
+    let mut report = FsckReport::default();
+    let inode_count = fs.inode_count();
+    //从根目录出发统计每个inode被目录项引用的次数
+    let mut links: BTreeMap<u32, u32> = BTreeMap::new();
+    links.insert(0, 1); //根目录没有父目录，硬链接计数为1
+    let mut dirs = VecDeque::from([0u32]);
+    while let Some(dir) = dirs.pop_front() {
+        for (index, entry) in read_entries(&fs, dir)? {
+            let ino = entry.node_number();
+            if ino >= inode_count || !fs.is_inode_allocated(ino)? {
+                report.problems.push(Problem::DanglingEntry {
+                    dir,
+                    name: entry.name().to_string(),
+                    ino,
+                });
+                if repair {
+                    read_node(&fs, dir, |node| {
+                        node.write_at(
+                            index * DIRENTRY_SIZE,
+                            DirEntry::empty().as_bytes(),
+                            &fs.block_device,
+                        )
+                    })??;
+                    report.repaired += 1;
+                }
+                continue;
+            }
+            let count = links.entry(ino).or_insert(0);
+            *count += 1;
+            if *count == 1 && read_node(&fs, ino, |node| node.is_dir())? {
+                dirs.push_back(ino);
+            }
+        }
+    }
+    //检查每个inode的硬链接计数与使用的块
+    let data_area = fs.data_area();
+    let device = fs.block_device.clone();
+    let mut owners: BTreeMap<u32, u32> = BTreeMap::new();
+    for (ino, count) in links.iter() {
+        let ino = *ino;
+        let nlink = read_node(&fs, ino, |node| node.nlink)?;
+        if nlink != *count {
+            report.problems.push(Problem::WrongNlink {
+                ino,
+                nlink,
+                links: *count,
+            });
+            if repair {
+                modify_node(&fs, ino, |node| node.nlink = *count)?;
+                report.repaired += 1;
+            }
+        }
+        let (blocks, data_blocks) = read_node(&fs, ino, |node| {
+            (node.block_ids(&device), node.data_blocks())
+        })?;
+        for (pos, block_id) in blocks?.into_iter().enumerate() {
+            if !data_area.contains(&block_id) {
+                report.problems.push(Problem::BadBlock { block_id, ino });
+                continue;
+            }
+            if let Some(owner) = owners.get(&block_id) {
+                report.problems.push(Problem::SharedBlock {
+                    block_id,
+                    ino,
+                    owner: *owner,
+                });
+                //数据块可以复制一份，共享的索引块与没有空闲块时无法修复
+                let new_block = if repair && pos < data_blocks as usize {
+                    fs.alloc_data()?
+                } else {
+                    None
+                };
+                if let Some(new_block) = new_block {
+                    let content = get_block_cache(block_id as usize, device.clone())?
+                        .lock()
+                        .read(0, |block: &[u8; crate::BLOCK_SIZE]| *block);
+                    get_block_cache(new_block as usize, device.clone())?
+                        .lock()
+                        .modify(0, |block: &mut [u8; crate::BLOCK_SIZE]| *block = content);
+                    let remapped = modify_node(&fs, ino, |node| {
+                        node.set_block_id(pos as u32, new_block, &device)
+                    })??;
+                    if !remapped {
+                        //extent记录放不下时改为使用块索引
+                        let (size, data) =
+                            read_node(&fs, ino, |node| (node.size, node.block_ids(&device)))?;
+                        let mut data = data?;
+                        let index = match fs.alloc_data_run(None, DiskNode::index_blocks(size))? {
+                            Some(index) => index,
+                            None => {
+                                fs.dealloc_data(new_block)?;
+                                continue;
+                            }
+                        };
+                        data[pos] = new_block;
+                        for block_id in index.iter() {
+                            owners.insert(*block_id, ino);
+                        }
+                        modify_node(&fs, ino, |node| node.map_blocks(size, data, index, &device))??;
+                    }
+                    owners.insert(new_block, ino);
+                    report.repaired += 1;
+                }
+                continue;
+            }
+            owners.insert(block_id, ino);
+            if !fs.is_data_allocated(block_id)? {
+                report
+                    .problems
+                    .push(Problem::UnallocatedBlock { block_id, ino });
+                if repair {
+                    fs.data_bitmap
+                        .set((block_id - data_area.start) as usize, device.clone())?;
+                    report.repaired += 1;
+                }
+            }
+        }
+    }
+    //没有被引用的inode与块
+    for ino in 0..inode_count {
+        if fs.is_inode_allocated(ino)? && !links.contains_key(&ino) {
+            report.problems.push(Problem::OrphanInode { ino });
+            if repair {
+                //它使用的块没有被其它文件使用，会在下面作为泄漏的块回收
+                fs.dealloc_inode(ino)?;
+                report.repaired += 1;
+            }
+        }
+    }
+    for block_id in data_area {
+        if fs.is_data_allocated(block_id)? && !owners.contains_key(&block_id) {
+            report.problems.push(Problem::LeakedBlock { block_id });
+            if repair {
+                fs.dealloc_data(block_id)?;
+                report.repaired += 1;
+            }
+        }
+    }
+    report.inodes = links.len();
+    report.blocks = owners.len();
+    if repair {
+        block_cache_sync_device(&device)?;
+    }
+    Ok(report)
+}
+
+/// 将连续的块号合并为区间显示
+fn format_blocks(blocks: &[u32]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < blocks.len() {
+        let mut j = i;
+        while j + 1 < blocks.len() && blocks[j + 1] == blocks[j] + 1 {
+            j += 1;
+        }
+        if !out.is_empty() {
+            out.push(',');
+        }
+        if i == j {
+            write!(out, "{}", blocks[i]).unwrap();
+        } else {
+            write!(out, "{}-{}", blocks[i], blocks[j]).unwrap();
+        }
+        i = j + 1;
+    }
+    out
+}
+
+/// 打印磁盘布局、所有已分配的inode以及目录内容
+pub fn dump(fs: &Arc<Mutex<FileSystem>>) -> BlockResult<String> {
+    let fs = fs.lock();
+    let device = fs.block_device.clone();
+    let mut out = String::new();
+    let superblock = get_block_cache(0, device.clone())?
+        .lock()
+        .read(0, |sb: &SuperBlock| {
+            (
+                sb.total_blocks,
+                sb.inode_bitmap_blocks,
+                sb.inode_area_blocks,
+                sb.data_bitmap_blocks,
+                sb.data_area_blocks,
+                sb.version(),
+                sb.journal_start,
+                sb.journal_blocks,
+            )
+        });
+    let (total, inode_bitmap, inode_area, data_bitmap, data_blocks, version, journal, journal_len) =
+        superblock;
+    let data_area = fs.data_area();
+    writeln!(out, "easyfs version {}, {} blocks", version, total).unwrap();
+    writeln!(out, "  superblock    0").unwrap();
+    writeln!(out, "  inode bitmap  1-{}", inode_bitmap).unwrap();
+    writeln!(
+        out,
+        "  inode area    {}-{}",
+        1 + inode_bitmap,
+        inode_bitmap + inode_area
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  data bitmap   {}-{}",
+        data_area.start - data_bitmap,
+        data_area.start - 1
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  data area     {}-{}",
+        data_area.start,
+        data_area.start + data_blocks - 1
+    )
+    .unwrap();
+    if journal_len != 0 {
+        writeln!(
+            out,
+            "  journal       {}-{}",
+            journal,
+            journal + journal_len - 1
+        )
+        .unwrap();
+    }
+    let mut inodes: Vec<u32> = Vec::new();
+    for ino in 0..fs.inode_count() {
+        if fs.is_inode_allocated(ino)? {
+            inodes.push(ino);
+        }
+    }
+    let mut used = 0;
+    for block_id in data_area.clone() {
+        if fs.is_data_allocated(block_id)? {
+            used += 1;
+        }
+    }
+    writeln!(
+        out,
+        "inodes {}/{}, data blocks {}/{}",
+        inodes.len(),
+        fs.inode_count(),
+        used,
+        data_blocks
+    )
+    .unwrap();
+    for ino in inodes {
+        let (mode, nlink, uid, gid, size, is_dir, layout, blocks) = read_node(&fs, ino, |node| {
+            (
+                node.st_mode(),
+                node.nlink,
+                node.uid,
+                node.gid,
+                node.size,
+                node.is_dir(),
+                if node.is_inline() {
+                    "inline"
+                } else if node.is_extent() {
+                    "extents"
+                } else {
+                    "blocks"
+                },
+                node.block_ids(&device),
+            )
+        })?;
+        let blocks = blocks?;
+        writeln!(
+            out,
+            "inode {:>4}: mode {:o} nlink {} uid {} gid {} size {} {} [{}]",
+            ino,
+            mode,
+            nlink,
+            uid,
+            gid,
+            size,
+            layout,
+            format_blocks(&blocks)
+        )
+        .unwrap();
+        if is_dir {
+            for (index, entry) in read_entries(&fs, ino)? {
+                writeln!(
+                    out,
+                    "    [{:>3}] {:<28} -> {}",
+                    index,
+                    entry.name(),
+                    entry.node_number()
+                )
+                .unwrap();
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,181 @@
+///! 元数据日志
+///! 一次操作可能修改位图、索引节点、索引块与目录项等多个块，中途断电会使它们互相矛盾
+///! 每个操作作为一个事务，事务内修改的元数据块先完整地写入日志区，再写入日志头作为提交记录，
+///! 之后才写回原来的位置，最后清空日志头。打开文件系统时如果日志头中仍有记录，说明上一次
+///! 写回没有完成，按照日志重新写回即可
+///! 文件数据不写入日志，而是在提交元数据之前写回
+use crate::block_cache::{block_cache_invalidate, BlockCache, BLOCK_CACHE_MANAGER};
+use crate::block_dev::{BlockDevice, BlockResult};
+use crate::BLOCK_SIZE;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+const JOURNAL_MAGIC: u32 = 0x4a524e4c;
+const JOURNAL_TARGETS: usize = BLOCK_SIZE / 4 - 2; //日志头中能记录的块数
+
+/// 日志头，位于日志区的第一个块
+/// count不为0时表示事务已经提交但是没有完成写回
+#[repr(C)]
+struct JournalHeader {
+    magic: u32,
+    count: u32,
+    targets: [u32; JOURNAL_TARGETS], //日志块对应的原始块号
+}
+
+const _: () = assert!(core::mem::size_of::<JournalHeader>() == BLOCK_SIZE);
+
+/// 日志区的位置，blocks为0表示没有日志
+#[derive(Copy, Clone)]
+pub struct Journal {
+    start: u32,
+    blocks: u32,
+}
+
+impl Journal {
+    pub fn new(start: u32, blocks: u32) -> Self {
+        Self { start, blocks }
+    }
+    /// 一次写入日志的块数
+    fn capacity(&self) -> usize {
+        (self.blocks as usize - 1).min(JOURNAL_TARGETS)
+    }
+    fn write_header(&self, device: &Arc<dyn BlockDevice>, targets: &[u32]) -> BlockResult {
+        let mut header = JournalHeader {
+            magic: JOURNAL_MAGIC,
+            count: targets.len() as u32,
+            targets: [0; JOURNAL_TARGETS],
+        };
+        header.targets[..targets.len()].copy_from_slice(targets);
+        let buf =
+            unsafe { core::slice::from_raw_parts(&header as *const _ as *const u8, BLOCK_SIZE) };
+        device.write_block(self.start as usize, buf)
+    }
+    /// 清空日志区
+    pub fn format(&self, device: &Arc<dyn BlockDevice>) -> BlockResult {
+        let zero = vec![0u8; self.blocks as usize * BLOCK_SIZE];
+        device.write_blocks(self.start as usize, &zero)?;
+        if self.blocks != 0 {
+            self.write_header(device, &[])?;
+        }
+        Ok(())
+    }
+    /// 重新写回已经提交的事务，返回写回的块数
+    /// 日志直接读写磁盘，结束后丢弃该设备的缓存，写回失败时日志头保持不变
+    pub fn replay(&self, device: &Arc<dyn BlockDevice>) -> BlockResult<usize> {
+        if self.blocks == 0 {
+            return Ok(0);
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        device.read_block(self.start as usize, &mut buf)?;
+        let header = unsafe { &*(buf.as_ptr() as *const JournalHeader) };
+        //记录的块数超出日志容量或者块号超出设备时日志头已经损坏，视为没有事务
+        let count = header.count as usize;
+        if header.magic != JOURNAL_MAGIC || count == 0 || count > self.capacity() {
+            return Ok(0);
+        }
+        if header.targets[..count]
+            .iter()
+            .any(|target| *target as usize >= device.num_blocks())
+        {
+            return Ok(0);
+        }
+        let targets: Vec<u32> = header.targets[..count].to_vec();
+        let mut blocks = vec![0u8; targets.len() * BLOCK_SIZE];
+        device.read_blocks(self.start as usize + 1, &mut blocks)?;
+        for (target, block) in targets.iter().zip(blocks.chunks(BLOCK_SIZE)) {
+            device.write_block(*target as usize, block)?;
+        }
+        device.flush()?;
+        self.write_header(device, &[])?;
+        block_cache_invalidate(device);
+        Ok(targets.len())
+    }
+    /// 开始一个事务，在提交之前修改过的元数据块不会被替换出缓存
+    pub fn begin(&self, device: &Arc<dyn BlockDevice>) {
+        BLOCK_CACHE_MANAGER.lock().begin(device);
+    }
+    /// 放弃事务，提交时返回false，嵌套的事务使外层的事务一起被放弃
+    pub fn abort(&self, device: &Arc<dyn BlockDevice>) {
+        BLOCK_CACHE_MANAGER.lock().abort(device);
+    }
+    /// 提交事务，嵌套的事务在最外层提交
+    /// 只修改了一个元数据块时，单个块的写入本身就是原子的，不需要经过日志
+    /// 修改的元数据块超出日志容量时无法原子地提交，丢弃事务的所有修改并返回false
+    /// 提交之前写入失败时同样丢弃事务并返回错误
+    pub fn commit(&self, device: &Arc<dyn BlockDevice>) -> BlockResult<bool> {
+        let (aborted, caches) = {
+            let mut manager = BLOCK_CACHE_MANAGER.lock();
+            (manager.is_aborted(device), manager.end(device))
+        };
+        let caches = match caches {
+            Some(caches) => caches,
+            None => return Ok(!aborted),
+        };
+        if aborted {
+            drop(caches);
+            block_cache_invalidate(device);
+            return Ok(false);
+        }
+        let (metadata, data): (Vec<_>, Vec<_>) = caches
+            .into_iter()
+            .partition(|cache| cache.lock().is_dirty_metadata());
+        if metadata.is_empty() {
+            //没有修改元数据时文件数据可以继续留在缓存中
+            return Ok(true);
+        }
+        if self.blocks != 0 && metadata.len() > self.capacity() {
+            //修改都还在缓存中，丢弃缓存后磁盘上仍然是事务开始之前的状态
+            drop((metadata, data));
+            block_cache_invalidate(device);
+            return Ok(false);
+        }
+        //先写回文件数据，保证提交后的元数据不会指向没有写入的数据
+        let written = sync_all(&data).and_then(|_| self.write_metadata(device, &metadata));
+        if written.is_err() {
+            drop((metadata, data));
+            block_cache_invalidate(device);
+        }
+        written
+    }
+    /// 经过日志写回元数据块，日志头写入之后的失败由重新写回日志完成
+    fn write_metadata(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        metadata: &[Arc<Mutex<BlockCache>>],
+    ) -> BlockResult<bool> {
+        if self.blocks == 0 || metadata.len() == 1 {
+            sync_all(metadata)?;
+            return Ok(true);
+        }
+        let mut targets = Vec::with_capacity(metadata.len());
+        let mut blocks = Vec::with_capacity(metadata.len() * BLOCK_SIZE);
+        for cache in metadata.iter() {
+            let cache = cache.lock();
+            cache.read(0, |block: &[u8; BLOCK_SIZE]| {
+                blocks.extend_from_slice(block)
+            });
+            targets.push(cache.block_id() as u32);
+        }
+        //日志块一次写入，落盘之后再写日志头，日志头写入后事务即提交
+        device.write_blocks(self.start as usize + 1, &blocks)?;
+        device.flush()?;
+        self.write_header(device, &targets)?;
+        let written = device
+            .flush()
+            .and_then(|_| sync_all(metadata))
+            .and_then(|_| device.flush())
+            .and_then(|_| self.write_header(device, &[]));
+        if written.is_err() {
+            //缓存中的块可能只写回了一部分，按照日志重新写回
+            self.replay(device)?;
+        }
+        Ok(true)
+    }
+}
+
+/// 逐个写回，遇到错误时停止
+fn sync_all(caches: &[Arc<Mutex<BlockCache>>]) -> BlockResult {
+    caches.iter().try_for_each(|cache| cache.lock().sync())
+}
@@ -6,7 +6,9 @@
 // 数据块位图： 多块 记录数据库的使用情况
 // 数据块：多块 存放所有文件或目录的数据
 
-use crate::EFS_MAGIC;
+use crate::{BLOCK_SIZE, EFS_MAGIC, EFS_VERSION, QUOTA_SLOTS};
+
+const BLOCK_BITS: u64 = BLOCK_SIZE as u64 * 8; //每个位图块记录的块数
 
 #[repr(C)]
 pub struct SuperBlock {
@@ -16,6 +18,20 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,   //
     pub data_bitmap_blocks: u32,  //
     pub data_area_blocks: u32,    //
+    pub version: u32,             //磁盘格式版本，第一版镜像中为0
+    pub journal_start: u32,       //日志区的起始块
+    pub journal_blocks: u32,      //日志区的块数，为0时没有日志
+    pub features: u32,            //可选的功能，旧版本的镜像中为0
+    //按uid设置的块限额，旧版本的镜像中全为0
+    pub quotas: [QuotaLimit; QUOTA_SLOTS],
+}
+
+/// 超级块中记录的一个用户的块限额，limit为0的槽位是空闲的
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct QuotaLimit {
+    pub uid: u32,
+    pub limit: u32,
 }
 
 impl SuperBlock {
@@ -35,9 +51,30 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            version: EFS_VERSION,
+            journal_start: 0,
+            journal_blocks: 0,
+            features: 0,
+            quotas: [QuotaLimit::default(); QUOTA_SLOTS],
         };
     }
     pub fn is_valid(&self) -> bool {
         self.magic == EFS_MAGIC
     }
+    pub fn version(&self) -> u32 {
+        self.version.max(1)
+    }
+    /// 各个区域都位于文件系统之内，并且文件系统不超过num_blocks个块
+    pub fn fits(&self, num_blocks: usize) -> bool {
+        let areas = 1
+            + self.inode_bitmap_blocks as u64
+            + self.inode_area_blocks as u64
+            + self.data_bitmap_blocks as u64
+            + self.data_area_blocks as u64;
+        let journal_end = self.journal_start as u64 + self.journal_blocks as u64;
+        self.total_blocks as usize <= num_blocks
+            && areas <= self.total_blocks as u64
+            && journal_end <= self.total_blocks as u64
+            && self.data_bitmap_blocks as u64 * BLOCK_BITS >= self.data_area_blocks as u64
+    }
 }
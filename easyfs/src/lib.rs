@@ -7,14 +7,28 @@ extern crate alloc;
 mod bitmap;
 mod block_cache;
 mod block_dev;
+mod clock;
 mod config;
 mod dir_entry;
 mod disknode;
 mod efs;
+mod fsck;
+mod journal;
 mod layout;
+mod migrate;
 mod vfs;
 
-pub use block_dev::BlockDevice;
+pub use block_cache::{
+    block_cache_invalidate, block_cache_stats, block_cache_sync, block_cache_sync_device,
+    block_cache_try_sync, get_block_cache, set_block_cache_capacity, BlockCache, BlockCacheManager,
+    BlockCacheStats,
+};
+pub use block_dev::{
+    check_range, BlockCompletion, BlockDevice, BlockError, BlockRequest, BlockResult,
+};
+pub use clock::{now, set_clock};
 pub use config::*;
-pub use efs::FileSystem;
-pub use vfs::Inode;
+pub use efs::{FileSystem, Quota, StatFs};
+pub use fsck::{dump, fsck, FsckReport, Problem};
+pub use journal::Journal;
+pub use vfs::{Inode, Metadata, RenameError};
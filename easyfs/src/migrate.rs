@@ -0,0 +1,218 @@
+///! 旧版本磁盘格式的迁移
+///! 第一版的索引节点有27个直接索引，没有时间、所有者与权限信息
+///! 第二版减少了直接索引的数量，为这些元数据让出空间
+///! 第三版的文件大小为64位，增加了三级与四级索引
+///! 第四版可以使用extent记录，第三版的inode中对应的标志位只需要清0
+///! 迁移时按顺序读出每个文件的数据块，再按照新的索引结构重新组织，数据块本身不移动
+use crate::block_cache::{block_cache_sync, get_block_cache};
+use crate::block_dev::BlockResult;
+use crate::disknode::{DiskNode, DiskNodeType};
+use crate::efs::FileSystem;
+use crate::{BLOCK_SIZE, BLOCK_U32};
+use alloc::vec::Vec;
+
+const V1_DIRECT_MAX: usize = 27;
+const V3_FLAGS_OFFSET: usize = 15; //第三版中node_type之后的填充字节
+const V2_DIRECT_MAX: usize = 20;
+
+/// 第一版的索引节点
+#[repr(C)]
+struct DiskNodeV1 {
+    nlink: u32,
+    size: u32,
+    direct: [u32; V1_DIRECT_MAX],
+    indirect1: u32,
+    indirect2: u32,
+    node_type: u8, //0为文件，1为目录
+}
+
+/// 第二版的索引节点
+#[repr(C)]
+struct DiskNodeV2 {
+    nlink: u32,
+    size: u32,
+    direct: [u32; V2_DIRECT_MAX],
+    indirect1: u32,
+    indirect2: u32,
+    node_type: u8,
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    atime: u32,
+    mtime: u32,
+    ctime: u32,
+    reserved: [u32; 2],
+}
+
+type Indirect = [u32; BLOCK_U32];
+
+/// 旧版本索引节点中需要保留的内容
+struct OldNode {
+    nlink: u32,
+    size: u32,
+    node_type: u8,
+    meta: Option<(u16, u32, u32, u32, u32, u32)>, //权限位、所有者与时间，第一版没有
+    data: Vec<u32>,                               //按顺序排列的数据块
+    index: Vec<u32>,                              //旧的索引块
+}
+
+/// 将旧版本的文件系统迁移为当前版本，读写失败时停止迁移，超级块中仍然是旧的版本
+pub fn migrate(fs: &mut FileSystem, version: u32) -> BlockResult {
+    for inode_id in 0..fs.inode_bitmap.bit_num() {
+        if fs
+            .inode_bitmap
+            .is_allocated(inode_id, fs.block_device.clone())?
+        {
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id as u32);
+            let old = match version {
+                1 => read_v1(fs, block_id, block_offset)?,
+                2 => read_v2(fs, block_id, block_offset)?,
+                _ => {
+                    //从旧版本迁移来的inode中填充字节可能不为0
+                    get_block_cache(block_id as usize, fs.block_device.clone())?
+                        .lock()
+                        .modify(block_offset + V3_FLAGS_OFFSET, |flags: &mut u8| *flags = 0);
+                    continue;
+                }
+            };
+            rebuild_inode(fs, block_id, block_offset, old)?;
+        }
+    }
+    block_cache_sync()
+}
+
+fn read_indirect(fs: &FileSystem, block_id: u32) -> BlockResult<Indirect> {
+    let cache = get_block_cache(block_id as usize, fs.block_device.clone())?;
+    let array = cache.lock().read(0, |array: &Indirect| *array);
+    Ok(array)
+}
+
+/// 按顺序收集旧的直接索引、一级索引与二级索引中的数据块与索引块
+fn collect_blocks(
+    fs: &FileSystem,
+    size: u32,
+    direct: &[u32],
+    indirect1: u32,
+    indirect2: u32,
+) -> BlockResult<(Vec<u32>, Vec<u32>)> {
+    let count = (size as usize + BLOCK_SIZE - 1) / BLOCK_SIZE;
+    let direct_max = direct.len();
+    let mut data: Vec<u32> = direct[..count.min(direct_max)].to_vec();
+    let mut index: Vec<u32> = Vec::new();
+    if count > direct_max {
+        index.push(indirect1);
+        let array = read_indirect(fs, indirect1)?;
+        data.extend_from_slice(&array[..(count - direct_max).min(BLOCK_U32)]);
+    }
+    if count > direct_max + BLOCK_U32 {
+        index.push(indirect2);
+        let first = read_indirect(fs, indirect2)?;
+        let mut rest = count - direct_max - BLOCK_U32;
+        for second_id in first.iter() {
+            if rest == 0 {
+                break;
+            }
+            index.push(*second_id);
+            let second = read_indirect(fs, *second_id)?;
+            data.extend_from_slice(&second[..rest.min(BLOCK_U32)]);
+            rest -= rest.min(BLOCK_U32);
+        }
+    }
+    Ok((data, index))
+}
+
+fn read_v1(fs: &FileSystem, block_id: u32, block_offset: usize) -> BlockResult<OldNode> {
+    let (nlink, size, node_type, direct, indirect1, indirect2) =
+        get_block_cache(block_id as usize, fs.block_device.clone())?
+            .lock()
+            .read(block_offset, |old: &DiskNodeV1| {
+                (
+                    old.nlink,
+                    old.size,
+                    old.node_type,
+                    old.direct,
+                    old.indirect1,
+                    old.indirect2,
+                )
+            });
+    let (data, index) = collect_blocks(fs, size, &direct, indirect1, indirect2)?;
+    Ok(OldNode {
+        nlink,
+        size,
+        node_type,
+        meta: None,
+        data,
+        index,
+    })
+}
+
+fn read_v2(fs: &FileSystem, block_id: u32, block_offset: usize) -> BlockResult<OldNode> {
+    let (nlink, size, node_type, meta, direct, indirect1, indirect2) =
+        get_block_cache(block_id as usize, fs.block_device.clone())?
+            .lock()
+            .read(block_offset, |old: &DiskNodeV2| {
+                (
+                    old.nlink,
+                    old.size,
+                    old.node_type,
+                    (old.mode, old.uid, old.gid, old.atime, old.mtime, old.ctime),
+                    old.direct,
+                    old.indirect1,
+                    old.indirect2,
+                )
+            });
+    let (data, index) = collect_blocks(fs, size, &direct, indirect1, indirect2)?;
+    Ok(OldNode {
+        nlink,
+        size,
+        node_type,
+        meta: Some(meta),
+        data,
+        index,
+    })
+}
+
+/// 按照当前的索引结构重新写入索引节点
+fn rebuild_inode(
+    fs: &mut FileSystem,
+    block_id: u32,
+    block_offset: usize,
+    old: OldNode,
+) -> BlockResult {
+    let OldNode {
+        nlink,
+        size,
+        node_type,
+        meta,
+        data,
+        index: mut old_index,
+    } = old;
+    //新的索引结构需要的索引块，不够时分配，多余的回收
+    let need = DiskNode::index_blocks(size as u64);
+    while old_index.len() < need {
+        old_index.push(fs.alloc_data()?.expect("No space left for migration"));
+    }
+    for extra in old_index.split_off(need) {
+        fs.dealloc_data(extra)?;
+    }
+    let device = fs.block_device.clone();
+    get_block_cache(block_id as usize, device.clone())?
+        .lock()
+        .modify(block_offset, |node: &mut DiskNode| {
+            node.initialize(if node_type == 1 {
+                DiskNodeType::DIRECTORY
+            } else {
+                DiskNodeType::FILE
+            });
+            node.nlink = nlink;
+            if let Some((mode, uid, gid, atime, mtime, ctime)) = meta {
+                node.mode = mode;
+                node.uid = uid;
+                node.gid = gid;
+                node.atime = atime;
+                node.mtime = mtime;
+                node.ctime = ctime;
+            }
+            node.map_blocks(size as u64, data, old_index, &device)
+        })
+}
@@ -1,8 +1,12 @@
-use crate::block_cache::{block_cache_sync, get_block_cache};
-use crate::block_dev::BlockDevice;
+use crate::block_cache::{block_cache_sync_device, get_block_cache};
+use crate::block_dev::{BlockDevice, BlockError, BlockResult};
+use crate::clock::now;
 use crate::dir_entry::{DirEntry, DIRENTRY_SIZE};
 use crate::disknode::{DiskNode, DiskNodeType};
 use crate::efs::FileSystem;
+use crate::{
+    BLOCK_SIZE, MAX_FILE_SIZE, NAME_LENGTH_MAX, SYMLINK_INLINE_MAX, SYMLINK_MAX, WRITE_CHUNK_BLOCKS,
+};
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -10,6 +14,40 @@ use spin::Mutex;
 use spin::MutexGuard;
 ///! 索引节点层，负责提供系统调用
 
+/// 文件元数据，供fstat使用
+#[derive(Debug, Copy, Clone)]
+pub struct Metadata {
+    pub ino: usize,
+    pub mode: u32, //文件类型与权限位
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: usize,
+    pub blocks: usize, //占用的512B扇区数
+    pub atime: u32,
+    pub mtime: u32,
+    pub ctime: u32,
+}
+
+/// 重命名失败的原因
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    InvalidName,    //新的名称为空或者过长
+    NotDir,         //不是目录
+    NotFound,       //原来的文件不存在
+    TypeMismatch,   //目录与不是目录的文件不能互相替换
+    NotEmpty,       //被替换的目录不为空
+    Loop,           //目录不能移动到它自己或者它的子目录中
+    NoSpace,        //目录无法扩大，或者修改超出日志容量
+    Io(BlockError), //读写失败，整个事务被丢弃
+}
+
+impl From<BlockError> for RenameError {
+    fn from(err: BlockError) -> Self {
+        RenameError::Io(err)
+    }
+}
+
 ///Inode与Disknode的区别在于Inode存在于内存中
 ///记录文件索引节点的相关信息
 /// 可以根据id、offset找到disknode
@@ -34,33 +72,37 @@ impl Inode {
         }
     }
 
-    pub fn get_disk_nlink(&self) -> u32 {
-        self.read_disk_inode(|disknode| disknode.nlink)
+    pub fn get_disk_nlink(&self) -> BlockResult<u32> {
+        self.read_disk_inode(|disknode| Ok(disknode.nlink))
     }
 
-    pub fn add_disk_nlink(&self) {
+    pub fn add_disk_nlink(&self) -> BlockResult {
         self.modify_disk_inode(|disknode| {
             disknode.nlink += 1;
+            disknode.ctime = now();
+            Ok(())
         })
     }
-    pub fn sub_disk_nlink(&self) {
+    pub fn sub_disk_nlink(&self) -> BlockResult {
         self.modify_disk_inode(|disknode| {
             disknode.nlink -= 1;
+            disknode.ctime = now();
+            Ok(())
         })
     }
     ///查看文件大小
-    pub fn get_file_size(&self) -> usize {
-        self.read_disk_inode(|disk_node| disk_node.size as usize)
+    pub fn get_file_size(&self) -> BlockResult<usize> {
+        self.read_disk_inode(|disk_node| Ok(disk_node.size as usize))
     }
     ///查看文件类型
-    pub fn get_disk_type(&self) -> u32 {
-        self.read_disk_inode(|disknode| {
-            if disknode.is_dir() {
-                0o040000
-            } else {
-                0o100000
-            }
-        })
+    pub fn get_disk_type(&self) -> BlockResult<u32> {
+        self.read_disk_inode(|disknode| Ok(disknode.st_mode() & 0o170000))
+    }
+    pub fn is_dir(&self) -> BlockResult<bool> {
+        self.read_disk_inode(|disknode| Ok(disknode.is_dir()))
+    }
+    pub fn is_symlink(&self) -> BlockResult<bool> {
+        self.read_disk_inode(|disknode| Ok(disknode.is_symlink()))
     }
     ///查看文件inode编号
     pub fn get_disk_inode(&self) -> usize {
@@ -68,218 +110,758 @@ impl Inode {
         fs.get_disk_inode(self.block_id as u32, self.block_offset) as usize
     }
 
-    pub fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskNode) -> V) -> V {
-        //读取索引节点的内容
-        get_block_cache(self.block_id, self.block_device.clone())
+    ///查看文件元数据
+    pub fn metadata(&self) -> BlockResult<Metadata> {
+        let ino = self.get_disk_inode();
+        self.read_disk_inode(|disknode| {
+            Ok(Metadata {
+                ino,
+                mode: disknode.st_mode(),
+                nlink: disknode.nlink,
+                uid: disknode.uid,
+                gid: disknode.gid,
+                size: disknode.size as usize,
+                blocks: disknode.blocks() as usize * (BLOCK_SIZE / 512),
+                atime: disknode.atime,
+                mtime: disknode.mtime,
+                ctime: disknode.ctime,
+            })
+        })
+    }
+    ///文件占用的所有块，包括数据块与索引块
+    pub fn block_ids(&self) -> BlockResult<Vec<u32>> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disknode| disknode.block_ids(&self.block_device))
+    }
+    ///修改权限位
+    pub fn set_mode(&self, mode: u16) -> BlockResult {
+        self.modify_disk_inode(|disknode| {
+            disknode.mode = mode & 0o7777;
+            disknode.ctime = now();
+            Ok(())
+        })
+    }
+    ///修改所有者，占用的块转移到新的所有者，超过新的所有者的限额时返回false
+    pub fn set_owner(&self, uid: u32, gid: u32) -> BlockResult<bool> {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disknode| {
+            let blocks = disknode.blocks() as usize;
+            if disknode.uid != uid {
+                if !fs.charge(uid, blocks) {
+                    return Ok(false);
+                }
+                fs.credit(disknode.uid, blocks);
+            }
+            disknode.uid = uid;
+            disknode.gid = gid;
+            disknode.ctime = now();
+            Ok(true)
+        })
+    }
+
+    /// 读取索引节点的内容，索引节点所在的块或者f读取的块无法读出时返回错误
+    pub fn read_disk_inode<V>(
+        &self,
+        f: impl FnOnce(&DiskNode) -> BlockResult<V>,
+    ) -> BlockResult<V> {
+        get_block_cache(self.block_id, self.block_device.clone())?
             .lock()
             .read(self.block_offset, f)
     }
-    pub fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskNode) -> V) -> V {
-        //修改索引节点内容
-        get_block_cache(self.block_id, self.block_device.clone())
+    /// 修改索引节点内容
+    pub fn modify_disk_inode<V>(
+        &self,
+        f: impl FnOnce(&mut DiskNode) -> BlockResult<V>,
+    ) -> BlockResult<V> {
+        get_block_cache(self.block_id, self.block_device.clone())?
             .lock()
             .modify(self.block_offset, f)
     }
+    /// 根据名称找到文件，不存在或者目录无法读出时返回None
     pub fn find_inode(&self, name: &str) -> Option<Arc<Inode>> {
+        self.lookup(name).ok().flatten()
+    }
+    fn lookup(&self, name: &str) -> BlockResult<Option<Arc<Inode>>> {
         //根据名称找到文件索引节点号
         let fs = self.fs.lock(); //尝试获得文件系统的互斥锁
         self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Inode::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
+            Ok(self
+                .find_inode_id(name, disk_inode)?
+                .map(|inode_id| self.inode_from_id(inode_id, &fs)))
         })
     }
-    pub fn find_inode_id(&self, name: &str, disk_inode: &DiskNode) -> Option<u32> {
+    /// 在一个事务中执行操作，操作修改的元数据在结束时通过日志一起写回磁盘
+    /// 调用时不能持有文件系统与块缓存的锁
+    /// 修改的元数据块超出日志容量时整个事务被丢弃，返回Ok(None)
+    /// 操作或者提交时读写失败同样丢弃整个事务并返回错误，位于外层事务中时外层事务也被丢弃
+    fn transaction<V>(&self, f: impl FnOnce() -> BlockResult<V>) -> BlockResult<Option<V>> {
+        let journal = self.fs.lock().journal();
+        journal.begin(&self.block_device);
+        let ret = f();
+        if ret.is_err() {
+            journal.abort(&self.block_device);
+        }
+        let committed = journal.commit(&self.block_device);
+        if committed != Ok(true) {
+            //重新统计失败时保留原来的统计，之后的提交会再次统计
+            let _ = self.fs.lock().recount_quotas();
+        }
+        let ret = ret?;
+        match committed? {
+            true => Ok(Some(ret)),
+            false => Ok(None),
+        }
+    }
+    /// 不需要区分失败原因的事务，读写失败与事务被丢弃都返回None
+    fn try_transaction<V>(&self, f: impl FnOnce() -> BlockResult<Option<V>>) -> Option<V> {
+        self.transaction(f).ok().flatten().flatten()
+    }
+    fn inode_from_id(&self, inode_id: u32, fs: &FileSystem) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Inode::new(
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+    pub fn find_inode_id(&self, name: &str, disk_inode: &DiskNode) -> BlockResult<Option<u32>> {
+        Ok(self
+            .find_entry(name, disk_inode)?
+            .map(|(_, direntry)| direntry.node_number()))
+    }
+    /// 根据名称找到目录项及其在目录中的位置
+    fn find_entry(
+        &self,
+        name: &str,
+        disk_inode: &DiskNode,
+    ) -> BlockResult<Option<(usize, DirEntry)>> {
         //判断是否是目录
         assert!(disk_inode.is_dir(), "This is not a directory");
-        let mut direntry = DirEntry::empty(); //创建一个空的目录项
+        if name.is_empty() {
+            return Ok(None);
+        }
         let direntry_num = disk_inode.size as usize / DIRENTRY_SIZE; //目录文件中包含的目录项数目
         for index in 0..direntry_num {
-            assert_eq!(
-                disk_inode.read_at(
-                    index * DIRENTRY_SIZE,
-                    direntry.as_mut_bytes(),
-                    &self.block_device
-                ),
-                DIRENTRY_SIZE
-            );
+            let direntry = self.read_entry(index, disk_inode)?;
             if direntry.name() == name {
-                return Some(direntry.node_number());
+                return Ok(Some((index, direntry)));
             }
         }
-        None
+        Ok(None)
+    }
+    fn read_entry(&self, index: usize, disk_inode: &DiskNode) -> BlockResult<DirEntry> {
+        let mut direntry = DirEntry::empty(); //创建一个空的目录项
+        let size = disk_inode.read_at(
+            index * DIRENTRY_SIZE,
+            direntry.as_mut_bytes(),
+            &self.block_device,
+        )?;
+        assert_eq!(size, DIRENTRY_SIZE);
+        Ok(direntry)
+    }
+    fn write_entry(&self, index: usize, direntry: &DirEntry, disk_inode: &DiskNode) -> BlockResult {
+        let size = disk_inode.write_at(
+            index * DIRENTRY_SIZE,
+            direntry.as_bytes(),
+            &self.block_device,
+        )?;
+        assert_eq!(size, DIRENTRY_SIZE);
+        Ok(())
     }
-    pub fn ls(&self) -> Vec<String> {
+    /// 在目录中加入一个目录项
+    /// 优先复用被删除的目录项，没有时扩大目录，无法扩大时返回false
+    fn add_entry(
+        &self,
+        direntry: &DirEntry,
+        disk_inode: &mut DiskNode,
+        fs: &mut MutexGuard<FileSystem>,
+    ) -> BlockResult<bool> {
+        let direntry_num = disk_inode.size as usize / DIRENTRY_SIZE;
+        let mut free = None;
+        for index in 0..direntry_num {
+            if self.read_entry(index, disk_inode)?.is_empty() {
+                free = Some(index);
+                break;
+            }
+        }
+        let index = match free {
+            Some(index) => index,
+            None => {
+                let new_size = (direntry_num + 1) * DIRENTRY_SIZE; //新的目录大小
+                if !self.increase_size(new_size as u64, disk_inode, fs)? {
+                    return Ok(false);
+                }
+                direntry_num
+            }
+        };
+        self.write_entry(index, direntry, disk_inode)?;
+        Ok(true)
+    }
+    pub fn ls(&self) -> BlockResult<Vec<String>> {
         //列举目录下的所有文件名
         let _fs = self.fs.lock(); //防止在多核时其它核抢占文件系统锁
         self.read_disk_inode(|disk_inode| {
             let file_count = disk_inode.size as usize / DIRENTRY_SIZE; //目录项
             let mut file_names: Vec<String> = Vec::new();
             for i in 0..file_count {
-                let mut direntry = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(
-                        i * DIRENTRY_SIZE,
-                        direntry.as_mut_bytes(),
-                        &self.block_device
-                    ),
-                    DIRENTRY_SIZE
-                ); //判断是否是一个正确的目录项
-
+                let direntry = self.read_entry(i, disk_inode)?;
+                if direntry.is_empty() {
+                    continue; //跳过已经删除的目录项
+                }
                 file_names.push(String::from(direntry.name()));
             }
-            file_names
+            Ok(file_names)
+        })
+    }
+    /// 从目录项位置index开始的第一个文件，返回(下一个位置, 名称, 文件)，没有时返回None
+    /// 删除文件只清空目录项，其它文件的位置不变，可以用作读取目录的游标
+    pub fn read_dirent(&self, index: usize) -> BlockResult<Option<(usize, String, Arc<Inode>)>> {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = disk_inode.size as usize / DIRENTRY_SIZE;
+            for i in index..file_count {
+                let direntry = self.read_entry(i, disk_inode)?;
+                if !direntry.is_empty() {
+                    let inode = self.inode_from_id(direntry.node_number(), &fs);
+                    return Ok(Some((i + 1, String::from(direntry.name()), inode)));
+                }
+            }
+            Ok(None)
         })
     }
     pub fn create_nlink(&self, newname: &str, oldname: &str) -> Option<Arc<Inode>> {
+        self.try_transaction(|| self.create_nlink_inner(newname, oldname))
+    }
+    fn create_nlink_inner(&self, newname: &str, oldname: &str) -> BlockResult<Option<Arc<Inode>>> {
         //创建一个硬链接文件
         if self
             .modify_disk_inode(|root_node: &mut DiskNode| {
                 //查找是否已经存在此节点
                 assert!(root_node.is_dir(), "The root node is not directory");
                 self.find_inode_id(newname, root_node) //在根目录下查找
-            })
+            })?
             .is_some()
         {
-            return None; //存在文件
+            return Ok(None); //存在文件
         }
-        //新建一个文件
-        let old_inode = self.find_inode(oldname).unwrap();
-        // println!("oldinode:{}-{}",old_inode.block_id,old_inode.block_offset);
-        let (inode_block_id, inode_block_offset) = (old_inode.block_id, old_inode.block_offset);
-        self.modify_disk_inode(|root_inode| {
-            //在根目录下添加
-            let file_num = root_inode.size as usize / DIRENTRY_SIZE;
-            let new_size = (file_num + 1) * DIRENTRY_SIZE; //新的目录大小
-                                                           // println!("!!!!--- {}",old_inode.get_disk_inode());
-            let new_entry = DirEntry::new(newname, old_inode.get_disk_inode() as u32);
+        let old_inode = match self.lookup(oldname)? {
+            Some(inode) => inode,
+            None => return Ok(None),
+        };
+        if self.link_inner(newname, &old_inode)? {
+            Ok(Some(old_inode))
+        } else {
+            Ok(None)
+        }
+    }
+    /// 在当前目录下为inode(可以位于其它目录)添加一个硬链接
+    pub fn link(&self, name: &str, inode: &Inode) -> bool {
+        self.transaction(|| self.link_inner(name, inode)) == Ok(Some(true))
+    }
+    fn link_inner(&self, name: &str, inode: &Inode) -> BlockResult<bool> {
+        //目录不允许硬链接，否则目录树中可能出现环
+        if name.is_empty() || name.len() > NAME_LENGTH_MAX || inode.is_dir()? {
+            return Ok(false);
+        }
+        let new_entry = DirEntry::new(name, inode.get_disk_inode() as u32);
+        let added = self.modify_disk_inode(|root_inode| {
             let mut fs = self.fs.lock();
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-
-            let _number = root_inode.write_at(
-                file_num * DIRENTRY_SIZE as usize,
-                new_entry.as_bytes(),
-                &self.block_device,
-            ); //写入目录项
-        });
-        let new_inode = Inode::new(
-            inode_block_id as u32,
-            inode_block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        );
-        new_inode.add_disk_nlink(); //添加硬链接
-        Some(Arc::new(new_inode))
+            if self.find_inode_id(name, root_inode)?.is_some() {
+                return Ok(false); //存在文件
+            }
+            self.add_entry(&new_entry, root_inode, &mut fs)
+        })?;
+        if added {
+            inode.add_disk_nlink()?; //添加硬链接
+        }
+        Ok(added)
+    }
+    /// 从目录中删除一个目录项并减少硬链接计数
+    /// 返回被删除的文件，硬链接计数为0时由调用者在文件不再使用后调用release回收
+    pub fn unlink(&self, name: &str) -> Option<Arc<Inode>> {
+        self.try_transaction(|| self.unlink_inner(name))
+    }
+    fn unlink_inner(&self, name: &str) -> BlockResult<Option<Arc<Inode>>> {
+        let inode = match self.lookup(name)? {
+            Some(inode) => inode,
+            None => return Ok(None),
+        };
+        //只能删除空目录，否则其中的文件无法回收
+        if inode.is_dir()? && !inode.ls()?.is_empty() {
+            return Ok(None);
+        }
+        {
+            let _fs = self.fs.lock();
+            self.modify_disk_inode(|root_inode| {
+                let (index, _) = self.find_entry(name, root_inode)?.unwrap();
+                self.write_entry(index, &DirEntry::empty(), root_inode)
+            })?;
+        }
+        inode.sub_disk_nlink()?;
+        Ok(Some(inode))
     }
     pub fn delete_nlink(&self, path: &str) -> isize {
-        //只需要找到文件inode并且将其换成一个空白文件即可
-        let inode = self.find_inode(path).unwrap(); //找到文件inode
-                                                    //需要从目录下删除文件并减少文件的硬链接计数
-        inode.sub_disk_nlink();
-        if inode.get_disk_nlink() == 0 {
-            inode.clear();
+        //删除目录项，没有其它硬链接时立即回收文件
+        //最后一个硬链接指向的普通文件先在目录项仍然存在时逐步截断为空，
+        //再在同一个事务中删除目录项与回收inode，中途崩溃只会留下较短的文件
+        if let Some(inode) = self.find_inode(path) {
+            let last_link = match (inode.is_dir(), inode.get_disk_nlink()) {
+                (Ok(is_dir), Ok(nlink)) => !is_dir && nlink == 1,
+                _ => return -1,
+            };
+            if last_link && !inode.shrink(0) {
+                return -1;
+            }
+        }
+        self.try_transaction(|| {
+            Ok(self.unlink_inner(path)?.map(|inode| {
+                //回收失败时放弃整个事务，目录项仍然保留
+                inode.release();
+                0
+            }))
+        })
+        .unwrap_or(-1)
+    }
+    /// 硬链接计数为0时回收文件的数据块与索引节点
+    /// 数据块较多时分成多个事务回收，中途崩溃时留下的是没有目录项的inode，由fsck回收
+    pub fn release(&self) -> bool {
+        if self.get_disk_nlink() != Ok(0) || !self.shrink(0) {
+            return false;
+        }
+        self.transaction(|| {
+            let inode_id = self.get_disk_inode() as u32;
+            self.fs.lock().dealloc_inode(inode_id)
+        }) == Ok(Some(()))
+    }
+    /// 将文件重命名为同一个文件系统中new_dir目录下的new_name，new_dir可以是自身
+    /// 目标存在时被替换，与POSIX相同，目录只能替换空目录，其它文件只能替换不是目录的文件
+    /// 目录不能被移动到它自己或者它的子目录中，两个目录的修改在同一个事务中完成
+    /// 返回被替换的文件，其硬链接计数已经减少，为0时由调用者回收
+    pub fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Inode,
+        new_name: &str,
+    ) -> Result<Option<Arc<Inode>>, RenameError> {
+        if new_name.is_empty() || new_name.len() > NAME_LENGTH_MAX {
+            return Err(RenameError::InvalidName);
+        }
+        if !self.is_dir()? || !new_dir.is_dir()? {
+            return Err(RenameError::NotDir);
+        }
+        self.transaction(|| self.rename_inner(old_name, new_dir, new_name))?
+            .unwrap_or(Err(RenameError::NoSpace))
+    }
+    fn rename_inner(
+        &self,
+        old_name: &str,
+        new_dir: &Inode,
+        new_name: &str,
+    ) -> BlockResult<Result<Option<Arc<Inode>>, RenameError>> {
+        let inode = match self.lookup(old_name)? {
+            Some(inode) => inode,
+            None => return Ok(Err(RenameError::NotFound)),
+        };
+        let ino = inode.get_disk_inode();
+        let replaced = new_dir.lookup(new_name)?;
+        if let Some(target) = replaced.as_ref() {
+            //指向同一个文件时不做任何事
+            if target.get_disk_inode() == ino {
+                return Ok(Ok(None));
+            }
+            let target_dir = target.is_dir()?;
+            if target_dir != inode.is_dir()? {
+                return Ok(Err(RenameError::TypeMismatch));
+            }
+            if target_dir && !target.ls()?.is_empty() {
+                return Ok(Err(RenameError::NotEmpty));
+            }
         }
-        0
+        let new_dir_ino = new_dir.get_disk_inode();
+        if new_dir_ino == self.get_disk_inode() {
+            let _fs = self.fs.lock();
+            self.modify_disk_inode(|root_inode| {
+                let (old_index, old_entry) = self.find_entry(old_name, root_inode)?.unwrap();
+                let new_entry = DirEntry::new(new_name, old_entry.node_number());
+                match self.find_entry(new_name, root_inode)? {
+                    Some((new_index, _)) => {
+                        self.write_entry(new_index, &new_entry, root_inode)?;
+                        self.write_entry(old_index, &DirEntry::empty(), root_inode)
+                    }
+                    //直接修改原目录项的名称
+                    None => self.write_entry(old_index, &new_entry, root_inode),
+                }
+            })?;
+        } else {
+            if inode.is_dir()? && inode.contains_dir(new_dir_ino)? {
+                return Ok(Err(RenameError::Loop));
+            }
+            //先在新目录中加入目录项，目录无法扩大时不做任何修改
+            let mut fs = self.fs.lock();
+            let new_entry = DirEntry::new(new_name, ino as u32);
+            let added = new_dir.modify_disk_inode(|dir_inode| {
+                match new_dir.find_entry(new_name, dir_inode)? {
+                    Some((new_index, _)) => {
+                        new_dir.write_entry(new_index, &new_entry, dir_inode)?;
+                        Ok(true)
+                    }
+                    None => new_dir.add_entry(&new_entry, dir_inode, &mut fs),
+                }
+            })?;
+            if !added {
+                return Ok(Err(RenameError::NoSpace));
+            }
+            self.modify_disk_inode(|root_inode| {
+                let (old_index, _) = self.find_entry(old_name, root_inode)?.unwrap();
+                self.write_entry(old_index, &DirEntry::empty(), root_inode)
+            })?;
+        }
+        if let Some(target) = replaced.as_ref() {
+            target.sub_disk_nlink()?;
+        }
+        Ok(Ok(replaced))
+    }
+    /// 编号为ino的目录是否是这个目录本身或者位于它之下
+    fn contains_dir(&self, ino: usize) -> BlockResult<bool> {
+        if self.get_disk_inode() == ino {
+            return Ok(true);
+        }
+        for name in self.ls()? {
+            if let Some(child) = self.lookup(&name)? {
+                if child.is_dir()? && child.contains_dir(ino)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
     }
 
     pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
-        //创建一个文件/目录，这里只实现创建一个文件
+        self.create_as(name, 0)
+    }
+    /// 新建属于uid的文件
+    pub fn create_as(&self, name: &str, uid: u32) -> Option<Arc<Inode>> {
+        self.try_transaction(|| self.create_inner(name, DiskNodeType::FILE, uid))
+    }
+    /// 创建一个空目录
+    pub fn mkdir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.mkdir_as(name, 0)
+    }
+    /// 创建一个属于uid的空目录
+    pub fn mkdir_as(&self, name: &str, uid: u32) -> Option<Arc<Inode>> {
+        self.try_transaction(|| self.create_inner(name, DiskNodeType::DIRECTORY, uid))
+    }
+    /// 新建指向target的符号链接，目标为空或者超过SYMLINK_MAX时返回None
+    pub fn symlink(&self, name: &str, target: &str) -> Option<Arc<Inode>> {
+        self.symlink_as(name, target, 0)
+    }
+    /// 新建属于uid的符号链接，存放目标的块记入uid的限额
+    pub fn symlink_as(&self, name: &str, target: &str, uid: u32) -> Option<Arc<Inode>> {
+        if target.is_empty() || target.len() > SYMLINK_MAX {
+            return None;
+        }
+        self.try_transaction(|| {
+            let inode = match self.create_inner(name, DiskNodeType::Symlink, uid)? {
+                Some(inode) => inode,
+                None => return Ok(None),
+            };
+            let target = target.as_bytes();
+            if target.len() <= SYMLINK_INLINE_MAX {
+                inode.modify_disk_inode(|disk_node| {
+                    disk_node.set_inline(target);
+                    Ok(())
+                })?;
+            } else {
+                let mut fs = self.fs.lock();
+                let written = inode.modify_disk_inode(|disk_node| {
+                    if !inode.increase_size(target.len() as u64, disk_node, &mut fs)? {
+                        return Ok(false);
+                    }
+                    disk_node.write_at(0, target, &self.block_device)?;
+                    Ok(true)
+                })?;
+                if !written {
+                    //没有空间存放目标时删除新建的符号链接
+                    drop(fs);
+                    if let Some(inode) = self.unlink_inner(name)? {
+                        inode.release();
+                    }
+                    return Ok(None);
+                }
+            }
+            Ok(Some(inode))
+        })
+    }
+    /// 符号链接的目标，不是符号链接或者无法读出时返回None
+    pub fn readlink(&self) -> Option<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_node| {
+            if !disk_node.is_symlink() {
+                return Ok(None);
+            }
+            let mut target = alloc::vec![0u8; disk_node.size as usize];
+            disk_node.read_at(0, &mut target, &self.block_device)?;
+            Ok(String::from_utf8(target).ok())
+        })
+        .ok()
+        .flatten()
+    }
+    fn create_inner(
+        &self,
+        name: &str,
+        node_type: DiskNodeType,
+        uid: u32,
+    ) -> BlockResult<Option<Arc<Inode>>> {
+        //创建一个文件/目录
         let mut fs = self.fs.lock();
         if self
             .modify_disk_inode(|root_node: &mut DiskNode| {
                 //查找是否已经存在此节点
                 assert!(root_node.is_dir(), "The root node is not directory");
                 self.find_inode_id(name, root_node) //在根目录下查找
-            })
+            })?
             .is_some()
         {
-            return None; //存在文件
+            return Ok(None); //存在文件
         }
         //新建一个文件
-        let inode_id = fs.alloc_inode();
+        let inode_id = match fs.alloc_inode()? {
+            Some(inode_id) => inode_id,
+            None => return Ok(None),
+        };
         let (inode_block_id, inode_block_offset) = fs.get_disk_inode_pos(inode_id);
+        let extents = fs.extents() && node_type == DiskNodeType::FILE;
         // println!("create {}-{}-{}",inode_id,inode_block_id,inode_block_offset);
-        get_block_cache(inode_block_id as usize, self.block_device.clone())
+        get_block_cache(inode_block_id as usize, self.block_device.clone())?
             .lock()
             .modify(inode_block_offset, |new_disk_inode: &mut DiskNode| {
-                new_disk_inode.initialize(DiskNodeType::FILE);
+                new_disk_inode.initialize(node_type);
+                new_disk_inode.uid = uid;
+                if extents {
+                    new_disk_inode.use_extents();
+                }
             });
-        self.modify_disk_inode(|root_inode| {
+        let added = self.modify_disk_inode(|root_inode| {
             //在根目录下添加
-            let file_num = root_inode.size as usize / DIRENTRY_SIZE;
-            let new_size = (file_num + 1) * DIRENTRY_SIZE; //新的目录大小
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            let new_entry = DirEntry::new(name, inode_id);
-
-            let _number = root_inode.write_at(
-                file_num * DIRENTRY_SIZE as usize,
-                new_entry.as_bytes(),
-                &self.block_device,
-            ); //写入目录项
-               // println!("The filenames: {},the inode_id: {},offset: {}",name,inode_id,file_num*DIRENTRY_SIZE);
-        });
-        // let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-        block_cache_sync(); //同步数据到磁盘
-        Some(Arc::new(Inode::new(
+            self.add_entry(&DirEntry::new(name, inode_id), root_inode, &mut fs)
+        })?;
+        if !added {
+            fs.dealloc_inode(inode_id)?;
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(Inode::new(
             inode_block_id,
             inode_block_offset,
             self.fs.clone(),
             self.block_device.clone(),
-        )))
+        ))))
     }
+    /// 扩大文件，空闲块不够或者超过所有者的限额时不做修改并返回false
     pub fn increase_size(
         &self,
-        new_size: u32,
+        new_size: u64,
         disk_node: &mut DiskNode,
         fs: &mut MutexGuard<FileSystem>,
-    ) {
+    ) -> BlockResult<bool> {
         //增加文件大小
         assert!(new_size > disk_node.size);
         //先计算需要增加的大小,需要添加的索引块和数据块
-        let block_need_add = disk_node.addition_blocks(new_size);
+        let block_need_add = disk_node.addition_blocks(new_size) as usize;
         // println!("[filesystem]vfs::increase_size::block_need_add:{}",block_need_add);
-        let mut alloc_block_ids: Vec<u32> = Vec::new();
-        for _ in 0..block_need_add {
-            alloc_block_ids.push(fs.alloc_data()); //分配一些数据块
+        //紧接着文件的最后一个块分配，使文件的数据块尽量连续
+        let goal = disk_node
+            .last_block(&self.block_device)?
+            .map(|block_id| block_id + 1);
+        let alloc_block_ids = match Self::alloc_blocks(fs, disk_node.uid, goal, block_need_add)? {
+            Some(blocks) => blocks,
+            None => return Ok(false),
+        };
+        if !disk_node.is_extent() {
+            disk_node.increase_size(new_size, &self.block_device, alloc_block_ids)?;
+        } else if !disk_node.append_extents(new_size, &alloc_block_ids) {
+            //extent记录放不下时改为使用块索引
+            let index_need = DiskNode::index_blocks(new_size);
+            let index = match Self::alloc_blocks(fs, disk_node.uid, None, index_need)? {
+                Some(index) => index,
+                None => {
+                    for block_id in alloc_block_ids {
+                        fs.dealloc_data(block_id)?;
+                    }
+                    fs.credit(disk_node.uid, block_need_add);
+                    return Ok(false);
+                }
+            };
+            let mut data = disk_node.block_ids(&self.block_device)?;
+            data.extend(alloc_block_ids);
+            disk_node.map_blocks(new_size, data, index, &self.block_device)?;
+        }
+        Ok(true)
+    }
+    /// 无法一次扩大到new_size时逐块扩大，直到无法再分配
+    fn grow_blocks(
+        &self,
+        new_size: u64,
+        disk_node: &mut DiskNode,
+        fs: &mut MutexGuard<FileSystem>,
+    ) -> BlockResult {
+        while disk_node.size < new_size {
+            //先用完最后一个块，再每次增加一个块
+            let mut size = disk_node.data_blocks() as u64 * BLOCK_SIZE as u64;
+            if size <= disk_node.size {
+                size += BLOCK_SIZE as u64;
+            }
+            if !self.increase_size(size.min(new_size), disk_node, fs)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// 为uid分配count个数据块并记入它的限额
+    fn alloc_blocks(
+        fs: &mut MutexGuard<FileSystem>,
+        uid: u32,
+        goal: Option<u32>,
+        count: usize,
+    ) -> BlockResult<Option<Vec<u32>>> {
+        if !fs.charge(uid, count) {
+            return Ok(None);
+        }
+        let blocks = fs.alloc_data_run(goal, count);
+        if !matches!(blocks, Ok(Some(_))) {
+            fs.credit(uid, count);
         }
-        disk_node.increase_size(new_size, &self.block_device, alloc_block_ids);
+        blocks
     }
 }
 
 impl Inode {
     pub fn clear(&self) {
         //清空文件内容
+        self.shrink(0);
+    }
+    /// 修改文件大小，扩大的部分读出为0，缩小时回收不再使用的块
+    /// 超过最大文件大小、空闲块不够或者超过限额时返回false，此时文件可能已经扩大了一部分
+    pub fn truncate(&self, new_size: usize) -> bool {
+        if new_size as u64 > MAX_FILE_SIZE {
+            return false;
+        }
+        let size = match self.get_file_size() {
+            Ok(size) => size,
+            Err(_) => return false,
+        };
+        if new_size > size {
+            //与write_at一样分成多个事务扩大
+            let step = WRITE_CHUNK_BLOCKS * BLOCK_SIZE;
+            (size..new_size).step_by(step).all(|start| {
+                let end = (start + step).min(new_size) as u64;
+                self.transaction(|| self.resize_inner(end)) == Ok(Some(true))
+            })
+        } else {
+            self.shrink(new_size as u64)
+        }
+    }
+    /// 从文件末尾开始分成多个事务缩小到new_size，每个事务回收的数据块不超过WRITE_CHUNK_BLOCKS
+    /// 每个事务提交之后文件都是完整的，只是比原来短
+    fn shrink(&self, new_size: u64) -> bool {
+        let step = (WRITE_CHUNK_BLOCKS * BLOCK_SIZE) as u64;
+        let mut size = match self.get_file_size() {
+            Ok(size) => size as u64,
+            Err(_) => return false,
+        };
+        loop {
+            let end = size.saturating_sub(step).max(new_size);
+            if self.transaction(|| self.resize_inner(end)) != Ok(Some(true)) {
+                return false;
+            }
+            if end == new_size {
+                return true;
+            }
+            size = end;
+        }
+    }
+    fn resize_inner(&self, new_size: u64) -> BlockResult<bool> {
         let mut fs = self.fs.lock();
         self.modify_disk_inode(|disk_node| {
-            let size = disk_node.size;
-            let block_realloc_ids = disk_node.clear_size(&self.block_device);
-            //判断回收回来的数据块是否正确
-            assert_eq!(
-                block_realloc_ids.len(),
-                DiskNode::total_blocks(size) as usize
-            );
-            block_realloc_ids
-                .into_iter()
-                .for_each(|indx| fs.dealloc_data(indx)); //回收数据块，将会清0
+            if new_size > disk_node.size {
+                if !self.increase_size(new_size, disk_node, &mut fs)? {
+                    return Ok(false);
+                }
+            } else if new_size < disk_node.size {
+                let freed = disk_node.decrease_size(new_size, &self.block_device)?;
+                fs.credit(disk_node.uid, freed.len());
+                for block_id in freed {
+                    fs.dealloc_data(block_id)?;
+                }
+            }
+            let time = now();
+            disk_node.mtime = time;
+            disk_node.ctime = time;
+            Ok(true)
         })
     }
-    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+    ///写回文件所在块设备上的脏块
+    pub fn fsync(&self) -> BlockResult {
+        block_cache_sync_device(&self.block_device)
+    }
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> BlockResult<usize> {
         //对文件进行读写
         let _fs = self.fs.lock();
-        self.read_disk_inode(|disk_node| disk_node.read_at(offset, buf, &self.block_device))
+        let (size, atime) = self.read_disk_inode(|disk_node| {
+            Ok((
+                disk_node.read_at(offset, buf, &self.block_device)?,
+                disk_node.atime,
+            ))
+        })?;
+        //访问时间变化时才写回，避免每次读取都弄脏块缓存
+        //数据已经读出，更新访问时间失败时忽略，不影响读取的结果
+        let time = now();
+        if time != atime {
+            let _ = self.modify_disk_inode(|disk_node| {
+                disk_node.atime = time;
+                Ok(())
+            });
+        }
+        Ok(size)
     }
-    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+    /// 没有空间时返回已经写入的字节数，没有写入任何字节时才报告读写错误
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> BlockResult<usize> {
         // println!("[filesystem]vfs::write_at");
+        //超过最大文件大小的部分不写入
+        let max_len = MAX_FILE_SIZE.saturating_sub(offset as u64);
+        let buf = &buf[..buf.len().min(max_len as usize)];
+        //分成多个事务写入，使得每个事务修改的元数据块不超过日志的容量
+        //没有空间时停止，返回已经写入的字节数
+        let mut write_size = 0;
+        for chunk in buf.chunks(WRITE_CHUNK_BLOCKS * BLOCK_SIZE) {
+            let size = match self.transaction(|| self.write_chunk(offset + write_size, chunk)) {
+                Ok(size) => size.unwrap_or(0),
+                Err(err) if write_size == 0 => return Err(err),
+                Err(_) => break,
+            };
+            write_size += size;
+            if size < chunk.len() {
+                break;
+            }
+        }
+        Ok(write_size)
+    }
+    fn write_chunk(&self, offset: usize, buf: &[u8]) -> BlockResult<usize> {
         let mut fs = self.fs.lock();
         self.modify_disk_inode(|disk_node| {
-            //先扩容
-            self.increase_size((offset + buf.len()) as u32, disk_node, &mut fs);
+            //先扩容，空间不够时只写入放得下的部分
+            let end = (offset + buf.len()) as u64;
+            if end > disk_node.size && !self.increase_size(end, disk_node, &mut fs)? {
+                self.grow_blocks(end, disk_node, &mut fs)?;
+            }
+            let end = end.min(disk_node.size);
+            if end <= offset as u64 {
+                return Ok(0);
+            }
+            let buf = &buf[..end as usize - offset];
+            let time = now();
+            disk_node.mtime = time;
+            disk_node.ctime = time;
             disk_node.write_at(offset, buf, &self.block_device)
         })
     }
@@ -0,0 +1,338 @@
+//! 目录项
+//! 每个文件有一个8.3格式的短目录项，名称放不下时在它前面用若干个长文件名目录项保存UTF-16编码的名称
+use crate::layout::{le16, le32};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const DIRENT_SIZE: usize = 32;
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+pub const ATTR_LONG_NAME: u8 = 0x0f;
+pub const DELETED: u8 = 0xe5; //被删除的目录项
+const CASE_LOWER_BASE: u8 = 0x08; //短名称的主文件名以小写显示
+const CASE_LOWER_EXT: u8 = 0x10; //短名称的扩展名以小写显示
+const LAST_LONG_ENTRY: u8 = 0x40;
+const LONG_NAME_CHARS: usize = 13; //每个长文件名目录项保存的UTF-16字符数
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+pub const NAME_LENGTH_MAX: usize = 255;
+
+/// 短目录项
+#[derive(Debug, Clone, Copy)]
+pub struct ShortEntry {
+    pub name: [u8; 11], //主文件名与扩展名，用空格填充
+    pub attr: u8,
+    pub case: u8,
+    pub ctime: u16,
+    pub cdate: u16,
+    pub adate: u16,
+    pub mtime: u16,
+    pub mdate: u16,
+    pub cluster: u32,
+    pub size: u32,
+}
+
+impl ShortEntry {
+    pub fn new(name: [u8; 11], case: u8, attr: u8, cluster: u32, now: u32) -> Self {
+        let (date, time) = fat_datetime(now);
+        Self {
+            name,
+            attr,
+            case,
+            ctime: time,
+            cdate: date,
+            adate: date,
+            mtime: time,
+            mdate: date,
+            cluster,
+            size: 0,
+        }
+    }
+    pub fn parse(raw: &[u8; DIRENT_SIZE]) -> Self {
+        let mut name = [0u8; 11];
+        name.copy_from_slice(&raw[0..11]);
+        Self {
+            name,
+            attr: raw[11],
+            case: raw[12],
+            ctime: le16(raw, 14),
+            cdate: le16(raw, 16),
+            adate: le16(raw, 18),
+            mtime: le16(raw, 22),
+            mdate: le16(raw, 24),
+            cluster: (le16(raw, 20) as u32) << 16 | le16(raw, 26) as u32,
+            size: le32(raw, 28),
+        }
+    }
+    pub fn to_bytes(self) -> [u8; DIRENT_SIZE] {
+        let mut raw = [0u8; DIRENT_SIZE];
+        raw[0..11].copy_from_slice(&self.name);
+        raw[11] = self.attr;
+        raw[12] = self.case;
+        raw[14..16].copy_from_slice(&self.ctime.to_le_bytes());
+        raw[16..18].copy_from_slice(&self.cdate.to_le_bytes());
+        raw[18..20].copy_from_slice(&self.adate.to_le_bytes());
+        raw[20..22].copy_from_slice(&((self.cluster >> 16) as u16).to_le_bytes());
+        raw[22..24].copy_from_slice(&self.mtime.to_le_bytes());
+        raw[24..26].copy_from_slice(&self.mdate.to_le_bytes());
+        raw[26..28].copy_from_slice(&(self.cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&self.size.to_le_bytes());
+        raw
+    }
+    pub fn is_dir(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+    /// 按照大小写标记显示的短名称
+    pub fn display_name(&self) -> String {
+        let mut name = String::new();
+        let lower = |byte: u8, flag: u8| {
+            //0x05表示首字节实际上是0xe5
+            let byte = if byte == 0x05 { DELETED } else { byte };
+            if self.case & flag != 0 {
+                byte.to_ascii_lowercase()
+            } else {
+                byte
+            }
+        };
+        for &byte in self.name[..8].iter().take_while(|byte| **byte != b' ') {
+            name.push(lower(byte, CASE_LOWER_BASE) as char);
+        }
+        if self.name[8] != b' ' {
+            name.push('.');
+            for &byte in self.name[8..].iter().take_while(|byte| **byte != b' ') {
+                name.push(lower(byte, CASE_LOWER_EXT) as char);
+            }
+        }
+        name
+    }
+    pub fn mtime(&self) -> u32 {
+        unix_time(self.mdate, self.mtime)
+    }
+    pub fn atime(&self) -> u32 {
+        unix_time(self.adate, 0)
+    }
+    pub fn ctime(&self) -> u32 {
+        unix_time(self.cdate, self.ctime)
+    }
+    pub fn touch(&mut self, now: u32) {
+        let (date, time) = fat_datetime(now);
+        self.mdate = date;
+        self.mtime = time;
+        self.adate = date;
+    }
+}
+
+/// 长文件名目录项中的校验和，由短名称计算得到
+pub fn checksum(name: &[u8; 11]) -> u8 {
+    name.iter()
+        .fold(0u8, |sum, byte| sum.rotate_right(1).wrapping_add(*byte))
+}
+
+/// 长文件名目录项的序号，最后一项带有0x40标记
+pub fn long_order(raw: &[u8; DIRENT_SIZE]) -> (usize, bool) {
+    ((raw[0] & 0x1f) as usize, raw[0] & LAST_LONG_ENTRY != 0)
+}
+
+/// 长文件名目录项中的13个UTF-16字符
+pub fn long_chars(raw: &[u8; DIRENT_SIZE]) -> [u16; LONG_NAME_CHARS] {
+    let mut chars = [0u16; LONG_NAME_CHARS];
+    for (ch, offset) in chars.iter_mut().zip(LONG_NAME_OFFSETS) {
+        *ch = le16(raw, offset);
+    }
+    chars
+}
+
+/// 拼接长文件名目录项中的字符，遇到0结束
+pub fn decode_long_name(units: &[u16]) -> Option<String> {
+    let end = units
+        .iter()
+        .position(|unit| *unit == 0)
+        .unwrap_or(units.len());
+    char::decode_utf16(units[..end].iter().copied())
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+/// 生成长文件名目录项，按照在磁盘上的顺序排列，即最后一部分在最前面
+pub fn long_entries(name: &str, checksum: u8) -> Vec<[u8; DIRENT_SIZE]> {
+    let units: Vec<u16> = name.encode_utf16().collect();
+    let count = units.len().div_ceil(LONG_NAME_CHARS);
+    let mut entries = Vec::new();
+    for order in (1..=count).rev() {
+        let mut raw = [0u8; DIRENT_SIZE];
+        raw[0] = order as u8 | if order == count { LAST_LONG_ENTRY } else { 0 };
+        raw[11] = ATTR_LONG_NAME;
+        raw[13] = checksum;
+        //名称之后是一个0，剩余的位置用0xffff填充
+        for (i, offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+            let index = (order - 1) * LONG_NAME_CHARS + i;
+            let unit = match index {
+                index if index < units.len() => units[index],
+                index if index == units.len() => 0,
+                _ => 0xffff,
+            };
+            raw[*offset..*offset + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        entries.push(raw);
+    }
+    entries
+}
+
+/// 名称是否可以作为长文件名
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.encode_utf16().count() <= NAME_LENGTH_MAX
+        && !name.ends_with(' ')
+        && !name.ends_with('.')
+        && !name
+            .chars()
+            .any(|ch| (ch as u32) < 0x20 || "\"*/:<>?\\|".contains(ch))
+}
+
+fn short_char(ch: char) -> Option<u8> {
+    match ch {
+        'A'..='Z' | '0'..='9' => Some(ch as u8),
+        '!' | '#' | '$' | '%' | '&' | '\'' | '(' | ')' | '-' | '@' | '^' | '_' | '`' | '{'
+        | '}' | '~' => Some(ch as u8),
+        _ => None,
+    }
+}
+
+/// 将全部大写或者全部小写的部分转换为短名称的字符，返回是否是小写
+fn short_part(part: &str, max: usize, out: &mut [u8]) -> Option<bool> {
+    if part.len() > max {
+        return None;
+    }
+    let lower = part.chars().any(|ch| ch.is_ascii_lowercase());
+    let upper = part.chars().any(|ch| ch.is_ascii_uppercase());
+    if lower && upper {
+        return None;
+    }
+    for (dst, ch) in out.iter_mut().zip(part.chars()) {
+        *dst = short_char(ch.to_ascii_uppercase())?;
+    }
+    Some(lower)
+}
+
+/// 名称本身符合8.3格式时不需要长文件名，返回短名称与大小写标记
+pub fn exact_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    if base.is_empty() || (name.contains('.') && ext.is_empty()) {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    let lower_base = short_part(base, 8, &mut short[..8])?;
+    let lower_ext = short_part(ext, 3, &mut short[8..])?;
+    let mut case = 0;
+    if lower_base {
+        case |= CASE_LOWER_BASE;
+    }
+    if lower_ext {
+        case |= CASE_LOWER_EXT;
+    }
+    Some((short, case))
+}
+
+/// 为长文件名生成带有~n后缀的短名称，taken判断短名称是否已经被使用
+pub fn generate_short_name(name: &str, taken: impl Fn(&[u8; 11]) -> bool) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 => (&name[..dot], &name[dot + 1..]),
+        _ => (name, ""),
+    };
+    let convert = |part: &str, max: usize| -> Vec<u8> {
+        part.chars()
+            .filter(|ch| *ch != ' ' && *ch != '.')
+            .map(|ch| short_char(ch.to_ascii_uppercase()).unwrap_or(b'_'))
+            .take(max)
+            .collect()
+    };
+    let base = convert(base, 8);
+    let ext = convert(ext, 3);
+    let mut short = [b' '; 11];
+    short[8..8 + ext.len()].copy_from_slice(&ext);
+    for n in 1..1_000_000usize {
+        let mut tail = Vec::new();
+        tail.push(b'~');
+        let mut digits = [0u8; 8];
+        let mut len = 0;
+        let mut rest = n;
+        while rest > 0 {
+            digits[len] = b'0' + (rest % 10) as u8;
+            rest /= 10;
+            len += 1;
+        }
+        tail.extend(digits[..len].iter().rev());
+        let keep = base.len().min(8 - tail.len());
+        short[..8].fill(b' ');
+        short[..keep].copy_from_slice(&base[..keep]);
+        short[keep..keep + tail.len()].copy_from_slice(&tail);
+        if !taken(&short) {
+            return Some(short);
+        }
+    }
+    None
+}
+
+/// 不区分大小写比较名称，FAT中的名称都不区分大小写
+pub fn name_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.chars()
+            .zip(b.chars())
+            .all(|(x, y)| x.to_uppercase().eq(y.to_uppercase()))
+}
+
+const FAT_EPOCH_YEAR: i64 = 1980;
+
+/// 公历日期到1970年1月1日的天数
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// 1970年1月1日之后的天数对应的公历日期
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// 将秒数转换为FAT的日期与时间，早于1980年的时间记为1980年1月1日
+pub fn fat_datetime(secs: u32) -> (u16, u16) {
+    let secs = (secs as i64).max(days_from_civil(FAT_EPOCH_YEAR, 1, 1) * 86400);
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let rest = secs % 86400;
+    let year = (year - FAT_EPOCH_YEAR).min(127);
+    let date = (year << 9 | month << 5 | day) as u16;
+    let time = ((rest / 3600) << 11 | (rest % 3600 / 60) << 5 | (rest % 60 / 2)) as u16;
+    (date, time)
+}
+
+/// FAT的日期与时间对应的秒数，时间的精度是2秒
+pub fn unix_time(date: u16, time: u16) -> u32 {
+    if date == 0 {
+        return 0;
+    }
+    let year = FAT_EPOCH_YEAR + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xf).clamp(1, 12) as i64;
+    let day = (date & 0x1f).max(1) as i64;
+    let secs =
+        (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3f) as i64 * 60 + (time & 0x1f) as i64 * 2;
+    (days_from_civil(year, month, day) * 86400 + secs) as u32
+}
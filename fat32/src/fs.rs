@@ -0,0 +1,270 @@
+//! FAT32文件系统，负责簇的分配与回收
+//! 所有扇区都通过easyfs的块缓存读写，与easyfs共用缓存的容量与写回
+use crate::dir::{ShortEntry, ATTR_DIRECTORY, DIRENT_SIZE};
+use crate::inode::FatInode;
+use crate::layout::{read_fsinfo, write_fsinfo, Bpb, FAT_ENTRY_MASK, FAT_EOC, FAT_EOC_MIN};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use easyfs::{get_block_cache, BlockDevice, BlockResult, BLOCK_SIZE};
+use spin::mutex::Mutex;
+
+pub struct FatFileSystem {
+    pub(crate) device: Arc<dyn BlockDevice>,
+    pub(crate) bpb: Bpb,
+    inner: Mutex<FatInner>,
+}
+
+struct FatInner {
+    free_count: Option<u32>, //空闲簇数，FSInfo中没有记录时在第一次使用时统计
+    next_free: u32,          //从这里开始查找空闲簇
+    //FAT没有inode，打开的文件按照短目录项的位置共享同一个FatInode
+    nodes: BTreeMap<u64, Weak<FatInode>>,
+    next_ino: usize,
+}
+
+const ROOT_KEY: u64 = 0; //0号扇区是引导扇区，不会是目录项的位置
+pub const ROOT_INO: usize = 1;
+
+impl FatFileSystem {
+    /// 打开块设备上的FAT32文件系统，不是FAT32或者读取失败时返回None
+    pub fn open(device: Arc<dyn BlockDevice>) -> Option<Arc<Self>> {
+        let bpb = get_block_cache(0, device.clone())
+            .ok()?
+            .lock()
+            .read(0, |sector: &[u8; BLOCK_SIZE]| Bpb::parse(sector))?;
+        let mut free_count = None;
+        let mut next_free = 2;
+        if bpb.fsinfo_sector != 0 {
+            let fsinfo = get_block_cache(bpb.fsinfo_sector as usize, device.clone())
+                .ok()?
+                .lock()
+                .read(0, |sector: &[u8; BLOCK_SIZE]| read_fsinfo(sector));
+            //超出范围的值表示没有记录
+            if let Some((free, next)) = fsinfo {
+                free_count = Some(free).filter(|free| *free <= bpb.cluster_count());
+                next_free = Some(next)
+                    .filter(|next| (2..=bpb.max_cluster()).contains(next))
+                    .unwrap_or(2);
+            }
+        }
+        Some(Arc::new(Self {
+            device,
+            bpb,
+            inner: Mutex::new(FatInner {
+                free_count,
+                next_free,
+                nodes: BTreeMap::new(),
+                next_ino: ROOT_INO + 1,
+            }),
+        }))
+    }
+    pub fn root(self: &Arc<Self>) -> Arc<FatInode> {
+        let entry = ShortEntry::new([b' '; 11], 0, ATTR_DIRECTORY, self.bpb.root_cluster, 0);
+        self.node(ROOT_KEY, |_| {
+            FatInode::new(self.clone(), ROOT_INO, None, entry)
+        })
+    }
+    /// 位于pos的目录项对应的FatInode，已经被打开时返回同一个
+    pub(crate) fn load(self: &Arc<Self>, pos: u64, entry: ShortEntry) -> Arc<FatInode> {
+        self.node(pos, |ino| {
+            FatInode::new(self.clone(), ino, Some(pos), entry)
+        })
+    }
+    fn node(&self, key: u64, new: impl FnOnce(usize) -> FatInode) -> Arc<FatInode> {
+        let mut inner = self.inner.lock();
+        if let Some(node) = inner.nodes.get(&key).and_then(|node| node.upgrade()) {
+            return node;
+        }
+        inner.nodes.retain(|_, node| node.strong_count() > 0);
+        let ino = if key == ROOT_KEY {
+            ROOT_INO
+        } else {
+            inner.next_ino += 1;
+            inner.next_ino - 1
+        };
+        let node = Arc::new(new(ino));
+        inner.nodes.insert(key, Arc::downgrade(&node));
+        node
+    }
+    /// 目录项被删除后，同一位置的新目录项不再对应原来的文件
+    pub(crate) fn forget(&self, pos: u64) {
+        self.inner.lock().nodes.remove(&pos);
+    }
+    /// 目录项被移动到新的位置
+    pub(crate) fn rekey(&self, old: u64, new: u64) {
+        let mut inner = self.inner.lock();
+        if let Some(node) = inner.nodes.remove(&old) {
+            inner.nodes.insert(new, node);
+        }
+    }
+
+    pub fn device(&self) -> Arc<dyn BlockDevice> {
+        self.device.clone()
+    }
+    pub fn cluster_size(&self) -> usize {
+        self.bpb.cluster_size()
+    }
+    pub fn cluster_count(&self) -> u32 {
+        self.bpb.cluster_count()
+    }
+    /// 卷标，去掉末尾的空格
+    pub fn label(&self) -> &[u8] {
+        let len = self
+            .bpb
+            .label
+            .iter()
+            .rposition(|byte| *byte != b' ')
+            .map_or(0, |pos| pos + 1);
+        &self.bpb.label[..len]
+    }
+
+    /// 读出FAT中的表项
+    pub(crate) fn fat_get(&self, cluster: u32) -> BlockResult<u32> {
+        let (sector, offset) = self.bpb.fat_position(0, cluster);
+        let entry = get_block_cache(sector, self.device.clone())?
+            .lock()
+            .read(offset, |entry: &[u8; 4]| u32::from_le_bytes(*entry));
+        Ok(entry & FAT_ENTRY_MASK)
+    }
+    /// 修改所有FAT中的表项，保留高4位
+    pub(crate) fn fat_set(&self, cluster: u32, value: u32) -> BlockResult {
+        for fat in 0..self.bpb.num_fats {
+            let (sector, offset) = self.bpb.fat_position(fat, cluster);
+            get_block_cache(sector, self.device.clone())?.lock().modify(
+                offset,
+                |entry: &mut [u8; 4]| {
+                    let old = u32::from_le_bytes(*entry);
+                    *entry = (old & !FAT_ENTRY_MASK | value & FAT_ENTRY_MASK).to_le_bytes();
+                },
+            );
+        }
+        Ok(())
+    }
+    /// 表项指向的下一个簇，簇链结束或者表项损坏时返回None
+    pub(crate) fn next_cluster(&self, cluster: u32) -> BlockResult<Option<u32>> {
+        let next = self.fat_get(cluster)?;
+        if next >= 2 && next <= self.bpb.max_cluster() && next < FAT_EOC_MIN {
+            Ok(Some(next))
+        } else {
+            Ok(None)
+        }
+    }
+    /// 从start开始的簇链，最多包含所有的簇，避免损坏的FAT形成环
+    pub(crate) fn chain(&self, start: u32) -> BlockResult<Vec<u32>> {
+        let mut chain = Vec::new();
+        let mut cluster = Some(start).filter(|cluster| *cluster >= 2);
+        while let Some(current) = cluster {
+            if chain.len() > self.bpb.cluster_count() as usize {
+                break;
+            }
+            chain.push(current);
+            cluster = self.next_cluster(current)?;
+        }
+        Ok(chain)
+    }
+    /// 分配一个清零的簇并接在prev之后，prev为0时是新的簇链，没有空闲簇时返回None
+    pub(crate) fn alloc_cluster(&self, prev: u32) -> BlockResult<Option<u32>> {
+        let cluster = {
+            let mut inner = self.inner.lock();
+            if inner.free_count == Some(0) {
+                return Ok(None);
+            }
+            let max = self.bpb.max_cluster();
+            let start = inner.next_free;
+            let mut free = None;
+            for cluster in (start..=max).chain(2..start) {
+                if self.fat_get(cluster)? == 0 {
+                    free = Some(cluster);
+                    break;
+                }
+            }
+            let cluster = match free {
+                Some(cluster) => cluster,
+                None => {
+                    inner.free_count = Some(0);
+                    return Ok(None);
+                }
+            };
+            self.fat_set(cluster, FAT_EOC)?;
+            if prev != 0 {
+                self.fat_set(prev, cluster)?;
+            }
+            inner.free_count = inner.free_count.map(|free| free - 1);
+            inner.next_free = if cluster == max { 2 } else { cluster + 1 };
+            cluster
+        };
+        let first = self.bpb.cluster_sector(cluster);
+        for sector in first..first + self.bpb.sectors_per_cluster as usize {
+            get_block_cache(sector, self.device.clone())?
+                .lock()
+                .modify(0, |data: &mut [u8; BLOCK_SIZE]| data.fill(0));
+        }
+        Ok(Some(cluster))
+    }
+    /// 释放从start开始的整个簇链
+    pub(crate) fn free_chain(&self, start: u32) -> BlockResult {
+        let chain = self.chain(start)?;
+        let mut inner = self.inner.lock();
+        for cluster in chain.iter() {
+            self.fat_set(*cluster, 0)?;
+        }
+        inner.free_count = inner.free_count.map(|free| free + chain.len() as u32);
+        if let Some(first) = chain.iter().min() {
+            inner.next_free = inner.next_free.min(*first);
+        }
+        Ok(())
+    }
+    /// 空闲簇的数量，FSInfo中没有记录时扫描整个FAT
+    pub fn free_clusters(&self) -> BlockResult<u32> {
+        let mut inner = self.inner.lock();
+        if inner.free_count.is_none() {
+            let mut free = 0;
+            for cluster in 2..=self.bpb.max_cluster() {
+                if self.fat_get(cluster)? == 0 {
+                    free += 1;
+                }
+            }
+            inner.free_count = Some(free);
+        }
+        Ok(inner.free_count.unwrap())
+    }
+
+    /// 读出pos处的目录项
+    pub(crate) fn read_dirent(&self, pos: u64) -> BlockResult<[u8; DIRENT_SIZE]> {
+        let raw = get_block_cache(pos as usize / BLOCK_SIZE, self.device.clone())?
+            .lock()
+            .read(pos as usize % BLOCK_SIZE, |raw: &[u8; DIRENT_SIZE]| *raw);
+        Ok(raw)
+    }
+    pub(crate) fn write_dirent(&self, pos: u64, raw: &[u8; DIRENT_SIZE]) -> BlockResult {
+        get_block_cache(pos as usize / BLOCK_SIZE, self.device.clone())?
+            .lock()
+            .modify(pos as usize % BLOCK_SIZE, |dst: &mut [u8; DIRENT_SIZE]| {
+                *dst = *raw
+            });
+        Ok(())
+    }
+    /// 读出整个扇区
+    pub(crate) fn read_sector(&self, sector: usize) -> BlockResult<[u8; BLOCK_SIZE]> {
+        let data = get_block_cache(sector, self.device.clone())?
+            .lock()
+            .read(0, |data: &[u8; BLOCK_SIZE]| *data);
+        Ok(data)
+    }
+
+    /// 将空闲簇的统计写入FSInfo，再写回所有的脏块
+    pub fn sync(&self) -> BlockResult {
+        let inner = self.inner.lock();
+        if self.bpb.fsinfo_sector != 0 {
+            let free_count = inner.free_count.unwrap_or(crate::layout::FSINFO_UNKNOWN);
+            get_block_cache(self.bpb.fsinfo_sector as usize, self.device.clone())?
+                .lock()
+                .modify(0, |sector: &mut [u8; BLOCK_SIZE]| {
+                    write_fsinfo(sector, free_count, inner.next_free)
+                });
+        }
+        drop(inner);
+        easyfs::block_cache_sync_device(&self.device)
+    }
+}
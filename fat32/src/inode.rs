@@ -0,0 +1,681 @@
+//! FAT32中的文件与目录
+//! FAT没有inode，文件的元数据保存在父目录的短目录项中，修改后立即写回目录项
+use crate::dir::*;
+use crate::fs::{FatFileSystem, ROOT_INO};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easyfs::{BlockResult, Metadata, RenameError, BLOCK_SIZE};
+use spin::mutex::Mutex;
+
+pub struct FatInode {
+    fs: Arc<FatFileSystem>,
+    ino: usize,
+    inner: Mutex<FatInodeInner>,
+}
+
+struct FatInodeInner {
+    pos: Option<u64>,     //短目录项在磁盘上的位置，根目录没有目录项
+    entry: ShortEntry,    //短目录项在内存中的副本
+    removed: bool,        //目录项已经被删除，最后一次关闭时回收簇链
+    cursor: (usize, u32), //最近访问的(簇在文件中的序号，簇号)，顺序读写时不需要从头遍历簇链
+}
+
+/// 目录中的一个文件
+struct DirItem {
+    name: String,
+    entry: ShortEntry,
+    pos: u64,        //短目录项的位置
+    slots: Vec<u64>, //包括长文件名目录项在内的所有位置
+    next: usize,     //短目录项之后的目录项序号，删除其它文件时不变
+}
+
+impl DirItem {
+    fn matches(&self, name: &str) -> bool {
+        name_eq(&self.name, name) || name_eq(&self.entry.display_name(), name)
+    }
+}
+
+impl FatInode {
+    pub(crate) fn new(
+        fs: Arc<FatFileSystem>,
+        ino: usize,
+        pos: Option<u64>,
+        entry: ShortEntry,
+    ) -> Self {
+        Self {
+            fs,
+            ino,
+            inner: Mutex::new(FatInodeInner {
+                pos,
+                entry,
+                removed: false,
+                cursor: (0, 0),
+            }),
+        }
+    }
+    pub fn ino(&self) -> usize {
+        self.ino
+    }
+    pub fn is_dir(&self) -> bool {
+        self.inner.lock().entry.is_dir()
+    }
+    pub fn size(&self) -> BlockResult<usize> {
+        let inner = self.inner.lock();
+        if inner.entry.is_dir() {
+            Ok(self.fs.chain(inner.entry.cluster)?.len() * self.fs.cluster_size())
+        } else {
+            Ok(inner.entry.size as usize)
+        }
+    }
+    pub fn metadata(&self) -> BlockResult<Metadata> {
+        let inner = self.inner.lock();
+        let entry = &inner.entry;
+        let clusters = if entry.is_dir() {
+            self.fs.chain(entry.cluster)?.len()
+        } else {
+            (entry.size as usize).div_ceil(self.fs.cluster_size())
+        };
+        let write = if entry.attr & ATTR_READ_ONLY != 0 {
+            0
+        } else {
+            0o200
+        };
+        let mode = if entry.is_dir() {
+            0o040555 | write
+        } else {
+            0o100444 | write
+        };
+        //根目录没有目录项，也就没有时间
+        Ok(Metadata {
+            ino: self.ino,
+            mode,
+            nlink: if inner.removed { 0 } else { 1 },
+            uid: 0,
+            gid: 0,
+            size: if entry.is_dir() {
+                clusters * self.fs.cluster_size()
+            } else {
+                entry.size as usize
+            },
+            blocks: clusters * self.fs.cluster_size() / BLOCK_SIZE,
+            atime: entry.atime(),
+            mtime: entry.mtime(),
+            ctime: entry.ctime(),
+        })
+    }
+
+    /// 将内存中的短目录项写回磁盘
+    fn store(&self, inner: &FatInodeInner) -> BlockResult {
+        match inner.pos {
+            Some(pos) => self.fs.write_dirent(pos, &inner.entry.to_bytes()),
+            None => Ok(()),
+        }
+    }
+    /// 文件中第index个簇，alloc为true时在簇链不够长时分配新的簇
+    fn cluster_at(
+        &self,
+        inner: &mut FatInodeInner,
+        index: usize,
+        alloc: bool,
+    ) -> BlockResult<Option<u32>> {
+        if inner.entry.cluster == 0 {
+            if !alloc {
+                return Ok(None);
+            }
+            inner.entry.cluster = match self.fs.alloc_cluster(0)? {
+                Some(cluster) => cluster,
+                None => return Ok(None),
+            };
+            inner.cursor = (0, inner.entry.cluster);
+            self.store(inner)?;
+        }
+        let (mut i, mut cluster) = match inner.cursor {
+            (i, cluster) if cluster != 0 && i <= index => (i, cluster),
+            _ => (0, inner.entry.cluster),
+        };
+        while i < index {
+            cluster = match self.fs.next_cluster(cluster)? {
+                Some(next) => next,
+                None if alloc => match self.fs.alloc_cluster(cluster)? {
+                    Some(next) => next,
+                    None => return Ok(None),
+                },
+                None => return Ok(None),
+            };
+            i += 1;
+            inner.cursor = (i, cluster);
+        }
+        inner.cursor = (i, cluster);
+        Ok(Some(cluster))
+    }
+    /// 访问文件中[offset, offset+len)范围内的数据，f的参数是扇区号、扇区内的偏移与在范围内的偏移
+    /// 返回访问的字节数，簇链不够长并且无法分配时提前结束，读写失败时同时返回错误
+    fn for_each_sector(
+        &self,
+        inner: &mut FatInodeInner,
+        offset: usize,
+        len: usize,
+        alloc: bool,
+        mut f: impl FnMut(usize, usize, usize, usize) -> BlockResult,
+    ) -> (usize, BlockResult) {
+        let cluster_size = self.fs.cluster_size();
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let cluster = match self.cluster_at(inner, pos / cluster_size, alloc) {
+                Ok(Some(cluster)) => cluster,
+                Ok(None) => break,
+                Err(err) => return (done, Err(err)),
+            };
+            let in_cluster = pos % cluster_size;
+            let sector = self.fs.bpb.cluster_sector(cluster) + in_cluster / BLOCK_SIZE;
+            let start = in_cluster % BLOCK_SIZE;
+            let n = (BLOCK_SIZE - start).min(len - done);
+            if let Err(err) = f(sector, start, n, done) {
+                return (done, Err(err));
+            }
+            done += n;
+        }
+        (done, Ok(()))
+    }
+    /// 读出的字节数，没有读出任何字节时才报告读写错误
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> BlockResult<usize> {
+        let mut inner = self.inner.lock();
+        if inner.entry.is_dir() {
+            return Ok(0);
+        }
+        let len = buf
+            .len()
+            .min((inner.entry.size as usize).saturating_sub(offset));
+        let (read, result) =
+            self.for_each_sector(&mut inner, offset, len, false, |sector, start, n, done| {
+                let data = self.fs.read_sector(sector)?;
+                buf[done..done + n].copy_from_slice(&data[start..start + n]);
+                Ok(())
+            });
+        if read == 0 {
+            result?;
+        }
+        Ok(read)
+    }
+    /// 将[from, 簇的末尾)清零，文件变大时之前写过的数据不能再被读到，之后的簇在分配时已经清零
+    fn zero_tail(&self, inner: &mut FatInodeInner, from: usize) -> BlockResult {
+        let cluster_size = self.fs.cluster_size();
+        let partial = from % cluster_size;
+        if partial == 0 {
+            return Ok(());
+        }
+        self.for_each_sector(
+            inner,
+            from,
+            cluster_size - partial,
+            false,
+            |sector, start, n, _| {
+                easyfs::get_block_cache(sector, self.fs.device.clone())?
+                    .lock()
+                    .modify(0, |data: &mut [u8; BLOCK_SIZE]| {
+                        data[start..start + n].fill(0)
+                    });
+                Ok(())
+            },
+        )
+        .1
+    }
+    /// 写入的字节数，没有写入任何字节时才报告读写错误
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> BlockResult<usize> {
+        let mut inner = self.inner.lock();
+        if inner.entry.is_dir() || offset >= u32::MAX as usize {
+            return Ok(0);
+        }
+        //FAT32的文件最大为4GiB-1
+        let len = buf.len().min(u32::MAX as usize - offset);
+        let size = inner.entry.size as usize;
+        if offset > size {
+            self.zero_tail(&mut inner, size)?;
+        }
+        let (written, result) =
+            self.for_each_sector(&mut inner, offset, len, true, |sector, start, n, done| {
+                easyfs::get_block_cache(sector, self.fs.device.clone())?
+                    .lock()
+                    .modify(0, |data: &mut [u8; BLOCK_SIZE]| {
+                        data[start..start + n].copy_from_slice(&buf[done..done + n])
+                    });
+                Ok(())
+            });
+        let end = offset + written;
+        if written > 0 && end > size {
+            inner.entry.size = end as u32;
+        }
+        inner.entry.touch(easyfs::now());
+        inner.entry.attr |= ATTR_ARCHIVE;
+        let stored = self.store(&inner);
+        if written == 0 {
+            result?;
+        }
+        stored?;
+        Ok(written)
+    }
+    /// 只保留能容纳size字节的簇
+    fn shrink(&self, inner: &mut FatInodeInner, size: usize) -> BlockResult {
+        let cluster_size = self.fs.cluster_size();
+        let keep = size.div_ceil(cluster_size);
+        if keep == 0 {
+            if inner.entry.cluster != 0 {
+                self.fs.free_chain(inner.entry.cluster)?;
+                inner.entry.cluster = 0;
+            }
+        } else if let Some(last) = self.cluster_at(inner, keep - 1, false)? {
+            if let Some(next) = self.fs.next_cluster(last)? {
+                self.fs.free_chain(next)?;
+            }
+            self.fs.fat_set(last, crate::layout::FAT_EOC)?;
+        }
+        inner.cursor = (0, 0);
+        inner.entry.size = inner.entry.size.min(size as u32);
+        Ok(())
+    }
+    /// 修改文件大小，扩大的部分读出来是0
+    /// 空间不足或者读写失败时返回false，空间不足时保持原来的大小
+    pub fn truncate(&self, size: usize) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.entry.is_dir() || size > u32::MAX as usize {
+            return false;
+        }
+        self.resize(&mut inner, size) == Ok(true)
+    }
+    fn resize(&self, inner: &mut FatInodeInner, size: usize) -> BlockResult<bool> {
+        let old = inner.entry.size as usize;
+        if size > old {
+            self.zero_tail(inner, old)?;
+            let cluster_size = self.fs.cluster_size();
+            if self
+                .cluster_at(inner, (size - 1) / cluster_size, true)?
+                .is_none()
+            {
+                self.shrink(inner, old)?;
+                self.store(inner)?;
+                return Ok(false);
+            }
+            inner.entry.size = size as u32;
+        } else {
+            self.shrink(inner, size)?;
+        }
+        inner.entry.touch(easyfs::now());
+        self.store(inner)?;
+        Ok(true)
+    }
+    /// 文件的修改都在块缓存中，写回整个设备
+    pub fn fsync(&self) -> BlockResult {
+        easyfs::block_cache_sync_device(&self.fs.device)
+    }
+
+    /// 读出目录中所有的文件，跳过.、..与卷标
+    fn read_dir(&self, first: u32) -> BlockResult<Vec<DirItem>> {
+        let mut items = Vec::new();
+        //正在拼接的长文件名：下一个序号、校验和、UTF-16字符与位置
+        let mut long: Option<(usize, u8, Vec<u16>, Vec<u64>)> = None;
+        let mut next = 0;
+        self.for_each_slot(first, |pos, raw| {
+            next += 1;
+            if raw[0] == 0 {
+                return false;
+            }
+            if raw[0] == DELETED {
+                long = None;
+                return true;
+            }
+            if raw[11] & 0x3f == ATTR_LONG_NAME {
+                let (order, last) = long_order(raw);
+                let chars = long_chars(raw);
+                if last && order > 0 {
+                    let mut units = alloc::vec![0u16; order * 13];
+                    units[(order - 1) * 13..].copy_from_slice(&chars);
+                    long = Some((order - 1, raw[13], units, alloc::vec![pos]));
+                } else {
+                    long = match long.take() {
+                        Some((next, sum, mut units, mut slots))
+                            if next == order && order > 0 && sum == raw[13] =>
+                        {
+                            units[(order - 1) * 13..order * 13].copy_from_slice(&chars);
+                            slots.push(pos);
+                            Some((order - 1, sum, units, slots))
+                        }
+                        _ => None,
+                    };
+                }
+                return true;
+            }
+            let entry = ShortEntry::parse(raw);
+            let long = long.take();
+            if raw[11] & ATTR_VOLUME_ID != 0 || raw[0] == b'.' {
+                return true;
+            }
+            //长文件名完整并且校验和一致时才使用
+            let (name, mut slots) = match long {
+                Some((0, sum, units, slots)) if sum == checksum(&entry.name) => {
+                    match decode_long_name(&units) {
+                        Some(name) => (name, slots),
+                        None => (entry.display_name(), Vec::new()),
+                    }
+                }
+                _ => (entry.display_name(), Vec::new()),
+            };
+            slots.push(pos);
+            items.push(DirItem {
+                name,
+                entry,
+                pos,
+                slots,
+                next,
+            });
+            true
+        })?;
+        Ok(items)
+    }
+    /// 按顺序访问目录中所有的目录项，f返回false时停止
+    fn for_each_slot(
+        &self,
+        first: u32,
+        mut f: impl FnMut(u64, &[u8; DIRENT_SIZE]) -> bool,
+    ) -> BlockResult {
+        for cluster in self.fs.chain(first)? {
+            let start = self.fs.bpb.cluster_sector(cluster);
+            for sector in start..start + self.fs.bpb.sectors_per_cluster as usize {
+                let data = self.fs.read_sector(sector)?;
+                for (i, raw) in data.chunks(DIRENT_SIZE).enumerate() {
+                    let pos = (sector * BLOCK_SIZE + i * DIRENT_SIZE) as u64;
+                    if !f(pos, raw.try_into().unwrap()) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// 找到目录中连续的count个空闲目录项，不够时扩大目录
+    fn free_slots(&self, inner: &mut FatInodeInner, count: usize) -> BlockResult<Option<Vec<u64>>> {
+        let mut run = Vec::new();
+        self.for_each_slot(inner.entry.cluster, |pos, raw| {
+            if raw[0] == 0 || raw[0] == DELETED {
+                run.push(pos);
+            } else {
+                run.clear();
+            }
+            run.len() < count
+        })?;
+        while run.len() < count {
+            let last = match self.fs.chain(inner.entry.cluster)?.last() {
+                Some(last) => *last,
+                None => return Ok(None),
+            };
+            let cluster = match self.fs.alloc_cluster(last)? {
+                Some(cluster) => cluster,
+                None => return Ok(None),
+            };
+            let start = self.fs.bpb.cluster_sector(cluster) * BLOCK_SIZE;
+            let slots = self.fs.cluster_size() / DIRENT_SIZE;
+            run.extend((0..slots).map(|i| (start + i * DIRENT_SIZE) as u64));
+        }
+        run.truncate(count);
+        Ok(Some(run))
+    }
+    /// 写入长文件名目录项与短目录项，返回短目录项的位置
+    fn insert(
+        &self,
+        inner: &mut FatInodeInner,
+        long: Option<&str>,
+        entry: &ShortEntry,
+    ) -> BlockResult<Option<u64>> {
+        let mut raws = long.map_or(Vec::new(), |name| long_entries(name, checksum(&entry.name)));
+        raws.push(entry.to_bytes());
+        let slots = match self.free_slots(inner, raws.len())? {
+            Some(slots) => slots,
+            None => return Ok(None),
+        };
+        for (pos, raw) in slots.iter().zip(raws.iter()) {
+            self.fs.write_dirent(*pos, raw)?;
+        }
+        Ok(slots.last().copied())
+    }
+    /// 为name选择短名称，符合8.3格式时不需要长文件名
+    fn short_name<'a>(
+        name: &'a str,
+        items: &[DirItem],
+        skip: &[u64],
+    ) -> Option<([u8; 11], u8, Option<&'a str>)> {
+        if let Some((short, case)) = exact_short_name(name) {
+            return Some((short, case, None));
+        }
+        let short = generate_short_name(name, |short| {
+            items
+                .iter()
+                .any(|item| !skip.contains(&item.pos) && item.entry.name == *short)
+        })?;
+        Some((short, 0, Some(name)))
+    }
+    fn remove_slots(&self, item: &DirItem) -> BlockResult {
+        for pos in item.slots.iter() {
+            let mut raw = self.fs.read_dirent(*pos)?;
+            raw[0] = DELETED;
+            self.fs.write_dirent(*pos, &raw)?;
+        }
+        Ok(())
+    }
+    fn is_empty_dir(&self, entry: &ShortEntry) -> BlockResult<bool> {
+        Ok(self.read_dir(entry.cluster)?.is_empty())
+    }
+    /// 目录的内容被修改
+    fn touch_dir(&self, inner: &mut FatInodeInner) -> BlockResult {
+        inner.entry.touch(easyfs::now());
+        self.store(inner)
+    }
+
+    /// 在目录中查找文件，名称不区分大小写，读取失败时同样返回None
+    pub fn find_inode(&self, name: &str) -> Option<Arc<FatInode>> {
+        let inner = self.inner.lock();
+        if !inner.entry.is_dir() {
+            return None;
+        }
+        let item = self
+            .read_dir(inner.entry.cluster)
+            .ok()?
+            .into_iter()
+            .find(|item| item.matches(name))?;
+        Some(self.fs.load(item.pos, item.entry))
+    }
+    pub fn ls(&self) -> BlockResult<Vec<String>> {
+        let inner = self.inner.lock();
+        if !inner.entry.is_dir() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .read_dir(inner.entry.cluster)?
+            .into_iter()
+            .map(|item| item.name)
+            .collect())
+    }
+    /// 从目录项序号cursor开始按顺序访问目录中的文件，f的参数为(下一个序号, 名称, 文件)，返回false时停止
+    /// 删除文件只标记目录项，其它文件的序号不变，可以用作读取目录的游标
+    pub fn read_dir_from(
+        &self,
+        cursor: usize,
+        mut f: impl FnMut(usize, &str, Arc<FatInode>) -> bool,
+    ) -> BlockResult {
+        let items = {
+            let inner = self.inner.lock();
+            if !inner.entry.is_dir() {
+                return Ok(());
+            }
+            self.read_dir(inner.entry.cluster)?
+        };
+        for item in items.into_iter().filter(|item| item.next > cursor) {
+            if !f(item.next, &item.name, self.fs.load(item.pos, item.entry)) {
+                break;
+            }
+        }
+        Ok(())
+    }
+    pub fn create(&self, name: &str) -> Option<Arc<FatInode>> {
+        self.create_entry(name, false).ok().flatten()
+    }
+    pub fn mkdir(&self, name: &str) -> Option<Arc<FatInode>> {
+        self.create_entry(name, true).ok().flatten()
+    }
+    fn create_entry(&self, name: &str, dir: bool) -> BlockResult<Option<Arc<FatInode>>> {
+        let mut inner = self.inner.lock();
+        if !inner.entry.is_dir() || inner.removed || !valid_name(name) {
+            return Ok(None);
+        }
+        let items = self.read_dir(inner.entry.cluster)?;
+        if items.iter().any(|item| item.matches(name)) {
+            return Ok(None);
+        }
+        let (short, case, long) = match Self::short_name(name, &items, &[]) {
+            Some(short) => short,
+            None => return Ok(None),
+        };
+        let now = easyfs::now();
+        let (attr, cluster) = if dir {
+            match self.fs.alloc_cluster(0)? {
+                Some(cluster) => (ATTR_DIRECTORY, cluster),
+                None => return Ok(None),
+            }
+        } else {
+            (ATTR_ARCHIVE, 0)
+        };
+        let entry = ShortEntry::new(short, case, attr, cluster, now);
+        if dir {
+            //新目录的前两项是.与..，指向根目录时簇号记为0
+            let start = (self.fs.bpb.cluster_sector(cluster) * BLOCK_SIZE) as u64;
+            let parent = if self.ino == ROOT_INO {
+                0
+            } else {
+                inner.entry.cluster
+            };
+            let dot = ShortEntry::new(*b".          ", 0, ATTR_DIRECTORY, cluster, now);
+            let dotdot = ShortEntry::new(*b"..         ", 0, ATTR_DIRECTORY, parent, now);
+            self.fs.write_dirent(start, &dot.to_bytes())?;
+            self.fs
+                .write_dirent(start + DIRENT_SIZE as u64, &dotdot.to_bytes())?;
+        }
+        let pos = match self.insert(&mut inner, long, &entry)? {
+            Some(pos) => pos,
+            None => {
+                if dir {
+                    self.fs.free_chain(cluster)?;
+                }
+                return Ok(None);
+            }
+        };
+        self.touch_dir(&mut inner)?;
+        Ok(Some(self.fs.load(pos, entry)))
+    }
+    /// 删除目录项，返回被删除的文件，只能删除空目录
+    /// 文件的簇链在release时回收
+    pub fn unlink(&self, name: &str) -> Option<Arc<FatInode>> {
+        self.unlink_entry(name).ok().flatten()
+    }
+    fn unlink_entry(&self, name: &str) -> BlockResult<Option<Arc<FatInode>>> {
+        let mut inner = self.inner.lock();
+        if !inner.entry.is_dir() {
+            return Ok(None);
+        }
+        let item = match self
+            .read_dir(inner.entry.cluster)?
+            .into_iter()
+            .find(|item| item.matches(name))
+        {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        if item.entry.is_dir() && !self.is_empty_dir(&item.entry)? {
+            return Ok(None);
+        }
+        let node = self.fs.load(item.pos, item.entry);
+        self.remove_slots(&item)?;
+        self.fs.forget(item.pos);
+        node.mark_removed();
+        self.touch_dir(&mut inner)?;
+        Ok(Some(node))
+    }
+    fn mark_removed(&self) {
+        let mut inner = self.inner.lock();
+        inner.pos = None;
+        inner.removed = true;
+    }
+    /// 在同一个目录中重命名，new_name存在时被替换，返回被替换的文件
+    /// 新的目录项写入成功之后才删除原来的目录项
+    pub fn rename(
+        &self,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<Option<Arc<FatInode>>, RenameError> {
+        let mut inner = self.inner.lock();
+        if !inner.entry.is_dir() {
+            return Err(RenameError::NotDir);
+        }
+        if !valid_name(new_name) {
+            return Err(RenameError::InvalidName);
+        }
+        let items = self.read_dir(inner.entry.cluster)?;
+        let source = match items.iter().find(|item| item.matches(old_name)) {
+            Some(source) => source,
+            None => return Err(RenameError::NotFound),
+        };
+        //只是修改大小写时目标就是它自己
+        let target = items
+            .iter()
+            .find(|item| item.matches(new_name) && item.pos != source.pos);
+        if let Some(target) = target {
+            if target.entry.is_dir() && !self.is_empty_dir(&target.entry)? {
+                return Err(RenameError::NotEmpty);
+            }
+        }
+        let mut skip = alloc::vec![source.pos];
+        skip.extend(target.map(|target| target.pos));
+        let (short, case, long) = match Self::short_name(new_name, &items, &skip) {
+            Some(short) => short,
+            None => return Err(RenameError::NoSpace),
+        };
+        let node = self.fs.load(source.pos, source.entry);
+        let mut node_inner = node.inner.lock();
+        let mut entry = node_inner.entry;
+        entry.name = short;
+        entry.case = case;
+        let pos = match self.insert(&mut inner, long, &entry)? {
+            Some(pos) => pos,
+            None => return Err(RenameError::NoSpace),
+        };
+        self.remove_slots(source)?;
+        self.fs.rekey(source.pos, pos);
+        node_inner.pos = Some(pos);
+        node_inner.entry = entry;
+        drop(node_inner);
+        let replaced = match target {
+            Some(target) => {
+                let replaced = self.fs.load(target.pos, target.entry);
+                self.remove_slots(target)?;
+                self.fs.forget(target.pos);
+                replaced.mark_removed();
+                Some(replaced)
+            }
+            None => None,
+        };
+        self.touch_dir(&mut inner)?;
+        Ok(replaced)
+    }
+    /// 目录项被删除并且不再被打开时回收簇链
+    pub fn release(&self) -> bool {
+        let mut inner = self.inner.lock();
+        if !inner.removed || inner.entry.cluster == 0 {
+            return false;
+        }
+        if self.fs.free_chain(inner.entry.cluster).is_err() {
+            return false;
+        }
+        inner.entry.cluster = 0;
+        inner.cursor = (0, 0);
+        true
+    }
+}
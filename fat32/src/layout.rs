@@ -0,0 +1,232 @@
+//! 引导扇区与FSInfo扇区的磁盘布局，以及格式化
+use alloc::sync::Arc;
+use easyfs::{BlockDevice, BLOCK_SIZE};
+
+pub const FAT_ENTRY_MASK: u32 = 0x0fff_ffff; //FAT32的表项只使用低28位
+pub const FAT_EOC: u32 = 0x0fff_ffff; //簇链结束
+pub const FAT_EOC_MIN: u32 = 0x0fff_fff8; //不小于该值的表项都表示簇链结束
+pub const FSINFO_UNKNOWN: u32 = 0xffff_ffff; //FSInfo中没有记录空闲簇数
+
+const BOOT_SIGNATURE: u16 = 0xaa55;
+const FSINFO_LEAD_SIG: u32 = 0x4161_5252;
+const FSINFO_STRUC_SIG: u32 = 0x6141_7272;
+const FSINFO_TRAIL_SIG: u32 = 0xaa55_0000;
+const RESERVED_SECTORS: u32 = 32;
+const BACKUP_BOOT_SECTOR: u32 = 6;
+const NUM_FATS: u32 = 2;
+
+pub fn le16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+pub fn le32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+fn put16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+fn put32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// 引导扇区中的BIOS参数块，只保留文件系统用到的字段
+#[derive(Debug, Clone)]
+pub struct Bpb {
+    pub sectors_per_cluster: u32,
+    pub reserved_sectors: u32,
+    pub num_fats: u32,
+    pub fat_sectors: u32, //每个FAT占用的扇区数
+    pub total_sectors: u32,
+    pub root_cluster: u32,
+    pub fsinfo_sector: u32, //为0表示没有FSInfo扇区
+    pub volume_id: u32,
+    pub label: [u8; 11],
+}
+
+impl Bpb {
+    /// 解析引导扇区，不是FAT32或者扇区大小不是512B时返回None
+    pub fn parse(sector: &[u8; BLOCK_SIZE]) -> Option<Self> {
+        if le16(sector, 510) != BOOT_SIGNATURE || le16(sector, 11) as usize != BLOCK_SIZE {
+            return None;
+        }
+        let sectors_per_cluster = sector[13] as u32;
+        //FAT32没有固定的根目录区，FAT的大小记录在32位的字段中
+        if !sectors_per_cluster.is_power_of_two() || le16(sector, 17) != 0 || le16(sector, 22) != 0
+        {
+            return None;
+        }
+        let total_sectors = match le16(sector, 19) {
+            0 => le32(sector, 32),
+            total => total as u32,
+        };
+        let mut label = [0u8; 11];
+        label.copy_from_slice(&sector[71..82]);
+        let bpb = Self {
+            sectors_per_cluster,
+            reserved_sectors: le16(sector, 14) as u32,
+            num_fats: sector[16] as u32,
+            fat_sectors: le32(sector, 36),
+            total_sectors,
+            root_cluster: le32(sector, 44),
+            fsinfo_sector: le16(sector, 48) as u32,
+            volume_id: le32(sector, 67),
+            label,
+        };
+        let valid = bpb.reserved_sectors > 0
+            && bpb.num_fats > 0
+            && bpb.fat_sectors > 0
+            && bpb.data_start() < bpb.total_sectors
+            && bpb.root_cluster >= 2
+            && bpb.root_cluster <= bpb.max_cluster()
+            //FAT要能够容纳所有的簇
+            && bpb.fat_sectors as u64 * (BLOCK_SIZE / 4) as u64 > bpb.max_cluster() as u64;
+        if valid {
+            Some(bpb)
+        } else {
+            None
+        }
+    }
+    /// 数据区的第一个扇区，簇号从2开始
+    pub fn data_start(&self) -> u32 {
+        self.reserved_sectors + self.num_fats * self.fat_sectors
+    }
+    pub fn cluster_count(&self) -> u32 {
+        (self.total_sectors - self.data_start()) / self.sectors_per_cluster
+    }
+    pub fn max_cluster(&self) -> u32 {
+        self.cluster_count() + 1
+    }
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster as usize * BLOCK_SIZE
+    }
+    /// 簇的第一个扇区
+    pub fn cluster_sector(&self, cluster: u32) -> usize {
+        self.data_start() as usize + (cluster as usize - 2) * self.sectors_per_cluster as usize
+    }
+    /// 第fat个FAT中保存cluster表项的扇区与偏移
+    pub fn fat_position(&self, fat: u32, cluster: u32) -> (usize, usize) {
+        let offset = cluster as usize * 4;
+        (
+            (self.reserved_sectors + fat * self.fat_sectors) as usize + offset / BLOCK_SIZE,
+            offset % BLOCK_SIZE,
+        )
+    }
+}
+
+/// FSInfo扇区中记录的空闲簇数与下一个空闲簇的提示
+pub fn read_fsinfo(sector: &[u8; BLOCK_SIZE]) -> Option<(u32, u32)> {
+    if le32(sector, 0) != FSINFO_LEAD_SIG
+        || le32(sector, 484) != FSINFO_STRUC_SIG
+        || le32(sector, 508) != FSINFO_TRAIL_SIG
+    {
+        return None;
+    }
+    Some((le32(sector, 488), le32(sector, 492)))
+}
+
+pub fn write_fsinfo(sector: &mut [u8; BLOCK_SIZE], free_count: u32, next_free: u32) {
+    put32(sector, 0, FSINFO_LEAD_SIG);
+    put32(sector, 484, FSINFO_STRUC_SIG);
+    put32(sector, 488, free_count);
+    put32(sector, 492, next_free);
+    put32(sector, 508, FSINFO_TRAIL_SIG);
+}
+
+/// 按照微软的推荐值根据容量选择簇的大小
+fn sectors_per_cluster(total_sectors: u32) -> u32 {
+    match total_sectors {
+        0..=532_480 => 1,
+        532_481..=16_777_216 => 8,
+        16_777_217..=33_554_432 => 16,
+        33_554_433..=67_108_864 => 32,
+        _ => 64,
+    }
+}
+
+/// 在整个块设备上建立空的FAT32文件系统，根目录占用一个簇
+/// 直接写入块设备，调用者需要保证设备上的块没有被缓存
+/// 设备太小放不下FAT与根目录时返回false
+pub fn format(device: &Arc<dyn BlockDevice>, label: &str) -> bool {
+    let total_sectors = device.num_blocks().min(u32::MAX as usize) as u32;
+    let spc = sectors_per_cluster(total_sectors);
+    if total_sectors < RESERVED_SECTORS + 2 * NUM_FATS + spc {
+        return false;
+    }
+    //FAT的大小按照规范中的公式计算，可能略大于实际需要
+    let rest = total_sectors - RESERVED_SECTORS;
+    let per_fat = (256 * spc + NUM_FATS) / 2;
+    let fat_sectors = rest.div_ceil(per_fat);
+    let bpb = Bpb {
+        sectors_per_cluster: spc,
+        reserved_sectors: RESERVED_SECTORS,
+        num_fats: NUM_FATS,
+        fat_sectors,
+        total_sectors,
+        root_cluster: 2,
+        fsinfo_sector: 1,
+        volume_id: easyfs::now() ^ 0x4744_4f53,
+        label: [b' '; 11],
+    };
+    if bpb.data_start() + spc > total_sectors {
+        return false;
+    }
+    let mut boot = [0u8; BLOCK_SIZE];
+    boot[0..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+    boot[3..11].copy_from_slice(b"GODOS   ");
+    put16(&mut boot, 11, BLOCK_SIZE as u16);
+    boot[13] = spc as u8;
+    put16(&mut boot, 14, RESERVED_SECTORS as u16);
+    boot[16] = NUM_FATS as u8;
+    boot[21] = 0xf8; //固定磁盘
+    put16(&mut boot, 24, 32); //每磁道扇区数
+    put16(&mut boot, 26, 64); //磁头数
+    put32(&mut boot, 32, total_sectors);
+    put32(&mut boot, 36, fat_sectors);
+    put32(&mut boot, 44, bpb.root_cluster);
+    put16(&mut boot, 48, bpb.fsinfo_sector as u16);
+    put16(&mut boot, 50, BACKUP_BOOT_SECTOR as u16);
+    boot[64] = 0x80;
+    boot[66] = 0x29; //扩展引导标记，之后的卷序列号、卷标与类型有效
+    put32(&mut boot, 67, bpb.volume_id);
+    let mut name = [b' '; 11];
+    let label = if label.is_empty() { "NO NAME" } else { label };
+    for (dst, src) in name.iter_mut().zip(label.bytes()) {
+        *dst = src.to_ascii_uppercase();
+    }
+    boot[71..82].copy_from_slice(&name);
+    boot[82..90].copy_from_slice(b"FAT32   ");
+    put16(&mut boot, 510, BOOT_SIGNATURE);
+    //根目录占用了一个簇
+    let mut fsinfo = [0u8; BLOCK_SIZE];
+    write_fsinfo(&mut fsinfo, bpb.cluster_count() - 1, 3);
+    let zero = [0u8; BLOCK_SIZE];
+    let mut written = true;
+    for sector in 0..bpb.data_start() + spc {
+        let data = match sector {
+            0 | BACKUP_BOOT_SECTOR => &boot,
+            1 | 7 => &fsinfo,
+            _ => &zero,
+        };
+        written &= device.write_block(sector as usize, data).is_ok();
+    }
+    //前两个表项保留，第三个是根目录的簇
+    let mut fat = [0u8; BLOCK_SIZE];
+    put32(&mut fat, 0, 0x0fff_fff8);
+    put32(&mut fat, 4, FAT_EOC);
+    put32(&mut fat, 8, FAT_EOC);
+    for index in 0..NUM_FATS {
+        let (sector, _) = bpb.fat_position(index, 0);
+        written &= device.write_block(sector, &fat).is_ok();
+    }
+    written && device.flush().is_ok()
+}
+
+/// 检查块设备上是否是FAT32文件系统
+pub fn is_fat32(device: &Arc<dyn BlockDevice>) -> bool {
+    let mut sector = [0u8; BLOCK_SIZE];
+    device.read_block(0, &mut sector).is_ok() && Bpb::parse(&sector).is_some()
+}
@@ -0,0 +1,13 @@
+#![no_std]
+//! FAT32文件系统，可以作为根文件系统，也可以与easyfs同时挂载
+//! 建立在easyfs的BlockDevice与块缓存之上，扇区大小必须与块大小相同
+extern crate alloc;
+mod dir;
+mod fs;
+mod inode;
+mod layout;
+
+pub use dir::NAME_LENGTH_MAX;
+pub use fs::FatFileSystem;
+pub use inode::FatInode;
+pub use layout::{format, is_fat32};
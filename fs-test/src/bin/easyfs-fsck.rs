@@ -0,0 +1,122 @@
+//! 检查easyfs镜像的一致性，可以修复发现的问题并打印磁盘布局
+//! easyfs-fsck [-r] [-d] <image>
+//! 不修复时以只读方式打开镜像，打开时重新写回日志与迁移旧版本都只发生在内存中
+use easyfs::{BlockDevice, BlockError, BlockResult, FileSystem, BLOCK_SIZE};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::sync::{Arc, Mutex};
+extern crate clap;
+use clap::{App, Arg};
+#[path = "../block_file.rs"]
+mod block_file;
+use block_file::BlockFile;
+
+/// 只检查时使用的设备，写入的块保存在内存中，不修改镜像
+struct ReadOnlyImage {
+    image: BlockFile,
+    written: Mutex<HashMap<usize, Vec<u8>>>,
+}
+
+impl BlockDevice for ReadOnlyImage {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        if let Some(block) = self.written.lock().unwrap().get(&block_id) {
+            buf.copy_from_slice(block);
+            return Ok(());
+        }
+        self.image.read_block(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::InvalidBuf);
+        }
+        if block_id >= self.num_blocks() {
+            return Err(BlockError::OutOfRange);
+        }
+        self.written.lock().unwrap().insert(block_id, buf.to_vec());
+        Ok(())
+    }
+    fn num_blocks(&self) -> usize {
+        self.image.num_blocks()
+    }
+}
+
+/// 读写镜像失败时无法继续检查
+fn io_error(path: &str, err: BlockError) -> ! {
+    eprintln!("easyfs-fsck: {}: {:?}", path, err);
+    std::process::exit(2);
+}
+
+fn main() {
+    let matches = App::new("easyfs-fsck")
+        .about("Check and repair an easyfs image")
+        .arg(
+            Arg::new("image")
+                .help("Path of the image")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("repair")
+                .short('r')
+                .long("repair")
+                .help("Repair the problems found"),
+        )
+        .arg(
+            Arg::new("dump")
+                .short('d')
+                .long("dump")
+                .help("Print the layout and every inode"),
+        )
+        .get_matches();
+    let path = matches.value_of("image").unwrap();
+    let repair = matches.is_present("repair");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(repair)
+        .open(path)
+        .unwrap_or_else(|err| {
+            eprintln!("easyfs-fsck: {}: {}", path, err);
+            std::process::exit(2);
+        });
+    let image = BlockFile(Mutex::new(file));
+    let device: Arc<dyn BlockDevice> = if repair {
+        Arc::new(image)
+    } else {
+        Arc::new(ReadOnlyImage {
+            image,
+            written: Mutex::new(HashMap::new()),
+        })
+    };
+    //打开时会重新写回日志中已经提交的事务
+    let fs = FileSystem::try_open(device).unwrap_or_else(|| {
+        eprintln!("easyfs-fsck: {}: not a readable easyfs image", path);
+        std::process::exit(2);
+    });
+    if matches.is_present("dump") {
+        let dump = easyfs::dump(&fs).unwrap_or_else(|err| io_error(path, err));
+        print!("{}", dump);
+    }
+    let report = easyfs::fsck(&fs, repair).unwrap_or_else(|err| io_error(path, err));
+    for problem in report.problems.iter() {
+        println!("{}", problem);
+    }
+    println!(
+        "{}: {} inodes, {} blocks, {} problems, {} repaired",
+        path,
+        report.inodes,
+        report.blocks,
+        report.problems.len(),
+        report.repaired
+    );
+    //修复之后重新检查，仍然有问题时返回非0
+    let clean = if repair && !report.is_clean() {
+        let again = easyfs::fsck(&fs, false).unwrap_or_else(|err| io_error(path, err));
+        for problem in again.problems.iter() {
+            println!("unrepaired: {}", problem);
+        }
+        again.is_clean()
+    } else {
+        report.is_clean()
+    };
+    std::process::exit(if clean { 0 } else { 1 });
+}
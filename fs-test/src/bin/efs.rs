@@ -0,0 +1,435 @@
+//! 在主机上操作easyfs镜像
+//! efs <image> mkfs|ls|cat|put|get|mkdir|rm|ln ...
+use easyfs::{block_cache_sync, BlockError, FileSystem, Inode, NAME_LENGTH_MAX};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+extern crate clap;
+use clap::{App, AppSettings, Arg, ArgMatches};
+#[path = "../block_file.rs"]
+mod block_file;
+use block_file::BlockFile;
+
+const BITS_PER_BLOCK: usize = easyfs::BLOCK_SIZE * 8;
+
+type Result<T> = std::result::Result<T, String>;
+
+/// 读写镜像失败
+fn io_error(err: BlockError) -> String {
+    format!("I/O error: {:?}", err)
+}
+
+fn components(path: &str) -> Vec<&str> {
+    path.split('/').filter(|name| !name.is_empty()).collect()
+}
+
+///从根目录开始查找路径对应的文件
+fn lookup(root: &Arc<Inode>, path: &str) -> Result<Arc<Inode>> {
+    let mut inode = root.clone();
+    for name in components(path) {
+        if !inode.is_dir().map_err(io_error)? {
+            return Err(format!("{}: not a directory", path));
+        }
+        inode = inode
+            .find_inode(name)
+            .ok_or_else(|| format!("{}: no such file or directory", path))?;
+    }
+    Ok(inode)
+}
+
+///找到路径的父目录与最后一级名称
+fn lookup_parent<'a>(root: &Arc<Inode>, path: &'a str) -> Result<(Arc<Inode>, &'a str)> {
+    let mut names = components(path);
+    let name = names
+        .pop()
+        .ok_or_else(|| format!("{}: invalid path", path))?;
+    if name.len() > NAME_LENGTH_MAX {
+        return Err(format!("{}: file name too long", path));
+    }
+    let parent = lookup(root, &names.join("/"))?;
+    if !parent.is_dir().map_err(io_error)? {
+        return Err(format!("{}: not a directory", path));
+    }
+    Ok((parent, name))
+}
+
+fn permissions(mode: u32) -> String {
+    let mut out = String::from(if mode & 0o040000 != 0 { "d" } else { "-" });
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 2 != 0 { 'w' } else { '-' });
+        out.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+    out
+}
+
+fn mkfs(image: &str, matches: &ArgMatches) -> Result<()> {
+    let blocks: usize = matches
+        .value_of("blocks")
+        .unwrap()
+        .parse()
+        .map_err(|_| "invalid block count".to_string())?;
+    let inodes: usize = matches
+        .value_of("inodes")
+        .unwrap()
+        .parse()
+        .map_err(|_| "invalid inode count".to_string())?;
+    let inode_bitmap_blocks = ((inodes + BITS_PER_BLOCK - 1) / BITS_PER_BLOCK).max(1);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(image)
+        .map_err(|err| format!("{}: {}", image, err))?;
+    file.set_len((blocks * easyfs::BLOCK_SIZE) as u64)
+        .map_err(|err| format!("{}: {}", image, err))?;
+    let fs = FileSystem::create(
+        Arc::new(BlockFile(Mutex::new(file))),
+        blocks,
+        inode_bitmap_blocks,
+    )
+    .map_err(io_error)?;
+    if matches.is_present("extents") {
+        fs.lock().set_extents(true).map_err(io_error)?;
+        block_cache_sync().map_err(io_error)?;
+    }
+    Ok(())
+}
+
+fn ls(root: &Arc<Inode>, path: &str, long: bool) -> Result<()> {
+    let inode = lookup(root, path)?;
+    let entries = if inode.is_dir().map_err(io_error)? {
+        let mut names = inode.ls().map_err(io_error)?;
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                //列出之后目录项可能已经被删除或者无法读取
+                let child = inode.find_inode(&name).ok_or_else(|| {
+                    format!(
+                        "{}/{}: no such file or directory",
+                        path.trim_end_matches('/'),
+                        name
+                    )
+                })?;
+                Ok((name, child))
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        vec![(path.to_string(), inode)]
+    };
+    for (name, inode) in entries {
+        if long {
+            let metadata = inode.metadata().map_err(io_error)?;
+            println!(
+                "{} {:>3} {:>5} {:>5} {:>10} {:>5} {}",
+                permissions(metadata.mode),
+                metadata.nlink,
+                metadata.uid,
+                metadata.gid,
+                metadata.size,
+                metadata.ino,
+                name
+            );
+        } else {
+            println!("{}", name);
+        }
+    }
+    Ok(())
+}
+
+fn read_all(inode: &Inode) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; inode.get_file_size().map_err(io_error)?];
+    let len = inode.read_at(0, &mut buffer).map_err(io_error)?;
+    buffer.truncate(len);
+    Ok(buffer)
+}
+
+fn cat(root: &Arc<Inode>, path: &str) -> Result<()> {
+    use std::io::Write;
+    let inode = lookup(root, path)?;
+    if inode.is_dir().map_err(io_error)? {
+        return Err(format!("{}: is a directory", path));
+    }
+    std::io::stdout()
+        .write_all(&read_all(&inode)?)
+        .map_err(|err| err.to_string())
+}
+
+///将主机上的文件或目录复制到镜像中，目标是已经存在的目录时复制到该目录下
+fn put(root: &Arc<Inode>, host: &Path, path: &str, recursive: bool) -> Result<()> {
+    let host_name = host
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("{}: invalid file name", host.display()))?;
+    let path = match lookup(root, path) {
+        Ok(inode) if inode.is_dir().map_err(io_error)? => format!("{}/{}", path, host_name),
+        _ => path.to_string(),
+    };
+    copy_in(root, host, &path, recursive)
+}
+
+fn copy_in(root: &Arc<Inode>, host: &Path, path: &str, recursive: bool) -> Result<()> {
+    let (parent, name) = lookup_parent(root, path)?;
+    if host.is_dir() {
+        if !recursive {
+            return Err(format!("{}: is a directory", host.display()));
+        }
+        match parent.find_inode(name) {
+            Some(inode) if inode.is_dir().map_err(io_error)? => {}
+            Some(_) => return Err(format!("{}: not a directory", path)),
+            None => {
+                parent
+                    .mkdir(name)
+                    .ok_or_else(|| format!("{}: cannot create directory", path))?;
+            }
+        }
+        let mut children: Vec<_> = std::fs::read_dir(host)
+            .map_err(|err| format!("{}: {}", host.display(), err))?
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        children.sort();
+        for child in children {
+            let child_name = child.file_name().unwrap().to_string_lossy().into_owned();
+            copy_in(root, &child, &format!("{}/{}", path, child_name), true)?;
+        }
+        return Ok(());
+    }
+    let data = std::fs::read(host).map_err(|err| format!("{}: {}", host.display(), err))?;
+    //已经存在的文件被覆盖
+    let inode = match parent.find_inode(name) {
+        Some(inode) if inode.is_dir().map_err(io_error)? => {
+            return Err(format!("{}: is a directory", path))
+        }
+        Some(inode) => {
+            inode.clear();
+            inode
+        }
+        None => parent
+            .create(name)
+            .ok_or_else(|| format!("{}: cannot create file", path))?,
+    };
+    inode.write_at(0, &data).map_err(io_error)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(host).unwrap().permissions().mode();
+        inode.set_mode((mode & 0o777) as u16).map_err(io_error)?;
+    }
+    Ok(())
+}
+
+///将镜像中的文件或目录复制到主机上
+fn get(root: &Arc<Inode>, path: &str, host: &Path, recursive: bool) -> Result<()> {
+    let inode = lookup(root, path)?;
+    if !inode.is_dir().map_err(io_error)? {
+        return std::fs::write(host, read_all(&inode)?)
+            .map_err(|err| format!("{}: {}", host.display(), err));
+    }
+    if !recursive {
+        return Err(format!("{}: is a directory", path));
+    }
+    std::fs::create_dir_all(host).map_err(|err| format!("{}: {}", host.display(), err))?;
+    for name in inode.ls().map_err(io_error)? {
+        get(root, &format!("{}/{}", path, name), &host.join(&name), true)?;
+    }
+    Ok(())
+}
+
+fn mkdir(root: &Arc<Inode>, path: &str, parents: bool) -> Result<()> {
+    if parents {
+        let mut dir = root.clone();
+        for name in components(path) {
+            dir = match dir.find_inode(name) {
+                Some(inode) if inode.is_dir().map_err(io_error)? => inode,
+                Some(_) => return Err(format!("{}: not a directory", path)),
+                None => dir
+                    .mkdir(name)
+                    .ok_or_else(|| format!("{}: cannot create directory", path))?,
+            };
+        }
+        return Ok(());
+    }
+    let (parent, name) = lookup_parent(root, path)?;
+    parent
+        .mkdir(name)
+        .map(|_| ())
+        .ok_or_else(|| format!("{}: file exists", path))
+}
+
+fn rm(root: &Arc<Inode>, path: &str, recursive: bool) -> Result<()> {
+    let (parent, name) = lookup_parent(root, path)?;
+    let inode = parent
+        .find_inode(name)
+        .ok_or_else(|| format!("{}: no such file or directory", path))?;
+    if inode.is_dir().map_err(io_error)? {
+        if !recursive {
+            return Err(format!("{}: is a directory", path));
+        }
+        for child in inode.ls().map_err(io_error)? {
+            rm(root, &format!("{}/{}", path, child), true)?;
+        }
+    }
+    if parent.delete_nlink(name) != 0 {
+        return Err(format!("{}: cannot remove", path));
+    }
+    Ok(())
+}
+
+fn ln(root: &Arc<Inode>, target: &str, link: &str) -> Result<()> {
+    let inode = lookup(root, target)?;
+    if inode.is_dir().map_err(io_error)? {
+        return Err(format!("{}: hard link not allowed for directory", target));
+    }
+    let (parent, name) = lookup_parent(root, link)?;
+    if parent.link(name, &inode) {
+        Ok(())
+    } else {
+        Err(format!("{}: file exists", link))
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let image = matches.value_of("image").unwrap();
+    if let Some(("mkfs", sub)) = matches.subcommand() {
+        return mkfs(image, sub);
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(image)
+        .map_err(|err| format!("{}: {}", image, err))?;
+    let fs = FileSystem::try_open(Arc::new(BlockFile(Mutex::new(file))))
+        .ok_or_else(|| format!("{}: not a readable easyfs image", image))?;
+    let root = Arc::new(FileSystem::root_inode(&fs));
+    let ret = match matches.subcommand() {
+        Some(("ls", sub)) => ls(
+            &root,
+            sub.value_of("path").unwrap_or("/"),
+            sub.is_present("long"),
+        ),
+        Some(("cat", sub)) => cat(&root, sub.value_of("path").unwrap()),
+        Some(("put", sub)) => put(
+            &root,
+            Path::new(sub.value_of("host").unwrap()),
+            sub.value_of("path").unwrap_or("/"),
+            sub.is_present("recursive"),
+        ),
+        Some(("get", sub)) => get(
+            &root,
+            sub.value_of("path").unwrap(),
+            Path::new(sub.value_of("host").unwrap()),
+            sub.is_present("recursive"),
+        ),
+        Some(("mkdir", sub)) => mkdir(
+            &root,
+            sub.value_of("path").unwrap(),
+            sub.is_present("parents"),
+        ),
+        Some(("rm", sub)) => rm(
+            &root,
+            sub.value_of("path").unwrap(),
+            sub.is_present("recursive"),
+        ),
+        Some(("ln", sub)) => ln(
+            &root,
+            sub.value_of("target").unwrap(),
+            sub.value_of("link").unwrap(),
+        ),
+        _ => unreachable!(),
+    };
+    block_cache_sync().map_err(io_error)?;
+    ret
+}
+
+fn main() {
+    let path = |name| Arg::new(name).required(true);
+    let recursive = Arg::new("recursive")
+        .short('r')
+        .long("recursive")
+        .help("Operate on directories recursively");
+    let matches = App::new("efs")
+        .about("Inspect and modify an easyfs image")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(Arg::new("image").help("Path of the image").required(true))
+        .subcommand(
+            App::new("mkfs")
+                .about("Create an empty file system")
+                .arg(
+                    Arg::new("blocks")
+                        .short('b')
+                        .long("blocks")
+                        .takes_value(true)
+                        .default_value("32768")
+                        .help("Size of the image in blocks"),
+                )
+                .arg(
+                    Arg::new("inodes")
+                        .short('i')
+                        .long("inodes")
+                        .takes_value(true)
+                        .default_value("4096")
+                        .help("Number of inodes, rounded up to a whole bitmap block"),
+                )
+                .arg(
+                    Arg::new("extents")
+                        .short('e')
+                        .long("extents")
+                        .help("Store new regular files as extents"),
+                ),
+        )
+        .subcommand(
+            App::new("ls")
+                .about("List a directory")
+                .arg(
+                    Arg::new("long")
+                        .short('l')
+                        .help("Show mode, links and size"),
+                )
+                .arg(Arg::new("path")),
+        )
+        .subcommand(App::new("cat").about("Print a file").arg(path("path")))
+        .subcommand(
+            App::new("put")
+                .about("Copy a host file or directory into the image")
+                .arg(recursive.clone())
+                .arg(path("host"))
+                .arg(Arg::new("path")),
+        )
+        .subcommand(
+            App::new("get")
+                .about("Copy a file or directory out of the image")
+                .arg(recursive.clone())
+                .arg(path("path"))
+                .arg(path("host")),
+        )
+        .subcommand(
+            App::new("mkdir")
+                .about("Create a directory")
+                .arg(
+                    Arg::new("parents")
+                        .short('p')
+                        .help("Create missing parent directories"),
+                )
+                .arg(path("path")),
+        )
+        .subcommand(
+            App::new("rm")
+                .about("Remove a file or directory")
+                .arg(recursive)
+                .arg(path("path")),
+        )
+        .subcommand(
+            App::new("ln")
+                .about("Create a hard link")
+                .arg(path("target"))
+                .arg(path("link")),
+        )
+        .get_matches();
+    if let Err(err) = run(&matches) {
+        eprintln!("efs: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,61 @@
+//! 使用本地的文件模拟一个块设备，测试与各个命令行工具共用
+use easyfs::{BlockDevice, BlockError, BlockResult, BLOCK_SIZE};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
+
+pub struct BlockFile(pub Mutex<File>);
+
+/// 文件的大小就是设备的容量
+fn file_blocks(file: &File) -> usize {
+    file.metadata()
+        .map_or(0, |meta| meta.len() as usize / BLOCK_SIZE)
+}
+
+/// 定位到block_id处，读写的范围超出文件时返回OutOfRange
+fn seek(file: &mut File, block_id: usize, len: usize) -> BlockResult {
+    let partial = len % BLOCK_SIZE;
+    if partial != 0 {
+        return Err(BlockError::InvalidBuf);
+    }
+    if block_id + len / BLOCK_SIZE > file_blocks(file) {
+        return Err(BlockError::OutOfRange);
+    }
+    file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+        .map_err(|_| BlockError::Io)?;
+    Ok(())
+}
+
+impl BlockDevice for BlockFile {
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        self.write_blocks(block_id, buf)
+    } //通过Seek访问特定的块
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        self.read_blocks(block_id, buf)
+    } //通过Seek访问特定的块
+
+    fn num_blocks(&self) -> usize {
+        file_blocks(&self.0.lock().unwrap())
+    }
+
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        let mut file = self.0.lock().unwrap();
+        seek(&mut file, block_id, buf.len())?;
+        file.read_exact(buf).map_err(|_| BlockError::Io)
+    } //连续的多个块一次读出
+
+    fn write_blocks(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        let mut file = self.0.lock().unwrap();
+        seek(&mut file, block_id, buf.len())?;
+        file.write_all(buf).map_err(|_| BlockError::Io)
+    } //连续的多个块一次写入
+
+    fn flush(&self) -> BlockResult {
+        self.0
+            .lock()
+            .unwrap()
+            .sync_data()
+            .map_err(|_| BlockError::Io)
+    }
+}
@@ -1,36 +1,22 @@
 #![allow(dead_code)]
-use easyfs::{BlockDevice, FileSystem, Inode};
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::sync::{Arc, Mutex};
+use easyfs::{
+    BlockCacheManager, BlockCompletion, BlockDevice, BlockError, BlockRequest, BlockResult,
+    FileSystem, Inode, Problem, Quota, RenameError, DIRECT_MAX, EFS_MAGIC, INDIRECT1_MAX,
+    INDIRECT2_MAX, INDIRECT3_MAX, JOURNAL_BLOCKS, MAX_FILE_SIZE, QUOTA_SLOTS,
+};
+use fat32::{FatFileSystem, FatInode};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 extern crate clap;
+mod block_file;
+use block_file::BlockFile;
 use clap::{App, Arg};
 
 const BLOCK_SIZE: usize = 512;
 
-///使用本地的文件模拟一个块设备
-struct BlockFile(Mutex<File>);
-
-impl BlockDevice for BlockFile {
-    fn write_block(&self, block_id: usize, buf: &[u8]) {
-        let mut file = self.0.lock().unwrap();
-        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
-            .expect("Error seeking");
-        assert_eq!(
-            file.write(buf).unwrap(),
-            BLOCK_SIZE,
-            "Not a completed block"
-        );
-    } //通过Seek访问特定的块
-
-    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        let mut file = self.0.lock().unwrap();
-        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
-            .expect("Error seeking");
-        assert_eq!(file.read(buf).unwrap(), BLOCK_SIZE, "Not a completed block");
-    } //通过Seek访问特定的块
-}
-
 fn crate_filesystem() -> Inode {
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
@@ -43,13 +29,43 @@ fn crate_filesystem() -> Inode {
         f
     })));
     //创建文件系统
-    FileSystem::create(block_file.clone(), 8192 * 4, 1);
+    FileSystem::create(block_file.clone(), 8192 * 4, 1).unwrap();
     let fs = FileSystem::open(block_file.clone());
+    //应用程序使用extent记录，内核加载时可以一次读出多个块
+    fs.lock().set_extents(true).unwrap();
     //得到根目录节点
     let root_inode = FileSystem::root_inode(&fs);
     root_inode
 }
 
+///在临时目录下新建一个空的镜像文件，返回路径与对应的块设备，测试之间互不影响
+///不需要重新打开镜像时可以立即删除路径，文件关闭后自动回收
+fn create_image(name: &str, blocks: usize) -> (std::path::PathBuf, Arc<dyn BlockDevice>) {
+    let path = std::env::temp_dir().join(format!("{}-{}.img", name, std::process::id()));
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    f.set_len((blocks * BLOCK_SIZE) as u64).unwrap();
+    (path, Arc::new(BlockFile(Mutex::new(f))))
+}
+
+///在临时镜像上新建一个easyfs，返回文件系统以便查看使用情况与设置限额
+fn create_test_fs(name: &str, blocks: usize) -> Arc<spin::Mutex<FileSystem>> {
+    let (path, device) = create_image(&format!("easyfs-{}", name), blocks);
+    std::fs::remove_file(&path).unwrap();
+    FileSystem::create(device.clone(), blocks, 1).unwrap();
+    FileSystem::open(device)
+}
+
+///在临时镜像上新建一个easyfs，返回根目录
+fn create_test_filesystem(name: &str, blocks: usize) -> Inode {
+    FileSystem::root_inode(&create_test_fs(name, blocks))
+}
+
 #[test]
 fn fs_test() -> std::io::Result<()> {
     //测试文件系统
@@ -57,15 +73,15 @@ fn fs_test() -> std::io::Result<()> {
     root_inode.create("file1");
     root_inode.create("file2");
     println!("test list file names...");
-    for l in root_inode.ls() {
+    for l in root_inode.ls().unwrap() {
         println!("name: {}", l);
     }
     //测试文件写入与读出
     let file1 = root_inode.find_inode("file1").unwrap();
     let hello_str = "Hello world";
-    file1.write_at(0, hello_str.as_bytes());
+    file1.write_at(0, hello_str.as_bytes()).unwrap();
     let mut buffer = [0u8; 255];
-    let len = file1.read_at(0, &mut buffer);
+    let len = file1.read_at(0, &mut buffer).unwrap();
     assert_eq!(hello_str, core::str::from_utf8(&buffer[..len]).unwrap());
     println!(
         "{}=={}",
@@ -75,9 +91,9 @@ fn fs_test() -> std::io::Result<()> {
 
     let file2 = root_inode.find_inode("file2").unwrap();
     let hello_str = "Hello world file2";
-    file2.write_at(0, hello_str.as_bytes());
+    file2.write_at(0, hello_str.as_bytes()).unwrap();
     let mut buffer = [0u8; 255];
-    let len = file2.read_at(0, &mut buffer);
+    let len = file2.read_at(0, &mut buffer).unwrap();
     assert_eq!(hello_str, core::str::from_utf8(&buffer[..len]).unwrap());
     println!(
         "{}=={}",
@@ -89,20 +105,20 @@ fn fs_test() -> std::io::Result<()> {
     let mut random_str_test = |len: usize| {
         //
         file1.clear(); //清空文件，回收各个数据块
-        assert_eq!(file1.read_at(0, &mut buffer), 0); //内容长度为0
+        assert_eq!(file1.read_at(0, &mut buffer).unwrap(), 0); //内容长度为0
         let mut str = String::new();
         for _ in 0..len {
             //随机产生数字
             str.push(char::from('0' as u8 + rand::random::<u8>() % 10));
         }
         println!("str.len():{}", str.len());
-        file1.write_at(0, str.as_bytes());
+        file1.write_at(0, str.as_bytes()).unwrap();
 
         let mut read_buffer = [0u8; 127];
         let mut offset = 0usize;
         let mut read_str = String::new();
         loop {
-            let len = file1.read_at(offset, &mut read_buffer);
+            let len = file1.read_at(offset, &mut read_buffer).unwrap();
             if len == 0 {
                 break;
             }
@@ -122,6 +138,1571 @@ fn fs_test() -> std::io::Result<()> {
     random_str_test(2000 * BLOCK_SIZE);
     Ok(())
 }
+#[test]
+fn unlink_test() {
+    let root_inode = create_test_filesystem("unlink", 4096);
+    let file1 = root_inode.create("file1").unwrap();
+    file1.write_at(0, &[1u8; 3 * BLOCK_SIZE]).unwrap();
+    let ino = file1.get_disk_inode();
+    root_inode.create_nlink("link1", "file1").unwrap();
+    assert_eq!(file1.get_disk_nlink().unwrap(), 2);
+    //删除一个硬链接后另一个名称仍然可以访问
+    assert_eq!(root_inode.delete_nlink("file1"), 0);
+    assert_eq!(root_inode.ls().unwrap(), vec!["link1"]);
+    assert!(root_inode.find_inode("file1").is_none());
+    let link1 = root_inode.find_inode("link1").unwrap();
+    assert_eq!(link1.get_disk_nlink().unwrap(), 1);
+    assert_eq!(link1.get_file_size().unwrap(), 3 * BLOCK_SIZE);
+    //删除最后一个硬链接后回收inode，新文件会复用inode和目录项
+    assert_eq!(root_inode.delete_nlink("link1"), 0);
+    assert!(root_inode.ls().unwrap().is_empty());
+    assert_eq!(root_inode.delete_nlink("link1"), -1);
+    let file2 = root_inode.create("file2").unwrap();
+    assert_eq!(file2.get_disk_inode(), ino);
+    assert_eq!(file2.get_file_size().unwrap(), 0);
+    assert_eq!(root_inode.get_file_size().unwrap(), 2 * 32);
+    //仍然打开的文件在release之前可以继续读取
+    file2.write_at(0, "still here".as_bytes()).unwrap();
+    let file2 = root_inode.unlink("file2").unwrap();
+    assert_eq!(file2.get_disk_nlink().unwrap(), 0);
+    let mut buffer = [0u8; 16];
+    let len = file2.read_at(0, &mut buffer).unwrap();
+    assert_eq!(&buffer[..len], "still here".as_bytes());
+    assert!(file2.release());
+    assert_eq!(root_inode.create("file3").unwrap().get_disk_inode(), ino);
+}
+#[test]
+fn read_dirent_test() {
+    let root_inode = create_test_filesystem("read_dirent", 4096);
+    for name in ["a", "b", "c", "d"] {
+        root_inode.create(name).unwrap();
+    }
+    let (cursor, name, inode) = root_inode.read_dirent(0).unwrap().unwrap();
+    assert_eq!(name, "a");
+    assert_eq!(
+        inode.get_disk_inode(),
+        root_inode.find_inode("a").unwrap().get_disk_inode()
+    );
+    //删除已经读过与还没有读的文件，游标之后的文件不会被跳过
+    assert_eq!(root_inode.delete_nlink("a"), 0);
+    assert_eq!(root_inode.delete_nlink("c"), 0);
+    let (cursor, name, _) = root_inode.read_dirent(cursor).unwrap().unwrap();
+    assert_eq!(name, "b");
+    let (cursor, name, _) = root_inode.read_dirent(cursor).unwrap().unwrap();
+    assert_eq!(name, "d");
+    assert!(root_inode.read_dirent(cursor).unwrap().is_none());
+}
+
+#[test]
+fn rename_test() {
+    let fs = create_test_fs("rename", 4096);
+    let root_inode = FileSystem::root_inode(&fs);
+    let src = root_inode.create("src").unwrap();
+    src.write_at(0, "source".as_bytes()).unwrap();
+    let dst = root_inode.create("dst").unwrap();
+    dst.write_at(0, "target".as_bytes()).unwrap();
+    let dst_ino = dst.get_disk_inode();
+    //目标不存在时只修改名称
+    assert!(root_inode
+        .rename("src", &root_inode, "moved")
+        .unwrap()
+        .is_none());
+    assert!(root_inode.find_inode("src").is_none());
+    assert_eq!(
+        root_inode.find_inode("moved").unwrap().get_disk_inode(),
+        src.get_disk_inode()
+    );
+    //目标存在时替换目标，被替换的文件硬链接计数减少
+    let replaced = root_inode
+        .rename("moved", &root_inode, "dst")
+        .unwrap()
+        .unwrap();
+    assert_eq!(replaced.get_disk_inode(), dst_ino);
+    assert_eq!(replaced.get_disk_nlink().unwrap(), 0);
+    assert!(replaced.release());
+    assert_eq!(root_inode.ls().unwrap(), vec!["dst"]);
+    let mut buffer = [0u8; 16];
+    let dst = root_inode.find_inode("dst").unwrap();
+    let len = dst.read_at(0, &mut buffer).unwrap();
+    assert_eq!(&buffer[..len], "source".as_bytes());
+    //重命名为自身的硬链接时不做任何事
+    root_inode.create_nlink("alias", "dst").unwrap();
+    assert!(root_inode
+        .rename("alias", &root_inode, "dst")
+        .unwrap()
+        .is_none());
+    assert_eq!(dst.get_disk_nlink().unwrap(), 2);
+    assert_eq!(
+        root_inode.rename("missing", &root_inode, "dst").err(),
+        Some(RenameError::NotFound)
+    );
+    assert_eq!(
+        root_inode.rename("dst", &root_inode, "").err(),
+        Some(RenameError::InvalidName)
+    );
+    //目录只能替换空目录，其它文件只能替换不是目录的文件
+    root_inode.mkdir("dir").unwrap();
+    let full = root_inode.mkdir("full").unwrap();
+    full.create("child").unwrap();
+    assert_eq!(
+        root_inode.rename("dst", &root_inode, "dir").err(),
+        Some(RenameError::TypeMismatch)
+    );
+    assert!(root_inode.rename("dir", &root_inode, "dst").is_err());
+    assert_eq!(
+        root_inode.rename("dir", &root_inode, "full").err(),
+        Some(RenameError::NotEmpty)
+    );
+    assert_eq!(
+        root_inode.ls().unwrap(),
+        vec!["alias", "dst", "dir", "full"]
+    );
+    assert_eq!(full.delete_nlink("child"), 0);
+    let replaced = root_inode
+        .rename("dir", &root_inode, "full")
+        .unwrap()
+        .unwrap();
+    assert!(replaced.release());
+    assert_eq!(root_inode.ls().unwrap(), vec!["alias", "dst", "full"]);
+    let full = root_inode.find_inode("full").unwrap();
+    assert!(full.is_dir().unwrap());
+    //移动到其它目录，文件的硬链接计数不变
+    assert!(root_inode.rename("dst", &full, "moved").unwrap().is_none());
+    assert_eq!(root_inode.ls().unwrap(), vec!["alias", "full"]);
+    let moved = full.find_inode("moved").unwrap();
+    assert_eq!(moved.get_disk_inode(), dst.get_disk_inode());
+    assert_eq!(moved.get_disk_nlink().unwrap(), 2);
+    //移动并替换其它目录中的文件
+    let other = root_inode.create("other").unwrap();
+    let replaced = full.rename("moved", &root_inode, "other").unwrap().unwrap();
+    assert_eq!(replaced.get_disk_inode(), other.get_disk_inode());
+    assert_eq!(replaced.get_disk_nlink().unwrap(), 0);
+    assert!(replaced.release());
+    assert!(full.ls().unwrap().is_empty());
+    assert_eq!(
+        root_inode.find_inode("other").unwrap().get_disk_inode(),
+        dst.get_disk_inode()
+    );
+    //目录可以移动到其它目录中，但是不能移动到自身或者自己的子目录中
+    let sub = full.mkdir("sub").unwrap();
+    let outer = root_inode.mkdir("outer").unwrap();
+    assert_eq!(
+        root_inode.rename("full", &full, "self").err(),
+        Some(RenameError::Loop)
+    );
+    assert_eq!(
+        root_inode.rename("full", &sub, "inner").err(),
+        Some(RenameError::Loop)
+    );
+    assert!(root_inode.rename("full", &outer, "full").unwrap().is_none());
+    assert_eq!(root_inode.ls().unwrap(), vec!["alias", "other", "outer"]);
+    assert_eq!(outer.ls().unwrap(), vec!["full"]);
+    assert_eq!(
+        outer
+            .find_inode("full")
+            .unwrap()
+            .find_inode("sub")
+            .unwrap()
+            .get_disk_inode(),
+        sub.get_disk_inode()
+    );
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+}
+
+#[test]
+fn mkdir_test() {
+    let root_inode = create_test_filesystem("mkdir", 4096);
+    let dir = root_inode.mkdir("dir").unwrap();
+    assert!(dir.is_dir().unwrap());
+    assert_eq!(dir.metadata().unwrap().mode, 0o040755);
+    assert!(root_inode.mkdir("dir").is_none());
+    let sub = dir.mkdir("sub").unwrap();
+    let file = sub.create("file").unwrap();
+    file.write_at(0, "nested".as_bytes()).unwrap();
+    //可以在其它目录下为文件添加硬链接，目录不能添加硬链接
+    assert!(root_inode.link("alias", &file));
+    assert!(!root_inode.link("alias", &file));
+    assert!(!root_inode.link("dir2", &dir));
+    assert_eq!(file.get_disk_nlink().unwrap(), 2);
+    assert_eq!(
+        read_all(&root_inode.find_inode("alias").unwrap()),
+        b"nested"
+    );
+    //非空目录不能删除
+    assert_eq!(dir.delete_nlink("sub"), -1);
+    assert_eq!(sub.delete_nlink("file"), 0);
+    assert_eq!(file.get_disk_nlink().unwrap(), 1);
+    assert_eq!(dir.delete_nlink("sub"), 0);
+    assert!(dir.ls().unwrap().is_empty());
+    assert_eq!(root_inode.delete_nlink("dir"), 0);
+    assert_eq!(root_inode.ls().unwrap(), vec!["alias"]);
+}
+
+#[test]
+fn symlink_test() {
+    let fs = create_test_fs("symlink", 4096);
+    let root_inode = FileSystem::root_inode(&fs);
+    root_inode
+        .create("file")
+        .unwrap()
+        .write_at(0, "target".as_bytes())
+        .unwrap();
+    //短的目标存放在inode中，不占用数据块
+    let short = root_inode.symlink("short", "/dir/../file").unwrap();
+    assert!(short.is_symlink().unwrap());
+    assert_eq!(short.metadata().unwrap().mode, 0o120777);
+    assert_eq!(short.get_file_size().unwrap(), "/dir/../file".len());
+    assert!(short.block_ids().unwrap().is_empty());
+    assert_eq!(short.readlink().unwrap(), "/dir/../file");
+    //长的目标存放在一个数据块中
+    let target = "a/".repeat(100) + "file";
+    let long = root_inode.symlink("long", &target).unwrap();
+    assert_eq!(long.block_ids().unwrap().len(), 1);
+    assert_eq!(
+        root_inode.find_inode("long").unwrap().readlink().unwrap(),
+        target
+    );
+    //普通文件没有目标，已经存在的名称与过长的目标都不能新建
+    assert!(root_inode.find_inode("file").unwrap().readlink().is_none());
+    assert!(root_inode.symlink("file", "other").is_none());
+    assert!(root_inode
+        .symlink("huge", &"x".repeat(BLOCK_SIZE + 1))
+        .is_none());
+    assert!(root_inode.symlink("empty", "").is_none());
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //删除符号链接不影响目标，回收时释放数据块
+    assert_eq!(root_inode.delete_nlink("long"), 0);
+    assert_eq!(root_inode.delete_nlink("short"), 0);
+    assert_eq!(read_all(&root_inode.find_inode("file").unwrap()), b"target");
+    let report = easyfs::fsck(&fs, false).unwrap();
+    assert!(report.is_clean());
+    assert_eq!(report.inodes, 2);
+    //回收的inode被新文件复用时不再是符号链接
+    let reused = root_inode.create("reused").unwrap();
+    assert!(!reused.is_symlink().unwrap());
+    assert!(reused.block_ids().unwrap().is_empty());
+    reused.write_at(0, &[7u8; 2 * BLOCK_SIZE]).unwrap();
+    assert_eq!(read_all(&reused), vec![7u8; 2 * BLOCK_SIZE]);
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+}
+
+#[test]
+fn truncate_test() {
+    let fs = create_test_fs("truncate", 4096);
+    let root_inode = FileSystem::root_inode(&fs);
+    let file = root_inode.create("file").unwrap();
+    let content = file_content(400, 9);
+    file.write_at(0, &content).unwrap();
+    //依次缩小到二级索引、一级索引、直接索引与块内的位置
+    for size in [
+        300 * BLOCK_SIZE + 17,
+        149 * BLOCK_SIZE,
+        100 * BLOCK_SIZE - 3,
+        20 * BLOCK_SIZE,
+        5 * BLOCK_SIZE + 100,
+        0,
+    ] {
+        file.truncate(size);
+        assert_eq!(file.get_file_size().unwrap(), size);
+        assert_eq!(read_all(&file), &content[..size]);
+        assert!(
+            easyfs::fsck(&fs, false).unwrap().is_clean(),
+            "size {}",
+            size
+        );
+    }
+    //扩大后新的部分读出为0，缩小时截掉的数据不会重新出现
+    file.write_at(0, &content[..BLOCK_SIZE]).unwrap();
+    file.truncate(100);
+    file.truncate(250 * BLOCK_SIZE);
+    let data = read_all(&file);
+    assert_eq!(data.len(), 250 * BLOCK_SIZE);
+    assert_eq!(&data[..100], &content[..100]);
+    assert!(data[100..].iter().all(|byte| *byte == 0));
+    assert_eq!(file.metadata().unwrap().blocks, 250 + 1 + 1 + 1);
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+}
+
+///只保存非0块的内存块设备，没有写入数据的大文件几乎不占用内存
+struct SparseDevice {
+    blocks: Mutex<HashMap<usize, [u8; BLOCK_SIZE]>>,
+    num_blocks: usize,
+    batch_reads: AtomicUsize, //一次读取多个块的次数
+}
+
+impl SparseDevice {
+    fn new(num_blocks: usize) -> Self {
+        Self {
+            blocks: Mutex::new(HashMap::new()),
+            num_blocks,
+            batch_reads: AtomicUsize::new(0),
+        }
+    }
+    /// 复制当前的内容，用于从同一个镜像开始多次测试
+    fn snapshot(&self) -> Self {
+        let device = Self::new(self.num_blocks);
+        *device.blocks.lock().unwrap() = self.blocks.lock().unwrap().clone();
+        device
+    }
+}
+
+impl BlockDevice for SparseDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        if block_id >= self.num_blocks() {
+            return Err(BlockError::OutOfRange);
+        }
+        match self.blocks.lock().unwrap().get(&block_id) {
+            Some(block) => buf.copy_from_slice(block),
+            None => buf.fill(0),
+        }
+        Ok(())
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        if block_id >= self.num_blocks() {
+            return Err(BlockError::OutOfRange);
+        }
+        let mut blocks = self.blocks.lock().unwrap();
+        if buf.iter().all(|byte| *byte == 0) {
+            blocks.remove(&block_id);
+        } else {
+            blocks.insert(block_id, buf.try_into().unwrap());
+        }
+        Ok(())
+    }
+    fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        self.batch_reads.fetch_add(1, Ordering::SeqCst);
+        for (i, block) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            self.read_block(block_id + i, block)?;
+        }
+        Ok(())
+    }
+}
+
+///在后台线程中按提交顺序完成请求的块设备
+struct AsyncDevice {
+    device: Arc<SparseDevice>,
+    next_id: AtomicUsize,
+    sender: Mutex<mpsc::Sender<(usize, BlockRequest)>>,
+    completed: Arc<Mutex<VecDeque<BlockCompletion>>>,
+}
+
+impl AsyncDevice {
+    fn new(device: Arc<SparseDevice>) -> Self {
+        let (sender, receiver) = mpsc::channel::<(usize, BlockRequest)>();
+        let completed = Arc::new(Mutex::new(VecDeque::new()));
+        let worker = (device.clone(), completed.clone());
+        //设备被丢弃后发送端关闭，线程随之退出
+        std::thread::spawn(move || {
+            let (device, completed) = worker;
+            for (id, request) in receiver {
+                let mut data = Vec::new();
+                let result = match request {
+                    BlockRequest::Read { block_id, count } => {
+                        data = vec![0u8; count * BLOCK_SIZE];
+                        device.read_blocks(block_id, &mut data)
+                    }
+                    BlockRequest::Write { block_id, data } => device.write_blocks(block_id, &data),
+                    BlockRequest::Flush => device.flush(),
+                };
+                completed
+                    .lock()
+                    .unwrap()
+                    .push_back(BlockCompletion { id, result, data });
+            }
+        });
+        Self {
+            device,
+            next_id: AtomicUsize::new(0),
+            sender: Mutex::new(sender),
+            completed,
+        }
+    }
+    /// 等待下一个完成的请求
+    fn wait(&self) -> BlockCompletion {
+        loop {
+            if let Some(completion) = self.poll() {
+                return completion;
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+impl BlockDevice for AsyncDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        self.device.read_block(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        self.device.write_block(block_id, buf)
+    }
+    fn num_blocks(&self) -> usize {
+        self.device.num_blocks()
+    }
+    fn submit(&self, request: BlockRequest) -> BlockResult<usize> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.sender
+            .lock()
+            .unwrap()
+            .send((id, request))
+            .map_err(|_| BlockError::Io)?;
+        Ok(id)
+    }
+    fn poll(&self) -> Option<BlockCompletion> {
+        self.completed.lock().unwrap().pop_front()
+    }
+}
+
+#[test]
+fn block_device_test() {
+    let device = SparseDevice::new(16);
+    //多块读写与逐块读写的结果一致，超出设备范围或者不足一块时返回错误
+    let content = file_content(4, 1);
+    let mut data = content[..3 * BLOCK_SIZE].to_vec();
+    device.write_blocks(4, &data).unwrap();
+    let mut block = [0u8; BLOCK_SIZE];
+    device.read_block(5, &mut block).unwrap();
+    assert_eq!(&block[..], &data[BLOCK_SIZE..2 * BLOCK_SIZE]);
+    assert_eq!(
+        device.read_blocks(14, &mut data),
+        Err(BlockError::OutOfRange)
+    );
+    assert_eq!(
+        device.write_blocks(0, &[0; 100]),
+        Err(BlockError::InvalidBuf)
+    );
+    assert_eq!(device.flush(), Ok(()));
+    //请求由后台线程处理，submit立即返回，结果按提交顺序取回
+    let device = AsyncDevice::new(Arc::new(device));
+    let write = device
+        .submit(BlockRequest::Write {
+            block_id: 8,
+            data: vec![0xaa; 2 * BLOCK_SIZE],
+        })
+        .unwrap();
+    let read = device
+        .submit(BlockRequest::Read {
+            block_id: 8,
+            count: 2,
+        })
+        .unwrap();
+    let bad = device
+        .submit(BlockRequest::Read {
+            block_id: 15,
+            count: 2,
+        })
+        .unwrap();
+    let completion = device.wait();
+    assert_eq!((completion.id, completion.result), (write, Ok(())));
+    let completion = device.wait();
+    assert_eq!((completion.id, completion.result), (read, Ok(())));
+    assert_eq!(completion.data, vec![0xaa; 2 * BLOCK_SIZE]);
+    let completion = device.wait();
+    assert_eq!(
+        (completion.id, completion.result),
+        (bad, Err(BlockError::OutOfRange))
+    );
+    assert!(device.poll().is_none());
+    assert!(device.device.blocks.lock().unwrap().get(&8).is_some());
+    //不支持请求队列的设备只能同步读写
+    let log = LogDevice {
+        blocks: Mutex::new(vec![[0u8; BLOCK_SIZE]; 4]),
+        reads: Mutex::new(Vec::new()),
+        writes: Mutex::new(Vec::new()),
+    };
+    assert_eq!(
+        log.submit(BlockRequest::Flush),
+        Err(BlockError::Unsupported)
+    );
+}
+
+#[test]
+fn large_file_test() {
+    let device: Arc<dyn BlockDevice> = Arc::new(SparseDevice::new(2_150_000));
+    FileSystem::create(device.clone(), 2_150_000, 1).unwrap();
+    let fs = FileSystem::open(device);
+    let root_inode = FileSystem::root_inode(&fs);
+    let file = root_inode.create("large").unwrap();
+    //文件扩大到四级索引，在每一级索引的边界上写入跨越边界的两个块
+    let boundaries = [DIRECT_MAX, INDIRECT1_MAX, INDIRECT2_MAX, INDIRECT3_MAX];
+    assert!(file.truncate((INDIRECT3_MAX + 2) * BLOCK_SIZE));
+    for (i, pos) in boundaries.iter().enumerate() {
+        let content = file_content(2, i as u8);
+        let offset = (pos - 1) * BLOCK_SIZE;
+        assert_eq!(file.write_at(offset, &content).unwrap(), content.len());
+    }
+    for (i, pos) in boundaries.iter().enumerate() {
+        let content = file_content(2, i as u8);
+        let mut buffer = vec![0u8; 4 * BLOCK_SIZE];
+        let offset = (pos - 2) * BLOCK_SIZE;
+        assert_eq!(file.read_at(offset, &mut buffer).unwrap(), buffer.len());
+        assert_eq!(
+            &buffer[BLOCK_SIZE..BLOCK_SIZE + content.len()],
+            &content[..]
+        );
+        //边界前后的块没有写入过，仍然为0
+        assert!(buffer[..BLOCK_SIZE].iter().all(|byte| *byte == 0));
+        assert!(buffer[BLOCK_SIZE + content.len()..]
+            .iter()
+            .all(|byte| *byte == 0));
+    }
+    //一级索引1块，二级索引1+128块，三级索引1+128+128*128块，四级索引的每一层各1块
+    let index_blocks = 1 + (1 + 128) + (1 + 128 + 128 * 128) + 4;
+    assert_eq!(
+        file.metadata().unwrap().blocks,
+        INDIRECT3_MAX + 2 + index_blocks
+    );
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //超过最大文件大小的写入与截断失败
+    assert_eq!(file.write_at(MAX_FILE_SIZE as usize, &[1]).unwrap(), 0);
+    assert!(!file.truncate(MAX_FILE_SIZE as usize + 1));
+    assert_eq!(
+        file.get_file_size().unwrap(),
+        (INDIRECT3_MAX + 2) * BLOCK_SIZE
+    );
+    //逐级缩小到每一级索引的边界，回收不再使用的索引块
+    for (i, pos) in boundaries.iter().enumerate().rev() {
+        assert!(file.truncate(pos * BLOCK_SIZE));
+        let mut buffer = vec![0u8; BLOCK_SIZE];
+        file.read_at((pos - 1) * BLOCK_SIZE, &mut buffer).unwrap();
+        assert_eq!(buffer, &file_content(2, i as u8)[..BLOCK_SIZE]);
+        assert!(easyfs::fsck(&fs, false).unwrap().is_clean(), "size {}", pos);
+    }
+    assert_eq!(file.metadata().unwrap().blocks, DIRECT_MAX);
+}
+
+#[test]
+fn extent_test() {
+    let sparse = Arc::new(SparseDevice::new(4096));
+    let device: Arc<dyn BlockDevice> = sparse.clone();
+    FileSystem::create(device.clone(), 4096, 1).unwrap();
+    let fs = FileSystem::open(device.clone());
+    fs.lock().set_extents(true).unwrap();
+    let root_inode = FileSystem::root_inode(&fs);
+    //连续分配的文件只需要一个extent，没有索引块
+    let big = root_inode.create("big").unwrap();
+    let mut content = file_content(300, 3);
+    big.write_at(0, &content).unwrap();
+    let blocks = big.block_ids().unwrap();
+    assert!(blocks.windows(2).all(|pair| pair[1] == pair[0] + 1));
+    assert_eq!(big.metadata().unwrap().blocks, 300);
+    //整块读取时一次读出多个块，缓存中还没有写回的修改以缓存为准
+    easyfs::block_cache_sync().unwrap();
+    big.write_at(10 * BLOCK_SIZE, &[0xff; 8]).unwrap();
+    content[10 * BLOCK_SIZE..10 * BLOCK_SIZE + 8].fill(0xff);
+    sparse.batch_reads.store(0, Ordering::SeqCst);
+    assert_eq!(read_all(&big), content);
+    assert!(sparse.batch_reads.load(Ordering::SeqCst) > 0);
+    //交替写入的两个文件的块不连续，extent记录放不下时改为使用块索引
+    let a = root_inode.create("a").unwrap();
+    let b = root_inode.create("b").unwrap();
+    let content_a = file_content(40, 5);
+    let content_b = file_content(40, 7);
+    for (a_chunk, b_chunk) in content_a
+        .chunks(BLOCK_SIZE)
+        .zip(content_b.chunks(BLOCK_SIZE))
+    {
+        a.write_at(a.get_file_size().unwrap(), a_chunk).unwrap();
+        b.write_at(b.get_file_size().unwrap(), b_chunk).unwrap();
+    }
+    assert_eq!(read_all(&a), content_a);
+    assert_eq!(read_all(&b), content_b);
+    assert_eq!(a.metadata().unwrap().blocks, 40 + 1);
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //截断后回收extent末尾的块
+    big.truncate(100 * BLOCK_SIZE + 5);
+    assert_eq!(read_all(&big), &content[..100 * BLOCK_SIZE + 5]);
+    assert_eq!(big.metadata().unwrap().blocks, 101);
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    root_inode.delete_nlink("a");
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //重新打开后新建的文件仍然使用extent
+    easyfs::block_cache_sync().unwrap();
+    let fs = FileSystem::open(device);
+    assert!(fs.lock().extents());
+    let root_inode = FileSystem::root_inode(&fs);
+    assert_eq!(
+        read_all(&root_inode.find_inode("big").unwrap()),
+        &content[..100 * BLOCK_SIZE + 5]
+    );
+    assert_eq!(read_all(&root_inode.find_inode("b").unwrap()), content_b);
+    assert!(easyfs::dump(&fs).unwrap().contains(" extents ["));
+}
+
+static TEST_CLOCK: AtomicU32 = AtomicU32::new(1000);
+
+#[test]
+fn metadata_test() {
+    easyfs::set_clock(|| TEST_CLOCK.load(Ordering::SeqCst));
+    let root_inode = create_test_filesystem("metadata", 4096);
+    let file = root_inode.create("file").unwrap();
+    let metadata = file.metadata().unwrap();
+    assert_eq!(metadata.mode, 0o100644);
+    assert_eq!(root_inode.metadata().unwrap().mode, 0o040755);
+    assert_eq!((metadata.uid, metadata.gid), (0, 0));
+    assert!(metadata.mtime >= 1000);
+    //写入后修改时间与大小随之变化
+    TEST_CLOCK.store(2000, Ordering::SeqCst);
+    file.write_at(0, &[7u8; 3 * BLOCK_SIZE + 1]).unwrap();
+    let metadata = file.metadata().unwrap();
+    assert_eq!(metadata.size, 3 * BLOCK_SIZE + 1);
+    assert_eq!(metadata.blocks, 4);
+    assert!(metadata.mtime >= 2000 && metadata.ctime >= 2000);
+    //读取只修改访问时间
+    TEST_CLOCK.store(3000, Ordering::SeqCst);
+    let mut buffer = [0u8; 16];
+    file.read_at(0, &mut buffer).unwrap();
+    let after_read = file.metadata().unwrap();
+    assert!(after_read.atime >= 3000);
+    assert_eq!(after_read.mtime, metadata.mtime);
+    //修改权限与所有者只修改inode的修改时间
+    TEST_CLOCK.store(4000, Ordering::SeqCst);
+    file.set_mode(0o600).unwrap();
+    file.set_owner(1000, 100).unwrap();
+    let metadata = file.metadata().unwrap();
+    assert_eq!(metadata.mode, 0o100600);
+    assert_eq!((metadata.uid, metadata.gid), (1000, 100));
+    assert_eq!(metadata.mtime, after_read.mtime);
+    assert!(metadata.ctime >= 4000);
+}
+
+#[test]
+fn no_space_test() {
+    let fs = create_test_fs("nospace", 512);
+    let root_inode = FileSystem::root_inode(&fs);
+    let empty = fs.lock().statfs().unwrap();
+    assert_eq!(empty.block_size, BLOCK_SIZE);
+    assert_eq!(empty.free_blocks, empty.blocks);
+    assert_eq!(empty.free_inodes, empty.inodes - 1);
+    //写满磁盘时只写入放得下的部分
+    let big = root_inode.create("big").unwrap();
+    let content = file_content(empty.blocks + 10, 5);
+    let written = big.write_at(0, &content).unwrap();
+    assert!(written > 0 && written < content.len());
+    let full = fs.lock().statfs().unwrap();
+    assert_eq!(full.free_blocks, 0);
+    assert_eq!(full.free_inodes, empty.free_inodes - 1);
+    assert_eq!(big.get_file_size().unwrap(), written);
+    assert_eq!(read_all(&big), &content[..written]);
+    assert_eq!(big.write_at(written, &content[written..]).unwrap(), 0);
+    //目录还有空闲的目录项时可以新建文件，但是不能写入
+    let file = root_inode.create("file").unwrap();
+    assert_eq!(file.write_at(0, b"no space").unwrap(), 0);
+    assert!(!file.truncate(BLOCK_SIZE));
+    assert_eq!(file.get_file_size().unwrap(), 0);
+    assert!(root_inode
+        .symlink("link", &"x".repeat(BLOCK_SIZE))
+        .is_none());
+    assert!(root_inode.find_inode("link").is_none());
+    //目录需要扩大时新建失败，分配的inode被回收
+    let mut count = 2;
+    while root_inode.create(&format!("f{}", count)).is_some() {
+        count += 1;
+    }
+    assert_eq!(count, BLOCK_SIZE / 32);
+    assert_eq!(
+        fs.lock().statfs().unwrap().free_inodes,
+        empty.free_inodes - count
+    );
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //删除文件后空间恢复
+    assert_eq!(root_inode.delete_nlink("big"), 0);
+    let after = fs.lock().statfs().unwrap();
+    assert_eq!(after.free_blocks, empty.blocks - 1);
+    assert_eq!(file.write_at(0, b"space").unwrap(), 5);
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+}
+
+#[test]
+fn quota_test() {
+    let fs = create_test_fs("quota", 4096);
+    let root_inode = FileSystem::root_inode(&fs);
+    let file = root_inode.create("file").unwrap();
+    file.write_at(0, &[1u8; 2 * BLOCK_SIZE]).unwrap();
+    assert!(file.set_owner(1000, 100).unwrap());
+    //设置时统计已经占用的块
+    fs.lock().set_quota(1000, Some(10)).unwrap();
+    let quota = fs.lock().quota(1000).unwrap();
+    assert_eq!((quota.limit, quota.used), (10, 2));
+    assert!(fs.lock().quota(0).is_none());
+    //超过限额的部分不写入
+    let content = file_content(20, 3);
+    assert_eq!(file.write_at(0, &content).unwrap(), 10 * BLOCK_SIZE);
+    assert_eq!(fs.lock().quota(1000).unwrap().used, 10);
+    assert_eq!(read_all(&file), &content[..10 * BLOCK_SIZE]);
+    assert!(!file.truncate(11 * BLOCK_SIZE));
+    //其它用户不受影响，所有者变化时占用随之转移
+    let other = root_inode.create("other").unwrap();
+    assert_eq!(other.write_at(0, &content).unwrap(), content.len());
+    assert!(!other.set_owner(1000, 100).unwrap());
+    assert_eq!(other.metadata().unwrap().uid, 0);
+    assert!(file.truncate(5 * BLOCK_SIZE));
+    assert_eq!(fs.lock().quota(1000).unwrap().used, 5);
+    let small = root_inode.create("small").unwrap();
+    small.write_at(0, &[2u8; 3 * BLOCK_SIZE]).unwrap();
+    assert!(small.set_owner(1000, 100).unwrap());
+    assert_eq!(fs.lock().quota(1000).unwrap().used, 8);
+    assert!(small.set_owner(0, 0).unwrap());
+    assert_eq!(fs.lock().quota(1000).unwrap().used, 5);
+    //删除文件时归还占用，取消限额后不再限制
+    assert_eq!(root_inode.delete_nlink("file"), 0);
+    assert_eq!(fs.lock().quota(1000).unwrap().used, 0);
+    assert!(small.set_owner(1000, 100).unwrap());
+    assert!(fs.lock().set_quota(1000, None).unwrap());
+    assert!(fs.lock().quota(1000).is_none());
+    assert_eq!(small.write_at(0, &content).unwrap(), content.len());
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //限额保存在超级块中，重新打开后重新统计占用
+    assert!(fs.lock().set_quota(1000, Some(50)).unwrap());
+    assert!(fs.lock().set_quota(2000, Some(7)).unwrap());
+    assert!(fs.lock().set_quota(2000, None).unwrap());
+    assert!(!fs
+        .lock()
+        .set_quota(3000, Some(u32::MAX as usize + 1))
+        .unwrap());
+    let used = fs.lock().quota(1000).unwrap().used;
+    let device = fs.lock().block_device.clone();
+    easyfs::block_cache_sync().unwrap();
+    let fs = FileSystem::open(device);
+    assert_eq!(fs.lock().quota(1000), Some(Quota { limit: 50, used }));
+    assert!(fs.lock().quota(2000).is_none());
+    //超级块中的槽位用完时不能再设置
+    let free = (1..)
+        .take_while(|uid| fs.lock().set_quota(10000 + uid, Some(1)).unwrap())
+        .count();
+    assert_eq!(free, QUOTA_SLOTS - 1);
+    assert!(fs.lock().set_quota(1000, Some(60)).unwrap());
+}
+
+#[test]
+fn try_open_test() {
+    let sparse = Arc::new(SparseDevice::new(4096));
+    let device: Arc<dyn BlockDevice> = sparse.clone();
+    //没有文件系统的设备
+    assert!(FileSystem::try_open(device.clone()).is_none());
+    FileSystem::create(device.clone(), 4096, 1).unwrap();
+    assert!(FileSystem::try_open(device.clone()).is_some());
+    //修改超级块中的一项，之后恢复
+    let patch = |offset: usize, value: u32| -> u32 {
+        easyfs::block_cache_invalidate(&device);
+        let mut block = [0u8; BLOCK_SIZE];
+        sparse.read_block(0, &mut block).unwrap();
+        let old = u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap());
+        block[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        sparse.write_block(0, &block).unwrap();
+        old
+    };
+    //不支持的版本、超出设备的大小、数据区超出文件系统以及错误的魔数
+    for (offset, value) in [(24, 100), (4, 8192), (20, 5000), (0, 0)] {
+        let old = patch(offset, value);
+        assert!(FileSystem::try_open(device.clone()).is_none());
+        patch(offset, old);
+    }
+    let fs = FileSystem::try_open(device).unwrap();
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+}
+
+///按照第一版的磁盘格式构造镜像
+///4096个块，1个inode位图块：inode区为2..130，数据位图为130，数据区从131开始
+struct V1Image {
+    data: Vec<u8>,
+    next_block: u32,
+}
+
+impl V1Image {
+    const INODE_START: usize = 2;
+    const DATA_BITMAP: usize = 130;
+    const DATA_START: u32 = 131;
+
+    fn new() -> Self {
+        let mut image = Self {
+            data: vec![0u8; 4096 * BLOCK_SIZE],
+            next_block: 0,
+        };
+        //超级块，第一版没有版本号
+        for (i, value) in [EFS_MAGIC, 4096, 1, 128, 1, 3965].iter().enumerate() {
+            image.put_u32(0, i * 4, *value);
+        }
+        image
+    }
+    fn put_u32(&mut self, block: usize, offset: usize, value: u32) {
+        let start = block * BLOCK_SIZE + offset;
+        self.data[start..start + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    fn alloc_block(&mut self) -> u32 {
+        let bit = self.next_block as usize;
+        self.data[Self::DATA_BITMAP * BLOCK_SIZE + bit / 8] |= 1 << (bit % 8);
+        self.next_block += 1;
+        Self::DATA_START + bit as u32
+    }
+    ///写入第ino个inode及其数据，目录的node_type为1
+    fn put_inode(&mut self, ino: usize, node_type: u8, content: &[u8]) {
+        self.data[BLOCK_SIZE + ino / 8] |= 1 << (ino % 8);
+        let mut blocks = Vec::new();
+        for chunk in content.chunks(BLOCK_SIZE) {
+            let block = self.alloc_block();
+            let start = block as usize * BLOCK_SIZE;
+            self.data[start..start + chunk.len()].copy_from_slice(chunk);
+            blocks.push(block);
+        }
+        let (block, offset) = (Self::INODE_START + ino / 4, ino % 4 * 128);
+        self.put_u32(block, offset, 1);
+        self.put_u32(block, offset + 4, content.len() as u32);
+        for (i, id) in blocks.iter().take(27).enumerate() {
+            self.put_u32(block, offset + 8 + i * 4, *id);
+        }
+        let rest: Vec<u32> = blocks.iter().skip(27).cloned().collect();
+        if !rest.is_empty() {
+            let indirect1 = self.alloc_block();
+            self.put_u32(block, offset + 116, indirect1);
+            for (i, id) in rest.iter().take(128).enumerate() {
+                self.put_u32(indirect1 as usize, i * 4, *id);
+            }
+        }
+        if rest.len() > 128 {
+            let indirect2 = self.alloc_block();
+            self.put_u32(block, offset + 120, indirect2);
+            for (i, chunk) in rest[128..].chunks(128).enumerate() {
+                let second = self.alloc_block();
+                self.put_u32(indirect2 as usize, i * 4, second);
+                for (j, id) in chunk.iter().enumerate() {
+                    self.put_u32(second as usize, j * 4, *id);
+                }
+            }
+        }
+        self.data[block * BLOCK_SIZE + offset + 124] = node_type;
+    }
+}
+
+fn file_content(blocks: usize, seed: u8) -> Vec<u8> {
+    (0..blocks * BLOCK_SIZE - 7)
+        .map(|i| (i / BLOCK_SIZE) as u8 ^ seed)
+        .collect()
+}
+
+#[test]
+fn migrate_test() {
+    let files = [
+        ("small", file_content(3, 1)),
+        ("medium", file_content(25, 2)),
+        ("large", file_content(200, 3)),
+    ];
+    let mut image = V1Image::new();
+    let mut root = Vec::new();
+    for (i, (name, _)) in files.iter().enumerate() {
+        let mut entry = [0u8; 32];
+        entry[..name.len()].copy_from_slice(name.as_bytes());
+        entry[28..].copy_from_slice(&(i as u32 + 1).to_le_bytes());
+        root.extend_from_slice(&entry);
+    }
+    image.put_inode(0, 1, &root);
+    for (i, (_, content)) in files.iter().enumerate() {
+        image.put_inode(i + 1, 0, content);
+    }
+
+    let (path, block_file) = create_image("easyfs-migrate", image.data.len() / BLOCK_SIZE);
+    std::fs::remove_file(&path).unwrap();
+    for (block_id, block) in image.data.chunks(BLOCK_SIZE).enumerate() {
+        block_file.write_block(block_id, block).unwrap();
+    }
+    let fs = FileSystem::open(block_file.clone());
+    let root_inode = FileSystem::root_inode(&fs);
+    //迁移后超级块记录新的版本号
+    let mut block = [0u8; BLOCK_SIZE];
+    block_file.read_block(0, &mut block).unwrap();
+    assert_eq!(
+        u32::from_le_bytes(block[24..28].try_into().unwrap()),
+        easyfs::EFS_VERSION
+    );
+    assert_eq!(root_inode.ls().unwrap(), vec!["small", "medium", "large"]);
+    assert_eq!(root_inode.metadata().unwrap().mode, 0o040755);
+    for (name, content) in files.iter() {
+        let inode = root_inode.find_inode(name).unwrap();
+        assert_eq!(inode.metadata().unwrap().mode, 0o100644);
+        assert_eq!(inode.get_disk_nlink().unwrap(), 1);
+        let mut buffer = vec![0u8; content.len() + BLOCK_SIZE];
+        let len = inode.read_at(0, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &content[..]);
+    }
+    //迁移后的文件可以继续扩容与回收
+    let large = root_inode.find_inode("large").unwrap();
+    let size = large.get_file_size().unwrap();
+    large.write_at(size, &[9u8; 100 * BLOCK_SIZE]).unwrap();
+    let mut buffer = [0u8; 4];
+    large.read_at(size + 99 * BLOCK_SIZE, &mut buffer).unwrap();
+    assert_eq!(buffer, [9u8; 4]);
+    large.clear();
+    let file = root_inode.create("new").unwrap();
+    file.write_at(0, &[5u8; 300 * BLOCK_SIZE]).unwrap();
+    let small = root_inode.find_inode("small").unwrap();
+    let mut buffer = vec![0u8; files[0].1.len()];
+    small.read_at(0, &mut buffer).unwrap();
+    assert_eq!(buffer, files[0].1);
+}
+
+///记录读写顺序的内存块设备
+struct LogDevice {
+    blocks: Mutex<Vec<[u8; BLOCK_SIZE]>>,
+    reads: Mutex<Vec<usize>>,
+    writes: Mutex<Vec<usize>>,
+}
+
+impl BlockDevice for LogDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        self.reads.lock().unwrap().push(block_id);
+        buf.copy_from_slice(&self.blocks.lock().unwrap()[block_id]);
+        Ok(())
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        self.writes.lock().unwrap().push(block_id);
+        self.blocks.lock().unwrap()[block_id].copy_from_slice(buf);
+        Ok(())
+    }
+    fn num_blocks(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+}
+
+#[test]
+fn block_cache_test() {
+    let log = Arc::new(LogDevice {
+        blocks: Mutex::new(vec![[0u8; BLOCK_SIZE]; 16]),
+        reads: Mutex::new(Vec::new()),
+        writes: Mutex::new(Vec::new()),
+    });
+    let device: Arc<dyn BlockDevice> = log.clone();
+    let mut manager = BlockCacheManager::new(4);
+    for block_id in [3, 1, 2, 0] {
+        manager
+            .get_block_cache(block_id, device.clone())
+            .unwrap()
+            .lock()
+            .modify(0, |value: &mut u32| *value = block_id as u32 + 100);
+    }
+    //修改不会立即写回
+    assert!(log.writes.lock().unwrap().is_empty());
+    //访问块3后块1成为最久未使用的块
+    manager.get_block_cache(3, device.clone()).unwrap();
+    manager.get_block_cache(4, device.clone()).unwrap();
+    //替换脏块时按块号顺序批量写回所有未被引用的脏块
+    assert_eq!(*log.writes.lock().unwrap(), vec![0, 1, 2, 3]);
+    manager.get_block_cache(1, device.clone()).unwrap();
+    assert_eq!(*log.reads.lock().unwrap(), vec![3, 1, 2, 0, 4, 1]);
+    assert_eq!(
+        manager
+            .get_block_cache(1, device.clone())
+            .unwrap()
+            .lock()
+            .read(0, |value: &u32| *value),
+        101
+    );
+    //所有块都被引用时暂时超出容量而不是panic
+    let pinned: Vec<_> = (5..10)
+        .map(|block_id| manager.get_block_cache(block_id, device.clone()))
+        .collect();
+    let stats = manager.stats();
+    assert_eq!(stats.cached, 5);
+    drop(pinned);
+    manager.get_block_cache(10, device.clone()).unwrap();
+    let stats = manager.stats();
+    assert_eq!(stats.cached, 4);
+    assert_eq!(stats.hits, 2);
+    assert_eq!(stats.misses, 12);
+    assert_eq!(stats.dirty, 0);
+}
+
+///第limit次写入时panic模拟断电，之后所有的写入都被丢弃
+struct CrashDevice<D = BlockFile> {
+    inner: D,
+    limit: usize,
+    writes: AtomicUsize,
+}
+
+impl<D: BlockDevice> BlockDevice for CrashDevice<D> {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        self.inner.read_block(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        let count = self.writes.fetch_add(1, Ordering::SeqCst);
+        if count < self.limit {
+            self.inner.write_block(block_id, buf)?;
+        } else if count == self.limit {
+            panic!("crash at write {}", count);
+        }
+        Ok(())
+    }
+    fn num_blocks(&self) -> usize {
+        self.inner.num_blocks()
+    }
+}
+
+fn open_image(path: &std::path::Path) -> BlockFile {
+    BlockFile(Mutex::new(
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap(),
+    ))
+}
+
+fn read_all(inode: &Inode) -> Vec<u8> {
+    let mut buffer = vec![0u8; inode.get_file_size().unwrap()];
+    let len = inode.read_at(0, &mut buffer).unwrap();
+    buffer.truncate(len);
+    buffer
+}
+
+///检查目录项、硬链接计数、位图与文件占用的块是否一致
+fn check_consistency(fs: &Arc<spin::Mutex<FileSystem>>, contents: &[&[u8]]) {
+    let root_inode = FileSystem::root_inode(fs);
+    let mut links: BTreeMap<usize, (u32, Arc<Inode>)> = BTreeMap::new();
+    for name in root_inode.ls().unwrap() {
+        let inode = root_inode.find_inode(&name).unwrap();
+        let content = read_all(&inode);
+        assert!(
+            contents.iter().any(|expect| expect.starts_with(&content)),
+            "{} has unexpected content",
+            name
+        );
+        links.entry(inode.get_disk_inode()).or_insert((0, inode)).0 += 1;
+    }
+    let mut used = BTreeSet::new();
+    for block_id in root_inode.block_ids().unwrap() {
+        assert!(used.insert(block_id));
+    }
+    for (ino, (count, inode)) in links.iter() {
+        assert!(fs.lock().is_inode_allocated(*ino as u32).unwrap());
+        assert_eq!(inode.get_disk_nlink().unwrap(), *count);
+        for block_id in inode.block_ids().unwrap() {
+            assert!(used.insert(block_id), "block {} is shared", block_id);
+        }
+    }
+    let fs = fs.lock();
+    for block_id in used.iter() {
+        assert!(fs.is_data_allocated(*block_id).unwrap());
+    }
+    let allocated = fs
+        .data_area()
+        .filter(|id| fs.is_data_allocated(*id).unwrap())
+        .count();
+    assert_eq!(allocated, used.len(), "data blocks leaked");
+    let inodes = (0..fs.inode_count())
+        .filter(|ino| fs.is_inode_allocated(*ino).unwrap())
+        .count();
+    assert_eq!(inodes, links.len() + 1, "inodes leaked");
+}
+
+#[test]
+fn crash_test() {
+    let content_a = vec![1u8; 3 * BLOCK_SIZE];
+    let content_b = vec![2u8; 25 * BLOCK_SIZE];
+    let content_c: Vec<u8> = (0..40 * BLOCK_SIZE)
+        .map(|i| (i / BLOCK_SIZE) as u8)
+        .collect();
+    let contents: [&[u8]; 3] = [&content_a, &content_b, &content_c];
+    //准备崩溃之前的镜像
+    let (path, device) = create_image("easyfs-crash", 4096);
+    {
+        FileSystem::create(device.clone(), 4096, 1).unwrap();
+        let fs = FileSystem::open(device);
+        let root_inode = FileSystem::root_inode(&fs);
+        root_inode
+            .create("a")
+            .unwrap()
+            .write_at(0, &content_a)
+            .unwrap();
+        root_inode
+            .create("b")
+            .unwrap()
+            .write_at(0, &content_b)
+            .unwrap();
+        root_inode.create_nlink("b2", "b").unwrap().fsync().unwrap();
+    }
+    let image = std::fs::read(&path).unwrap();
+    //每个操作都是一个事务，在任意一次写入时崩溃都应该得到一致的文件系统
+    let scenario = |limit: usize| -> usize {
+        std::fs::write(&path, &image).unwrap();
+        let device = Arc::new(CrashDevice {
+            inner: open_image(&path),
+            limit,
+            writes: AtomicUsize::new(0),
+        });
+        let fs = FileSystem::open(device.clone());
+        let root_inode = FileSystem::root_inode(&fs);
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let c = root_inode.create("c").unwrap();
+            c.write_at(0, &content_c).unwrap();
+            root_inode.create_nlink("d", "c").unwrap();
+            root_inode.rename("a", &root_inode, "b").unwrap();
+            root_inode.delete_nlink("b2");
+            c.clear();
+            root_inode.delete_nlink("d");
+            c.fsync().unwrap();
+        }));
+        //崩溃后内存中的缓存全部丢失
+        easyfs::block_cache_invalidate(&(device.clone() as Arc<dyn BlockDevice>));
+        device.writes.load(Ordering::SeqCst)
+    };
+    //没有崩溃时得到最终的状态
+    let total = scenario(usize::MAX);
+    let fs = FileSystem::open(Arc::new(open_image(&path)));
+    check_consistency(&fs, &contents);
+    let root_inode = FileSystem::root_inode(&fs);
+    assert_eq!(root_inode.ls().unwrap(), vec!["b", "c"]);
+    assert_eq!(read_all(&root_inode.find_inode("b").unwrap()), content_a);
+    assert_eq!(
+        root_inode.find_inode("c").unwrap().get_file_size().unwrap(),
+        0
+    );
+    //缓存替换的时机不同，写入次数可能略有差别
+    for limit in 0..total {
+        scenario(limit);
+        let fs = FileSystem::open(Arc::new(open_image(&path)));
+        check_consistency(&fs, &contents);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+///可以随时让所有读写失败的块设备
+struct FaultDevice {
+    inner: SparseDevice,
+    failing: AtomicBool,
+}
+
+impl BlockDevice for FaultDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> BlockResult {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(BlockError::Io);
+        }
+        self.inner.read_block(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> BlockResult {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(BlockError::Io);
+        }
+        self.inner.write_block(block_id, buf)
+    }
+    fn num_blocks(&self) -> usize {
+        self.inner.num_blocks()
+    }
+    fn flush(&self) -> BlockResult {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(BlockError::Io);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn io_error_test() {
+    let device = Arc::new(FaultDevice {
+        inner: SparseDevice::new(4096),
+        failing: AtomicBool::new(false),
+    });
+    let dyn_device: Arc<dyn BlockDevice> = device.clone();
+    FileSystem::create(dyn_device.clone(), 4096, 1).unwrap();
+    let fs = FileSystem::open(dyn_device.clone());
+    let root_inode = FileSystem::root_inode(&fs);
+    let content = vec![3u8; 5 * BLOCK_SIZE];
+    let file = root_inode.create("file").unwrap();
+    file.write_at(0, &content).unwrap();
+    file.fsync().unwrap();
+    //读写失败时返回错误，而不是panic
+    easyfs::block_cache_invalidate(&dyn_device);
+    device.failing.store(true, Ordering::SeqCst);
+    let mut buffer = vec![0u8; content.len()];
+    assert_eq!(file.read_at(0, &mut buffer), Err(BlockError::Io));
+    assert_eq!(file.write_at(0, &[4u8; 10]), Err(BlockError::Io));
+    assert!(root_inode.find_inode("file").is_none());
+    assert!(root_inode.create("other").is_none());
+    //设备恢复后可以继续读写，fsync报告写回的失败
+    device.failing.store(false, Ordering::SeqCst);
+    file.write_at(0, &[4u8; 10]).unwrap();
+    device.failing.store(true, Ordering::SeqCst);
+    assert_eq!(file.fsync(), Err(BlockError::Io));
+    device.failing.store(false, Ordering::SeqCst);
+    file.fsync().unwrap();
+    easyfs::block_cache_invalidate(&dyn_device);
+    let mut expect = content.clone();
+    expect[..10].fill(4);
+    assert_eq!(read_all(&file), expect);
+    assert_eq!(root_inode.ls().unwrap(), vec!["file"]);
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+}
+
+#[test]
+fn crash_large_test() {
+    //文件的数据块分布在比日志容量更多的位图块中，删除时不能放在一个事务里
+    let bits = BLOCK_SIZE * 8;
+    let big_blocks = (JOURNAL_BLOCKS + 1) * bits;
+    let total_blocks = big_blocks + 4 * bits;
+    let content_a = vec![1u8; 3 * BLOCK_SIZE];
+    let content_big = vec![0u8; big_blocks * BLOCK_SIZE];
+    let contents: [&[u8]; 2] = [&content_a, &content_big];
+    let image = {
+        let sparse = Arc::new(SparseDevice::new(total_blocks));
+        let device: Arc<dyn BlockDevice> = sparse.clone();
+        FileSystem::create(device.clone(), total_blocks, 1).unwrap();
+        let fs = FileSystem::open(device.clone());
+        let root_inode = FileSystem::root_inode(&fs);
+        root_inode
+            .create("a")
+            .unwrap()
+            .write_at(0, &content_a)
+            .unwrap();
+        let big = root_inode.create("big").unwrap();
+        assert!(big.truncate(content_big.len()));
+        big.fsync().unwrap();
+        easyfs::block_cache_invalidate(&device);
+        sparse.snapshot()
+    };
+    let scenario = |limit: usize| -> (usize, SparseDevice) {
+        let device = Arc::new(CrashDevice {
+            inner: image.snapshot(),
+            limit,
+            writes: AtomicUsize::new(0),
+        });
+        let fs = FileSystem::open(device.clone());
+        let root_inode = FileSystem::root_inode(&fs);
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            assert_eq!(root_inode.delete_nlink("big"), 0);
+            root_inode.fsync().unwrap();
+        }));
+        easyfs::block_cache_invalidate(&(device.clone() as Arc<dyn BlockDevice>));
+        (
+            device.writes.load(Ordering::SeqCst),
+            device.inner.snapshot(),
+        )
+    };
+    let (total, image) = scenario(usize::MAX);
+    let fs = FileSystem::open(Arc::new(image));
+    check_consistency(&fs, &contents);
+    assert_eq!(FileSystem::root_inode(&fs).ls().unwrap(), vec!["a"]);
+    //每个事务的写入次数相同，抽取其中的一部分崩溃位置
+    for limit in (0..total).step_by(total / 16 + 1).chain(total - 4..total) {
+        let (_, image) = scenario(limit);
+        let fs = FileSystem::open(Arc::new(image));
+        check_consistency(&fs, &contents);
+    }
+}
+
+#[test]
+fn fsck_test() {
+    let content_a = vec![1u8; 3 * BLOCK_SIZE];
+    let content_b = vec![2u8; 2 * BLOCK_SIZE];
+    let contents: [&[u8]; 2] = [&content_a, &content_b];
+    let (path, device) = create_image("easyfs-fsck", 4096);
+    FileSystem::create(device.clone(), 4096, 1).unwrap();
+    let fs = FileSystem::open(device);
+    let root_inode = FileSystem::root_inode(&fs);
+    let a = root_inode.create("a").unwrap();
+    a.write_at(0, &content_a).unwrap();
+    let b = root_inode.create("b").unwrap();
+    b.write_at(0, &content_b).unwrap();
+    root_inode.create("dangling").unwrap();
+    root_inode
+        .create("orphan")
+        .unwrap()
+        .write_at(0, &[3u8; 4 * BLOCK_SIZE])
+        .unwrap();
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    //绕过正常的接口制造各种不一致
+    let orphan = root_inode.unlink("orphan").unwrap(); //没有release
+    let orphan_ino = orphan.get_disk_inode() as u32;
+    let orphan_blocks = orphan.block_ids().unwrap();
+    a.add_disk_nlink().unwrap();
+    let dangling = root_inode.find_inode("dangling").unwrap().get_disk_inode() as u32;
+    fs.lock().dealloc_inode(dangling).unwrap();
+    let leaked = fs.lock().alloc_data().unwrap().unwrap();
+    let unallocated = b.block_ids().unwrap()[0];
+    fs.lock().dealloc_data(unallocated).unwrap();
+    let report = easyfs::fsck(&fs, false).unwrap();
+    let mut expect = vec![
+        Problem::DanglingEntry {
+            dir: 0,
+            name: "dangling".to_string(),
+            ino: dangling,
+        },
+        Problem::WrongNlink {
+            ino: a.get_disk_inode() as u32,
+            nlink: 2,
+            links: 1,
+        },
+        Problem::UnallocatedBlock {
+            block_id: unallocated,
+            ino: b.get_disk_inode() as u32,
+        },
+        Problem::OrphanInode { ino: orphan_ino },
+    ];
+    let mut leaks: Vec<u32> = orphan_blocks.clone();
+    leaks.push(leaked);
+    leaks.sort();
+    expect.extend(leaks.iter().map(|block_id| Problem::LeakedBlock {
+        block_id: *block_id,
+    }));
+    assert_eq!(report.problems, expect);
+    assert_eq!(report.repaired, 0);
+    //修复之后再次检查没有问题，文件内容不变
+    let report = easyfs::fsck(&fs, true).unwrap();
+    assert_eq!(report.repaired, expect.len());
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    check_consistency(&fs, &contents);
+    assert_eq!(root_inode.ls().unwrap(), vec!["a", "b"]);
+    assert_eq!(read_all(&a), content_a);
+    assert_eq!(read_all(&b), content_b);
+    let dump = easyfs::dump(&fs).unwrap();
+    assert!(dump.contains("inodes 3/"));
+    assert!(dump.contains(" a ") && dump.contains(" b "));
+    //修复的结果已经写回磁盘
+    drop(fs);
+    let fs = FileSystem::open(Arc::new(open_image(&path)));
+    assert!(easyfs::fsck(&fs, false).unwrap().is_clean());
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn fat_read_all(inode: &FatInode) -> Vec<u8> {
+    let mut buffer = vec![0u8; inode.size().unwrap()];
+    let len = inode.read_at(0, &mut buffer).unwrap();
+    buffer.truncate(len);
+    buffer
+}
+
+///写回缓存并丢弃，之后重新打开镜像读到的是磁盘上的内容
+fn fat_reopen(fs: &Arc<FatFileSystem>, path: &std::path::Path) -> Arc<FatFileSystem> {
+    fs.sync().unwrap();
+    easyfs::block_cache_invalidate(&fs.device());
+    FatFileSystem::open(Arc::new(open_image(path))).unwrap()
+}
+
+///宿主机上是否有某个命令
+fn has_command(name: &str) -> bool {
+    std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("command -v {}", name))
+        .output()
+        .map_or(false, |output| output.status.success())
+}
+
+#[test]
+fn fat32_test() {
+    let (path, device) = create_image("fat32", 16384);
+    assert!(!fat32::is_fat32(&device));
+    assert!(fat32::format(&device, "godos"));
+    assert!(fat32::is_fat32(&device));
+    let fs = FatFileSystem::open(device).unwrap();
+    assert_eq!(fs.label(), b"GODOS");
+    let free = fs.free_clusters().unwrap();
+    let root = fs.root();
+    assert!(root.is_dir());
+    //跨越多个簇的写入与读出
+    let content: Vec<u8> = (0..20 * BLOCK_SIZE + 100)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let file = root.create("data.bin").unwrap();
+    assert_eq!(file.write_at(0, &content).unwrap(), content.len());
+    assert_eq!(fat_read_all(&file), content);
+    assert!(root.create("DATA.BIN").is_none());
+    //同一个文件只有一个FatInode
+    assert!(Arc::ptr_eq(&file, &root.find_inode("Data.Bin").unwrap()));
+    let mut buffer = [0u8; 700];
+    assert_eq!(file.read_at(BLOCK_SIZE - 50, &mut buffer).unwrap(), 700);
+    assert_eq!(&buffer[..], &content[BLOCK_SIZE - 50..BLOCK_SIZE + 650]);
+    //缩小之后再扩大，中间的部分读到0
+    assert!(file.truncate(10));
+    assert!(file.truncate(3 * BLOCK_SIZE));
+    let data = fat_read_all(&file);
+    assert_eq!(&data[..10], &content[..10]);
+    assert!(data[10..].iter().all(|byte| *byte == 0));
+    assert_eq!(file.write_at(5 * BLOCK_SIZE, b"tail").unwrap(), 4);
+    assert_eq!(file.size().unwrap(), 5 * BLOCK_SIZE + 4);
+    assert!(fat_read_all(&file)[3 * BLOCK_SIZE..5 * BLOCK_SIZE]
+        .iter()
+        .all(|byte| *byte == 0));
+    assert!(file.write_at(0, &content).unwrap() == content.len());
+    //子目录
+    let dir = root.mkdir("subdir").unwrap();
+    assert!(dir.is_dir());
+    for i in 0..40 {
+        dir.create(&format!("file{}", i))
+            .unwrap()
+            .write_at(0, format!("content {}", i).as_bytes())
+            .unwrap();
+    }
+    assert_eq!(dir.ls().unwrap().len(), 40);
+    assert!(root.unlink("subdir").is_none());
+    //重新打开之后内容不变
+    drop(file);
+    let fs = fat_reopen(&fs, &path);
+    let root = fs.root();
+    assert_eq!(root.ls().unwrap(), vec!["data.bin", "subdir"]);
+    let file = root.find_inode("data.bin").unwrap();
+    assert_eq!(fat_read_all(&file), content);
+    assert_ne!(file.ino(), fs.root().ino());
+    let dir = root.find_inode("SUBDIR").unwrap();
+    assert_eq!(
+        fat_read_all(&dir.find_inode("file17").unwrap()),
+        b"content 17"
+    );
+    //重命名时替换已经存在的文件，被打开的文件在release时才回收
+    let renamed = root.create("other").unwrap();
+    renamed.write_at(0, b"other").unwrap();
+    let replaced = root.rename("data.bin", "other").unwrap().unwrap();
+    assert!(Arc::ptr_eq(&replaced, &renamed));
+    assert_eq!(replaced.metadata().unwrap().nlink, 0);
+    assert!(replaced.release());
+    assert_eq!(fat_read_all(&root.find_inode("other").unwrap()), content);
+    assert!(Arc::ptr_eq(&file, &root.find_inode("other").unwrap()));
+    assert!(root.find_inode("data.bin").is_none());
+    //删除所有文件之后空闲簇恢复
+    for name in dir.ls().unwrap() {
+        assert!(dir.unlink(&name).unwrap().release());
+    }
+    assert!(root.unlink("subdir").unwrap().release());
+    let removed = root.unlink("other").unwrap();
+    assert!(Arc::ptr_eq(&removed, &file));
+    assert_eq!(fat_read_all(&file), content);
+    assert!(file.release());
+    //子目录扩大时分配的簇随子目录一起回收
+    assert_eq!(fs.free_clusters().unwrap(), free);
+    let fs = fat_reopen(&fs, &path);
+    assert_eq!(fs.free_clusters().unwrap(), free);
+    assert!(fs.root().ls().unwrap().is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn fat32_long_name_test() {
+    let (path, device) = create_image("fat32-lfn", 8192);
+    assert!(fat32::format(&device, ""));
+    let fs = FatFileSystem::open(device).unwrap();
+    let root = fs.root();
+    let names = [
+        "README",
+        "readme.md",
+        "A Long File Name With Spaces.text",
+        "a long file name with spaces.text2",
+        "MixedCase.Rs",
+        "中文文件名.txt",
+        "archive.tar.gz",
+        &"x".repeat(255),
+    ];
+    for (i, name) in names.iter().enumerate() {
+        root.create(name)
+            .unwrap()
+            .write_at(0, &[i as u8; 3])
+            .unwrap();
+    }
+    assert!(root.create(&"x".repeat(256)).is_none());
+    assert!(root.create("bad:name").is_none());
+    assert!(root.create("trailing.").is_none());
+    //名称中的大小写被保留，查找时不区分大小写
+    let mut expect: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    assert_eq!(root.ls().unwrap(), expect);
+    assert!(root.find_inode("mixedcase.rs").is_some());
+    assert!(root
+        .find_inode("A LONG FILE NAME WITH SPACES.TEXT")
+        .is_some());
+    //长文件名同时有一个带~n后缀的短名称
+    assert!(root.find_inode("ALONGF~1.TEX").is_some());
+    assert!(root.find_inode("ALONGF~2.TEX").is_some());
+    root.rename("archive.tar.gz", "Archive.TAR.GZ").unwrap();
+    //新的目录项写在目录的末尾
+    expect.remove(6);
+    expect.push(String::from("Archive.TAR.GZ"));
+    let fs = fat_reopen(&fs, &path);
+    let root = fs.root();
+    assert_eq!(root.ls().unwrap(), expect);
+    //按照原来的名称查找，不区分大小写
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(fat_read_all(&root.find_inode(name).unwrap()), [i as u8; 3]);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+///使用mkfs.vfat生成的镜像，需要主机上有dosfstools，通过cargo test -- --ignored运行
+///有mtools时用它写入文件，有fsck.fat时检查我们写入之后的镜像
+#[test]
+#[ignore]
+fn fat32_mkfs_vfat_test() {
+    assert!(has_command("mkfs.vfat"), "mkfs.vfat not found");
+    let (path, device) = create_image("fat32-mkfs", 131072);
+    drop(device);
+    let status = std::process::Command::new("mkfs.vfat")
+        .args(["-F", "32", "-n", "FIXTURE"])
+        .arg(&path)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+    let mtools = has_command("mcopy") && has_command("mmd") && has_command("mtype");
+    let content: Vec<u8> = (0..10000).map(|i| (i % 253) as u8).collect();
+    if mtools {
+        let source = path.with_extension("src");
+        std::fs::write(&source, &content).unwrap();
+        let image = path.to_str().unwrap();
+        let run = |args: &[&str]| {
+            let status = std::process::Command::new(args[0])
+                .args(["-i", image])
+                .args(&args[1..])
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        run(&["mmd", "::/Some Directory"]);
+        run(&[
+            "mcopy",
+            source.to_str().unwrap(),
+            "::/Some Directory/Long Name.bin",
+        ]);
+        std::fs::remove_file(&source).unwrap();
+    }
+    let device: Arc<dyn BlockDevice> = Arc::new(open_image(&path));
+    assert!(fat32::is_fat32(&device));
+    let fs = FatFileSystem::open(device).unwrap();
+    assert_eq!(fs.label(), b"FIXTURE");
+    let root = fs.root();
+    if mtools {
+        let dir = root.find_inode("Some Directory").unwrap();
+        assert_eq!(dir.ls().unwrap(), vec!["Long Name.bin"]);
+        assert_eq!(
+            fat_read_all(&dir.find_inode("long name.bin").unwrap()),
+            content
+        );
+    }
+    let file = root.create("Written By GodOS.txt").unwrap();
+    file.write_at(0, &content).unwrap();
+    root.mkdir("dir").unwrap().create("inner").unwrap();
+    let fs = fat_reopen(&fs, &path);
+    assert_eq!(
+        fat_read_all(&fs.root().find_inode("written by godos.txt").unwrap()),
+        content
+    );
+    drop(fs);
+    if has_command("fsck.fat") {
+        let output = std::process::Command::new("fsck.fat")
+            .arg("-n")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+    if mtools {
+        let output = std::process::Command::new("mtype")
+            .args(["-i", path.to_str().unwrap(), "::/Written By GodOS.txt"])
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, content);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
 // 打包应用程序
 fn package() -> std::io::Result<()> {
     let matches = App::new("Get Application Package")
@@ -142,6 +1723,11 @@ fn package() -> std::io::Result<()> {
                 .help("Set the target path")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("fat")
+                .long("fat")
+                .help("Use FAT32 as the root filesystem"),
+        )
         .get_matches();
     let source = matches.value_of("source").unwrap(); //获取源文件目录
     let target = matches.value_of("target").unwrap();
@@ -159,6 +1745,9 @@ fn package() -> std::io::Result<()> {
         .collect();
 
     filenames.sort();
+    if matches.is_present("fat") {
+        return package_fat(&filenames, target);
+    }
     let root_inode = crate_filesystem(); // for name in filenames{
 
     let mut size_count = 0;
@@ -170,13 +1759,13 @@ fn package() -> std::io::Result<()> {
         let new_inode = root_inode.create(name.as_str()).unwrap(); //新建一个文件
         size_v.push(data.len());
         // println!("name: {},size:{}",name,data.len());
-        new_inode.write_at(0, data.as_slice());
-        size_count += new_inode.get_file_size();
+        new_inode.write_at(0, data.as_slice()).unwrap();
+        size_count += new_inode.get_file_size().unwrap();
     });
     let mut i = 0;
     filenames.iter().for_each(|name| {
         let inode = root_inode.find_inode(name.as_str()).unwrap();
-        let size = inode.get_file_size();
+        let size = inode.get_file_size().unwrap();
         // println!("{}-{}",size_V[i],size);
         assert_eq!(size, size_v[i]);
         i += 1;
@@ -185,6 +1774,27 @@ fn package() -> std::io::Result<()> {
     Ok(())
 }
 
+/// 将应用程序打包到FAT32格式的根文件系统中
+fn package_fat(filenames: &[String], target: &str) -> std::io::Result<()> {
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open("../user/target/riscv64gc-unknown-none-elf/release/fs.img")?;
+    f.set_len(8192 * 512 * 4)?;
+    let device: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(f)));
+    assert!(fat32::format(&device, "GODOS"));
+    let fs = FatFileSystem::open(device).unwrap();
+    let root = fs.root();
+    for name in filenames {
+        let data = std::fs::read(format!("{}{}", target, name))?;
+        let inode = root.create(name).unwrap();
+        assert_eq!(inode.write_at(0, &data).unwrap(), data.len());
+    }
+    fs.sync().unwrap();
+    Ok(())
+}
+
 fn main() {
     // println!("Test filesystem...");
     package().unwrap();
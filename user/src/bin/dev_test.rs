@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+use lib::{close, fstat, ls_dir, lseek, open, read, write, OpenFlags, Stat, StatMode, SEEK_END};
+
+/// 测试/dev中的设备节点，输出 Test dev OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 512];
+    let len = ls_dir("/dev\0", &mut buf);
+    assert!(len > 0);
+    let names = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    for device in ["console", "null", "zero", "random", "vda"] {
+        assert!(names.lines().any(|name| name == device));
+    }
+
+    //重定向到/dev/null时会带上新建标志，设备节点不会被清空或者替换
+    let null = open("/dev/null\0", OpenFlags::C | OpenFlags::W);
+    assert!(null >= 0);
+    assert_eq!(write(null as usize, b"discarded"), 9);
+    assert_eq!(read(null as usize, &mut buf), 0);
+    let stat = Stat::new();
+    fstat(null as usize, &stat);
+    assert_eq!(stat.mode.file_type(), StatMode::CHAR);
+    close(null as usize);
+
+    let zero = open("/dev/zero\0", OpenFlags::R) as usize;
+    buf.fill(1);
+    assert_eq!(read(zero, &mut buf[..100]), 100);
+    assert!(buf[..100].iter().all(|byte| *byte == 0));
+    close(zero);
+
+    let random = open("/dev/random\0", OpenFlags::R) as usize;
+    let mut other = [0u8; 32];
+    assert_eq!(read(random, &mut buf[..32]), 32);
+    assert_eq!(read(random, &mut other), 32);
+    assert_ne!(buf[..32], other);
+    close(random);
+
+    //块设备以字节为单位读写，大小是整个磁盘的容量
+    let vda = open("/dev/vda\0", OpenFlags::R) as usize;
+    let stat = Stat::new();
+    fstat(vda, &stat);
+    assert_eq!(stat.mode.file_type(), StatMode::BLOCK);
+    assert!(stat.size > 0);
+    assert_eq!(read(vda, &mut buf[..100]), 100);
+    assert_eq!(lseek(vda, 0, SEEK_END), stat.size as isize);
+    assert_eq!(read(vda, &mut buf), 0);
+    close(vda);
+
+    assert_eq!(open("/dev/unknown\0", OpenFlags::R), -1);
+    let console = open("/dev/console\0", OpenFlags::W) as usize;
+    let message = "Test dev OK!\n";
+    assert_eq!(write(console, message.as_bytes()), message.len() as isize);
+    close(console);
+    0
+}
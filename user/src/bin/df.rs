@@ -0,0 +1,78 @@
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::format;
+use lib::{println, statfs, StatFs};
+
+/// df [dir...]   显示目录所在文件系统的块与inode的使用情况
+/// 没有参数时显示根目录以及/tmp、/proc、/dev，大小以KiB为单位
+
+const DEFAULT_DIRS: [&str; 4] = ["/", "/tmp", "/proc", "/dev"];
+
+/// 根据statfs返回的标识得到文件系统的名称
+fn fs_name(fs_type: i64) -> &'static str {
+    match fs_type {
+        0x3b800001 => "easyfs",
+        0x01021994 => "tmpfs",
+        0x4d44 => "vfat",
+        0x9fa0 => "proc",
+        0x1373 => "devfs",
+        _ => "unknown",
+    }
+}
+
+/// 使用的百分比，与df一样向上取整
+fn percent(used: u64, total: u64) -> u64 {
+    if total == 0 {
+        0
+    } else {
+        (used * 100 + total - 1) / total
+    }
+}
+
+fn show(dir: &str) -> bool {
+    let mut stat = StatFs::new();
+    if statfs(format!("{}\0", dir).as_str(), &mut stat) != 0 {
+        println!("df: cannot access {}", dir);
+        return false;
+    }
+    let kib = |blocks: u64| blocks * stat.bsize as u64 / 1024;
+    let used = stat.blocks - stat.bfree;
+    let inodes_used = stat.files - stat.ffree;
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>4}% {:>8} {:>8} {:>8} {}",
+        fs_name(stat.fs_type),
+        kib(stat.blocks),
+        kib(used),
+        kib(stat.bavail),
+        percent(used, stat.blocks),
+        stat.files,
+        inodes_used,
+        stat.ffree,
+        dir
+    );
+    true
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>5} {:>8} {:>8} {:>8} {}",
+        "Type", "KiB", "Used", "Avail", "Use%", "Inodes", "IUsed", "IFree", "Mounted on"
+    );
+    let ok = if argc > 1 {
+        argv.iter()
+            .take(argc)
+            .skip(1)
+            .fold(true, |ok, dir| show(dir) && ok)
+    } else {
+        DEFAULT_DIRS.iter().fold(true, |ok, dir| show(dir) && ok)
+    };
+    if ok {
+        0
+    } else {
+        -1
+    }
+}
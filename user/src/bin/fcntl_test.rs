@@ -0,0 +1,153 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    close, dup, dup2, dup3, exec, exit, fcntl, fork, fstat, open, pipe, pread, read, unlink,
+    wait_pid, write, OpenFlags, Stat, FD_CLOEXEC, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL,
+    F_SETFD, F_SETFL,
+};
+
+/// 测试dup2/dup3、FD_CLOEXEC与fcntl，输出 Test fcntl OK! 就算正确。
+
+const PATH: &str = "/tmp/fcntl_file\0";
+//exec之后检查的描述符
+const KEPT_FD: usize = 20;
+const CLOEXEC_FD: usize = 21;
+
+fn is_open(fd: usize) -> bool {
+    fstat(fd, &Stat::new()) == 0
+}
+
+/// exec之后运行，设置了FD_CLOEXEC的描述符已经被关闭
+fn after_exec() -> i32 {
+    if is_open(KEPT_FD) && !is_open(CLOEXEC_FD) {
+        0
+    } else {
+        1
+    }
+}
+
+fn dup_test() {
+    let fd = open(PATH, OpenFlags::C | OpenFlags::RW) as usize;
+    //dup2替换已经打开的描述符
+    let other = open(PATH, OpenFlags::R) as usize;
+    assert_eq!(dup2(fd, other), other as isize);
+    write(other, b"shared");
+    let mut buf = [0u8; 6];
+    assert_eq!(pread(fd, &mut buf, 0), 6);
+    assert_eq!(&buf, b"shared");
+    //相同的描述符什么都不做，无效的描述符失败
+    assert_eq!(dup2(fd, fd), fd as isize);
+    assert_eq!(dup2(30, 31), -1);
+    assert_eq!(dup2(30, 30), -1);
+    //可以复制到比描述符表更大的位置
+    assert_eq!(dup2(fd, 40), 40);
+    assert!(is_open(40));
+    close(40);
+    assert!(!is_open(40));
+    //dup3不允许相同的描述符
+    assert_eq!(dup3(fd, fd, OpenFlags::CLOEXEC), -1);
+    assert_eq!(dup3(fd, 41, OpenFlags::APPEND), -1);
+    assert_eq!(dup3(fd, 41, OpenFlags::CLOEXEC), 41);
+    assert_eq!(fcntl(41, F_GETFD, 0), FD_CLOEXEC as isize);
+    close(41);
+
+    //F_DUPFD返回不小于参数的最小描述符
+    assert_eq!(fcntl(fd, F_DUPFD, 50), 50);
+    assert_eq!(fcntl(fd, F_DUPFD, 50), 51);
+    close(50);
+    assert_eq!(fcntl(fd, F_DUPFD_CLOEXEC, 50), 50);
+    assert_eq!(fcntl(50, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(51, F_GETFD, 0), 0);
+    close(50);
+    close(51);
+    //新的描述符不会继承原来的FD_CLOEXEC
+    assert_eq!(fcntl(fd, F_SETFD, FD_CLOEXEC), 0);
+    let copy = dup(fd) as usize;
+    assert_eq!(fcntl(copy, F_GETFD, 0), 0);
+    assert_eq!(fcntl(fd, F_SETFD, 0), 0);
+    assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+    close(copy);
+    close(other);
+    close(fd);
+}
+
+fn status_flags_test() {
+    let fd = open(PATH, OpenFlags::W) as usize;
+    assert_eq!(fcntl(fd, F_GETFL, 0), OpenFlags::W.bits() as isize);
+    let fd_r = open(PATH, OpenFlags::R) as usize;
+    assert_eq!(fcntl(fd_r, F_GETFL, 0), OpenFlags::R.bits() as isize);
+    close(fd_r);
+    //文件状态标志由复制的描述符共享，访问模式不能修改
+    let copy = dup(fd) as usize;
+    let flags = OpenFlags::APPEND | OpenFlags::RW;
+    assert_eq!(fcntl(copy, F_SETFL, flags.bits() as usize), 0);
+    let expected = OpenFlags::W | OpenFlags::APPEND;
+    assert_eq!(fcntl(fd, F_GETFL, 0), expected.bits() as isize);
+    write(fd, b"!");
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    assert_eq!(stat.size, 7);
+    //重新打开的文件有自己的状态标志
+    let again = open(PATH, OpenFlags::W) as usize;
+    assert_eq!(fcntl(again, F_GETFL, 0), OpenFlags::W.bits() as isize);
+    close(again);
+    close(copy);
+    close(fd);
+
+    //非阻塞的管道没有数据时立即返回
+    let mut fds = [0usize; 2];
+    pipe(&mut fds);
+    let copy = dup(fds[0]) as usize;
+    let flags = OpenFlags::NONBLOCK.bits() as usize;
+    assert_eq!(fcntl(fds[0], F_SETFL, flags), 0);
+    let expected = OpenFlags::R | OpenFlags::NONBLOCK;
+    assert_eq!(fcntl(copy, F_GETFL, 0), expected.bits() as isize);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(copy, &mut buf), -1);
+    write(fds[1], b"ab");
+    assert_eq!(read(copy, &mut buf), 2);
+    assert_eq!(&buf[..2], b"ab");
+    close(fds[1]);
+    assert_eq!(read(copy, &mut buf), 0);
+    close(copy);
+    close(fds[0]);
+}
+
+fn cloexec_test() {
+    let pid = fork();
+    if pid == 0 {
+        let fd = open(PATH, OpenFlags::R) as usize;
+        dup2(fd, KEPT_FD);
+        dup3(fd, CLOEXEC_FD, OpenFlags::CLOEXEC);
+        close(fd);
+        let fd = open(PATH, OpenFlags::R | OpenFlags::CLOEXEC) as usize;
+        assert_eq!(fcntl(fd, F_GETFD, 0), FD_CLOEXEC as isize);
+        exec(
+            "fcntl_test\0",
+            &[
+                "fcntl_test\0".as_ptr(),
+                "exec\0".as_ptr(),
+                core::ptr::null(),
+            ],
+        );
+        exit(-1);
+    }
+    let mut exit_code = -1;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "exec" {
+        return after_exec();
+    }
+    dup_test();
+    status_flags_test();
+    cloexec_test();
+    assert_eq!(unlink(PATH), 0);
+    println!("Test fcntl OK!");
+    0
+}
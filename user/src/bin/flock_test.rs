@@ -0,0 +1,168 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    close, dup, exit, fcntl_lock, flock, fork, getpid, open, pipe, pread, unlink, wait_pid, write,
+    yield_, Flock, OpenFlags, F_GETLK, F_RDLCK, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK, LOCK_EX,
+    LOCK_NB, LOCK_SH, LOCK_UN,
+};
+
+/// 测试flock与fcntl记录锁，输出 Test flock OK! 就算正确。
+
+const PATH: &str = "/tmp/lock_file\0";
+
+fn open_rw() -> usize {
+    let fd = open(PATH, OpenFlags::RW);
+    assert!(fd >= 0);
+    fd as usize
+}
+
+/// 在子进程中运行f，返回子进程的返回码
+fn in_child(f: impl FnOnce() -> i32) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exit(f());
+    }
+    let mut exit_code = 0;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+/// 其它进程对[start,start+len)加写锁时是否冲突
+fn locked_by_other(start: i64, len: i64) -> bool {
+    in_child(|| {
+        let fd = open_rw();
+        let mut lock = Flock::new(F_WRLCK, start, len);
+        assert_eq!(fcntl_lock(fd, F_GETLK, &mut lock), 0);
+        (lock.l_type != F_UNLCK) as i32
+    }) == 1
+}
+
+fn flock_test() {
+    let fd1 = open_rw();
+    let fd2 = open_rw();
+    assert_eq!(flock(fd1, LOCK_EX), 0);
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), -1);
+    assert_eq!(flock(fd2, LOCK_EX | LOCK_NB), -1);
+    //dup得到的描述符共享同一个锁，最后一个描述符关闭时才释放
+    let fd3 = dup(fd1) as usize;
+    assert_eq!(flock(fd3, LOCK_EX | LOCK_NB), 0);
+    close(fd1);
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), -1);
+    close(fd3);
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), 0);
+    //共享锁可以同时持有
+    let fd4 = open_rw();
+    assert_eq!(flock(fd4, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(fd4, LOCK_EX | LOCK_NB), -1);
+    assert_eq!(flock(fd2, LOCK_UN), 0);
+    assert_eq!(flock(fd4, LOCK_EX | LOCK_NB), 0);
+    close(fd4);
+    close(fd2);
+    //管道不能加锁
+    let mut fds = [0usize; 2];
+    pipe(&mut fds);
+    assert_eq!(flock(fds[0], LOCK_EX), -1);
+    close(fds[0]);
+    close(fds[1]);
+
+    //子进程等待父进程释放独占锁之后才能看到父进程写入的内容
+    let fd = open_rw();
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    let pid = fork();
+    if pid == 0 {
+        let fd = open_rw();
+        assert_eq!(flock(fd, LOCK_EX), 0);
+        let mut buf = [0u8; 6];
+        pread(fd, &mut buf, 0);
+        exit(if &buf == b"parent" { 0 } else { 1 });
+    }
+    for _ in 0..20 {
+        yield_();
+    }
+    write(fd, b"parent");
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    let mut exit_code = -1;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(fd);
+}
+
+fn record_lock_test() {
+    let fd = open_rw();
+    let mut lock = Flock::new(F_WRLCK, 0, 10);
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+    let parent = getpid() as i32;
+    //fork继承的描述符上的记录锁仍然属于父进程
+    let exit_code = in_child(|| {
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_WRLCK, 5, 10)), -1);
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_RDLCK, 0, 1)), -1);
+        let mut lock = Flock::new(F_WRLCK, 5, 10);
+        assert_eq!(fcntl_lock(fd, F_GETLK, &mut lock), 0);
+        assert_eq!(lock.l_type, F_WRLCK);
+        assert_eq!((lock.l_start, lock.l_len, lock.l_pid), (0, 10, parent));
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_WRLCK, 10, 10)), 0);
+        0
+    });
+    assert_eq!(exit_code, 0);
+    //子进程退出时释放了它的锁
+    assert!(!locked_by_other(10, 10));
+
+    //解锁中间的一段会把锁分成两段
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_UNLCK, 3, 4)), 0);
+    assert!(locked_by_other(0, 3));
+    assert!(!locked_by_other(3, 4));
+    assert!(locked_by_other(7, 3));
+    //读锁之间不冲突，len为0时一直锁到文件末尾之后
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_RDLCK, 0, 0)), 0);
+    let exit_code = in_child(|| {
+        let fd = open_rw();
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_RDLCK, 100, 0)), 0);
+        assert_eq!(
+            fcntl_lock(fd, F_SETLK, &mut Flock::new(F_WRLCK, 1000, 1)),
+            -1
+        );
+        0
+    });
+    assert_eq!(exit_code, 0);
+
+    //F_SETLKW等待父进程解锁
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_WRLCK, 0, 10)), 0);
+    let pid = fork();
+    if pid == 0 {
+        let fd = open_rw();
+        assert_eq!(fcntl_lock(fd, F_SETLKW, &mut Flock::new(F_WRLCK, 0, 10)), 0);
+        let mut buf = [0u8; 6];
+        pread(fd, &mut buf, 0);
+        exit(if &buf == b"record" { 0 } else { 1 });
+    }
+    for _ in 0..20 {
+        yield_();
+    }
+    write(fd, b"record");
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_UNLCK, 0, 0)), 0);
+    let mut exit_code = -1;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    //进程关闭了文件的最后一个描述符时释放记录锁
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut Flock::new(F_WRLCK, 0, 0)), 0);
+    let other = open_rw();
+    close(other);
+    assert!(locked_by_other(0, 1));
+    close(fd);
+    assert!(!locked_by_other(0, 1));
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(PATH, OpenFlags::C | OpenFlags::W);
+    assert!(fd >= 0);
+    close(fd as usize);
+    flock_test();
+    record_lock_test();
+    assert_eq!(unlink(PATH), 0);
+    println!("Test flock OK!");
+    0
+}
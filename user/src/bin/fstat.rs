@@ -15,7 +15,7 @@ pub fn main(_args: usize, args_str: &[&str]) -> i32 {
     let stat: Stat = Stat::new();
     let ret = fstat(fd, &stat);
     assert_eq!(ret, 0);
-    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.mode.file_type(), StatMode::FILE);
     INFO!(
         "dev: {}\nino: {}\nmode: {:?}\nnlink: {}",
         stat.dev,
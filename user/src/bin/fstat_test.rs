@@ -14,7 +14,7 @@ pub fn main() -> i32 {
     let stat: Stat = Stat::new();
     let ret = fstat(fd, &stat);
     assert_eq!(ret, 0);
-    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.mode.file_type(), StatMode::FILE);
     close(fd);
     assert_eq!(stat.nlink, 1);
     // unlink(fname);
@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    close, fstat, getdents, lseek, mkdir, open, unlink, Dirents, OpenFlags, Stat, DT_DIR, DT_REG,
+    SEEK_SET,
+};
+
+/// 测试getdents64与O_DIRECTORY，输出 Test getdents OK! 就算正确。
+
+fn ino_of(path: &str) -> u64 {
+    let fd = open(path, OpenFlags::R);
+    assert!(fd >= 0);
+    let stat = Stat::new();
+    fstat(fd as usize, &stat);
+    close(fd as usize);
+    stat.ino
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/tmp/dents\0"), 0);
+    assert_eq!(mkdir("/tmp/dents/sub\0"), 0);
+    let fd = open("/tmp/dents/file\0", OpenFlags::C | OpenFlags::W);
+    assert!(fd >= 0);
+    //普通文件不能以O_DIRECTORY打开，也不能读取目录项
+    assert_eq!(
+        open("/tmp/dents/file\0", OpenFlags::R | OpenFlags::DIRECTORY),
+        -1
+    );
+    let mut buf = [0u8; 256];
+    assert_eq!(getdents(fd as usize, &mut buf), -1);
+    close(fd as usize);
+    //O_DIRECTORY不能与新建一起使用
+    assert_eq!(
+        open("/tmp/dents/new\0", OpenFlags::C | OpenFlags::DIRECTORY),
+        -1
+    );
+
+    let fd = open("/tmp/dents\0", OpenFlags::R | OpenFlags::DIRECTORY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let len = getdents(fd, &mut buf);
+    assert!(len > 0);
+    let mut count = 0;
+    for dirent in Dirents::new(&buf[..len as usize]) {
+        match dirent.name {
+            "." => {
+                assert_eq!(dirent.kind, DT_DIR);
+                assert_eq!(dirent.ino, ino_of("/tmp/dents\0"));
+            }
+            ".." => {
+                assert_eq!(dirent.kind, DT_DIR);
+                assert_eq!(dirent.ino, ino_of("/tmp\0"));
+            }
+            "sub" => {
+                assert_eq!(dirent.kind, DT_DIR);
+                assert_eq!(dirent.ino, ino_of("/tmp/dents/sub\0"));
+            }
+            "file" => {
+                assert_eq!(dirent.kind, DT_REG);
+                assert_eq!(dirent.ino, ino_of("/tmp/dents/file\0"));
+            }
+            name => panic!("unexpected entry {}", name),
+        }
+        count += 1;
+    }
+    assert_eq!(count, 4);
+    //读完之后返回0
+    assert_eq!(getdents(fd, &mut buf), 0);
+
+    //放不下一个目录项时返回-1，放得下时每次只返回能放下的目录项
+    lseek(fd, 0, SEEK_SET);
+    assert_eq!(getdents(fd, &mut buf[..16]), -1);
+    let mut names = 0;
+    loop {
+        let len = getdents(fd, &mut buf[..24]);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        assert_eq!(Dirents::new(&buf[..len as usize]).count(), 1);
+        names += 1;
+    }
+    assert_eq!(names, 4);
+    close(fd);
+
+    assert_eq!(unlink("/tmp/dents/file\0"), 0);
+    assert_eq!(unlink("/tmp/dents/sub\0"), 0);
+    assert_eq!(unlink("/tmp/dents\0"), 0);
+    println!("Test getdents OK!");
+    0
+}
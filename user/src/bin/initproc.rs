@@ -2,11 +2,19 @@
 #![no_std]
 #![allow(non_snake_case)]
 
-use lib::{exec, fork, getpid, wait};
+use lib::{close, dup2, exec, fork, getpid, open, wait, OpenFlags};
 use lib::{println, yield_};
 
 #[no_mangle]
 fn main() -> isize {
+    //标准输入、输出与错误输出都指向控制台，之后创建的进程会继承它们
+    let console = open("/dev/console\0", OpenFlags::RW);
+    if console >= 0 {
+        for fd in 0..3 {
+            dup2(console as usize, fd);
+        }
+        close(console as usize);
+    }
     println!("[user] goto the initproc");
     if fork() == 0 {
         println!("[user] This child process,pid :{}", getpid());
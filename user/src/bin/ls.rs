@@ -1,9 +1,141 @@
 #![no_main]
 #![no_std]
 
-use lib::ls;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lib::{
+    close, getdents, lstat, open, println, readlink, Dirents, OpenFlags, Stat, StatMode, DT_DIR,
+};
+
+/// ls [-l] [-a] [dir]   列出目录下的文件，默认是根目录，目录的名称后加/
+/// -l   同时显示权限、链接数、所有者、大小与修改时间
+/// -a   同时显示以.开头的文件
+
+fn mode_string(mode: StatMode) -> String {
+    let mut s = String::new();
+    s.push(match mode.file_type() {
+        StatMode::DIR => 'd',
+        StatMode::CHAR => 'c',
+        StatMode::BLOCK => 'b',
+        StatMode::LINK => 'l',
+        _ => '-',
+    });
+    let bits = [
+        (StatMode::OWNER_R, 'r'),
+        (StatMode::OWNER_W, 'w'),
+        (StatMode::OWNER_X, 'x'),
+        (StatMode::GROUP_R, 'r'),
+        (StatMode::GROUP_W, 'w'),
+        (StatMode::GROUP_X, 'x'),
+        (StatMode::OTHER_R, 'r'),
+        (StatMode::OTHER_W, 'w'),
+        (StatMode::OTHER_X, 'x'),
+    ];
+    for (bit, c) in bits.iter() {
+        s.push(if mode.contains(*bit) { *c } else { '-' });
+    }
+    s
+}
+
+/// 读出目录中的所有目录项的名称与类型
+fn read_dir(dir: &str) -> Option<Vec<(String, u8)>> {
+    let fd = open(
+        format!("{}\0", dir).as_str(),
+        OpenFlags::R | OpenFlags::DIRECTORY,
+    );
+    if fd < 0 {
+        return None;
+    }
+    let mut entries = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let len = getdents(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        for dirent in Dirents::new(&buf[..len as usize]) {
+            entries.push((String::from(dirent.name), dirent.kind));
+        }
+    }
+    close(fd as usize);
+    Some(entries)
+}
+
+fn print_long(dir: &str, name: &str) {
+    let path = if dir.ends_with('/') {
+        format!("{}{}\0", dir, name)
+    } else {
+        format!("{}/{}\0", dir, name)
+    };
+    //符号链接显示本身的信息与目标
+    let mut stat = Stat::new();
+    if lstat(path.as_str(), &mut stat) < 0 {
+        println!("ls: cannot access {}", name);
+        return;
+    }
+    let mut target = [0u8; 512];
+    let len = match stat.mode.file_type() {
+        StatMode::LINK => readlink(path.as_str(), &mut target).max(0) as usize,
+        _ => 0,
+    };
+    let target = core::str::from_utf8(&target[..len]).unwrap_or("?");
+    println!(
+        "{} {:>2} {:>4} {:>4} {:>8} {:>10} {}{}{}",
+        mode_string(stat.mode),
+        stat.nlink,
+        stat.uid,
+        stat.gid,
+        stat.size,
+        stat.mtime,
+        name,
+        if len > 0 { " -> " } else { "" },
+        target
+    );
+}
 
 #[no_mangle]
-fn main() {
-    ls();
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let mut long = false;
+    let mut all = false;
+    let mut dir = "/";
+    for arg in argv.iter().take(argc).skip(1) {
+        match arg.strip_prefix('-') {
+            Some(options) => {
+                for option in options.chars() {
+                    match option {
+                        'l' => long = true,
+                        'a' => all = true,
+                        _ => {
+                            println!("ls: invalid option -{}", option);
+                            return -1;
+                        }
+                    }
+                }
+            }
+            None => dir = arg,
+        }
+    }
+    let entries = match read_dir(dir) {
+        Some(entries) => entries,
+        None => {
+            println!("ls: cannot open directory {}", dir);
+            return -1;
+        }
+    };
+    for (name, kind) in entries.iter() {
+        if !all && name.starts_with('.') {
+            continue;
+        }
+        if long {
+            print_long(dir, name);
+        } else if *kind == DT_DIR {
+            println!("{}/", name);
+        } else {
+            println!("{}", name);
+        }
+    }
+    0
 }
@@ -0,0 +1,23 @@
+#![no_main]
+#![no_std]
+
+use lib::{mkdir, println};
+
+/// mkdir <dir>...  新建目录
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: mkdir <dir>...");
+        return -1;
+    }
+    let mut ret = 0;
+    //命令行参数在内存中以'\0'结尾，可以直接作为路径传给内核
+    for path in argv[1..].iter() {
+        if mkdir(path) != 0 {
+            println!("mkdir: cannot create directory {}", path);
+            ret = -1;
+        }
+    }
+    ret
+}
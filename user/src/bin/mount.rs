@@ -0,0 +1,22 @@
+#![no_main]
+#![no_std]
+
+use lib::{mount, println};
+
+/// mount <device> <dir> [fstype]
+/// 将块设备上的文件系统挂载到已经存在的目录，文件系统类型默认为easyfs，FAT32为vfat
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 3 {
+        println!("usage: mount <device> <dir> [fstype]");
+        return -1;
+    }
+    //命令行参数在内存中以'\0'结尾，可以直接作为字符串传给内核
+    let fstype = if argc > 3 { argv[3] } else { "easyfs\0" };
+    if mount(argv[1], argv[2], fstype) != 0 {
+        println!("mount: cannot mount {} on {}", argv[1], argv[2]);
+        return -1;
+    }
+    0
+}
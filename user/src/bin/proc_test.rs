@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use lib::println;
+use lib::{close, getpid, ls_dir, open, read, write, OpenFlags};
+
+/// 测试/proc中的进程信息与全局文件，输出 Test proc OK! 就算正确。
+
+fn read_all(path: &str, buf: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::R);
+    assert!(fd >= 0);
+    let mut len = 0;
+    loop {
+        let size = read(fd as usize, &mut buf[len..]);
+        if size <= 0 {
+            break;
+        }
+        len += size as usize;
+    }
+    close(fd as usize);
+    len
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let mut buf = [0u8; 1024];
+    let len = ls_dir("/proc\0", &mut buf);
+    assert!(len > 0);
+    let names = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    assert!(names.lines().any(|name| name == "meminfo"));
+    assert!(names.lines().any(|name| name == format!("{}", pid)));
+
+    let len = read_all(format!("/proc/{}/status\0", pid).as_str(), &mut buf);
+    let status = core::str::from_utf8(&buf[..len]).unwrap();
+    assert!(status.contains(format!("Pid:\t{}\n", pid).as_str()));
+    assert!(status.contains("State:\tR\n"));
+
+    //参数以\0分隔
+    let len = read_all(format!("/proc/{}/cmdline\0", pid).as_str(), &mut buf);
+    let cmdline = core::str::from_utf8(&buf[..len]).unwrap();
+    assert!(cmdline.ends_with('\0'));
+    assert!(cmdline.split('\0').next().unwrap().ends_with("proc_test"));
+
+    //每个进程都有代码段与用户栈
+    let len = read_all(format!("/proc/{}/maps\0", pid).as_str(), &mut buf);
+    let maps = core::str::from_utf8(&buf[..len]).unwrap();
+    assert!(maps.lines().any(|line| line.contains(" r-xp ")));
+
+    let len = read_all(format!("/proc/{}/fd\0", pid).as_str(), &mut buf);
+    let fds = core::str::from_utf8(&buf[..len]).unwrap();
+    assert!(fds.starts_with("0 stdin\n1 stdout\n"));
+
+    let len = read_all(format!("/proc/{}/task\0", pid).as_str(), &mut buf);
+    let tasks = core::str::from_utf8(&buf[..len]).unwrap();
+    assert!(tasks.starts_with("0 R "));
+
+    let len = read_all("/proc/meminfo\0", &mut buf);
+    assert!(core::str::from_utf8(&buf[..len])
+        .unwrap()
+        .starts_with("MemTotal:"));
+    assert!(read_all("/proc/uptime\0", &mut buf) > 0);
+    assert!(read_all("/proc/sched\0", &mut buf) > 0);
+
+    //文件都是只读的，不存在的进程没有目录
+    let fd = open("/proc/uptime\0", OpenFlags::W);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"0"), 0);
+    close(fd as usize);
+    assert_eq!(open("/proc/100000/status\0", OpenFlags::R), -1);
+    assert_eq!(open("/proc/new\0", OpenFlags::C | OpenFlags::W), -1);
+    println!("Test proc OK!");
+    0
+}
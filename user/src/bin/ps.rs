@@ -0,0 +1,50 @@
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lib::{println, proc_field, proc_pids, read_proc};
+
+/// ps  通过/proc列出所有进程的pid、父进程、状态、线程数、运行时间与启动参数
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pids = match proc_pids() {
+        Some(pids) => pids,
+        None => {
+            println!("ps: /proc is not mounted");
+            return -1;
+        }
+    };
+    println!(
+        "{:>5} {:>5} S {:>3} {:>10} CMD",
+        "PID", "PPID", "THR", "TIME(ms)"
+    );
+    for pid in pids {
+        //进程可能在列出之后退出
+        let status = match read_proc(format!("/proc/{}/status", pid).as_str()) {
+            Some(status) => status,
+            None => continue,
+        };
+        let cmdline = read_proc(format!("/proc/{}/cmdline", pid).as_str()).unwrap_or_default();
+        let args: Vec<&str> = cmdline.split('\0').filter(|arg| !arg.is_empty()).collect();
+        let time: usize = proc_field(&status, "Time").parse().unwrap_or(0);
+        println!(
+            "{:>5} {:>5} {} {:>3} {:>10} {}",
+            pid,
+            proc_field(&status, "PPid"),
+            proc_field(&status, "State"),
+            proc_field(&status, "Threads"),
+            time / 1000,
+            if args.is_empty() {
+                String::from(proc_field(&status, "Name"))
+            } else {
+                args.join(" ")
+            }
+        );
+    }
+    0
+}
@@ -0,0 +1,109 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{close, fstat, mkdir, open, read, rename, unlink, write, OpenFlags, Stat};
+
+/// 测试 rename/unlink，输出 Test rename OK! 就算正确。
+
+/// 目录只能替换空目录，其它文件只能替换不是目录的文件
+/// 在根目录与/tmp下分别测试，路径都以0结尾
+fn replace_type_test(file: &str, dir: &str, full: &str, child: &str) {
+    let fd = open(file, OpenFlags::C | OpenFlags::W) as usize;
+    close(fd);
+    assert_eq!(mkdir(dir), 0);
+    assert_eq!(mkdir(full), 0);
+    let fd = open(child, OpenFlags::C | OpenFlags::W) as usize;
+    close(fd);
+    assert_eq!(rename(file, dir), -1);
+    assert_eq!(rename(dir, file), -1);
+    assert_eq!(rename(dir, full), -1);
+    assert!(open(file, OpenFlags::R) >= 0);
+    //空目录可以被目录替换
+    assert_eq!(unlink(child), 0);
+    assert_eq!(rename(dir, full), 0);
+    assert_eq!(open(dir, OpenFlags::R), -1);
+    assert_eq!(unlink(full), 0);
+    assert_eq!(unlink(file), 0);
+}
+
+/// 在目录之间移动文件与目录，目录不能被移动到自身或者子目录中
+fn move_test(dir: &str, sub: &str, file: &str, moved: &str, moved_sub: &str, inner: &str) {
+    assert_eq!(mkdir(dir), 0);
+    assert_eq!(mkdir(sub), 0);
+    let fd = open(file, OpenFlags::C | OpenFlags::W) as usize;
+    close(fd);
+    assert_eq!(rename(file, moved), 0);
+    assert_eq!(open(file, OpenFlags::R), -1);
+    let fd = open(moved, OpenFlags::R);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(rename(dir, inner), -1);
+    assert_eq!(rename(dir, moved_sub), -1);
+    //移出后目录可以被删除
+    assert_eq!(rename(sub, inner), 0);
+    assert_eq!(unlink(inner), 0);
+    assert_eq!(unlink(moved), 0);
+    assert_eq!(unlink(dir), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, rename!";
+    let (src, dst, other) = ("rename_src\0", "rename_dst\0", "rename_other\0");
+    let fd = open(src, OpenFlags::C | OpenFlags::W) as usize;
+    write(fd, test_str.as_bytes());
+    close(fd);
+    let fd = open(other, OpenFlags::C | OpenFlags::W) as usize;
+    write(fd, "other".as_bytes());
+    close(fd);
+    //目标不存在
+    assert_eq!(rename(src, dst), 0);
+    assert_eq!(open(src, OpenFlags::R), -1);
+    //目标存在时被替换
+    assert_eq!(rename(dst, other), 0);
+    assert_eq!(open(dst, OpenFlags::R), -1);
+    let fd = open(other, OpenFlags::R) as usize;
+    let mut buf = [0u8; 100];
+    let read_len = read(fd, &mut buf) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buf[..read_len]).unwrap());
+    //删除仍然打开的文件后依然可以读取
+    assert_eq!(unlink(other), 0);
+    assert_eq!(open(other, OpenFlags::R), -1);
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    assert_eq!(stat.nlink, 0);
+    close(fd);
+    assert_eq!(unlink(other), -1);
+    assert_eq!(rename(src, dst), -1);
+    replace_type_test(
+        "rename_file\0",
+        "rename_dir\0",
+        "rename_full\0",
+        "rename_full/child\0",
+    );
+    replace_type_test(
+        "/tmp/rename_file\0",
+        "/tmp/rename_dir\0",
+        "/tmp/rename_full\0",
+        "/tmp/rename_full/child\0",
+    );
+    move_test(
+        "rename_move\0",
+        "rename_move/sub\0",
+        "rename_move/file\0",
+        "rename_moved\0",
+        "rename_move/sub/dir\0",
+        "rename_move/inner\0",
+    );
+    move_test(
+        "/tmp/rename_move\0",
+        "/tmp/rename_move/sub\0",
+        "/tmp/rename_move/file\0",
+        "/tmp/rename_moved\0",
+        "/tmp/rename_move/sub/dir\0",
+        "/tmp/rename_move/inner\0",
+    );
+    println!("Test rename OK!");
+    0
+}
@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    close, fstat, ftruncate, lseek, open, pread, pwrite, read, unlink, write, OpenFlags, Stat,
+    SEEK_CUR, SEEK_END, SEEK_SET,
+};
+
+/// 测试 lseek/pread/pwrite/ftruncate 与追加写，输出 Test seek OK! 就算正确。
+
+fn file_size(fd: usize) -> usize {
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    stat.size as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let name = "seek_test\0";
+    let fd = open(name, OpenFlags::C | OpenFlags::RW) as usize;
+    write(fd, "0123456789".as_bytes());
+    //移动读写位置后读取
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fd, &mut buf), 4);
+    assert_eq!(&buf, b"2345");
+    assert_eq!(lseek(fd, -1, SEEK_CUR), 5);
+    assert_eq!(lseek(fd, -3, SEEK_END), 7);
+    assert_eq!(lseek(fd, -1, SEEK_SET), -1);
+    assert_eq!(lseek(fd, 0, 3), -1);
+    //pread/pwrite不改变读写位置
+    assert_eq!(pwrite(fd, b"ab", 0), 2);
+    assert_eq!(pread(fd, &mut buf, 0), 4);
+    assert_eq!(&buf, b"ab23");
+    assert_eq!(pread(fd, &mut buf, 8), 2);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 7);
+    //移动到文件末尾之后写入，中间的部分读出为0
+    assert_eq!(lseek(fd, 12, SEEK_SET), 12);
+    write(fd, b"x");
+    assert_eq!(file_size(fd), 13);
+    assert_eq!(pread(fd, &mut buf, 9), 4);
+    assert_eq!(&buf, b"9\0\0\0");
+    //截断与扩大
+    assert_eq!(ftruncate(fd, 3), 0);
+    assert_eq!(file_size(fd), 3);
+    assert_eq!(ftruncate(fd, 2000), 0);
+    assert_eq!(file_size(fd), 2000);
+    assert_eq!(pread(fd, &mut buf, 1), 4);
+    assert_eq!(&buf, b"b2\0\0");
+    close(fd);
+    //追加写总是写到文件末尾
+    let fd = open(name, OpenFlags::W | OpenFlags::APPEND) as usize;
+    assert_eq!(ftruncate(fd, 4), 0);
+    write(fd, b"end");
+    assert_eq!(file_size(fd), 7);
+    close(fd);
+    let fd = open(name, OpenFlags::R) as usize;
+    let mut data = [0u8; 16];
+    assert_eq!(read(fd, &mut data), 7);
+    assert_eq!(&data[..7], b"ab2\0end");
+    assert_eq!(ftruncate(fd, 0), -1);
+    close(fd);
+    assert_eq!(unlink(name), 0);
+    println!("Test seek OK!");
+    0
+}
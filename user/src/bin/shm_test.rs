@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate lib;
+
+use lib::{exit, fork, shmat, shmctl, shmdt, shmget, wait, IPC_CREAT, IPC_EXCL, IPC_RMID};
+
+const KEY: usize = 0x5348;
+const SIZE: usize = 4096 * 2;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let shmid = shmget(KEY, SIZE, IPC_CREAT);
+    assert!(shmid > 0);
+    //相同的key得到同一个共享内存段
+    assert_eq!(shmget(KEY, SIZE, 0), shmid);
+    assert_eq!(shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL), -1);
+    let addr = shmat(shmid as usize, 0);
+    assert!(addr > 0);
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, SIZE) };
+    buf.fill(0);
+
+    let pid = fork();
+    if pid == 0 {
+        //子进程通过key重新映射到另一个地址
+        let shmid = shmget(KEY, 0, 0);
+        let addr = shmat(shmid as usize, 0);
+        assert!(addr > 0);
+        let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, SIZE) };
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        assert_eq!(shmdt(addr as usize), 0);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    for (i, byte) in buf.iter().enumerate() {
+        assert_eq!(*byte, i as u8);
+    }
+    assert_eq!(shmdt(addr as usize), 0);
+    assert_eq!(shmdt(addr as usize), -1);
+    //最后一个使用者解除映射后共享内存段被删除
+    assert_eq!(shmget(KEY, SIZE, 0), -1);
+    //没有映射过的段需要显式删除
+    let shmid = shmget(KEY, SIZE, IPC_CREAT);
+    assert!(shmid > 0);
+    assert_eq!(shmctl(shmid as usize, IPC_RMID), 0);
+    assert_eq!(shmget(KEY, SIZE, 0), -1);
+    assert_eq!(shmctl(shmid as usize, IPC_RMID), -1);
+    //仍有映射时只标记删除，映射仍然可以使用
+    let shmid = shmget(KEY, SIZE, IPC_CREAT);
+    let addr = shmat(shmid as usize, 0);
+    assert!(addr > 0);
+    assert_eq!(shmctl(shmid as usize, IPC_RMID), 0);
+    assert_eq!(shmat(shmid as usize, 0), -1);
+    unsafe { (addr as *mut u8).write_volatile(1) };
+    assert_eq!(shmdt(addr as usize), 0);
+    println!("shm_test passed!");
+    0
+}
@@ -0,0 +1,152 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    chown, close, exit, fork, fstat, fstatfs, getuid, mount, open, pipe, quotactl, setuid, stat,
+    statfs, umount, unlink, wait_pid, write, DiskQuota, OpenFlags, Stat, StatFs, Q_GETQUOTA,
+    Q_SETQUOTA,
+};
+
+/// 测试statfs、fstatfs、块限额与文件所有者，输出 Test statfs OK! 就算正确。
+
+const PATH: &str = "/statfs_file\0";
+const OWNED: &str = "/statfs_owned\0";
+const TMP_OWNED: &str = "/tmp/statfs_owned\0";
+const USER: usize = 1000;
+const EFS_MAGIC: i64 = 0x3b800001;
+const TMPFS_MAGIC: i64 = 0x01021994;
+const PROC_SUPER_MAGIC: i64 = 0x9fa0;
+
+fn usage(path: &str) -> StatFs {
+    let mut stat = StatFs::new();
+    assert_eq!(statfs(path, &mut stat), 0);
+    stat
+}
+
+fn statfs_test() {
+    let root = usage("/\0");
+    assert_eq!(root.fs_type, EFS_MAGIC);
+    assert!(root.blocks > 0 && root.bfree <= root.blocks);
+    assert!(root.files > 0 && root.ffree < root.files);
+    assert_eq!(usage("/tmp\0").fs_type, TMPFS_MAGIC);
+    assert_eq!(usage("/proc\0").fs_type, PROC_SUPER_MAGIC);
+    assert_eq!(statfs("/statfs_missing\0", &mut StatFs::new()), -1);
+
+    //写入文件后空闲块减少，删除后恢复
+    let fd = open(PATH, OpenFlags::C | OpenFlags::W) as usize;
+    let block = [7u8; 512];
+    for _ in 0..8 {
+        assert_eq!(write(fd, &block), 512);
+    }
+    let mut opened = StatFs::new();
+    assert_eq!(fstatfs(fd, &mut opened), 0);
+    let written = usage("/\0");
+    assert_eq!(opened.blocks, written.blocks);
+    assert_eq!(opened.bfree, written.bfree);
+    assert!(written.bfree + 8 <= root.bfree);
+    assert_eq!(written.ffree + 1, root.ffree);
+    close(fd);
+    assert_eq!(unlink(PATH), 0);
+    let removed = usage("/\0");
+    assert!(removed.bfree >= written.bfree + 8);
+    assert_eq!(removed.ffree, root.ffree);
+    //管道不属于任何文件系统
+    let mut fds = [0usize; 2];
+    pipe(&mut fds);
+    assert_eq!(fstatfs(fds[0], &mut StatFs::new()), -1);
+    close(fds[0]);
+    close(fds[1]);
+}
+
+fn quota_test() {
+    //超级用户新建的文件属于0
+    let fd = open(PATH, OpenFlags::C | OpenFlags::W) as usize;
+    let mut quota = DiskQuota::default();
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", 0, &mut quota), -1);
+    quota.limit = u32::MAX as u64;
+    assert_eq!(quotactl(Q_SETQUOTA, "/\0", 0, &mut quota), 0);
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", 0, &mut quota), 0);
+    assert!(quota.used > 0);
+    //超过限额的部分不写入，什么都写不进去时返回-1
+    quota.limit = quota.used + 4;
+    assert_eq!(quotactl(Q_SETQUOTA, "/\0", 0, &mut quota), 0);
+    let data = [1u8; 8 * 512];
+    assert_eq!(write(fd, &data), 4 * 512);
+    assert_eq!(write(fd, &data), -1);
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", 0, &mut quota), 0);
+    assert_eq!(quota.used, quota.limit);
+    //取消限额后可以继续写入
+    quota.limit = 0;
+    assert_eq!(quotactl(Q_SETQUOTA, "/\0", 0, &mut quota), 0);
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", 0, &mut quota), -1);
+    assert_eq!(write(fd, &data), data.len() as isize);
+    //内存文件系统不支持限额
+    assert_eq!(quotactl(Q_SETQUOTA, "/tmp\0", 0, &mut quota), -1);
+    close(fd);
+    assert_eq!(unlink(PATH), 0);
+}
+
+/// 普通用户新建的文件属于自己，不能设置限额、修改所有者或者挂载文件系统
+fn user_quota() -> i32 {
+    assert_eq!(setuid(USER), 0);
+    assert_eq!(getuid(), USER as isize);
+    assert_eq!(setuid(0), -1);
+    let fd = open(OWNED, OpenFlags::C | OpenFlags::W) as usize;
+    let owner = Stat::new();
+    assert_eq!(fstat(fd, &owner), 0);
+    assert_eq!(owner.uid, USER as u32);
+    //写入的块记入自己的限额
+    let data = [3u8; 8 * 512];
+    assert_eq!(write(fd, &data), 4 * 512);
+    close(fd);
+    let mut quota = DiskQuota::default();
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", USER, &mut quota), 0);
+    assert_eq!(quota.used, 4);
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", 0, &mut quota), -1);
+    quota.limit = 0;
+    assert_eq!(quotactl(Q_SETQUOTA, "/\0", USER, &mut quota), -1);
+    assert_eq!(chown(OWNED, 0, 0), -1);
+    //普通用户不能挂载或者卸载文件系统
+    assert_eq!(mount("ramfs\0", "/\0", "tmpfs\0"), -1);
+    assert_eq!(umount("/tmp\0"), -1);
+    let fd = open(TMP_OWNED, OpenFlags::C | OpenFlags::W) as usize;
+    assert_eq!(fstat(fd, &owner), 0);
+    assert_eq!(owner.uid, USER as u32);
+    close(fd);
+    0
+}
+
+fn owner_test() {
+    let mut quota = DiskQuota::default();
+    quota.limit = 4;
+    assert_eq!(quotactl(Q_SETQUOTA, "/\0", USER, &mut quota), 0);
+    let pid = fork();
+    if pid == 0 {
+        exit(user_quota());
+    }
+    let mut exit_code = -1;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(getuid(), 0);
+    //修改所有者后占用随之转移
+    assert_eq!(chown(OWNED, 0, 0), 0);
+    let mut owner = Stat::new();
+    assert_eq!(stat(OWNED, &mut owner), 0);
+    assert_eq!(owner.uid, 0);
+    assert_eq!(quotactl(Q_GETQUOTA, "/\0", USER, &mut quota), 0);
+    assert_eq!(quota.used, 0);
+    quota.limit = 0;
+    assert_eq!(quotactl(Q_SETQUOTA, "/\0", USER, &mut quota), 0);
+    assert_eq!(unlink(OWNED), 0);
+    assert_eq!(unlink(TMP_OWNED), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    statfs_test();
+    quota_test();
+    owner_test();
+    println!("Test statfs OK!");
+    0
+}
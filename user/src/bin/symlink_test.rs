@@ -0,0 +1,128 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::string::String;
+use lib::println;
+use lib::{
+    close, fstat, lstat, mkdir, open, read, readlink, stat, symlink, unlink, write, OpenFlags,
+    Stat, StatMode,
+};
+
+/// 测试符号链接的新建、读取与路径解析，输出 Test symlink OK! 就算正确。
+
+fn read_file(path: &str) -> Option<String> {
+    let fd = open(path, OpenFlags::R);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 64];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    Some(String::from(
+        core::str::from_utf8(&buf[..len as usize]).unwrap(),
+    ))
+}
+
+fn link_target(path: &str) -> String {
+    let mut buf = [0u8; 512];
+    let len = readlink(path, &mut buf);
+    assert!(len > 0);
+    String::from(core::str::from_utf8(&buf[..len as usize]).unwrap())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/sl_file\0", OpenFlags::C | OpenFlags::W);
+    assert!(fd >= 0);
+    write(fd as usize, b"through link");
+    close(fd as usize);
+    assert_eq!(mkdir("/sl_dir\0"), 0);
+
+    //绝对路径与相对于符号链接所在目录的路径
+    assert_eq!(symlink("/sl_file\0", "/sl_abs\0"), 0);
+    assert_eq!(symlink("../sl_file\0", "/sl_dir/up\0"), 0);
+    assert_eq!(symlink("/sl_abs\0", "/sl_chain\0"), 0);
+    assert_eq!(symlink("x\0", "/sl_abs\0"), -1);
+    assert_eq!(read_file("/sl_abs\0").unwrap(), "through link");
+    assert_eq!(read_file("/sl_dir/up\0").unwrap(), "through link");
+    assert_eq!(read_file("/sl_chain\0").unwrap(), "through link");
+    assert_eq!(link_target("/sl_abs\0"), "/sl_file");
+    assert_eq!(link_target("/sl_dir/up\0"), "../sl_file");
+    let mut buf = [0u8; 16];
+    assert_eq!(readlink("/sl_file\0", &mut buf), -1);
+    //目标超过缓冲区时截断
+    assert_eq!(readlink("/sl_abs\0", &mut buf[..3]), 3);
+    assert_eq!(&buf[..3], b"/sl");
+
+    //stat跟随符号链接，lstat查看符号链接本身
+    let mut target = Stat::new();
+    let mut link = Stat::new();
+    assert_eq!(stat("/sl_chain\0", &mut target), 0);
+    assert_eq!(lstat("/sl_chain\0", &mut link), 0);
+    assert_eq!(target.mode.file_type(), StatMode::FILE);
+    assert_eq!(link.mode.file_type(), StatMode::LINK);
+    assert_ne!(target.ino, link.ino);
+    assert_eq!(link.size, "/sl_abs".len() as i64);
+    let fd = open("/sl_chain\0", OpenFlags::R);
+    let opened = Stat::new();
+    fstat(fd as usize, &opened);
+    close(fd as usize);
+    assert_eq!(opened.ino, target.ino);
+
+    //O_NOFOLLOW只检查最后一个分量
+    assert_eq!(open("/sl_abs\0", OpenFlags::R | OpenFlags::NOFOLLOW), -1);
+    let fd = open("/sl_file\0", OpenFlags::R | OpenFlags::NOFOLLOW);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(symlink("/sl_dir\0", "/sl_dirlink\0"), 0);
+    let fd = open("/sl_dirlink/up\0", OpenFlags::R | OpenFlags::NOFOLLOW);
+    assert_eq!(fd, -1);
+    assert_eq!(read_file("/sl_dirlink/up\0").unwrap(), "through link");
+    let fd = open("/sl_dirlink\0", OpenFlags::R | OpenFlags::DIRECTORY);
+    assert!(fd >= 0);
+    close(fd as usize);
+
+    //长的目标存放在数据块中
+    let long = alloc::format!("/{}sl_file\0", "./".repeat(100));
+    assert_eq!(symlink(long.as_str(), "/sl_long\0"), 0);
+    assert_eq!(link_target("/sl_long\0").len(), long.len() - 1);
+    assert_eq!(read_file("/sl_long\0").unwrap(), "through link");
+
+    //形成环的符号链接与悬空的符号链接都不能打开，也不会通过它们新建文件
+    assert_eq!(symlink("/sl_loop_b\0", "/sl_loop_a\0"), 0);
+    assert_eq!(symlink("/sl_loop_a\0", "/sl_loop_b\0"), 0);
+    assert_eq!(open("/sl_loop_a\0", OpenFlags::R), -1);
+    assert_eq!(stat("/sl_loop_a\0", &mut Stat::new()), -1);
+    assert_eq!(lstat("/sl_loop_a\0", &mut Stat::new()), 0);
+    assert_eq!(symlink("/sl_missing\0", "/sl_dangling\0"), 0);
+    assert_eq!(open("/sl_dangling\0", OpenFlags::R), -1);
+    assert_eq!(open("/sl_dangling\0", OpenFlags::C | OpenFlags::W), -1);
+    assert_eq!(open("/sl_missing\0", OpenFlags::R), -1);
+
+    //内存文件系统中的符号链接可以指向其它文件系统
+    assert_eq!(symlink("/sl_file\0", "/tmp/sl_tmp\0"), 0);
+    assert_eq!(read_file("/tmp/sl_tmp\0").unwrap(), "through link");
+    assert_eq!(link_target("/tmp/sl_tmp\0"), "/sl_file");
+
+    //删除符号链接不影响目标
+    for path in [
+        "/tmp/sl_tmp\0",
+        "/sl_dangling\0",
+        "/sl_loop_a\0",
+        "/sl_loop_b\0",
+        "/sl_long\0",
+        "/sl_dirlink\0",
+        "/sl_chain\0",
+        "/sl_dir/up\0",
+        "/sl_abs\0",
+    ] {
+        assert_eq!(unlink(path), 0);
+    }
+    assert_eq!(read_file("/sl_file\0").unwrap(), "through link");
+    assert_eq!(unlink("/sl_dir\0"), 0);
+    assert_eq!(unlink("/sl_file\0"), 0);
+    println!("Test symlink OK!");
+    0
+}
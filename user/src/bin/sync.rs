@@ -0,0 +1,10 @@
+#![no_main]
+#![no_std]
+
+use lib::sync;
+
+/// 将文件系统缓存中的修改写回磁盘
+#[no_mangle]
+fn main() -> i32 {
+    sync() as i32
+}
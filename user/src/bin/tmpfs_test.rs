@@ -0,0 +1,106 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    close, fstat, ftruncate, link, ls_dir, lseek, mkdir, mount, open, pread, read, rename, statfs,
+    umount, unlink, write, OpenFlags, Stat, StatFs, SEEK_SET,
+};
+
+/// 测试挂载在/tmp的内存文件系统，输出 Test tmpfs OK! 就算正确。
+
+fn stat_of(fd: usize) -> Stat {
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    stat
+}
+
+/// 写满一个新挂载的内存文件系统，超过容量后写入失败，删除文件后空间恢复
+fn capacity_test() {
+    assert_eq!(mkdir("/tmp/full\0"), 0);
+    assert_eq!(mount("ramfs\0", "/tmp/full\0", "tmpfs\0"), 0);
+    let mut empty = StatFs::new();
+    assert_eq!(statfs("/tmp/full\0", &mut empty), 0);
+    assert!(empty.blocks > 0 && empty.bfree <= empty.blocks);
+    let fd = open("/tmp/full/file\0", OpenFlags::C | OpenFlags::W) as usize;
+    let page = [0x33u8; 4096];
+    for _ in 0..empty.bfree {
+        assert_eq!(write(fd, &page), 4096);
+    }
+    assert_eq!(write(fd, &page), -1);
+    let mut full = StatFs::new();
+    assert_eq!(statfs("/tmp/full\0", &mut full), 0);
+    assert_eq!(full.bfree, 0);
+    close(fd);
+    assert_eq!(unlink("/tmp/full/file\0"), 0);
+    let mut freed = StatFs::new();
+    assert_eq!(statfs("/tmp/full\0", &mut freed), 0);
+    assert_eq!(freed.bfree, empty.bfree);
+    assert_eq!(umount("/tmp/full\0"), 0);
+    assert_eq!(unlink("/tmp/full\0"), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/tmp/scratch\0", OpenFlags::C | OpenFlags::RW);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    //跨越多个页帧的写入
+    let data = [0x5au8; 5000];
+    assert_eq!(write(fd, &data), 5000);
+    assert_eq!(stat_of(fd).size, 5000);
+    let mut buf = [0u8; 5000];
+    assert_eq!(pread(fd, &mut buf, 0), 5000);
+    assert!(buf.iter().all(|byte| *byte == 0x5a));
+    //缩小后再扩大，中间的部分读到0
+    assert_eq!(ftruncate(fd, 100), 0);
+    assert_eq!(ftruncate(fd, 4200), 0);
+    assert_eq!(pread(fd, &mut buf, 0), 4200);
+    assert!(buf[..100].iter().all(|byte| *byte == 0x5a));
+    assert!(buf[100..4200].iter().all(|byte| *byte == 0));
+    lseek(fd, 0, SEEK_SET);
+    assert_eq!(read(fd, &mut buf[..10]), 10);
+
+    //与根文件系统是不同的设备
+    let root = open("/initproc\0", OpenFlags::R) as usize;
+    assert_ne!(stat_of(root).dev, stat_of(fd).dev);
+    close(root);
+
+    //硬链接、重命名与删除
+    assert_eq!(link("/tmp/scratch\0", "/tmp/linked\0"), 0);
+    assert_eq!(stat_of(fd).nlink, 2);
+    assert_eq!(rename("/tmp/linked\0", "/tmp/renamed\0"), 0);
+    assert_eq!(unlink("/tmp/scratch\0"), 0);
+    assert_eq!(stat_of(fd).nlink, 1);
+    close(fd);
+    let fd = open("/tmp/renamed\0", OpenFlags::R) as usize;
+    assert_eq!(stat_of(fd).size, 4200);
+    close(fd);
+
+    assert_eq!(mkdir("/tmp/dir\0"), 0);
+    let fd = open("/tmp/dir/file\0", OpenFlags::C | OpenFlags::W) as usize;
+    close(fd);
+    assert_eq!(unlink("/tmp/dir\0"), -1);
+    let mut names = [0u8; 256];
+    let len = ls_dir("/tmp\0", &mut names) as usize;
+    let names = core::str::from_utf8(&names[..len]).unwrap();
+    assert!(names.lines().any(|name| name == "dir"));
+    assert!(names.lines().any(|name| name == "renamed"));
+    assert!(!names.lines().any(|name| name == "scratch"));
+    assert_eq!(unlink("/tmp/dir/file\0"), 0);
+    assert_eq!(unlink("/tmp/dir\0"), 0);
+    assert_eq!(unlink("/tmp/renamed\0"), 0);
+
+    //另外挂载的内存文件系统在卸载后内容消失
+    assert_eq!(mkdir("/tmp/mnt\0"), 0);
+    assert_eq!(mount("ramfs\0", "/tmp/mnt\0", "tmpfs\0"), 0);
+    let fd = open("/tmp/mnt/file\0", OpenFlags::C | OpenFlags::W) as usize;
+    write(fd, b"gone");
+    close(fd);
+    assert_eq!(umount("/tmp/mnt\0"), 0);
+    assert_eq!(open("/tmp/mnt/file\0", OpenFlags::R), -1);
+    assert_eq!(unlink("/tmp/mnt\0"), 0);
+    capacity_test();
+    println!("Test tmpfs OK!");
+    0
+}
@@ -0,0 +1,120 @@
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lib::{get_time_ms, println, proc_field, proc_pids, read_proc, sleep};
+
+/// top [rounds]  每秒刷新一次系统负载、内存与各个进程的cpu占用，默认刷新3次
+
+fn number(content: &str, key: &str) -> usize {
+    proc_field(content, key)
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .unwrap_or(0)
+}
+
+struct Sample {
+    pid: usize,
+    name: String,
+    state: String,
+    threads: String,
+    pages: usize,
+    time: usize, //运行时间(us)
+}
+
+fn sample() -> Vec<Sample> {
+    proc_pids()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|pid| {
+            let status = read_proc(format!("/proc/{}/status", pid).as_str())?;
+            Some(Sample {
+                pid,
+                name: String::from(proc_field(&status, "Name")),
+                state: String::from(proc_field(&status, "State")),
+                threads: String::from(proc_field(&status, "Threads")),
+                pages: number(&status, "VmPages"),
+                time: number(&status, "Time"),
+            })
+        })
+        .collect()
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let rounds = if argc > 1 {
+        argv[1].parse().unwrap_or(3)
+    } else {
+        3
+    };
+    if read_proc("/proc/sched").is_none() {
+        println!("top: /proc is not mounted");
+        return -1;
+    }
+    let mut last: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut last_ms = get_time_ms();
+    for round in 0..rounds {
+        if round > 0 {
+            sleep(1000);
+        }
+        let now_ms = get_time_ms();
+        let interval = (now_ms - last_ms).max(1) * 1000; //两次采样的间隔(us)
+        last_ms = now_ms;
+        let uptime = read_proc("/proc/uptime").unwrap_or_default();
+        let sched = read_proc("/proc/sched").unwrap_or_default();
+        let meminfo = read_proc("/proc/meminfo").unwrap_or_default();
+        let mut samples = sample();
+        println!(
+            "up {}s, {} processes, {} ready, {} switches",
+            uptime.split(' ').next().unwrap_or("?"),
+            proc_field(&sched, "processes"),
+            proc_field(&sched, "ready"),
+            proc_field(&sched, "switches")
+        );
+        println!(
+            "Mem: {} kB total, {} kB used, {} kB free, heap {} kB",
+            number(&meminfo, "MemTotal"),
+            number(&meminfo, "MemUsed"),
+            number(&meminfo, "MemFree"),
+            number(&meminfo, "HeapSize")
+        );
+        //第一次采样时没有上一次的数据，按照开机以来的时间计算
+        let cpu = |sample: &Sample| {
+            let base = last.get(&sample.pid).copied().unwrap_or(0);
+            let span = if last.is_empty() {
+                now_ms * 1000
+            } else {
+                interval
+            };
+            sample.time.saturating_sub(base) * 100 / span.max(1)
+        };
+        samples.sort_by_key(|sample| core::cmp::Reverse(cpu(sample)));
+        println!(
+            "{:>5} S {:>3} {:>6} {:>4} NAME",
+            "PID", "THR", "PAGES", "CPU%"
+        );
+        for sample in samples.iter() {
+            println!(
+                "{:>5} {} {:>3} {:>6} {:>4} {}",
+                sample.pid,
+                sample.state,
+                sample.threads,
+                sample.pages,
+                cpu(sample),
+                sample.name
+            );
+        }
+        println!("");
+        last = samples
+            .iter()
+            .map(|sample| (sample.pid, sample.time))
+            .collect();
+    }
+    0
+}
@@ -0,0 +1,19 @@
+#![no_main]
+#![no_std]
+
+use lib::{println, umount};
+
+/// umount <dir>  卸载挂载在目录上的文件系统
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc != 2 {
+        println!("usage: umount <dir>");
+        return -1;
+    }
+    if umount(argv[1]) != 0 {
+        println!("umount: {}: target is busy or not mounted", argv[1]);
+        return -1;
+    }
+    0
+}
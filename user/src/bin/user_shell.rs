@@ -5,7 +5,7 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
 use lib::console::getchar;
-use lib::{close, dup, exec, fork, open, print, println, wait_pid, OpenFlags, INFO};
+use lib::{close, dup2, exec, fork, open, print, println, wait_pid, OpenFlags, INFO};
 
 const LF: u8 = 10; //换行键
 const CR: u8 = 13; //回车键
@@ -82,8 +82,7 @@ fn main() -> isize {
                                 return -4; //打不开文件
                             }
                             let input_fd = input_fd as usize;
-                            close(0); //将标准输入关闭
-                            assert_eq!(dup(input_fd), 0); //检查是否将输入文件替换为标准输入文件
+                            dup2(input_fd, 0); //将输入文件替换为标准输入文件
                             close(input_fd);
                         }
                         if !output.is_empty() {
@@ -93,8 +92,7 @@ fn main() -> isize {
                                 return -4; //打不开文件
                             }
                             let output_fd = output_fd as usize;
-                            close(1); //将标准输入关闭
-                            assert_eq!(dup(output_fd), 1); //检查是否将输入文件替换为标准输入文件
+                            dup2(output_fd, 1); //将输出文件替换为标准输出文件
                             close(output_fd);
                         }
                         let info = exec(command[0].as_str(), &args_addr); //&args_addr == args_addr.as_slice
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{
+    close, fstat, ftruncate, link, ls_dir, mkdir, mount, open, pread, rename, umount, unlink,
+    write, OpenFlags, Stat,
+};
+
+/// 测试挂载FAT32格式的第二块磁盘，输出 Test vfat OK! 就算正确。
+/// 磁盘需要在主机上格式化: mkfs.vfat -F 32 -C fat.img 65536，然后 make run DISK=fat.img
+
+fn stat_of(fd: usize) -> Stat {
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    stat
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/vfat\0"), 0);
+    if mount("vdb\0", "/vfat\0", "vfat\0") != 0 {
+        println!("no FAT32 disk, skip vfat test");
+        assert_eq!(unlink("/vfat\0"), 0);
+        println!("Test vfat OK!");
+        return 0;
+    }
+    //超过8.3格式的名称保存在长文件名目录项中
+    let fd = open("/vfat/A Long File Name.txt\0", OpenFlags::C | OpenFlags::RW);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let data = [0x3cu8; 3000];
+    assert_eq!(write(fd, &data), 3000);
+    assert_eq!(stat_of(fd).size, 3000);
+    assert_eq!(ftruncate(fd, 1000), 0);
+    assert_eq!(ftruncate(fd, 2000), 0);
+    let mut buf = [0u8; 3000];
+    assert_eq!(pread(fd, &mut buf, 0), 2000);
+    assert!(buf[..1000].iter().all(|byte| *byte == 0x3c));
+    assert!(buf[1000..2000].iter().all(|byte| *byte == 0));
+    close(fd);
+    //名称不区分大小写，不支持硬链接
+    let fd = open("/vfat/a long file name.TXT\0", OpenFlags::R);
+    assert!(fd >= 0);
+    let root = open("/initproc\0", OpenFlags::R) as usize;
+    assert_ne!(stat_of(root).dev, stat_of(fd as usize).dev);
+    close(root);
+    close(fd as usize);
+    assert_eq!(link("/vfat/A Long File Name.txt\0", "/vfat/linked\0"), -1);
+    assert_eq!(
+        rename("/vfat/A Long File Name.txt\0", "/vfat/Renamed.txt\0"),
+        0
+    );
+    assert_eq!(mkdir("/vfat/Sub Dir\0"), 0);
+    let fd = open("/vfat/Sub Dir/file\0", OpenFlags::C | OpenFlags::W) as usize;
+    close(fd);
+    assert_eq!(unlink("/vfat/Sub Dir\0"), -1);
+    let mut names = [0u8; 256];
+    let len = ls_dir("/vfat\0", &mut names) as usize;
+    let names = core::str::from_utf8(&names[..len]).unwrap();
+    assert!(names.lines().any(|name| name == "Renamed.txt"));
+    assert!(names.lines().any(|name| name == "Sub Dir"));
+    assert!(!names.lines().any(|name| name == "A Long File Name.txt"));
+    assert_eq!(unlink("/vfat/Sub Dir/file\0"), 0);
+    assert_eq!(unlink("/vfat/Sub Dir\0"), 0);
+    assert_eq!(unlink("/vfat/Renamed.txt\0"), 0);
+    assert_eq!(umount("/vfat\0"), 0);
+    assert_eq!(unlink("/vfat\0"), 0);
+    println!("Test vfat OK!");
+    0
+}
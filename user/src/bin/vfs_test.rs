@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+use lib::println;
+use lib::{close, mkdir, mount, open, read, rename, umount, unlink, write, OpenFlags};
+
+/// 测试目录路径解析与挂载，输出 Test vfs OK! 就算正确。
+/// 连接了第二块磁盘时同时测试挂载与卸载
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, vfs!";
+    assert_eq!(mkdir("vfs_dir\0"), 0);
+    assert_eq!(mkdir("vfs_dir\0"), -1);
+    let fd = open("/vfs_dir/file\0", OpenFlags::C | OpenFlags::W) as usize;
+    write(fd, test_str.as_bytes());
+    close(fd);
+    //.与..由内核处理
+    let fd = open("vfs_dir/../vfs_dir/./file\0", OpenFlags::R);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 100];
+    let read_len = read(fd as usize, &mut buf) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buf[..read_len]).unwrap());
+    close(fd as usize);
+    assert_eq!(open("vfs_dir/file/x\0", OpenFlags::R), -1);
+    assert_eq!(rename("vfs_dir/file\0", "vfs_dir/renamed\0"), 0);
+    //只能挂载到目录上，根文件系统不能卸载
+    assert_eq!(mount("vdb\0", "vfs_dir/renamed\0", "easyfs\0"), -1);
+    assert_eq!(mount("vda\0", "vfs_dir\0", "easyfs\0"), -1);
+    assert_eq!(mount("vdb\0", "vfs_dir\0", "unknown\0"), -1);
+    assert_eq!(umount("/\0"), -1);
+    if mount("vdb\0", "vfs_dir\0", "easyfs\0") == 0 {
+        //被覆盖的目录中的文件不可见，挂载点不能被删除
+        assert_eq!(open("vfs_dir/renamed\0", OpenFlags::R), -1);
+        assert_eq!(unlink("vfs_dir\0"), -1);
+        let fd = open("vfs_dir/../vfs_dir/vfs_test\0", OpenFlags::C | OpenFlags::W);
+        assert!(fd >= 0);
+        assert_eq!(umount("vfs_dir\0"), -1);
+        close(fd as usize);
+        assert_eq!(unlink("vfs_dir/vfs_test\0"), 0);
+        assert_eq!(umount("vfs_dir\0"), 0);
+    } else {
+        println!("no second disk, skip mount test");
+    }
+    assert_eq!(unlink("vfs_dir/renamed\0"), 0);
+    assert_eq!(unlink("vfs_dir\0"), 0);
+    println!("Test vfs OK!");
+    0
+}
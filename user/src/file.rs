@@ -4,12 +4,41 @@ bitflags! {
     pub struct StatMode: u32 {
         // StatMode 定义：
         const NULL  = 0;
+        /// 文件类型掩码
+        const TYPE_MASK = 0o170000;
         /// directory
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// character device
+        const CHAR  = 0o020000;
+        /// block device
+        const BLOCK = 0o060000;
+        /// symbolic link
+        const LINK  = 0o120000;
+        /// 所有者的读写执行权限
+        const OWNER_R = 0o400;
+        const OWNER_W = 0o200;
+        const OWNER_X = 0o100;
+        /// 所属组的读写执行权限
+        const GROUP_R = 0o040;
+        const GROUP_W = 0o020;
+        const GROUP_X = 0o010;
+        /// 其他用户的读写执行权限
+        const OTHER_R = 0o004;
+        const OTHER_W = 0o002;
+        const OTHER_X = 0o001;
     }
 }
+
+impl StatMode {
+    /// 只保留文件类型
+    pub fn file_type(&self) -> StatMode {
+        *self & StatMode::TYPE_MASK
+    }
+}
+
+/// 与Linux riscv64的struct stat布局相同
 #[repr(C)]
 #[derive(Debug)]
 pub struct Stat {
@@ -17,12 +46,28 @@ pub struct Stat {
     pub dev: u64,
     /// inode 文件所在 inode 编号
     pub ino: u64,
-    /// 文件类型
+    /// 文件类型与权限位
     pub mode: StatMode,
     /// 硬链接数量，初始为1
     pub nlink: u32,
-    /// 无需考虑，为了兼容性设计
-    pad: [u64; 7],
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    pad: u64,
+    /// 文件大小
+    pub size: i64,
+    /// 块大小
+    pub blksize: i32,
+    pad2: i32,
+    /// 占用的512B扇区数
+    pub blocks: i64,
+    pub atime: i64,
+    pub atime_nsec: u64,
+    pub mtime: i64,
+    pub mtime_nsec: u64,
+    pub ctime: i64,
+    pub ctime_nsec: u64,
+    unused: [u32; 2],
 }
 
 impl Stat {
@@ -32,7 +77,182 @@ impl Stat {
             ino: 0,
             mode: StatMode::NULL,
             nlink: 0,
-            pad: [0u64; 7],
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            pad: 0,
+            size: 0,
+            blksize: 0,
+            pad2: 0,
+            blocks: 0,
+            atime: 0,
+            atime_nsec: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+            ctime: 0,
+            ctime_nsec: 0,
+            unused: [0; 2],
+        }
+    }
+}
+
+/// getdents64返回的目录项类型
+pub const DT_UNKNOWN: u8 = 0;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_BLK: u8 = 6;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+
+/// 与Linux的struct linux_dirent64对应，名称借用getdents读出的缓冲区
+pub struct Dirent<'a> {
+    pub ino: u64,
+    /// 下一个目录项的位置，可以传给lseek
+    pub off: i64,
+    pub kind: u8,
+    pub name: &'a str,
+}
+
+/// 依次解析getdents读出的目录项
+pub struct Dirents<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Dirents<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for Dirents<'a> {
+    type Item = Dirent<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let buf = self.buf;
+        if buf.len() < 19 {
+            return None;
+        }
+        let reclen = u16::from_le_bytes([buf[16], buf[17]]) as usize;
+        if reclen < 20 || reclen > buf.len() {
+            return None;
+        }
+        let name = &buf[19..reclen];
+        let len = name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len());
+        let mut ino = [0u8; 8];
+        ino.copy_from_slice(&buf[0..8]);
+        let mut off = [0u8; 8];
+        off.copy_from_slice(&buf[8..16]);
+        self.buf = &buf[reclen..];
+        Some(Dirent {
+            ino: u64::from_le_bytes(ino),
+            off: i64::from_le_bytes(off),
+            kind: buf[18],
+            name: core::str::from_utf8(&name[..len]).unwrap_or(""),
+        })
+    }
+}
+
+//flock的操作
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+//fcntl的命令
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+/// F_GETFD与F_SETFD使用的描述符标志
+pub const FD_CLOEXEC: usize = 1;
+pub const F_GETLK: usize = 5;
+pub const F_SETLK: usize = 6;
+pub const F_SETLKW: usize = 7;
+
+//记录锁的类型
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+
+/// 与Linux的struct flock布局相同
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    /// 0表示一直到文件末尾之后
+    pub l_len: i64,
+    /// F_GETLK返回持有冲突的锁的进程
+    pub l_pid: i32,
+}
+
+impl Flock {
+    /// 从文件开头算起的[start,start+len)
+    pub fn new(l_type: i16, start: i64, len: i64) -> Self {
+        Self {
+            l_type,
+            l_whence: 0,
+            l_start: start,
+            l_len: len,
+            l_pid: 0,
         }
     }
 }
+
+/// 与Linux riscv64的struct statfs布局相同，块数以bsize为单位
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StatFs {
+    /// 文件系统的标识，与Linux的f_type相同
+    pub fs_type: i64,
+    pub bsize: i64,
+    /// 块的总数、空闲块数与普通用户可用的块数
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    /// inode的总数与空闲数，没有限制的文件系统为0
+    pub files: u64,
+    pub ffree: u64,
+    pub fsid: [i32; 2],
+    /// 文件名的最大长度
+    pub namelen: i64,
+    pub frsize: i64,
+    pub flags: i64,
+    spare: [i64; 4],
+}
+
+impl StatFs {
+    pub fn new() -> Self {
+        Self {
+            fs_type: 0,
+            bsize: 0,
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: 0,
+            ffree: 0,
+            fsid: [0; 2],
+            namelen: 0,
+            frsize: 0,
+            flags: 0,
+            spare: [0; 4],
+        }
+    }
+}
+
+//quotactl的命令
+pub const Q_GETQUOTA: usize = 0x80000700;
+pub const Q_SETQUOTA: usize = 0x80000800;
+
+/// quotactl读写的块限额，以文件系统的块为单位，limit为0表示没有限额
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskQuota {
+    pub limit: u64,
+    pub used: u64,
+}
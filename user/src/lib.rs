@@ -13,9 +13,16 @@ mod time;
 
 extern crate alloc;
 use crate::syscall::*;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use bitflags::bitflags;
-pub use file::{Stat, StatMode};
+pub use file::{
+    Dirent, Dirents, DiskQuota, Flock, Stat, StatFs, StatMode, DT_BLK, DT_CHR, DT_DIR, DT_LNK,
+    DT_REG, DT_UNKNOWN, FD_CLOEXEC, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_GETLK, F_RDLCK,
+    F_SETFD, F_SETFL, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+    Q_GETQUOTA, Q_SETQUOTA,
+};
 use syscall::{sys_getpid, sys_spawn};
 use system_allocator::init;
 pub use time::Time;
@@ -26,9 +33,19 @@ bitflags! {
         const RW = 1<<1;//读写
         const C = 1<<9;//新建
         const T = 1<<10;//打开清空
+        const APPEND = 1<<11;//追加写
+        const NONBLOCK = 1<<12;//读写不等待
+        const DIRECTORY = 1<<16;//只能打开目录
+        const NOFOLLOW = 1<<17;//最后一个分量是符号链接时失败
+        const CLOEXEC = 1<<19;//exec时关闭
     }
 }
 
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100; //fstatat不跟随最后一个符号链接
+
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
@@ -69,6 +86,13 @@ pub fn spawn(path: &str) -> isize {
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn getuid() -> isize {
+    sys_getuid()
+}
+/// 只有超级用户可以切换到其它用户
+pub fn setuid(uid: usize) -> isize {
+    sys_setuid(uid)
+}
 
 pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
@@ -76,6 +100,27 @@ pub fn munmap(start: usize, len: usize) -> isize {
 pub fn mmap(start: usize, len: usize, port: usize) -> isize {
     sys_mmap(start, len, port)
 }
+
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+pub const IPC_RMID: usize = 0;
+/// 根据key获取共享内存段
+pub fn shmget(key: usize, size: usize, flags: usize) -> isize {
+    sys_shmget(key, size, flags)
+}
+/// 映射共享内存段，addr为0时由内核选择地址，返回映射的地址
+pub fn shmat(shmid: usize, addr: usize) -> isize {
+    sys_shmat(shmid, addr)
+}
+/// 控制共享内存段，IPC_RMID删除共享内存段
+pub fn shmctl(shmid: usize, cmd: usize) -> isize {
+    sys_shmctl(shmid, cmd)
+}
+/// 解除共享内存段的映射
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
 pub fn pipe(pipe: &mut [usize]) -> isize {
     //创建一个管道
     sys_pipe(pipe)
@@ -139,21 +184,201 @@ pub fn open(path: &str, flags: OpenFlags) -> isize {
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
-pub fn ls() -> isize {
-    sys_ls()
+///将oldfd复制到newfd，newfd已经打开时先关闭
+pub fn dup2(oldfd: usize, newfd: usize) -> isize {
+    //相同时只检查oldfd是否有效
+    if oldfd == newfd {
+        return if fcntl(oldfd, F_GETFD, 0) < 0 {
+            -1
+        } else {
+            newfd as isize
+        };
+    }
+    sys_dup3(oldfd, newfd, 0)
+}
+///与dup2相同，flags可以是OpenFlags::CLOEXEC
+pub fn dup3(oldfd: usize, newfd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(oldfd, newfd, flags.bits())
+}
+///操作文件描述符，记录锁使用fcntl_lock
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+///对整个文件加锁或解锁，锁属于打开的文件
+pub fn flock(fd: usize, operation: usize) -> isize {
+    sys_flock(fd, operation)
+}
+///记录锁，F_SETLKW在冲突时等待，F_GETLK将冲突的锁写回lock
+pub fn fcntl_lock(fd: usize, cmd: usize, lock: &mut Flock) -> isize {
+    sys_fcntl(fd, cmd, lock as *mut Flock as usize)
+}
+///读取目录中的目录项，用Dirents解析，读完时返回0
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+///将根目录下的文件名以换行分隔写入buf，返回写入的字节数
+pub fn ls_names(buf: &mut [u8]) -> isize {
+    ls_dir("/\0", buf)
+}
+///将path目录下的文件名以换行分隔写入buf，不包括.与..，返回写入的字节数
+pub fn ls_dir(path: &str, buf: &mut [u8]) -> isize {
+    let fd = open(path, OpenFlags::R | OpenFlags::DIRECTORY);
+    if fd < 0 {
+        return -1;
+    }
+    let fd = fd as usize;
+    let mut dents = [0u8; 512];
+    let mut written = 0;
+    loop {
+        let len = getdents(fd, &mut dents);
+        if len <= 0 {
+            break;
+        }
+        for dirent in Dirents::new(&dents[..len as usize]) {
+            if dirent.name == "." || dirent.name == ".." {
+                continue;
+            }
+            for byte in dirent.name.bytes().chain(core::iter::once(b'\n')) {
+                if written == buf.len() {
+                    break;
+                }
+                buf[written] = byte;
+                written += 1;
+            }
+        }
+    }
+    close(fd);
+    written as isize
+}
+///读出整个文件，path不需要以\0结尾，打开失败或者不是UTF-8时返回None，用于读取/proc中的文件
+pub fn read_proc(path: &str) -> Option<String> {
+    let fd = open(format!("{}\0", path).as_str(), OpenFlags::R);
+    if fd < 0 {
+        return None;
+    }
+    let fd = fd as usize;
+    let mut data = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(fd, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd);
+    String::from_utf8(data).ok()
+}
+///从"键: 值"形式的内容中取出一项的值，没有这一项时返回"?"
+pub fn proc_field<'a>(content: &'a str, key: &str) -> &'a str {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .map_or("?", |value| value.trim())
+}
+///列出/proc中所有进程的pid，/proc没有挂载时返回None
+pub fn proc_pids() -> Option<Vec<usize>> {
+    let fd = open("/proc\0", OpenFlags::R | OpenFlags::DIRECTORY);
+    if fd < 0 {
+        return None;
+    }
+    let fd = fd as usize;
+    let mut dents = [0u8; 512];
+    let mut pids = Vec::new();
+    loop {
+        let len = getdents(fd, &mut dents);
+        if len <= 0 {
+            break;
+        }
+        pids.extend(
+            Dirents::new(&dents[..len as usize])
+                .filter_map(|dirent| dirent.name.parse::<usize>().ok()),
+        );
+    }
+    close(fd);
+    Some(pids)
 }
 ///硬链接
 pub fn link(oldpath: &str, newpath: &str) -> isize {
     sys_linkat(-100, oldpath.as_ptr(), -100, newpath.as_ptr(), 0) as isize
 }
+///新建指向target的符号链接，target在使用时才解析
+pub fn symlink(target: &str, linkpath: &str) -> isize {
+    sys_symlinkat(target.as_ptr(), -100, linkpath.as_ptr())
+}
+///将符号链接的目标写入buf，返回写入的字节数
+pub fn readlink(path: &str, buf: &mut [u8]) -> isize {
+    sys_readlinkat(-100, path.as_ptr(), buf)
+}
 ///解除链接
 pub fn unlink(path: &str) -> isize {
     sys_unlinkat(-100, path.as_ptr(), 0)
 }
+///重命名，目标存在时被替换
+pub fn rename(oldpath: &str, newpath: &str) -> isize {
+    sys_renameat(-100, oldpath.as_ptr(), -100, newpath.as_ptr())
+}
+///新建目录
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(-100, path.as_ptr(), 0o755)
+}
+///挂载文件系统，例如mount("vdb\0", "mnt\0", "easyfs\0")
+pub fn mount(source: &str, target: &str, fstype: &str) -> isize {
+    sys_mount(source.as_ptr(), target.as_ptr(), fstype.as_ptr(), 0)
+}
+///卸载文件系统
+pub fn umount(target: &str) -> isize {
+    sys_umount(target.as_ptr(), 0)
+}
+/// 根据路径查看文件信息，跟随符号链接
+pub fn stat(path: &str, state: &mut Stat) -> isize {
+    sys_fstatat(-100, path.as_ptr(), state, 0)
+}
+/// 查看符号链接本身的信息
+pub fn lstat(path: &str, state: &mut Stat) -> isize {
+    sys_fstatat(-100, path.as_ptr(), state, AT_SYMLINK_NOFOLLOW)
+}
 /// 查看文件信息
 pub fn fstat(fd: usize, state: &Stat) -> isize {
     sys_fstat(fd, state)
 }
+/// 查看path所在文件系统的使用情况
+pub fn statfs(path: &str, statfs: &mut StatFs) -> isize {
+    sys_statfs(path.as_ptr(), statfs)
+}
+/// 查看打开的文件所在文件系统的使用情况
+pub fn fstatfs(fd: usize, statfs: &mut StatFs) -> isize {
+    sys_fstatfs(fd, statfs)
+}
+/// 查看或者设置path所在文件系统中用户uid的块限额
+pub fn quotactl(cmd: usize, path: &str, uid: usize, quota: &mut DiskQuota) -> isize {
+    sys_quotactl(cmd, path.as_ptr(), uid, quota)
+}
+/// 修改文件的所有者，只有超级用户可以修改
+pub fn chown(path: &str, uid: usize, gid: usize) -> isize {
+    sys_fchownat(-100, path.as_ptr(), uid, gid, 0)
+}
+///将所有文件的修改写回磁盘
+pub fn sync() -> isize {
+    sys_sync()
+}
+///将文件的修改写回磁盘
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+///移动读写位置
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread(fd, buf, offset)
+}
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize {
+    sys_pwrite(fd, buf, offset)
+}
+pub fn ftruncate(fd: usize, len: usize) -> isize {
+    sys_ftruncate(fd, len)
+}
 pub fn thread_create(entry: usize, arg: usize) -> isize {
     sys_thread_create(entry, arg)
 }
@@ -11,6 +11,8 @@ const SYSCALL_EXEC: usize = 221;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_PID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_SETUID: usize = 146;
 const SYSCALL_MMAP: usize = 222;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_PIPE: usize = 59;
@@ -18,11 +20,35 @@ const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_MAILREAD: usize = 401;
 const SYSCALL_MAILWRITE: usize = 402;
 const SYSCALL_OPEN: usize = 56;
-const SYSCALL_DUP: usize = 24;
-const SYSCALL_LS: usize = 44; //自定义系统调用
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_FLOCK: usize = 32;
+const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_SYNC: usize = 81;
+const SYSCALL_FSYNC: usize = 82;
+const SYSCALL_LSEEK: usize = 62;
+const SYSCALL_PREAD: usize = 67;
+const SYSCALL_PWRITE: usize = 68;
+const SYSCALL_FTRUNCATE: usize = 46;
 const SYSCALL_LINKAT: usize = 37;
 const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_RENAMEAT: usize = 38;
+const SYSCALL_SYMLINKAT: usize = 36;
+const SYSCALL_READLINKAT: usize = 78;
+const SYSCALL_FSTATAT: usize = 79;
 const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_STATFS: usize = 43;
+const SYSCALL_FSTATFS: usize = 44;
+const SYSCALL_QUOTACTL: usize = 60;
+const SYSCALL_FCHOWNAT: usize = 54;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_UMOUNT: usize = 39;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
 
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
@@ -37,7 +63,7 @@ const SYSCALL_MONITOR_CREATE: usize = 1030;
 const SYSCALL_MONITOR_SIGNAL: usize = 1031;
 const SYSCALL_MONITOR_WAIT: usize = 1032;
 
-use crate::{Stat, Time};
+use crate::{DiskQuota, Stat, StatFs, Time};
 use alloc::sync::Arc;
 fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
@@ -52,6 +78,21 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     }
     ret
 }
+/// 需要第4个参数的系统调用
+fn syscall4(id: usize, args: [usize; 4]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!("ecall",
+        inlateout("x10") args[0] => ret,
+        in("x11") args[1],
+        in("x12") args[2],
+        in("x13") args[3],
+        in("x17") id,
+        options(nostack)
+        )
+    }
+    ret
+}
 /// fn syscall6(id: usize, args: [usize; 6]) -> isize {
 ///     let mut ret: isize;
 ///     unsafe {
@@ -147,6 +188,17 @@ pub fn sys_spawn(path: &str) -> isize {
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_PID, [0, 0, 0])
 }
+/// 功能：获取当前进程的用户id
+/// syscall ID：174
+pub fn sys_getuid() -> isize {
+    syscall(SYSCALL_GETUID, [0, 0, 0])
+}
+/// 功能：修改当前进程的用户id
+/// 返回值：成功返回0，不是超级用户并且uid不是自己时返回-1
+/// syscall ID：146
+pub fn sys_setuid(uid: usize) -> isize {
+    syscall(SYSCALL_SETUID, [uid, 0, 0])
+}
 
 ///申请一个len长度的物理内存，将其映射到start开始的许村，内存页属性为port
 ///其中port等待0位表示是否可读，1位是否可写，2表示是否可执行
@@ -158,6 +210,18 @@ pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
 pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
+pub fn sys_shmget(key: usize, size: usize, flags: usize) -> isize {
+    syscall(SYSCALL_SHMGET, [key, size, flags])
+}
+pub fn sys_shmctl(shmid: usize, cmd: usize) -> isize {
+    syscall(SYSCALL_SHMCTL, [shmid, cmd, 0])
+}
+pub fn sys_shmat(shmid: usize, addr: usize) -> isize {
+    syscall(SYSCALL_SHMAT, [shmid, addr, 0])
+}
+pub fn sys_shmdt(addr: usize) -> isize {
+    syscall(SYSCALL_SHMDT, [addr, 0, 0])
+}
 pub fn sys_pipe(pipe: &mut [usize]) -> isize {
     syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
 }
@@ -189,16 +253,46 @@ pub fn sys_open(path: &str, flags: u32) -> isize {
 /// 参数：fd 表示进程中一个已经打开的文件的文件描述符。
 /// 返回值：如果出现了错误则返回 -1，否则能够访问已打开文件的新文件描述符。
 /// 可能的错误原因是：传入的 fd 并不对应一个合法的已打开文件。
-/// syscall ID：24
+/// syscall ID：23
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
-/// ls
-/// 功能：查看指定目录下的文件
-/// 当前只能查看根目录下的文件
-pub fn sys_ls() -> isize {
-    syscall(SYSCALL_LS, [0, 0, 0])
+/// 功能：将 oldfd 复制到 newfd，newfd 已经打开时先将它关闭。
+/// 参数：flags 只能是 0 或者 O_CLOEXEC。
+/// 返回值：成功返回 newfd，oldfd 无效或者与 newfd 相同时返回 -1。
+/// syscall ID：24
+pub fn sys_dup3(oldfd: usize, newfd: usize, flags: u32) -> isize {
+    syscall(SYSCALL_DUP3, [oldfd, newfd, flags as usize])
+}
+
+/// 功能：对整个文件加建议性的共享锁或独占锁。
+/// 参数：operation 是 LOCK_SH、LOCK_EX 或 LOCK_UN，可以或上 LOCK_NB。
+/// 返回值：成功返回 0，不是磁盘文件或者 LOCK_NB 时锁被占用返回 -1。
+/// 锁属于打开的文件，dup与fork得到的描述符共享同一个锁。
+/// syscall ID：32
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    syscall(SYSCALL_FLOCK, [fd, operation, 0])
+}
+
+/// 功能：操作文件描述符，支持 F_DUPFD、F_GETFD/F_SETFD、F_GETFL/F_SETFL 与记录锁。
+/// 参数：arg 的含义由 cmd 决定，记录锁的 arg 是指向 struct flock 的指针。
+/// 返回值：F_DUPFD 返回新的描述符，F_GETFD 与 F_GETFL 返回标志，其它成功时返回 0，出错返回 -1。
+/// syscall ID：25
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd, arg])
+}
+
+/// 功能：读取目录中的目录项，以linux_dirent64的格式写入buf。
+/// 参数：fd 表示以O_DIRECTORY打开的目录。
+/// 返回值：写入的字节数，目录已经读完时返回0。
+/// fd 不是目录或者 buf 放不下一个目录项时返回 -1。
+/// syscall ID：61
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS64,
+        [fd, buf.as_mut_ptr() as usize, buf.len()],
+    )
 }
 
 /// 实现文件的硬连接
@@ -213,15 +307,143 @@ pub fn sys_linkat(
 ) -> isize {
     syscall(SYSCALL_LINKAT, [oldpath as usize, newpath as usize, 0])
 }
+/// 新建指向target的符号链接linkpath
+/// 与sys_linkat一样只关注target与linkpath
+/// syscall ID：36
+pub fn sys_symlinkat(target: *const u8, newdirfd: i32, linkpath: *const u8) -> isize {
+    syscall(SYSCALL_SYMLINKAT, [target as usize, linkpath as usize, 0])
+}
+/// 功能：读取符号链接的目标，写入buf的内容不以'\0'结束。
+/// 返回值：写入的字节数，path 不是符号链接时返回 -1。
+/// syscall ID：78
+pub fn sys_readlinkat(dirfd: i32, path: *const u8, buf: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_READLINKAT,
+        [path as usize, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+/// 根据路径查看文件信息
+/// flags 包含 AT_SYMLINK_NOFOLLOW 时查看符号链接本身
+/// syscall ID：79
+pub fn sys_fstatat(dirfd: i32, path: *const u8, stat: &mut Stat, flags: u32) -> isize {
+    syscall(
+        SYSCALL_FSTATAT,
+        [path as usize, stat as *mut Stat as usize, flags as usize],
+    )
+}
 /// 解除一个文件的链接
 pub fn sys_unlinkat(dirfd: i32, path: *const u8, flags: u32) -> isize {
     syscall(SYSCALL_UNLINKAT, [path as usize, 0, 0])
 }
+/// 重命名文件
+/// 与sys_linkat一样只关注oldpath与newpath
+pub fn sys_renameat(olddirfd: i32, oldpath: *const u8, newdirfd: i32, newpath: *const u8) -> isize {
+    syscall(SYSCALL_RENAMEAT, [oldpath as usize, newpath as usize, 0])
+}
+/// 新建目录
+/// 与sys_linkat一样只关注path
+pub fn sys_mkdirat(dirfd: i32, path: *const u8, mode: u32) -> isize {
+    syscall(SYSCALL_MKDIRAT, [path as usize, 0, 0])
+}
+/// 功能：将块设备source上类型为fstype的文件系统挂载到target目录
+/// 返回值：成功返回0，失败返回-1
+/// syscall ID：40
+pub fn sys_mount(source: *const u8, target: *const u8, fstype: *const u8, flags: usize) -> isize {
+    syscall4(
+        SYSCALL_MOUNT,
+        [source as usize, target as usize, fstype as usize, flags],
+    )
+}
+/// 功能：卸载target上的文件系统，文件系统中还有打开的文件时失败
+/// syscall ID：39
+pub fn sys_umount(target: *const u8, flags: usize) -> isize {
+    syscall(SYSCALL_UMOUNT, [target as usize, flags, 0])
+}
 /// 查看文件信息
 ///
 pub fn sys_fstat(fd: usize, stat: &Stat) -> isize {
     syscall(SYSCALL_FSTAT, [fd, stat as *const Stat as usize, 0])
 }
+/// 功能：查看path所在文件系统的块与inode的使用情况
+/// 返回值：成功返回0，路径不存在返回-1
+/// syscall ID：43
+pub fn sys_statfs(path: *const u8, statfs: &mut StatFs) -> isize {
+    syscall(
+        SYSCALL_STATFS,
+        [path as usize, statfs as *mut StatFs as usize, 0],
+    )
+}
+/// 功能：查看打开的文件所在文件系统的使用情况
+/// 返回值：成功返回0，fd 无效或者是管道等不属于文件系统的文件时返回-1
+/// syscall ID：44
+pub fn sys_fstatfs(fd: usize, statfs: &mut StatFs) -> isize {
+    syscall(SYSCALL_FSTATFS, [fd, statfs as *mut StatFs as usize, 0])
+}
+/// 功能：查看或者设置path所在文件系统中用户uid的块限额
+/// 参数：cmd 为 Q_GETQUOTA 或 Q_SETQUOTA，设置时 limit 为0表示取消限额
+/// 返回值：成功返回0，文件系统不支持限额或者没有设置限额时返回-1
+/// syscall ID：60
+pub fn sys_quotactl(cmd: usize, path: *const u8, uid: usize, quota: &mut DiskQuota) -> isize {
+    syscall4(
+        SYSCALL_QUOTACTL,
+        [cmd, path as usize, uid, quota as *mut DiskQuota as usize],
+    )
+}
+/// 功能：修改文件的所有者，跟随符号链接
+/// 返回值：成功返回0，不是超级用户、文件不存在或者文件系统不记录所有者时返回-1
+/// syscall ID：54
+/// 与sys_linkat一样只关注path
+pub fn sys_fchownat(dirfd: i32, path: *const u8, uid: usize, gid: usize, flags: u32) -> isize {
+    syscall(SYSCALL_FCHOWNAT, [path as usize, uid, gid])
+}
+
+/// 功能：将所有文件的修改写回磁盘
+/// syscall ID：81
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
+/// 功能：将文件的修改写回磁盘
+/// 参数：fd 表示文件描述符
+/// 返回值：成功返回0，fd不存在返回-1
+/// syscall ID：82
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+/// 功能：移动文件的读写位置
+/// 参数：whence为SEEK_SET/SEEK_CUR/SEEK_END，offset相对于它移动
+/// 返回值：成功返回新的位置，失败返回-1
+/// syscall ID：62
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+/// 功能：从文件的offset处读取，不改变读写位置
+/// 返回值：读取的字节数，失败返回-1
+/// syscall ID：67
+pub fn sys_pread(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    syscall4(
+        SYSCALL_PREAD,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), offset],
+    )
+}
+
+/// 功能：写入文件的offset处，不改变读写位置
+/// 返回值：写入的字节数，失败返回-1
+/// syscall ID：68
+pub fn sys_pwrite(fd: usize, buffer: &[u8], offset: usize) -> isize {
+    syscall4(
+        SYSCALL_PWRITE,
+        [fd, buffer.as_ptr() as usize, buffer.len(), offset],
+    )
+}
+
+/// 功能：将文件截断或扩大到len字节，扩大的部分读出为0
+/// syscall ID：46
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, len, 0])
+}
 /// 创建线程
 /// entry:线程入口地址
 /// arg:线程参数